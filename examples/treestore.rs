@@ -135,7 +135,7 @@ impl RwregStore {
             None,
             &[0, 1, 2, 3],
             &[
-                &16,
+                &18,
                 &"0 … 2500 [2000]",
                 &"0 … 25,00 mA [20 mA]",
                 &"Ausgangsstrom im oberen Punkt	*",
@@ -0,0 +1,593 @@
+//! Kommandozeilen Werkzeug für die 'NE4-MOD-BUS' Sensoren
+//!
+//! Bietet die Funktionen der GUI ohne Desktop Sitzung, z.B. für den Service und
+//! die Skripte der Fertigung. Mit `--json` werden die Ergebnisse als JSON ausgegeben.
+//!
+//! Exit Codes (nach sysexits.h):
+//! - 0  Erfolg
+//! - 64 Falscher Aufruf
+//! - 65 Ungültiger Wert, oder der zurückgelesene Wert weicht ab
+//! - 69 Keine oder nicht verfügbare Schnittstelle
+//! - 73 Sicherungsdatei konnte nicht geschrieben werden
+//! - 74 Übertragungsfehler, z.B. Timeout
+//! - 76 Der Sensor hat mit einer Modbus Exception geantwortet oder ist gesperrt
+#[macro_use]
+extern crate log;
+
+use clap::{crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
+use ne4_konfig::error::Error;
+use ne4_konfig::sensors::backup::{Backup, Change};
+use ne4_konfig::sensors::device_info::DeviceInfo;
+use ne4_konfig::sensors::ra_gas_ne4::{
+    find_rreg, find_rwreg, FunctionCode, Register, WorkingMode, CONNECTION_REGISTERS, RREGS, RWREGS,
+};
+use ne4_konfig::tokio_thread::{
+    bus_scan::{scan_address, ScanResult, SCAN_ADDRESSES},
+    get_ports, LineSettings, Ne4Client,
+};
+use serde_json::{json, Value};
+use std::{process, time::Duration};
+use tokio_serial::{DataBits, Parity, StopBits};
+
+const EX_OK: i32 = 0;
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_UNAVAILABLE: i32 = 69;
+const EX_CANTCREAT: i32 = 73;
+const EX_IOERR: i32 = 74;
+const EX_PROTOCOL: i32 = 76;
+
+/// Fehler mit Exit Code
+struct CliError {
+    code: i32,
+    message: String,
+}
+
+impl CliError {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        CliError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Error> for CliError {
+    fn from(e: Error) -> Self {
+        let code = match e {
+            Error::NoPort | Error::Open { .. } => EX_UNAVAILABLE,
+            Error::Exception(_) | Error::Locked { .. } => EX_PROTOCOL,
+            Error::OutOfRange { .. }
+            | Error::UnknownRegister(_)
+            | Error::ReadOnly(_)
+            | Error::ReadBack { .. }
+            | Error::NotRestored(_)
+            | Error::CommandValue { .. }
+            | Error::OtherDevice { .. }
+            | Error::Incomplete(_) => EX_DATAERR,
+            Error::Timeout | Error::InvalidFrame(_) | Error::Cancelled | Error::Io(_) => EX_IOERR,
+        };
+        CliError::new(code, e.to_string())
+    }
+}
+
+fn main() {
+    pretty_env_logger::init();
+
+    let matches = match app().get_matches_safe() {
+        Ok(matches) => matches,
+        // Hilfe und Version
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => {
+            eprintln!("{}", e.message);
+            process::exit(EX_USAGE);
+        }
+    };
+    let json = global_args(&matches).is_present("json");
+
+    let mut rt = tokio::runtime::Runtime::new().expect("create tokio runtime");
+    let code = match rt.block_on(run(&matches)) {
+        Ok(output) => {
+            print_output(output, json);
+            EX_OK
+        }
+        Err(e) => {
+            if json {
+                println!("{}", json!({ "error": e.message, "code": e.code }));
+            } else {
+                eprintln!("Fehler: {}", e.message);
+            }
+            e.code
+        }
+    };
+    process::exit(code);
+}
+
+fn app() -> App<'static, 'static> {
+    App::new("ne4_cli")
+        .version(crate_version!())
+        .about("Kommandozeilen Werkzeug für die 'NE4-MOD-BUS' Sensoren")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(
+            Arg::with_name("port")
+                .short("p")
+                .long("port")
+                .takes_value(true)
+                .global(true)
+                .help("Serielle Schnittstelle [Vorgabe: erste gefundene Schnittstelle]"),
+        )
+        .arg(
+            Arg::with_name("address")
+                .short("a")
+                .long("address")
+                .takes_value(true)
+                .default_value("247")
+                .global(true)
+                .help("Modbus Adresse des Sensors"),
+        )
+        .arg(
+            Arg::with_name("baud_rate")
+                .long("baud")
+                .takes_value(true)
+                .default_value("9600")
+                .global(true)
+                .help("Baudrate"),
+        )
+        .arg(
+            Arg::with_name("parity")
+                .long("parity")
+                .takes_value(true)
+                .possible_values(&["none", "even", "odd"])
+                .default_value("none")
+                .global(true)
+                .help("Parität"),
+        )
+        .arg(
+            Arg::with_name("data_bits")
+                .long("data-bits")
+                .takes_value(true)
+                .possible_values(&["7", "8"])
+                .default_value("8")
+                .global(true)
+                .help("Datenbits"),
+        )
+        .arg(
+            Arg::with_name("stop_bits")
+                .long("stop-bits")
+                .takes_value(true)
+                .possible_values(&["1", "2"])
+                .default_value("1")
+                .global(true)
+                .help("Stoppbits"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .default_value("100")
+                .global(true)
+                .help("Antwortzeit in Millisekunden"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .global(true)
+                .help("Ausgabe als JSON"),
+        )
+        .subcommand(SubCommand::with_name("ports").about("Verfügbare Schnittstellen auflisten"))
+        .subcommand(
+            SubCommand::with_name("read")
+                .about("Register lesen")
+                .arg(
+                    Arg::with_name("type")
+                        .required(true)
+                        .possible_values(&["input", "holding"])
+                        .help("Input Register (Rreg, 0x04) oder Holding Register (Rwreg, 0x03)"),
+                )
+                .arg(
+                    Arg::with_name("registers")
+                        .multiple(true)
+                        .help("Registernummern [Vorgabe: alle Register der Registertabelle]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("write")
+                .about("Rwreg schreiben und zurücklesen")
+                .arg(
+                    Arg::with_name("register")
+                        .required(true)
+                        .help("Registernummer"),
+                )
+                .arg(
+                    Arg::with_name("value")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("Wert (unskaliert)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("Kennung, Softwarestand und Zähler des Sensors anzeigen"),
+        )
+        .subcommand(
+            SubCommand::with_name("scan")
+                .about("Alle Modbus Adressen der Leitung nach Sensoren absuchen"),
+        )
+        .subcommand(
+            SubCommand::with_name("backup")
+                .about("Alle beschreibbaren Rwreg in eine Datei sichern")
+                .arg(
+                    Arg::with_name("file")
+                        .required(true)
+                        .help("Sicherungsdatei (JSON)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("restore")
+                .about("Geänderte Rwreg aus einer Sicherung zurückschreiben")
+                .arg(
+                    Arg::with_name("file")
+                        .required(true)
+                        .help("Sicherungsdatei (JSON)"),
+                )
+                .arg(
+                    Arg::with_name("dry_run")
+                        .long("dry-run")
+                        .help("Nur die Unterschiede anzeigen, nichts schreiben"),
+                )
+                .arg(
+                    Arg::with_name("other_device")
+                        .long("other-device")
+                        .help("Auch auf einen Sensor mit anderer Geräte ID (Rwreg 98) schreiben"),
+                ),
+        )
+        .subcommand(SubCommand::with_name("nullpunkt").about("Nullpunkt abgleichen"))
+        .subcommand(SubCommand::with_name("messgas").about("Messgas abgleichen"))
+        .subcommand(
+            SubCommand::with_name("modbus-address")
+                .about("Neue Modbus Adresse setzen")
+                .arg(
+                    Arg::with_name("new_address")
+                        .required(true)
+                        .help("Neue Modbus Adresse"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("working-mode")
+                .about("Neue Arbeitsweise setzen")
+                .arg(
+                    Arg::with_name("mode")
+                        .required(true)
+                        .help("Arbeitsweise, z.B. 10 für CO 1000 ppm"),
+                ),
+        )
+}
+
+/// Ergebnis eines Kommandos
+enum Output {
+    Ports(Vec<String>),
+    Registers(FunctionCode, Vec<(u16, u16)>),
+    Written(u16, u16),
+    Scan(Vec<ScanResult>),
+    Info(DeviceInfo),
+    Changes(&'static str, Vec<Change>),
+    Done(&'static str),
+}
+
+async fn run(matches: &ArgMatches<'static>) -> Result<Output, CliError> {
+    if let ("ports", _) = matches.subcommand() {
+        return Ok(Output::Ports(get_ports()));
+    }
+
+    let args = global_args(matches);
+    let port = match args.value_of("port") {
+        Some(port) => port.to_string(),
+        None => get_ports()
+            .into_iter()
+            .next()
+            .ok_or_else(|| CliError::new(EX_UNAVAILABLE, "Keine Schnittstelle gefunden"))?,
+    };
+    let port = Some(port);
+    let modbus_address: u8 = parse_arg(args, "address")?;
+    let ne4_client = Ne4Client::new(line_settings(args)?);
+
+    match matches.subcommand() {
+        ("read", Some(m)) => {
+            let (function, table) = match m.value_of("type") {
+                Some("holding") => (FunctionCode::ReadHoldingRegisters, RWREGS),
+                _ => (FunctionCode::ReadInputRegisters, RREGS),
+            };
+            let numbers = match m.values_of("registers") {
+                Some(values) => values
+                    .map(|v| {
+                        v.parse::<u16>().map_err(|_| {
+                            CliError::new(EX_USAGE, format!("Ungültige Registernummer: {}", v))
+                        })
+                    })
+                    .collect::<Result<Vec<u16>, CliError>>()?,
+                None => table.iter().map(|reg| reg.number).collect(),
+            };
+            let values = ne4_client
+                .read_registers_once(port, modbus_address, function, &numbers)
+                .await?;
+            Ok(Output::Registers(function, values))
+        }
+        ("write", Some(m)) => {
+            let reg_nr: u16 = parse_arg(m, "register")?;
+            let value: i32 = parse_arg(m, "value")?;
+            let reg = find_rwreg(reg_nr).ok_or_else(|| {
+                CliError::new(EX_DATAERR, format!("Register {} ist kein Rwreg", reg_nr))
+            })?;
+            if !reg.writable {
+                return Err(CliError::new(
+                    EX_DATAERR,
+                    format!("Register {} ist schreibgeschützt", reg_nr),
+                ));
+            }
+            let read_back = ne4_client
+                .write_register(port, modbus_address, reg_nr, reg.value_to_raw(value))
+                .await?;
+            Ok(Output::Written(reg_nr, read_back))
+        }
+        ("backup", Some(m)) => {
+            let file = m.value_of("file").unwrap_or_default();
+            let backup = ne4_client.backup(port, modbus_address).await?;
+            backup
+                .save(file)
+                .map_err(|e| CliError::new(EX_CANTCREAT, e.to_string()))?;
+            Ok(Output::Done("Konfiguration gesichert"))
+        }
+        ("restore", Some(m)) => {
+            let file = m.value_of("file").unwrap_or_default();
+            let backup =
+                Backup::load(file).map_err(|e| CliError::new(EX_DATAERR, e.to_string()))?;
+            let other_device = m.is_present("other_device");
+            if m.is_present("dry_run") {
+                let changes = ne4_client
+                    .restore_diff(port, modbus_address, &backup, other_device)
+                    .await?;
+                Ok(Output::Changes("Unterschiede zur Sicherung", changes))
+            } else {
+                let changes = ne4_client
+                    .restore(port, modbus_address, &backup, other_device)
+                    .await?;
+                Ok(Output::Changes("Geschrieben und geprüft", changes))
+            }
+        }
+        ("info", _) => {
+            let info = ne4_client.device_info(port, modbus_address).await?;
+            Ok(Output::Info(info))
+        }
+        ("scan", _) => {
+            let connection = ne4_client.scan_connection(port)?;
+            let mut found = vec![];
+            for modbus_address in SCAN_ADDRESSES {
+                debug!("Scan Modbus address {}", modbus_address);
+                if let Some(result) = scan_address(&connection, modbus_address).await? {
+                    found.push(result);
+                }
+            }
+            Ok(Output::Scan(found))
+        }
+        ("nullpunkt", _) => {
+            ne4_client.nullpunkt(port, modbus_address).await?;
+            Ok(Output::Done("Nullpunkt erfolgreich gesetzt"))
+        }
+        ("messgas", _) => {
+            ne4_client.messgas(port, modbus_address).await?;
+            Ok(Output::Done("Messgas erfolgreich gesetzt"))
+        }
+        ("modbus-address", Some(m)) => {
+            let new_modbus_address: u8 = parse_arg(m, "new_address")?;
+            ne4_client
+                .new_modbus_address(port, modbus_address, new_modbus_address)
+                .await?;
+            Ok(Output::Done("Neue Modbus Adresse erfolgreich gesetzt"))
+        }
+        ("working-mode", Some(m)) => {
+            let working_mode: u16 = parse_arg(m, "mode")?;
+            if let WorkingMode::Unknown(_) = WorkingMode::from(working_mode) {
+                return Err(CliError::new(
+                    EX_USAGE,
+                    format!("Unbekannte Arbeitsweise: {}", working_mode),
+                ));
+            }
+            ne4_client
+                .new_working_mode(port, modbus_address, working_mode)
+                .await?;
+            Ok(Output::Done("Neue Arbeitsweise erfolgreich gesetzt"))
+        }
+        _ => Err(CliError::new(EX_USAGE, "Unbekanntes Kommando")),
+    }
+}
+
+/// Globale Argumente
+///
+/// clap überträgt die vor dem Kommando angegebenen globalen Argumente in das
+/// Kommando, nicht umgekehrt. Das Kommando kennt daher alle globalen Argumente.
+fn global_args<'a>(matches: &'a ArgMatches<'static>) -> &'a ArgMatches<'static> {
+    matches.subcommand().1.unwrap_or(matches)
+}
+
+/// Wert eines Arguments
+fn parse_arg<T: std::str::FromStr>(matches: &ArgMatches, name: &str) -> Result<T, CliError> {
+    let value = matches.value_of(name).unwrap_or_default();
+    value
+        .parse()
+        .map_err(|_| CliError::new(EX_USAGE, format!("Ungültiger Wert für {}: {}", name, value)))
+}
+
+/// Schnittstellenparameter aus den Argumenten
+fn line_settings(matches: &ArgMatches) -> Result<LineSettings, CliError> {
+    Ok(LineSettings {
+        baud_rate: parse_arg(matches, "baud_rate")?,
+        parity: match matches.value_of("parity") {
+            Some("even") => Parity::Even,
+            Some("odd") => Parity::Odd,
+            _ => Parity::None,
+        },
+        data_bits: match matches.value_of("data_bits") {
+            Some("7") => DataBits::Seven,
+            _ => DataBits::Eight,
+        },
+        stop_bits: match matches.value_of("stop_bits") {
+            Some("2") => StopBits::Two,
+            _ => StopBits::One,
+        },
+        timeout: Duration::from_millis(parse_arg(matches, "timeout")?),
+    })
+}
+
+fn register_json(reg: Option<&Register>, number: u16, raw: u16) -> Value {
+    match reg {
+        Some(reg) => json!({
+            "register": number,
+            "raw": raw,
+            "value": reg.scaled(raw),
+            "unit": reg.unit,
+            "description": reg.description,
+        }),
+        None => json!({ "register": number, "raw": raw }),
+    }
+}
+
+fn print_output(output: Output, json: bool) {
+    debug!("Print output, json: {}", json);
+    match output {
+        Output::Ports(ports) => {
+            if json {
+                println!("{}", json!(ports));
+            } else {
+                for port in ports {
+                    println!("{}", port);
+                }
+            }
+        }
+        Output::Registers(function, values) => {
+            let find = match function {
+                FunctionCode::ReadHoldingRegisters => find_rwreg,
+                FunctionCode::ReadInputRegisters => find_rreg,
+            };
+            if json {
+                let registers: Vec<Value> = values
+                    .iter()
+                    .map(|&(number, raw)| register_json(find(number), number, raw))
+                    .collect();
+                println!("{}", Value::Array(registers));
+            } else {
+                for (number, raw) in values {
+                    match find(number) {
+                        Some(reg) => println!(
+                            "{:>3}  {:>10} {:<5} {}",
+                            number,
+                            reg.format(raw),
+                            reg.unit,
+                            reg.description
+                        ),
+                        None => println!("{:>3}  {:>10}", number, raw),
+                    }
+                }
+            }
+        }
+        Output::Written(number, raw) => {
+            if json {
+                println!("{}", register_json(find_rwreg(number), number, raw));
+            } else {
+                let value = find_rwreg(number).map_or(i32::from(raw), |reg| reg.raw_to_value(raw));
+                if CONNECTION_REGISTERS.contains(&number) {
+                    println!(
+                        "Register {} geschrieben: {}, der Sensor antwortet nur noch mit den neuen Einstellungen",
+                        number, value
+                    );
+                } else {
+                    println!("Register {} geschrieben, zurückgelesen: {}", number, value);
+                }
+            }
+        }
+        Output::Changes(message, changes) => {
+            if json {
+                let changes: Vec<Value> = changes
+                    .iter()
+                    .map(|change| {
+                        json!({
+                            "register": change.number,
+                            "sensor": change.sensor,
+                            "backup": change.backup,
+                        })
+                    })
+                    .collect();
+                println!("{}", Value::Array(changes));
+            } else {
+                println!("{}: {} Register", message, changes.len());
+                for change in changes {
+                    println!("{}", change);
+                }
+            }
+        }
+        Output::Scan(found) => {
+            if json {
+                let sensors: Vec<Value> = found
+                    .iter()
+                    .map(|result| {
+                        json!({
+                            "modbus_address": result.modbus_address,
+                            "customer_code": result.customer_code,
+                            "working_mode": result.working_mode,
+                            "device_id": result.device_id,
+                            "software_date": result.software_date,
+                        })
+                    })
+                    .collect();
+                println!("{}", Value::Array(sensors));
+            } else {
+                println!("Adresse  Kundencode  Arbeitsweise  Geräte ID  Softwarestand");
+                for result in found {
+                    println!(
+                        "{:>7}  {:>10}  {:>12}  {:>9}  {:>13}",
+                        result.modbus_address,
+                        optional_value(result.customer_code),
+                        optional_value(result.working_mode),
+                        optional_value(result.device_id),
+                        optional_value(result.software_date)
+                    );
+                }
+            }
+        }
+        Output::Info(info) => {
+            if json {
+                println!(
+                    "{}",
+                    json!({
+                        "customer_code": info.customer_code,
+                        "working_mode": info.working_mode.map(|mode| mode.code()),
+                        "device_id": info.device_id,
+                        "factory_working_mode": info.factory_working_mode.map(|mode| mode.code()),
+                        "software_date": info.software_date,
+                        "software_date_decoded": info
+                            .software_date_decoded()
+                            .map(|date| date.format("%Y-%m-%d").to_string()),
+                        "power_on_count": info.power_on_count,
+                        "operating_hours": info.operating_hours,
+                    })
+                );
+            } else {
+                for (title, value) in info.rows() {
+                    println!("{:<24}{}", title, value);
+                }
+            }
+        }
+        Output::Done(message) => {
+            if json {
+                println!("{}", json!({ "result": message }));
+            } else {
+                println!("{}", message);
+            }
+        }
+    }
+}
+
+/// Registerwert, vom Sensor abgelehnte Register als "-"
+fn optional_value(value: Option<u16>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
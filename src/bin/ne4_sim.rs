@@ -0,0 +1,123 @@
+//! NE4 Simulator
+//!
+//! Simuliert einen 'NE4-MOD-BUS' Sensor auf einem Pseudoterminal. Die GUI und
+//! `ne4_cli` verbinden sich mit dem ausgegebenen Pfad wie mit einer echten
+//! Schnittstelle. Fehler (Timeouts, Exceptions, CRC Fehler) können mit einer
+//! Wahrscheinlichkeit pro Anfrage eingestreut werden.
+#[macro_use]
+extern crate log;
+
+use clap::{crate_version, App, Arg, ArgMatches};
+use std::process;
+
+const EX_USAGE: i32 = 64;
+const EX_OSERR: i32 = 71;
+
+fn app() -> App<'static, 'static> {
+    App::new("ne4_sim")
+        .version(crate_version!())
+        .about("Simuliert einen 'NE4-MOD-BUS' Sensor auf einem Pseudoterminal")
+        .arg(
+            Arg::with_name("address")
+                .short("a")
+                .long("address")
+                .takes_value(true)
+                .default_value("247")
+                .help("Modbus Adresse des Sensors"),
+        )
+        .arg(
+            Arg::with_name("link")
+                .short("l")
+                .long("link")
+                .takes_value(true)
+                .help("Symbolischer Link auf das Pseudoterminal, z.B. /tmp/ttyNE4"),
+        )
+        .arg(
+            Arg::with_name("gas")
+                .long("gas")
+                .takes_value(true)
+                .default_value("0")
+                .help("Gas am Sensor, AD-Wert über Frischluft"),
+        )
+        .arg(
+            Arg::with_name("timeout_rate")
+                .long("timeout-rate")
+                .takes_value(true)
+                .default_value("0")
+                .help("Anteil der Anfragen ohne Antwort (0.0 … 1.0)"),
+        )
+        .arg(
+            Arg::with_name("exception_rate")
+                .long("exception-rate")
+                .takes_value(true)
+                .default_value("0")
+                .help("Anteil der Anfragen mit Modbus Exception (0.0 … 1.0)"),
+        )
+        .arg(
+            Arg::with_name("crc_error_rate")
+                .long("crc-error-rate")
+                .takes_value(true)
+                .default_value("0")
+                .help("Anteil der Antworten mit CRC Fehler (0.0 … 1.0)"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .default_value("1")
+                .help("Startwert des Zufallsgenerators für Drift und Fehler"),
+        )
+}
+
+/// Wert eines Arguments, beendet das Programm bei ungültigen Werten
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_arg<T: std::str::FromStr>(matches: &ArgMatches, name: &str) -> T {
+    let value = matches.value_of(name).unwrap_or_default();
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("Ungültiger Wert für {}: {}", name, value);
+        process::exit(EX_USAGE);
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn main() {
+    use ne4_konfig::simulator::{model::Ne4Model, Faults, Simulator};
+
+    pretty_env_logger::init();
+    let matches = app().get_matches();
+
+    let mut model = Ne4Model::new(parse_arg(&matches, "address"));
+    model.set_gas_adc(parse_arg(&matches, "gas"));
+    let faults = Faults {
+        timeout: parse_arg(&matches, "timeout_rate"),
+        exception: parse_arg(&matches, "exception_rate"),
+        crc_error: parse_arg(&matches, "crc_error_rate"),
+    };
+
+    let simulator =
+        Simulator::new(model, faults, parse_arg(&matches, "seed")).unwrap_or_else(|e| {
+            eprintln!("Pseudoterminal konnte nicht erzeugt werden: {}", e);
+            process::exit(EX_OSERR);
+        });
+    println!("{}", simulator.path());
+    if let Some(link) = matches.value_of("link") {
+        let _ = std::fs::remove_file(link);
+        if let Err(e) = std::os::unix::fs::symlink(simulator.path(), link) {
+            eprintln!("Link {} konnte nicht erzeugt werden: {}", link, e);
+            process::exit(EX_OSERR);
+        }
+        info!("Link {} -> {}", link, simulator.path());
+    }
+
+    if let Err(e) = simulator.run() {
+        eprintln!("Fehler: {}", e);
+        process::exit(EX_OSERR);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    let _ = app().get_matches();
+    eprintln!("Der Simulator benötigt ein Pseudoterminal und läuft nur unter Linux");
+    process::exit(EX_OSERR);
+}
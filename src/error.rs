@@ -0,0 +1,332 @@
+/// Error type of the crate
+///
+/// Every failure of a Modbus command ends up in one of these variants. The
+/// `Display` implementation is the German message shown in the GUI and by
+/// `ne4_cli`.
+use std::{fmt, io};
+
+/// Result with the crate error
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Modbus exception codes
+///
+/// The NE4 answers with an exception instead of the requested values, e.g.
+/// when a register doesn't exist or a value is out of range.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Exception {
+    IllegalFunction,
+    IllegalDataAddress,
+    IllegalDataValue,
+    ServerDeviceFailure,
+    Acknowledge,
+    ServerDeviceBusy,
+    MemoryParityError,
+    GatewayPathUnavailable,
+    GatewayTargetDevice,
+    /// Exception which tokio-modbus reports with an unknown text
+    Unknown(String),
+}
+
+/// Descriptions used by tokio-modbus, see `Exception::from_description`
+const EXCEPTIONS: &[(Exception, u8, &str)] = &[
+    (Exception::IllegalFunction, 0x01, "Illegal function"),
+    (Exception::IllegalDataAddress, 0x02, "Illegal data address"),
+    (Exception::IllegalDataValue, 0x03, "Illegal data value"),
+    (
+        Exception::ServerDeviceFailure,
+        0x04,
+        "Server device failure",
+    ),
+    (Exception::Acknowledge, 0x05, "Acknowledge"),
+    (Exception::ServerDeviceBusy, 0x06, "Server device busy"),
+    (Exception::MemoryParityError, 0x08, "Memory parity error"),
+    (
+        Exception::GatewayPathUnavailable,
+        0x0A,
+        "Gateway path unavailable",
+    ),
+    (
+        Exception::GatewayTargetDevice,
+        0x0B,
+        "Gateway target device failed to respond",
+    ),
+];
+
+/// Prefix of the exception messages of tokio-modbus, "Modbus function 3: …"
+const EXCEPTION_PREFIX: &str = "Modbus function";
+
+impl Exception {
+    /// Exception from the description tokio-modbus puts into the message
+    pub fn from_description(description: &str) -> Self {
+        let description = description.trim();
+        EXCEPTIONS
+            .iter()
+            .find(|(_, _, text)| text.eq_ignore_ascii_case(description))
+            .map(|(exception, _, _)| exception.clone())
+            .unwrap_or_else(|| Exception::Unknown(description.to_string()))
+    }
+
+    /// Exception code, `None` if unknown
+    pub fn code(&self) -> Option<u8> {
+        EXCEPTIONS
+            .iter()
+            .find(|(exception, _, _)| exception == self)
+            .map(|(_, code, _)| *code)
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            Exception::IllegalFunction => "Funktion wird nicht unterstützt",
+            Exception::IllegalDataAddress => "Register existiert nicht",
+            Exception::IllegalDataValue => "Ungültiger Wert",
+            Exception::ServerDeviceFailure => "Gerätefehler",
+            Exception::Acknowledge => "Anfrage angenommen, Bearbeitung läuft",
+            Exception::ServerDeviceBusy => "Gerät ist beschäftigt",
+            Exception::MemoryParityError => "Paritätsfehler im Speicher",
+            Exception::GatewayPathUnavailable => "Gateway Pfad nicht verfügbar",
+            Exception::GatewayTargetDevice => "Gerät hinter dem Gateway antwortet nicht",
+            Exception::Unknown(text) => return write!(f, "Unbekannte Exception: {}", text),
+        };
+        match self.code() {
+            Some(code) => write!(f, "{} (Exception {})", text, code),
+            None => write!(f, "{}", text),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// No serial port selected or available
+    NoPort,
+    /// The serial port couldn't be opened
+    Open { port: String, source: io::Error },
+    /// The sensor didn't answer within the response timeout
+    Timeout,
+    /// Garbled answer, CRC or framing error
+    InvalidFrame(String),
+    /// The sensor answered with a Modbus exception
+    Exception(Exception),
+    /// The register can only be written after unlocking the sensor (Rwreg 49)
+    Locked { register: u16 },
+    /// Value outside of the valid range of the register
+    OutOfRange {
+        register: u16,
+        value: i32,
+        range: String,
+    },
+    /// The register isn't in the Rwreg table
+    UnknownRegister(u16),
+    /// The register can't be written
+    ReadOnly(u16),
+    /// The read back value differs from the written value
+    ReadBack {
+        register: u16,
+        written: u16,
+        read: Option<u16>,
+    },
+    /// Registers which still differ after restoring a backup
+    NotRestored(Vec<u16>),
+    /// The value is a command for the register, e.g. 11111 in Rwreg 10
+    CommandValue { register: u16, value: u16 },
+    /// The backup was made from another sensor, device ids (Rwreg 98) differ
+    OtherDevice {
+        backup: Option<u16>,
+        sensor: Option<u16>,
+    },
+    /// Some of the requested registers are missing in the answer
+    Incomplete(&'static str),
+    /// Cancelled by the user, e.g. the bus scan
+    Cancelled,
+    /// Any other I/O error
+    Io(io::Error),
+}
+
+impl Error {
+    /// The sensor answered with a Modbus exception
+    ///
+    /// The connection itself is fine in this case.
+    pub fn is_exception(&self) -> bool {
+        match self {
+            Error::Exception(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Nothing or garbage answered, e.g. no sensor with this Modbus address
+    pub fn is_no_answer(&self) -> bool {
+        match self {
+            Error::Timeout | Error::InvalidFrame(_) => true,
+            _ => false,
+        }
+    }
+
+    /// A write to a register which needs unlocking failed
+    ///
+    /// The NE4 rejects writes to locked registers with "server device failure".
+    pub fn locked(self, register: u16) -> Self {
+        match self {
+            Error::Exception(Exception::ServerDeviceFailure) => Error::Locked { register },
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoPort => write!(f, "Keine serielle Schnittstelle ausgewählt"),
+            Error::Open { port, source } => write!(
+                f,
+                "Schnittstelle {} konnte nicht geöffnet werden: {}",
+                port, source
+            ),
+            Error::Timeout => write!(f, "Der Sensor antwortet nicht (Zeitüberschreitung)"),
+            Error::InvalidFrame(e) => write!(f, "Fehlerhafte Antwort (CRC oder Rahmen): {}", e),
+            Error::Exception(exception) => write!(f, "Der Sensor meldet: {}", exception),
+            Error::Locked { register } => write!(
+                f,
+                "Register {} ist gesperrt, der Sensor konnte nicht entsperrt werden",
+                register
+            ),
+            Error::OutOfRange {
+                register,
+                value,
+                range,
+            } => write!(
+                f,
+                "Wert {} liegt nicht im Wertebereich {} von Register {}",
+                value, range, register
+            ),
+            Error::UnknownRegister(register) => write!(f, "Register {} ist kein Rwreg", register),
+            Error::ReadOnly(register) => write!(f, "Register {} ist schreibgeschützt", register),
+            Error::ReadBack {
+                register,
+                written,
+                read: Some(read),
+            } => write!(
+                f,
+                "Register {}: geschrieben {}, zurückgelesen {}",
+                register, written, read
+            ),
+            Error::ReadBack { register, .. } => {
+                write!(f, "Register {} konnte nicht zurückgelesen werden", register)
+            }
+            Error::NotRestored(registers) => {
+                let registers: Vec<String> = registers.iter().map(|r| r.to_string()).collect();
+                write!(
+                    f,
+                    "Register {} stimmen nach dem Wiederherstellen nicht überein",
+                    registers.join(", ")
+                )
+            }
+            Error::CommandValue { register, value } => write!(
+                f,
+                "Wert {} ist bei Register {} ein Kommando und wird nicht wiederhergestellt",
+                value, register
+            ),
+            Error::OtherDevice { backup, sensor } => {
+                let id = |id: &Option<u16>| id.map_or_else(|| "-".to_string(), |id| id.to_string());
+                write!(
+                    f,
+                    "Die Sicherung stammt von einem anderen Sensor (Geräte ID {}, angeschlossen {})",
+                    id(backup),
+                    id(sensor)
+                )
+            }
+            Error::Incomplete(what) => write!(f, "{} unvollständig gelesen", what),
+            Error::Cancelled => write!(f, "Abgebrochen"),
+            Error::Io(e) => write!(f, "Ein-/Ausgabefehler: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Open { source, .. } => Some(source),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Classify the I/O errors of tokio-modbus and tokio-serial
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::TimedOut => Error::Timeout,
+            io::ErrorKind::InvalidData => Error::InvalidFrame(e.to_string()),
+            io::ErrorKind::Other => {
+                let message = e.to_string();
+                // "Modbus function 3: Illegal data address"
+                if message.starts_with(EXCEPTION_PREFIX) {
+                    let description = message.splitn(2, ':').nth(1).unwrap_or_default();
+                    Error::Exception(Exception::from_description(description))
+                } else {
+                    Error::Io(e)
+                }
+            }
+            _ => Error::Io(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exception_from_io_error() {
+        let e = io::Error::new(
+            io::ErrorKind::Other,
+            "Modbus function 3: Illegal data address",
+        );
+        let e = Error::from(e);
+        assert!(e.is_exception());
+        assert_eq!(
+            e.to_string(),
+            "Der Sensor meldet: Register existiert nicht (Exception 2)"
+        );
+
+        let e = io::Error::new(io::ErrorKind::Other, "Modbus function 6: Something new");
+        match Error::from(e) {
+            Error::Exception(exception) => {
+                assert_eq!(exception, Exception::Unknown("Something new".to_string()));
+                assert_eq!(exception.code(), None);
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn exception_codes() {
+        for (exception, code, description) in EXCEPTIONS {
+            assert_eq!(&Exception::from_description(description), exception);
+            assert_eq!(exception.code(), Some(*code));
+        }
+    }
+
+    #[test]
+    fn classify_io_errors() {
+        let timeout = Error::from(io::Error::new(io::ErrorKind::TimedOut, "Timeout"));
+        assert!(timeout.is_no_answer());
+        let crc = Error::from(io::Error::new(io::ErrorKind::InvalidData, "Invalid CRC"));
+        assert!(crc.is_no_answer());
+        assert!(!crc.is_exception());
+        let other = Error::from(io::Error::new(io::ErrorKind::Other, "Broken pipe"));
+        assert_eq!(other.to_string(), "Ein-/Ausgabefehler: Broken pipe");
+    }
+
+    #[test]
+    fn locked() {
+        let e = Error::Exception(Exception::ServerDeviceFailure).locked(50);
+        assert_eq!(
+            e.to_string(),
+            "Register 50 ist gesperrt, der Sensor konnte nicht entsperrt werden"
+        );
+        let e = Error::Exception(Exception::IllegalDataValue).locked(50);
+        assert!(e.is_exception());
+    }
+}
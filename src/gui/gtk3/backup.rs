@@ -0,0 +1,122 @@
+use crate::sensors::backup::{Backup, Change};
+/// Dialogs for backup and restore of the sensor configuration
+use gtk::prelude::*;
+use std::path::PathBuf;
+
+/// File filter for backup files
+fn json_filter() -> gtk::FileFilter {
+    let filter = gtk::FileFilter::new();
+    filter.set_name(Some("NE4 Sicherung (*.json)"));
+    filter.add_pattern("*.json");
+    filter
+}
+
+/// Ask for the file name and save the backup
+///
+/// Returns the path of the saved file, `None` if the user cancelled.
+pub fn save_backup<W: IsA<gtk::Window>>(
+    window: &W,
+    backup: &Backup,
+) -> Result<Option<PathBuf>, String> {
+    let dialog = gtk::FileChooserDialog::with_buttons(
+        Some("Konfiguration sichern"),
+        Some(window),
+        gtk::FileChooserAction::Save,
+        &[
+            ("_Abbrechen", gtk::ResponseType::Cancel),
+            ("_Speichern", gtk::ResponseType::Accept),
+        ],
+    );
+    dialog.set_do_overwrite_confirmation(true);
+    dialog.set_current_name(&backup.file_name());
+    dialog.add_filter(&json_filter());
+
+    let path = match dialog.run() {
+        gtk::ResponseType::Accept => dialog.get_filename(),
+        _ => None,
+    };
+    dialog.close();
+
+    match path {
+        Some(path) => backup
+            .save(&path)
+            .map(|_| Some(path))
+            .map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+/// Ask for a backup file and load it
+///
+/// Returns `None` if the user cancelled.
+pub fn open_backup<W: IsA<gtk::Window>>(window: &W) -> Result<Option<Backup>, String> {
+    let dialog = gtk::FileChooserDialog::with_buttons(
+        Some("Konfiguration wiederherstellen"),
+        Some(window),
+        gtk::FileChooserAction::Open,
+        &[
+            ("_Abbrechen", gtk::ResponseType::Cancel),
+            ("_Öffnen", gtk::ResponseType::Accept),
+        ],
+    );
+    dialog.add_filter(&json_filter());
+
+    let path = match dialog.run() {
+        gtk::ResponseType::Accept => dialog.get_filename(),
+        _ => None,
+    };
+    dialog.close();
+
+    match path {
+        Some(path) => Backup::load(&path).map(Some).map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+/// Show the differences between backup and sensor and ask whether to write them
+pub fn confirm_restore<W: IsA<gtk::Window>>(
+    window: &W,
+    backup: &Backup,
+    changes: &[Change],
+) -> bool {
+    let dialog = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Question,
+        gtk::ButtonsType::OkCancel,
+        &format!(
+            "{} Register unterscheiden sich von der Sicherung vom {}",
+            changes.len(),
+            backup.timestamp
+        ),
+    );
+    let changes: Vec<String> = changes.iter().map(|change| change.to_string()).collect();
+    dialog.set_property_secondary_text(Some(&format!(
+        "Sensor → Sicherung\n\n{}\n\nSollen die Register geschrieben werden?",
+        changes.join("\n")
+    )));
+
+    let response = dialog.run();
+    dialog.close();
+    response == gtk::ResponseType::Ok
+}
+
+/// The backup comes from another sensor, ask whether to compare it anyway
+///
+/// `message` describes both device ids, see `Error::OtherDevice`.
+pub fn confirm_other_device<W: IsA<gtk::Window>>(window: &W, message: &str) -> bool {
+    let dialog = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Warning,
+        gtk::ButtonsType::OkCancel,
+        message,
+    );
+    dialog.set_property_secondary_text(Some(
+        "Soll die Konfiguration trotzdem auf diesen Sensor übertragen werden?",
+    ));
+
+    let response = dialog.run();
+    dialog.close();
+    response == gtk::ResponseType::Ok
+}
@@ -0,0 +1,211 @@
+use crate::error::Result;
+use crate::tokio_thread::bus_scan::{ScanResult, SCAN_ADDRESSES};
+/// Bus scan page
+///
+/// Lists all sensors which answered on the selected serial port. A found sensor
+/// can be selected and connected.
+use gtk::prelude::*;
+use std::{cell::Cell, rc::Rc};
+
+#[derive(Clone)]
+pub struct BusScanUi {
+    store: gtk::ListStore,
+    treeview: gtk::TreeView,
+    button_scan: gtk::Button,
+    button_connect: gtk::Button,
+    progress_bar: gtk::ProgressBar,
+    scanning: Rc<Cell<bool>>,
+}
+
+impl BusScanUi {
+    pub fn new() -> Self {
+        let store = gtk::ListStore::new(&[
+            glib::Type::U32,
+            glib::Type::String,
+            glib::Type::String,
+            glib::Type::String,
+            glib::Type::String,
+        ]);
+
+        BusScanUi {
+            treeview: gtk::TreeView::with_model(&store),
+            store,
+            button_scan: gtk::Button::with_label("Bus scannen"),
+            button_connect: gtk::Button::with_label("Verbinden"),
+            progress_bar: gtk::ProgressBar::new(),
+            scanning: Rc::new(Cell::new(false)),
+        }
+    }
+
+    /// Build the bus scan page
+    ///
+    /// `on_scan` starts a new scan, `on_cancel` cancels the running scan and
+    /// `on_connect` is called with the Modbus address of the selected sensor.
+    pub fn build_ui<S, C, A>(&self, on_scan: S, on_cancel: C, on_connect: A) -> gtk::Box
+    where
+        S: Fn() + 'static,
+        C: Fn() + 'static,
+        A: Fn(u8) + 'static,
+    {
+        for (i, title) in [
+            "Modbus Adresse",
+            "Kundencode",
+            "Arbeitsweise",
+            "Geräte ID",
+            "Softwarestand",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            column.set_sort_column_id(i as i32);
+
+            let renderer = gtk::CellRendererText::new();
+            column.pack_end(&renderer, true);
+            column.add_attribute(&renderer, "text", i as i32);
+
+            self.treeview.append_column(&column);
+        }
+
+        let scrolled_window = gtk::ScrolledWindow::new(gtk::NONE_ADJUSTMENT, gtk::NONE_ADJUSTMENT);
+        scrolled_window.set_vexpand(true);
+        scrolled_window.add(&self.treeview);
+
+        self.progress_bar.set_show_text(true);
+        self.progress_bar.set_hexpand(true);
+        self.button_connect.set_sensitive(false);
+
+        let bus_scan_ui = self.clone();
+        self.button_scan.connect_clicked(move |_| {
+            if bus_scan_ui.scanning.get() {
+                on_cancel();
+            } else {
+                bus_scan_ui.scan_started();
+                on_scan();
+            }
+        });
+
+        let bus_scan_ui = self.clone();
+        self.treeview
+            .get_selection()
+            .connect_changed(move |_| bus_scan_ui.update_button_connect());
+
+        let bus_scan_ui = self.clone();
+        self.button_connect.connect_clicked(move |_| {
+            if let Some(modbus_address) = bus_scan_ui.selected_address() {
+                on_connect(modbus_address);
+            }
+        });
+        let button_connect = self.button_connect.clone();
+        self.treeview
+            .connect_row_activated(move |_, _, _| button_connect.clicked());
+
+        let box_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        box_buttons.pack_start(&self.button_scan, false, false, 0);
+        box_buttons.pack_start(&self.progress_bar, true, true, 0);
+        box_buttons.pack_end(&self.button_connect, false, false, 0);
+
+        let box_main = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        box_main.set_border_width(6);
+        box_main.pack_start(&box_buttons, false, false, 0);
+        box_main.pack_start(&scrolled_window, true, true, 0);
+
+        box_main
+    }
+
+    /// Reset the result list for a new scan
+    fn scan_started(&self) {
+        self.scanning.set(true);
+        self.store.clear();
+        self.button_scan.set_label("Abbrechen");
+        self.progress_bar.set_fraction(0.0);
+        self.progress_bar.set_text(Some("Scan gestartet"));
+        self.update_button_connect();
+    }
+
+    /// One address was probed
+    pub fn progress(&self, modbus_address: u8, result: Option<ScanResult>) {
+        let last = *SCAN_ADDRESSES.end();
+        self.progress_bar
+            .set_fraction(f64::from(modbus_address) / f64::from(last));
+        self.progress_bar
+            .set_text(Some(&format!("Adresse {} von {}", modbus_address, last)));
+
+        if let Some(result) = result {
+            self.store.insert_with_values(
+                None,
+                &[0, 1, 2, 3, 4],
+                &[
+                    &u32::from(result.modbus_address),
+                    &optional_value(result.customer_code),
+                    &optional_value(result.working_mode),
+                    &optional_value(result.device_id),
+                    &optional_value(result.software_date),
+                ],
+            );
+        }
+    }
+
+    /// The scan has ended, completely or cancelled
+    pub fn finished(&self, result: &Result<()>) {
+        self.scanning.set(false);
+        self.button_scan.set_label("Bus scannen");
+        let found = self.store.iter_n_children(None);
+        match result {
+            Ok(()) => {
+                self.progress_bar.set_fraction(1.0);
+                self.progress_bar
+                    .set_text(Some(&format!("{} Sensor(en) gefunden", found)));
+            }
+            Err(e) => {
+                self.progress_bar
+                    .set_text(Some(&format!("{}, {} Sensor(en) gefunden", e, found)));
+            }
+        }
+        self.update_button_connect();
+    }
+
+    /// The bus can't be scanned while connected
+    pub fn set_sensitive(&self, sensitive: bool) {
+        self.button_scan.set_sensitive(sensitive);
+        self.update_button_connect();
+    }
+
+    /// A scan is running, the serial port is in use
+    pub fn is_scanning(&self) -> bool {
+        self.scanning.get()
+    }
+
+    /// Modbus addresses of all found sensors, in the order of the list
+    pub fn found_addresses(&self) -> Vec<u8> {
+        let mut addresses = vec![];
+        self.store.foreach(|model, _, iter| {
+            if let Ok(Some(modbus_address)) = model.get_value(iter, 0).get::<u32>() {
+                addresses.push(modbus_address as u8);
+            }
+            false
+        });
+        addresses
+    }
+
+    /// Modbus address of the selected sensor
+    fn selected_address(&self) -> Option<u8> {
+        let (model, iter) = self.treeview.get_selection().get_selected()?;
+        let modbus_address = model.get_value(&iter, 0).get::<u32>().ok()??;
+        Some(modbus_address as u8)
+    }
+
+    fn update_button_connect(&self) {
+        self.button_connect.set_sensitive(
+            self.button_scan.get_sensitive()
+                && !self.scanning.get()
+                && self.selected_address().is_some(),
+        );
+    }
+}
+
+/// Value of a register, registers the sensor rejected are shown as "-"
+fn optional_value(value: Option<u16>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
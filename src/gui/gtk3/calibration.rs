@@ -0,0 +1,582 @@
+use crate::error::Result;
+use crate::sensors::calibration::{
+    CalibrationPoints, CalibrationStep, StabilityMonitor, CALIBRATION_RREG, DEFAULT_STABLE_TIME,
+    DEFAULT_TOLERANCE,
+};
+use crate::sensors::calibration_report::{
+    CalibrationRecord, CalibrationReport, CalibrationSnapshot,
+};
+use crate::sensors::device_info::DeviceInfo;
+/// Calibration wizard
+///
+/// Guides the technician through the zero point or span gas calibration. The
+/// command is only sent after the live sensor AD value has been stable within the
+/// tolerance for the selected time. The old and new calibration points are shown
+/// at the end. The wizard can be aborted on every page until the command is sent.
+///
+/// All calibrations of one session are collected in a calibration report, which
+/// can be saved as HTML and JSON on the result page.
+use crate::sensors::ra_gas_ne4::NE4;
+use chrono::{DateTime, Local};
+use gtk::prelude::*;
+use std::{cell::RefCell, rc::Rc, time::Duration, time::Instant};
+
+/// Pages of the wizard
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Page {
+    /// Old calibration points are read, tolerance and time are selected
+    Prepare,
+    /// Waiting for a stable reading
+    Stabilize,
+    /// Command sent, waiting for the result
+    Running,
+    Result,
+}
+
+impl Page {
+    fn name(self) -> &'static str {
+        match self {
+            Page::Prepare => "prepare",
+            Page::Stabilize => "stabilize",
+            Page::Running => "running",
+            Page::Result => "result",
+        }
+    }
+}
+
+const PREPARE_ZERO: &str = "Beim Nullpunktabgleich wird der aktuelle AD-Wert des Sensors als \
+                            Nullpunkt übernommen.";
+const PREPARE_SPAN: &str = "Beim Messgasabgleich wird der aktuelle AD-Wert des Sensors als \
+                            Kalibrierpunkt für die angegebene Prüfgaskonzentration übernommen.";
+
+/// State of a running calibration
+struct Calibration {
+    step: CalibrationStep,
+    page: Page,
+    modbus_address: u8,
+    started: DateTime<Local>,
+    /// Identity of the sensor, read when the wizard is opened
+    device_info: Option<DeviceInfo>,
+    old: Option<CalibrationPoints>,
+    monitor: StabilityMonitor,
+    report: Option<CalibrationReport>,
+}
+
+#[derive(Clone)]
+pub struct CalibrationUi {
+    dialog: gtk::Dialog,
+    stack: gtk::Stack,
+    button_cancel: gtk::Button,
+    button_next: gtk::Button,
+    button_save: gtk::Button,
+    button_span: gtk::Button,
+    label_prepare: gtk::Label,
+    label_points: gtk::Label,
+    box_concentration: gtk::Box,
+    spin_button_concentration: gtk::SpinButton,
+    spin_button_tolerance: gtk::SpinButton,
+    spin_button_stable_time: gtk::SpinButton,
+    entry_operator: gtk::Entry,
+    label_instruction: gtk::Label,
+    label_adc: gtk::Label,
+    label_gas: gtk::Label,
+    progress_bar: gtk::ProgressBar,
+    label_running: gtk::Label,
+    label_result: gtk::Label,
+    grid_result: gtk::Grid,
+    calibration: Rc<RefCell<Option<Calibration>>>,
+}
+
+impl CalibrationUi {
+    pub fn new() -> Self {
+        CalibrationUi {
+            dialog: gtk::Dialog::new(),
+            stack: gtk::Stack::new(),
+            button_cancel: gtk::Button::with_label("Abbrechen"),
+            button_next: gtk::Button::with_label("Weiter"),
+            button_save: gtk::Button::with_label("Protokoll speichern"),
+            button_span: gtk::Button::with_label("Messgas abgleichen"),
+            label_prepare: gtk::Label::new(None),
+            label_points: gtk::Label::new(None),
+            box_concentration: gtk::Box::new(gtk::Orientation::Horizontal, 6),
+            spin_button_concentration: gtk::SpinButton::with_range(1.0, 10000.0, 1.0),
+            spin_button_tolerance: gtk::SpinButton::with_range(1.0, 1000.0, 1.0),
+            spin_button_stable_time: gtk::SpinButton::with_range(5.0, 600.0, 5.0),
+            entry_operator: gtk::Entry::new(),
+            label_instruction: gtk::Label::new(None),
+            label_adc: gtk::Label::new(Some("-")),
+            label_gas: gtk::Label::new(Some("-")),
+            progress_bar: gtk::ProgressBar::new(),
+            label_running: gtk::Label::new(None),
+            label_result: gtk::Label::new(None),
+            grid_result: gtk::Grid::new(),
+            calibration: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Build the wizard dialog
+    ///
+    /// `window` is the parent of the dialogs. `on_calibrate` sends the zero point
+    /// or span gas command once the reading is stable.
+    pub fn build_ui<W, C>(&self, window: &W, on_calibrate: C)
+    where
+        W: IsA<gtk::Window>,
+        C: Fn(CalibrationStep) + 'static,
+    {
+        self.dialog.set_transient_for(Some(window));
+        self.dialog.set_modal(true);
+        self.dialog.set_default_size(480, -1);
+
+        // Vorbereitung
+        self.label_prepare.set_line_wrap(true);
+        self.label_prepare.set_xalign(0.0);
+        self.label_points.set_xalign(0.0);
+        self.label_points.set_selectable(true);
+        self.spin_button_concentration.set_value(100.0);
+        self.spin_button_tolerance
+            .set_value(f64::from(DEFAULT_TOLERANCE));
+        self.spin_button_stable_time
+            .set_value(DEFAULT_STABLE_TIME.as_secs() as f64);
+        // Nur beim Messgasabgleich sichtbar
+        let label_concentration = gtk::Label::new(Some("Prüfgaskonzentration [ppm]"));
+        label_concentration.show();
+        self.spin_button_concentration.show();
+        self.box_concentration
+            .pack_start(&label_concentration, false, false, 0);
+        self.box_concentration
+            .pack_end(&self.spin_button_concentration, false, false, 0);
+        self.box_concentration.set_no_show_all(true);
+        let grid_settings = gtk::Grid::new();
+        grid_settings.set_row_spacing(6);
+        grid_settings.set_column_spacing(12);
+        for (row, (title, spin_button)) in [
+            ("Toleranz [AD-Wert]", &self.spin_button_tolerance),
+            ("Stabil für [s]", &self.spin_button_stable_time),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let label = gtk::Label::new(Some(*title));
+            label.set_xalign(0.0);
+            label.set_hexpand(true);
+            grid_settings.attach(&label, 0, row as i32, 1, 1);
+            grid_settings.attach(*spin_button, 1, row as i32, 1, 1);
+        }
+        let label_operator = gtk::Label::new(Some("Prüfer"));
+        label_operator.set_xalign(0.0);
+        grid_settings.attach(&label_operator, 0, 2, 1, 1);
+        grid_settings.attach(&self.entry_operator, 1, 2, 1, 1);
+        let box_prepare = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        box_prepare.pack_start(&self.label_prepare, false, false, 0);
+        box_prepare.pack_start(&self.label_points, false, false, 0);
+        box_prepare.pack_start(&self.box_concentration, false, false, 0);
+        box_prepare.pack_start(&grid_settings, false, false, 0);
+        self.stack.add_named(&box_prepare, Page::Prepare.name());
+
+        // Stabilisierung
+        self.label_instruction.set_line_wrap(true);
+        self.label_instruction.set_xalign(0.0);
+        self.progress_bar.set_show_text(true);
+        let grid_live = gtk::Grid::new();
+        grid_live.set_row_spacing(6);
+        grid_live.set_column_spacing(12);
+        for (row, (title, label_value)) in [
+            ("AD-Wert des Sensors", &self.label_adc),
+            ("Gaskonzentration [ppm]", &self.label_gas),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let label = gtk::Label::new(Some(*title));
+            label.set_xalign(0.0);
+            label.get_style_context().add_class("dim-label");
+            label_value.set_xalign(0.0);
+            grid_live.attach(&label, 0, row as i32, 1, 1);
+            grid_live.attach(*label_value, 1, row as i32, 1, 1);
+        }
+        let box_stabilize = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        box_stabilize.pack_start(&self.label_instruction, false, false, 0);
+        box_stabilize.pack_start(&grid_live, false, false, 0);
+        box_stabilize.pack_start(&self.progress_bar, false, false, 0);
+        self.stack.add_named(&box_stabilize, Page::Stabilize.name());
+
+        // Abgleich läuft
+        let spinner = gtk::Spinner::new();
+        spinner.start();
+        let box_running = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        box_running.pack_start(&spinner, false, false, 0);
+        box_running.pack_start(&self.label_running, false, false, 0);
+        self.stack.add_named(&box_running, Page::Running.name());
+
+        // Ergebnis
+        self.label_result.set_line_wrap(true);
+        self.label_result.set_xalign(0.0);
+        self.grid_result.set_row_spacing(6);
+        self.grid_result.set_column_spacing(12);
+        let box_result = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        box_result.pack_start(&self.label_result, false, false, 0);
+        box_result.pack_start(&self.grid_result, false, false, 0);
+        self.stack.add_named(&box_result, Page::Result.name());
+
+        let box_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        box_buttons.pack_end(&self.button_next, false, false, 0);
+        box_buttons.pack_end(&self.button_cancel, false, false, 0);
+        box_buttons.pack_start(&self.button_save, false, false, 0);
+        box_buttons.pack_start(&self.button_span, false, false, 0);
+        // Nur auf der Ergebnisseite sichtbar
+        self.button_save.set_no_show_all(true);
+        self.button_span.set_no_show_all(true);
+
+        let content = self.dialog.get_content_area();
+        content.set_spacing(12);
+        content.set_border_width(12);
+        content.pack_start(&self.stack, true, true, 0);
+        content.pack_end(&box_buttons, false, false, 0);
+
+        let calibration_ui = self.clone();
+        self.button_cancel
+            .connect_clicked(move |_| calibration_ui.abort());
+        let calibration_ui = self.clone();
+        self.dialog.connect_delete_event(move |_, _| {
+            // Ein gesendeter Befehl kann nicht mehr abgebrochen werden
+            if calibration_ui.page() != Some(Page::Running) {
+                calibration_ui.abort();
+            }
+            gtk::Inhibit(true)
+        });
+        let calibration_ui = self.clone();
+        let window = window.clone().upcast::<gtk::Window>();
+        self.button_save
+            .connect_clicked(move |_| calibration_ui.save(&window));
+        let calibration_ui = self.clone();
+        self.button_span
+            .connect_clicked(move |_| calibration_ui.continue_with_span());
+        let calibration_ui = self.clone();
+        self.button_next
+            .connect_clicked(move |_| match calibration_ui.page() {
+                Some(Page::Prepare) => calibration_ui.stabilize(),
+                Some(Page::Stabilize) => {
+                    if let Some(step) = calibration_ui.run() {
+                        on_calibrate(step);
+                    }
+                }
+                _ => calibration_ui.abort(),
+            });
+    }
+
+    /// Open the wizard
+    ///
+    /// The caller reads identity and calibration points of the sensor, the result
+    /// is passed to `prepared`.
+    pub fn start(&self, step: CalibrationStep, modbus_address: u8) {
+        self.dialog.set_title(&format!("{} abgleichen", step));
+        self.label_prepare.set_text(match step {
+            CalibrationStep::Zero => PREPARE_ZERO,
+            CalibrationStep::Span { .. } => PREPARE_SPAN,
+        });
+        self.label_points
+            .set_text("Aktuelle Kalibrierpunkte werden gelesen …");
+        self.box_concentration
+            .set_visible(step != CalibrationStep::Zero);
+
+        *self.calibration.borrow_mut() = Some(Calibration {
+            step,
+            page: Page::Prepare,
+            modbus_address,
+            started: Local::now(),
+            device_info: None,
+            old: None,
+            monitor: StabilityMonitor::new(0.0, Duration::from_secs(0)),
+            report: None,
+        });
+        self.show_page(Page::Prepare);
+        self.button_next.set_sensitive(false);
+        self.dialog.show_all();
+        self.dialog.present();
+    }
+
+    /// Identity and calibration points read before the calibration
+    pub fn prepared(&self, result: Result<(DeviceInfo, CalibrationSnapshot)>) {
+        let mut calibration = self.calibration.borrow_mut();
+        let calibration = match &mut *calibration {
+            Some(calibration) if calibration.page == Page::Prepare => calibration,
+            _ => return,
+        };
+        match result {
+            Ok((device_info, snapshot)) => {
+                self.show_points(&snapshot.points);
+                // Prüfgas höchstens bis zum Messbereichsendwert des Gases
+                let full_scale = device_info
+                    .working_mode
+                    .and_then(|mode| mode.gas())
+                    .map_or(10000, |gas| gas.full_scale);
+                self.spin_button_concentration
+                    .set_range(1.0, f64::from(full_scale));
+                if snapshot.points.span_ppm > 0 {
+                    self.spin_button_concentration
+                        .set_value(f64::from(snapshot.points.span_ppm));
+                }
+                calibration.device_info = Some(device_info);
+                calibration.old = Some(snapshot.points);
+                self.button_next.set_sensitive(true);
+            }
+            Err(e) => {
+                self.label_points.set_text(&format!(
+                    "Kalibrierpunkte konnten nicht gelesen werden: {}",
+                    e
+                ));
+            }
+        }
+    }
+
+    /// Live values of a sensor update
+    pub fn update(&self, ne4: &NE4) {
+        let mut calibration = self.calibration.borrow_mut();
+        let calibration = match &mut *calibration {
+            Some(calibration) if calibration.page == Page::Stabilize => calibration,
+            _ => return,
+        };
+        let adc = match ne4.rreg(CALIBRATION_RREG) {
+            Some(adc) => adc,
+            None => return,
+        };
+        calibration.monitor.push(Instant::now(), f64::from(adc));
+        self.label_adc.set_text(&adc.to_string());
+        self.label_gas.set_text(&match ne4.rreg(2) {
+            Some(_) => ne4.concentration_gas().to_string(),
+            None => "–".to_string(),
+        });
+
+        let stable = calibration.monitor.is_stable();
+        self.progress_bar
+            .set_fraction(calibration.monitor.progress());
+        self.progress_bar.set_text(Some(&if stable {
+            "Messwert stabil".to_string()
+        } else {
+            format!(
+                "Stabil seit {} s von {} s",
+                calibration.monitor.stable_for().as_secs(),
+                calibration.monitor.stable_time().as_secs()
+            )
+        }));
+        self.button_next.set_sensitive(stable);
+    }
+
+    /// Result of the calibration command
+    pub fn calibrated(&self, step: CalibrationStep, result: Result<CalibrationRecord>) {
+        let mut calibration = self.calibration.borrow_mut();
+        let calibration = match &mut *calibration {
+            Some(calibration) if calibration.page == Page::Running && calibration.step == step => {
+                calibration
+            }
+            _ => return,
+        };
+        calibration.page = Page::Result;
+        for child in self.grid_result.get_children() {
+            self.grid_result.remove(&child);
+        }
+        let success = result.is_ok();
+        match result {
+            Ok(record) => {
+                let (old, new) = (record.before.points, record.after.points);
+                calibration.old = Some(new);
+                if let Some(report) = &mut calibration.report {
+                    report.push(record);
+                }
+                self.label_result
+                    .set_text(&format!("{} erfolgreich abgeglichen.", step));
+                for (column, title) in ["Rwreg Nr.", "Kalibrierpunkt", "Alt", "Neu"]
+                    .iter()
+                    .enumerate()
+                {
+                    let label = gtk::Label::new(Some(*title));
+                    label.get_style_context().add_class("dim-label");
+                    self.grid_result.attach(&label, column as i32, 0, 1, 1);
+                }
+                for (row, (number, description, old, new)) in
+                    old.compare(&new).into_iter().enumerate()
+                {
+                    for (column, text) in [
+                        number.to_string(),
+                        description.to_string(),
+                        old.to_string(),
+                        new.to_string(),
+                    ]
+                    .iter()
+                    .enumerate()
+                    {
+                        let label = gtk::Label::new(Some(text.as_str()));
+                        label.set_xalign(if column == 1 { 0.0 } else { 1.0 });
+                        self.grid_result
+                            .attach(&label, column as i32, row as i32 + 1, 1, 1);
+                    }
+                }
+            }
+            Err(e) => {
+                self.label_result
+                    .set_text(&format!("{} fehlgeschlagen: {}", step, e));
+            }
+        }
+        self.grid_result.show_all();
+        self.show_page(Page::Result);
+        self.button_next.set_label("Schließen");
+        self.button_next.set_sensitive(true);
+        self.button_cancel.set_visible(false);
+        self.button_save.set_visible(
+            calibration
+                .report
+                .as_ref()
+                .map_or(false, |report| !report.records.is_empty()),
+        );
+        // Nach dem Nullpunkt folgt üblicherweise das Messgas
+        self.button_span
+            .set_visible(success && step == CalibrationStep::Zero);
+    }
+
+    /// Continue the session with the span gas calibration
+    fn continue_with_span(&self) {
+        if let Some(calibration) = &mut *self.calibration.borrow_mut() {
+            calibration.step = CalibrationStep::Span { concentration: 0 };
+            calibration.page = Page::Prepare;
+            self.dialog.set_title("Messgas abgleichen");
+            self.label_prepare.set_text(PREPARE_SPAN);
+            if let Some(old) = &calibration.old {
+                self.show_points(old);
+            }
+        }
+        self.box_concentration.set_visible(true);
+        self.show_page(Page::Prepare);
+        self.button_next.set_sensitive(true);
+    }
+
+    /// Ask for the file name and save the calibration report
+    fn save(&self, window: &gtk::Window) {
+        let report = match &*self.calibration.borrow() {
+            Some(Calibration {
+                report: Some(report),
+                ..
+            }) => report.clone(),
+            _ => return,
+        };
+
+        let dialog = gtk::FileChooserDialog::with_buttons(
+            Some("Kalibrierprotokoll speichern"),
+            Some(window),
+            gtk::FileChooserAction::Save,
+            &[
+                ("_Abbrechen", gtk::ResponseType::Cancel),
+                ("_Speichern", gtk::ResponseType::Accept),
+            ],
+        );
+        dialog.set_do_overwrite_confirmation(true);
+        dialog.set_current_name(&report.file_name());
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("HTML Bericht (*.html)"));
+        filter.add_pattern("*.html");
+        dialog.add_filter(&filter);
+
+        let path = match dialog.run() {
+            gtk::ResponseType::Accept => dialog.get_filename(),
+            _ => None,
+        };
+        dialog.close();
+
+        if let Some(path) = path {
+            let message = match report.save(&path) {
+                Ok(json_path) => format!(
+                    "Protokoll gespeichert: {} und {}",
+                    path.display(),
+                    json_path.display()
+                ),
+                Err(e) => format!("Protokoll konnte nicht gespeichert werden: {}", e),
+            };
+            self.label_result.set_text(&message);
+        }
+    }
+
+    fn show_points(&self, points: &CalibrationPoints) {
+        self.label_points.set_text(&format!(
+            "Nullpunkt: AD-Wert {} bei {} ppm\nKalibrierpunkt: AD-Wert {} bei {} ppm",
+            points.zero_adc, points.zero_ppm, points.span_adc, points.span_ppm
+        ));
+    }
+
+    /// Close the wizard without sending a command
+    pub fn abort(&self) {
+        self.calibration.borrow_mut().take();
+        self.dialog.hide();
+    }
+
+    fn page(&self) -> Option<Page> {
+        self.calibration
+            .borrow()
+            .as_ref()
+            .map(|calibration| calibration.page)
+    }
+
+    /// Tolerance and time are taken, wait for a stable reading
+    fn stabilize(&self) {
+        if let Some(calibration) = &mut *self.calibration.borrow_mut() {
+            if let CalibrationStep::Span { .. } = calibration.step {
+                calibration.step = CalibrationStep::Span {
+                    concentration: self.spin_button_concentration.get_value_as_int() as u16,
+                };
+            }
+            calibration.monitor = StabilityMonitor::new(
+                self.spin_button_tolerance.get_value(),
+                Duration::from_secs(self.spin_button_stable_time.get_value_as_int() as u64),
+            );
+            calibration.page = Page::Stabilize;
+            self.label_instruction
+                .set_text(&calibration.step.instruction());
+            self.button_next
+                .set_label(&format!("{} setzen", calibration.step));
+        }
+        self.label_adc.set_text("-");
+        self.label_gas.set_text("-");
+        self.progress_bar.set_fraction(0.0);
+        self.progress_bar.set_text(Some("Warte auf Messwerte"));
+        self.button_next.set_sensitive(false);
+        self.show_page(Page::Stabilize);
+    }
+
+    /// Reading is stable, returns the step to send
+    fn run(&self) -> Option<CalibrationStep> {
+        let step = match &mut *self.calibration.borrow_mut() {
+            Some(calibration) if calibration.monitor.is_stable() => {
+                calibration.page = Page::Running;
+                let operator = self.entry_operator.get_text();
+                match &mut calibration.report {
+                    Some(report) => report.operator = operator.to_string(),
+                    None => {
+                        calibration.report = Some(CalibrationReport::new(
+                            &operator,
+                            calibration.modbus_address,
+                            &calibration.device_info.clone().unwrap_or_default(),
+                            calibration.started,
+                        ))
+                    }
+                }
+                calibration.step
+            }
+            _ => return None,
+        };
+        self.label_running
+            .set_text(&format!("{} wird abgeglichen …", step));
+        self.button_next.set_sensitive(false);
+        self.button_cancel.set_sensitive(false);
+        self.show_page(Page::Running);
+        Some(step)
+    }
+
+    fn show_page(&self, page: Page) {
+        self.stack.set_visible_child_name(page.name());
+        if page == Page::Prepare {
+            self.button_next.set_label("Weiter");
+            self.button_cancel.set_visible(true);
+            self.button_cancel.set_sensitive(true);
+            self.button_save.set_visible(false);
+            self.button_span.set_visible(false);
+        }
+    }
+}
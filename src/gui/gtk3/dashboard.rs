@@ -0,0 +1,297 @@
+use crate::error::Result;
+use crate::sensors::dashboard::{parse_addresses, CommStatus, Dashboard, DashboardSensor};
+use crate::sensors::ra_gas_ne4::NE4;
+/// Dashboard page
+///
+/// Shows one tile per sensor with live concentration, output current, working
+/// mode and communication status. While the page is visible the sensors are
+/// polled round-robin, clicking a tile opens the sensor in the single sensor
+/// view.
+use chrono::Local;
+use gtk::prelude::*;
+use std::{cell::RefCell, rc::Rc};
+
+/// Widgets of one sensor tile
+struct Tile {
+    modbus_address: u8,
+    container: gtk::Box,
+    label_concentration: gtk::Label,
+    label_output_current: gtk::Label,
+    label_working_mode: gtk::Label,
+    label_status: gtk::Label,
+}
+
+impl Tile {
+    fn new(modbus_address: u8) -> Self {
+        let container = gtk::Box::new(gtk::Orientation::Vertical, 3);
+        container.get_style_context().add_class("dashboard-tile");
+        container.set_size_request(160, -1);
+
+        let label_title = gtk::Label::new(None);
+        label_title.set_markup(&format!("<b>Modbus Adresse {}</b>", modbus_address));
+        let label_concentration = gtk::Label::new(None);
+        label_concentration
+            .get_style_context()
+            .add_class("dashboard-tile-concentration");
+        let label_output_current = gtk::Label::new(None);
+        let label_working_mode = gtk::Label::new(None);
+        let label_status = gtk::Label::new(None);
+        label_status.set_line_wrap(true);
+        label_status.get_style_context().add_class("dim-label");
+
+        container.pack_start(&label_title, false, false, 0);
+        container.pack_start(&label_concentration, false, false, 0);
+        container.pack_start(&label_output_current, false, false, 0);
+        container.pack_start(&label_working_mode, false, false, 0);
+        container.pack_start(&label_status, false, false, 0);
+
+        Tile {
+            modbus_address,
+            container,
+            label_concentration,
+            label_output_current,
+            label_working_mode,
+            label_status,
+        }
+    }
+
+    fn update(&self, sensor: &DashboardSensor) {
+        self.label_concentration.set_text(&sensor.concentration());
+        self.label_output_current.set_text(&sensor.output_current());
+        self.label_working_mode.set_text(
+            &sensor
+                .working_mode()
+                .map_or_else(|| "-".to_string(), |mode| mode.to_string()),
+        );
+        self.label_status.set_text(&sensor.status.to_string());
+        let tooltip = sensor
+            .last_seen
+            .map(|time| format!("Letzte Antwort: {}", time.format("%d.%m.%Y %H:%M:%S")));
+        self.container.set_tooltip_text(tooltip.as_deref());
+
+        let style_context = self.container.get_style_context();
+        for class in &["error", "offline", "over-range"] {
+            style_context.remove_class(class);
+        }
+        match &sensor.status {
+            status if status.is_offline() => style_context.add_class("offline"),
+            CommStatus::Error { .. } => style_context.add_class("error"),
+            _ if sensor.is_over_range() => style_context.add_class("over-range"),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DashboardUi {
+    dashboard: Rc<RefCell<Dashboard>>,
+    tiles: Rc<RefCell<Vec<Tile>>>,
+    flow_box: gtk::FlowBox,
+    entry_addresses: gtk::Entry,
+    button_add: gtk::Button,
+    button_remove: gtk::Button,
+    button_import: gtk::Button,
+    label_message: gtk::Label,
+}
+
+impl DashboardUi {
+    pub fn new() -> Self {
+        DashboardUi {
+            dashboard: Rc::new(RefCell::new(Dashboard::new())),
+            tiles: Rc::new(RefCell::new(vec![])),
+            flow_box: gtk::FlowBox::new(),
+            entry_addresses: gtk::Entry::new(),
+            button_add: gtk::Button::with_label("Hinzufügen"),
+            button_remove: gtk::Button::with_label("Entfernen"),
+            button_import: gtk::Button::with_label("Aus Bus Scan übernehmen"),
+            label_message: gtk::Label::new(Some("Noch keine Sensoren in der Übersicht")),
+        }
+    }
+
+    /// Build the dashboard page
+    ///
+    /// `on_import` returns the Modbus addresses found by the bus scan,
+    /// `on_changed` is called with all Modbus addresses after sensors were added
+    /// or removed and `on_open` with the Modbus address of a clicked tile.
+    pub fn build_ui<I, C, O>(&self, on_import: I, on_changed: C, on_open: O) -> gtk::Box
+    where
+        I: Fn() -> Vec<u8> + 'static,
+        C: Fn(Vec<u8>) + 'static,
+        O: Fn(u8) + 'static,
+    {
+        let on_changed = Rc::new(on_changed);
+
+        self.entry_addresses
+            .set_placeholder_text(Some("Modbus Adressen, z.B. 1, 5, 10-12"));
+        self.entry_addresses.set_hexpand(true);
+        self.label_message.set_xalign(0.0);
+
+        self.flow_box.set_selection_mode(gtk::SelectionMode::None);
+        self.flow_box.set_homogeneous(true);
+        self.flow_box.set_max_children_per_line(8);
+        self.flow_box.set_row_spacing(6);
+        self.flow_box.set_column_spacing(6);
+        self.flow_box.set_valign(gtk::Align::Start);
+
+        let dashboard_ui = self.clone();
+        self.flow_box.connect_child_activated(move |_, child| {
+            let index = child.get_index();
+            let modbus_address = dashboard_ui
+                .tiles
+                .borrow()
+                .get(index as usize)
+                .map(|tile| tile.modbus_address);
+            if let Some(modbus_address) = modbus_address {
+                on_open(modbus_address);
+            }
+        });
+
+        let dashboard_ui = self.clone();
+        let on_changed_add = on_changed.clone();
+        self.button_add.connect_clicked(move |_| {
+            match parse_addresses(&dashboard_ui.entry_addresses.get_text()) {
+                Ok(addresses) => {
+                    if dashboard_ui.add(&addresses) {
+                        dashboard_ui.entry_addresses.set_text("");
+                        on_changed_add(dashboard_ui.addresses());
+                    }
+                }
+                Err(e) => dashboard_ui.label_message.set_text(&e),
+            }
+        });
+        let button_add = self.button_add.clone();
+        self.entry_addresses
+            .connect_activate(move |_| button_add.clicked());
+
+        let dashboard_ui = self.clone();
+        let on_changed_remove = on_changed.clone();
+        self.button_remove.connect_clicked(move |_| {
+            match parse_addresses(&dashboard_ui.entry_addresses.get_text()) {
+                // Ohne Eingabe werden alle Sensoren entfernt
+                Ok(addresses) => {
+                    let addresses = if addresses.is_empty() {
+                        dashboard_ui.addresses()
+                    } else {
+                        addresses
+                    };
+                    {
+                        let mut dashboard = dashboard_ui.dashboard.borrow_mut();
+                        for modbus_address in addresses {
+                            dashboard.remove(modbus_address);
+                        }
+                    }
+                    dashboard_ui.entry_addresses.set_text("");
+                    dashboard_ui.rebuild();
+                    on_changed_remove(dashboard_ui.addresses());
+                }
+                Err(e) => dashboard_ui.label_message.set_text(&e),
+            }
+        });
+
+        let dashboard_ui = self.clone();
+        self.button_import.connect_clicked(move |_| {
+            let addresses = on_import();
+            if addresses.is_empty() {
+                dashboard_ui
+                    .label_message
+                    .set_text("Der Bus Scan hat keine Sensoren gefunden");
+            } else if dashboard_ui.add(&addresses) {
+                on_changed(dashboard_ui.addresses());
+            }
+        });
+
+        let scrolled_window = gtk::ScrolledWindow::new(gtk::NONE_ADJUSTMENT, gtk::NONE_ADJUSTMENT);
+        scrolled_window.set_vexpand(true);
+        scrolled_window.add(&self.flow_box);
+
+        let box_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        box_buttons.pack_start(&self.entry_addresses, true, true, 0);
+        box_buttons.pack_start(&self.button_add, false, false, 0);
+        box_buttons.pack_start(&self.button_remove, false, false, 0);
+        box_buttons.pack_end(&self.button_import, false, false, 0);
+
+        let box_main = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        box_main.set_border_width(6);
+        box_main.pack_start(&box_buttons, false, false, 0);
+        box_main.pack_start(&self.label_message, false, false, 0);
+        box_main.pack_start(&scrolled_window, true, true, 0);
+
+        box_main
+    }
+
+    /// Modbus addresses in the order they are polled
+    pub fn addresses(&self) -> Vec<u8> {
+        self.dashboard.borrow().addresses()
+    }
+
+    /// Result of polling one sensor
+    pub fn update(&self, modbus_address: u8, result: Result<NE4>) {
+        let mut dashboard = self.dashboard.borrow_mut();
+        if let Some(sensor) = dashboard.update(modbus_address, Local::now(), result) {
+            if let Some(tile) = self
+                .tiles
+                .borrow()
+                .iter()
+                .find(|tile| tile.modbus_address == modbus_address)
+            {
+                tile.update(sensor);
+            }
+        }
+    }
+
+    /// Forget all values, e.g. after disconnecting
+    pub fn reset(&self) {
+        self.dashboard.borrow_mut().reset();
+        let dashboard = self.dashboard.borrow();
+        for (tile, sensor) in self.tiles.borrow().iter().zip(dashboard.sensors()) {
+            tile.update(sensor);
+        }
+    }
+
+    /// Add sensors, returns true if at least one was added
+    ///
+    /// Addresses which are already on the dashboard are reported, not added twice.
+    fn add(&self, addresses: &[u8]) -> bool {
+        let mut errors = vec![];
+        let mut added = false;
+        {
+            let mut dashboard = self.dashboard.borrow_mut();
+            for modbus_address in addresses {
+                match dashboard.add(*modbus_address) {
+                    Ok(()) => added = true,
+                    Err(e) => errors.push(e),
+                }
+            }
+        }
+        if added {
+            self.rebuild();
+        }
+        if !errors.is_empty() {
+            self.label_message.set_text(&errors.join(", "));
+        }
+        added
+    }
+
+    /// Create the tiles for all sensors of the dashboard
+    fn rebuild(&self) {
+        for child in self.flow_box.get_children() {
+            self.flow_box.remove(&child);
+        }
+        let dashboard = self.dashboard.borrow();
+        let mut tiles = self.tiles.borrow_mut();
+        tiles.clear();
+        for sensor in dashboard.sensors() {
+            let tile = Tile::new(sensor.modbus_address);
+            tile.update(sensor);
+            self.flow_box.insert(&tile.container, -1);
+            tiles.push(tile);
+        }
+        self.flow_box.show_all();
+        self.label_message
+            .set_text(&match dashboard.sensors().len() {
+                0 => "Noch keine Sensoren in der Übersicht".to_string(),
+                1 => "1 Sensor, zum Öffnen auf die Kachel klicken".to_string(),
+                n => format!("{} Sensoren, zum Öffnen auf eine Kachel klicken", n),
+            });
+    }
+}
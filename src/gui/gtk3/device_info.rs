@@ -0,0 +1,57 @@
+use crate::sensors::device_info::DeviceInfo;
+/// Device information page
+///
+/// Shows identity, software date and lifetime counters of the connected sensor.
+/// The values are taken from the polled registers, so the page needs no
+/// requests of its own. Registers not read since connecting show "-".
+use gtk::prelude::*;
+
+#[derive(Clone)]
+pub struct DeviceInfoUi {
+    grid: gtk::Grid,
+    /// One value label per row of `DeviceInfo::rows()`
+    labels: Vec<gtk::Label>,
+}
+
+impl DeviceInfoUi {
+    pub fn new() -> Self {
+        let grid = gtk::Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(12);
+        grid.set_border_width(12);
+
+        let mut labels = vec![];
+        for (row, (title, _)) in DeviceInfo::default().rows().into_iter().enumerate() {
+            let label_title = gtk::Label::new(Some(title));
+            label_title.set_xalign(1.0);
+            label_title.get_style_context().add_class("dim-label");
+            let label_value = gtk::Label::new(Some("-"));
+            label_value.set_xalign(0.0);
+            label_value.set_selectable(true);
+
+            grid.attach(&label_title, 0, row as i32, 1, 1);
+            grid.attach(&label_value, 1, row as i32, 1, 1);
+            labels.push(label_value);
+        }
+
+        DeviceInfoUi { grid, labels }
+    }
+
+    /// Build the device information page
+    pub fn build_ui(&self) -> gtk::Box {
+        let box_main = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        box_main.pack_start(&self.grid, false, false, 0);
+        box_main
+    }
+
+    pub fn update(&self, device_info: &DeviceInfo) {
+        for (label, (_, value)) in self.labels.iter().zip(device_info.rows()) {
+            label.set_text(&value);
+        }
+    }
+
+    /// Show "-" while no sensor is connected
+    pub fn clear(&self) {
+        self.update(&DeviceInfo::default());
+    }
+}
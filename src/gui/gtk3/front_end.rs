@@ -0,0 +1,268 @@
+use crate::error::Result;
+use crate::gui::gtk3::presets::PresetsUi;
+/// Analog front-end page
+///
+/// One dropdown per field of Rwreg 21 to 29 with the named values, next to the
+/// value currently in the sensor. The status of the IC (Rwreg 20) is shown read
+/// only. Settings are stored as presets per gas type and written together after
+/// the range check.
+use crate::sensors::front_end::{
+    status_label, FrontEnd, FIELDS, FRONT_END_PRESETS, FRONT_END_STATUS,
+};
+use crate::sensors::ra_gas_ne4::NE4;
+use gtk::prelude::*;
+use std::{cell::Cell, rc::Rc};
+
+#[derive(Clone)]
+pub struct FrontEndUi {
+    /// Settings in the sensor, from the last poll
+    sensor: Rc<Cell<Option<FrontEnd>>>,
+    /// The dropdowns show settings of the sensor or a preset
+    loaded: Rc<Cell<bool>>,
+    presets_ui: PresetsUi<FrontEnd>,
+    /// One dropdown per field, the id is the raw value
+    combo_boxes: Vec<gtk::ComboBoxText>,
+    /// Value in the sensor per field
+    labels_sensor: Vec<gtk::Label>,
+    label_ic_status: gtk::Label,
+    button_read: gtk::Button,
+    button_write: gtk::Button,
+    label_status: gtk::Label,
+}
+
+impl FrontEndUi {
+    pub fn new() -> Self {
+        let combo_boxes = FIELDS
+            .iter()
+            .map(|field| {
+                let combo_box = gtk::ComboBoxText::new();
+                for (raw, option) in field.options.iter().enumerate() {
+                    combo_box.append(Some(&raw.to_string()), option);
+                }
+                combo_box
+            })
+            .collect();
+        let labels_sensor = FIELDS
+            .iter()
+            .map(|_| {
+                let label = gtk::Label::new(None);
+                label.set_xalign(0.0);
+                label
+            })
+            .collect();
+
+        FrontEndUi {
+            sensor: Rc::new(Cell::new(None)),
+            loaded: Rc::new(Cell::new(false)),
+            presets_ui: PresetsUi::new(FRONT_END_PRESETS),
+            combo_boxes,
+            labels_sensor,
+            label_ic_status: gtk::Label::new(None),
+            button_read: gtk::Button::with_label("Aus Sensor lesen"),
+            button_write: gtk::Button::with_label("In Sensor schreiben"),
+            label_status: gtk::Label::new(None),
+        }
+    }
+
+    /// Build the analog front-end page
+    ///
+    /// `on_read` reads the settings from the sensor, the result is passed to
+    /// `read`. `on_write` writes all fields.
+    pub fn build_ui<R, W>(&self, on_read: R, on_write: W) -> gtk::Box
+    where
+        R: Fn() + 'static,
+        W: Fn(FrontEnd) + 'static,
+    {
+        let grid = gtk::Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(12);
+        for (column, title) in ["Register", "Einstellung", "Im Sensor"].iter().enumerate() {
+            let label = gtk::Label::new(None);
+            label.set_markup(&format!("<b>{}</b>", title));
+            label.set_xalign(0.0);
+            grid.attach(&label, column as i32, 0, 1, 1);
+        }
+        let label = gtk::Label::new(Some(&format!("Status (Rwreg {})", FRONT_END_STATUS)));
+        label.set_xalign(0.0);
+        grid.attach(&label, 0, 1, 1, 1);
+        self.label_ic_status.set_xalign(0.0);
+        grid.attach(&self.label_ic_status, 2, 1, 1, 1);
+        for (row, ((field, combo_box), label_sensor)) in FIELDS
+            .iter()
+            .zip(self.combo_boxes.iter())
+            .zip(self.labels_sensor.iter())
+            .enumerate()
+        {
+            let row = row as i32 + 2;
+            let label = gtk::Label::new(Some(&format!("{} (Rwreg {})", field.name, field.number)));
+            label.set_xalign(0.0);
+            label.set_tooltip_text(Some(field.description));
+            grid.attach(&label, 0, row, 1, 1);
+            grid.attach(combo_box, 1, row, 1, 1);
+            grid.attach(label_sensor, 2, row, 1, 1);
+            let front_end_ui = self.clone();
+            combo_box.connect_changed(move |_| front_end_ui.show_sensor());
+        }
+
+        // Vorlagen
+        let front_end_ui = self.clone();
+        let on_load = move |front_end| {
+            front_end_ui.loaded.set(true);
+            front_end_ui.show(front_end);
+        };
+        let front_end_ui = self.clone();
+        let label_status = self.label_status.clone();
+        let box_presets = self.presets_ui.build_ui(
+            "Gas",
+            move || front_end_ui.values().unwrap_or_default(),
+            on_load,
+            move |message| label_status.set_text(message),
+        );
+
+        self.button_read.connect_clicked(move |_| on_read());
+        let front_end_ui = self.clone();
+        self.button_write.connect_clicked(move |_| {
+            let front_end = match front_end_ui.values() {
+                Some(front_end) => front_end,
+                None => {
+                    front_end_ui
+                        .label_status
+                        .set_text("Bitte für jedes Register einen Wert auswählen");
+                    return;
+                }
+            };
+            match front_end.validate() {
+                Ok(()) => {
+                    front_end_ui
+                        .label_status
+                        .set_text("Einstellungen werden geschrieben …");
+                    on_write(front_end);
+                }
+                Err(e) => front_end_ui.label_status.set_text(&e.to_string()),
+            }
+        });
+        box_presets.pack_end(&self.button_write, false, false, 0);
+        box_presets.pack_end(&self.button_read, false, false, 0);
+
+        self.label_status.set_xalign(0.0);
+        self.label_status.set_line_wrap(true);
+
+        let box_main = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        box_main.set_border_width(12);
+        box_main.pack_start(&grid, false, false, 0);
+        box_main.pack_start(&box_presets, false, false, 0);
+        box_main.pack_start(&self.label_status, false, false, 0);
+        self.clear();
+        self.set_sensitive(false);
+        box_main
+    }
+
+    /// Take the status and settings of the sensor from a poll
+    ///
+    /// The first settings read after connecting are loaded into the dropdowns, the
+    /// gas of the working mode is suggested as preset name.
+    pub fn update(&self, ne4: &NE4) {
+        if let Some(status) = ne4.rwreg(FRONT_END_STATUS) {
+            self.label_ic_status.set_text(status_label(status));
+        }
+        if let Some(gas) = ne4.work_mode().gas() {
+            self.presets_ui.suggest_name(gas.formula);
+        }
+        let sensor = FrontEnd::from_ne4(ne4);
+        self.sensor.set(sensor);
+        match sensor {
+            Some(front_end) if !self.loaded.get() => {
+                self.loaded.set(true);
+                self.show(front_end);
+            }
+            _ => self.show_sensor(),
+        }
+    }
+
+    /// Result of reading the settings from the sensor
+    pub fn read(&self, result: Result<FrontEnd>) {
+        match result {
+            Ok(front_end) => {
+                self.loaded.set(true);
+                self.sensor.set(Some(front_end));
+                self.show(front_end);
+                self.label_status
+                    .set_text("Einstellungen aus dem Sensor gelesen");
+            }
+            Err(e) => self.label_status.set_text(&format!(
+                "Einstellungen konnten nicht gelesen werden: {}",
+                e
+            )),
+        }
+    }
+
+    /// Result of writing the settings
+    pub fn written(&self, result: &Result<FrontEnd>) {
+        match result {
+            Ok(front_end) => {
+                self.sensor.set(Some(*front_end));
+                self.show_sensor();
+                self.label_status.set_text("Einstellungen geschrieben");
+            }
+            Err(e) => self.label_status.set_text(&format!(
+                "Einstellungen konnten nicht geschrieben werden: {}",
+                e
+            )),
+        }
+    }
+
+    /// Reading and writing only while connected, the presets can always be edited
+    pub fn set_sensitive(&self, sensitive: bool) {
+        self.button_read.set_sensitive(sensitive);
+        self.button_write.set_sensitive(sensitive);
+    }
+
+    /// Forget the values of the sensor, e.g. after disconnecting
+    pub fn clear(&self) {
+        self.sensor.set(None);
+        self.loaded.set(false);
+        self.label_ic_status.set_text("–");
+        self.show_sensor();
+    }
+
+    /// Selected settings, `None` if a field has no value
+    fn values(&self) -> Option<FrontEnd> {
+        let mut front_end = FrontEnd::default();
+        for (value, combo_box) in front_end.values.iter_mut().zip(self.combo_boxes.iter()) {
+            *value = combo_box.get_active_id()?.parse().ok()?;
+        }
+        Some(front_end)
+    }
+
+    /// Select the settings in the dropdowns, unknown values select nothing
+    fn show(&self, front_end: FrontEnd) {
+        for (value, combo_box) in front_end.values.iter().zip(self.combo_boxes.iter()) {
+            if !combo_box.set_active_id(Some(&value.to_string())) {
+                combo_box.set_active(None);
+            }
+        }
+        self.show_sensor();
+    }
+
+    /// Show the values of the sensor, fields which differ from the selection bold
+    fn show_sensor(&self) {
+        for (index, (field, label)) in FIELDS.iter().zip(self.labels_sensor.iter()).enumerate() {
+            let raw = match self.sensor.get() {
+                Some(sensor) => sensor.values[index],
+                None => {
+                    label.set_text("–");
+                    continue;
+                }
+            };
+            let text = glib::markup_escape_text(&field.label(raw));
+            let selected = self.combo_boxes[index]
+                .get_active_id()
+                .and_then(|id| id.parse::<u16>().ok());
+            if selected == Some(raw) {
+                label.set_markup(&text);
+            } else {
+                label.set_markup(&format!("<b>{}</b>", text));
+            }
+        }
+    }
+}
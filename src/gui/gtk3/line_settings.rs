@@ -0,0 +1,96 @@
+/// Serial line settings in the connection header
+use crate::tokio_thread::LineSettings;
+use gtk::prelude::*;
+use std::time::Duration;
+use tokio_serial::{DataBits, Parity, StopBits};
+
+#[derive(Clone)]
+pub struct LineSettingsUi {
+    combo_box_text_baud_rate: gtk::ComboBoxText,
+    combo_box_text_parity: gtk::ComboBoxText,
+    combo_box_text_data_bits: gtk::ComboBoxText,
+    combo_box_text_stop_bits: gtk::ComboBoxText,
+    spin_button_timeout: gtk::SpinButton,
+    spin_button_poll_interval: gtk::SpinButton,
+}
+
+impl LineSettingsUi {
+    pub fn new(builder: &gtk::Builder) -> Self {
+        LineSettingsUi {
+            combo_box_text_baud_rate: build!(builder, "combo_box_text_baud_rate"),
+            combo_box_text_parity: build!(builder, "combo_box_text_parity"),
+            combo_box_text_data_bits: build!(builder, "combo_box_text_data_bits"),
+            combo_box_text_stop_bits: build!(builder, "combo_box_text_stop_bits"),
+            spin_button_timeout: build!(builder, "spin_button_timeout"),
+            spin_button_poll_interval: build!(builder, "spin_button_poll_interval"),
+        }
+    }
+
+    /// Currently selected line settings
+    pub fn line_settings(&self) -> LineSettings {
+        let default = LineSettings::default();
+        LineSettings {
+            baud_rate: self
+                .combo_box_text_baud_rate
+                .get_active_id()
+                .and_then(|id| id.parse().ok())
+                .unwrap_or(default.baud_rate),
+            parity: match self.combo_box_text_parity.get_active_id().as_deref() {
+                Some("even") => Parity::Even,
+                Some("odd") => Parity::Odd,
+                _ => Parity::None,
+            },
+            data_bits: match self.combo_box_text_data_bits.get_active_id().as_deref() {
+                Some("7") => DataBits::Seven,
+                _ => DataBits::Eight,
+            },
+            stop_bits: match self.combo_box_text_stop_bits.get_active_id().as_deref() {
+                Some("2") => StopBits::Two,
+                _ => StopBits::One,
+            },
+            timeout: Duration::from_millis(self.spin_button_timeout.get_value_as_int() as u64),
+        }
+    }
+
+    /// Call `f` with the new line settings every time the user changes one of them
+    pub fn connect_changed<F: Fn(LineSettings) + Clone + 'static>(&self, f: F) {
+        for combo_box_text in &[
+            &self.combo_box_text_baud_rate,
+            &self.combo_box_text_parity,
+            &self.combo_box_text_data_bits,
+            &self.combo_box_text_stop_bits,
+        ] {
+            let line_settings_ui = self.clone();
+            let f = f.clone();
+            combo_box_text.connect_changed(move |_| f(line_settings_ui.line_settings()));
+        }
+        let line_settings_ui = self.clone();
+        self.spin_button_timeout
+            .connect_value_changed(move |_| f(line_settings_ui.line_settings()));
+    }
+
+    /// Pause between two polling rounds
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.spin_button_poll_interval.get_value_as_int() as u64)
+    }
+
+    /// Call `f` with the new poll interval every time the user changes it
+    pub fn connect_poll_interval_changed<F: Fn(Duration) + 'static>(&self, f: F) {
+        let line_settings_ui = self.clone();
+        self.spin_button_poll_interval
+            .connect_value_changed(move |_| f(line_settings_ui.poll_interval()));
+    }
+
+    /// The line settings can't be changed while connected, the poll interval can
+    pub fn set_sensitive(&self, sensitive: bool) {
+        for combo_box_text in &[
+            &self.combo_box_text_baud_rate,
+            &self.combo_box_text_parity,
+            &self.combo_box_text_data_bits,
+            &self.combo_box_text_stop_bits,
+        ] {
+            combo_box_text.set_sensitive(sensitive);
+        }
+        self.spin_button_timeout.set_sensitive(sensitive);
+    }
+}
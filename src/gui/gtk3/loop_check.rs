@@ -0,0 +1,712 @@
+use crate::error::Result;
+use crate::sensors::loop_check::{
+    LoopCheck, LoopPoint, OutputCalibration, DEFAULT_LOOP_TOLERANCE, LOOP_CHECK_POINTS,
+};
+/// Loop check wizard
+///
+/// Forces the output current (Rwreg 3) through 4, 8, 12, 16 and 20 mA, the
+/// technician enters the current read on the multimeter or PLC. Corrected
+/// calibration values for Rwreg 53 and 54 are computed from the deviations and
+/// written, afterwards the loop is checked again. The result with the deviation
+/// per point can be saved as JSON. The forced current is switched off when the
+/// wizard closes.
+use crate::sensors::test_values::TestValue;
+use chrono::Local;
+use gtk::prelude::*;
+use std::{cell::RefCell, rc::Rc};
+
+/// Pages of the wizard
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Page {
+    /// Calibration values are read, tolerance and operator are entered
+    Prepare,
+    /// One point of the loop is forced and measured
+    Measure,
+    /// Old and corrected calibration values
+    Correction,
+    /// Corrected calibration values are written
+    Writing,
+    Result,
+}
+
+impl Page {
+    fn name(self) -> &'static str {
+        match self {
+            Page::Prepare => "prepare",
+            Page::Measure => "measure",
+            Page::Correction => "correction",
+            Page::Writing => "writing",
+            Page::Result => "result",
+        }
+    }
+}
+
+const PREPARE: &str = "Der Ausgangsstrom wird nacheinander auf 4, 8, 12, 16 und 20 mA \
+                       vorgegeben. Bei jedem Schritt den Strom am Multimeter oder an der \
+                       SPS ablesen und eintragen. Die Gaskonzentration des Sensors wird \
+                       während der Prüfung nicht ausgegeben.";
+
+/// State of a running loop check
+struct Check {
+    page: Page,
+    /// Index of the measured point in `LOOP_CHECK_POINTS`
+    point: usize,
+    /// The forced current of the point has been written
+    forced: bool,
+    /// Second pass after writing the corrected calibration values
+    verify: bool,
+    modbus_address: u8,
+    old: Option<OutputCalibration>,
+    corrected: Option<OutputCalibration>,
+    result: Option<LoopCheck>,
+}
+
+#[derive(Clone)]
+pub struct LoopCheckUi {
+    dialog: gtk::Dialog,
+    stack: gtk::Stack,
+    button_cancel: gtk::Button,
+    button_skip: gtk::Button,
+    button_next: gtk::Button,
+    button_save: gtk::Button,
+    label_old: gtk::Label,
+    spin_button_tolerance: gtk::SpinButton,
+    entry_operator: gtk::Entry,
+    label_step: gtk::Label,
+    label_forced: gtk::Label,
+    spin_button_measured: gtk::SpinButton,
+    label_correction: gtk::Label,
+    spinner: gtk::Spinner,
+    label_writing: gtk::Label,
+    label_result: gtk::Label,
+    grid_result: gtk::Grid,
+    check: Rc<RefCell<Option<Check>>>,
+}
+
+impl LoopCheckUi {
+    pub fn new() -> Self {
+        LoopCheckUi {
+            dialog: gtk::Dialog::new(),
+            stack: gtk::Stack::new(),
+            button_cancel: gtk::Button::with_label("Abbrechen"),
+            button_skip: gtk::Button::with_label("Ohne Abgleich abschließen"),
+            button_next: gtk::Button::with_label("Weiter"),
+            button_save: gtk::Button::with_label("Ergebnis speichern"),
+            label_old: gtk::Label::new(None),
+            spin_button_tolerance: gtk::SpinButton::with_range(0.01, 2.0, 0.01),
+            entry_operator: gtk::Entry::new(),
+            label_step: gtk::Label::new(None),
+            label_forced: gtk::Label::new(None),
+            spin_button_measured: gtk::SpinButton::with_range(0.0, 25.0, 0.01),
+            label_correction: gtk::Label::new(None),
+            spinner: gtk::Spinner::new(),
+            label_writing: gtk::Label::new(None),
+            label_result: gtk::Label::new(None),
+            grid_result: gtk::Grid::new(),
+            check: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Build the wizard dialog
+    ///
+    /// `window` is the parent of the dialogs. `on_force` forces the output
+    /// current with the raw value of Rwreg 3, `None` switches it off.
+    /// `on_write` writes the corrected calibration values.
+    pub fn build_ui<W, F, C>(&self, window: &W, on_force: F, on_write: C)
+    where
+        W: IsA<gtk::Window>,
+        F: Fn(Option<u16>) + 'static,
+        C: Fn(OutputCalibration) + 'static,
+    {
+        let on_force = Rc::new(on_force);
+        self.dialog.set_transient_for(Some(window));
+        self.dialog.set_modal(true);
+        self.dialog.set_default_size(480, -1);
+        self.dialog.set_title("Stromschleife prüfen");
+
+        // Vorbereitung
+        let label_prepare = gtk::Label::new(Some(PREPARE));
+        label_prepare.set_line_wrap(true);
+        label_prepare.set_xalign(0.0);
+        self.label_old.set_xalign(0.0);
+        self.label_old.set_selectable(true);
+        self.spin_button_tolerance.set_digits(2);
+        self.spin_button_tolerance.set_value(DEFAULT_LOOP_TOLERANCE);
+        let grid_settings = gtk::Grid::new();
+        grid_settings.set_row_spacing(6);
+        grid_settings.set_column_spacing(12);
+        let label_tolerance = gtk::Label::new(Some("Zulässige Abweichung [mA]"));
+        label_tolerance.set_xalign(0.0);
+        label_tolerance.set_hexpand(true);
+        grid_settings.attach(&label_tolerance, 0, 0, 1, 1);
+        grid_settings.attach(&self.spin_button_tolerance, 1, 0, 1, 1);
+        let label_operator = gtk::Label::new(Some("Prüfer"));
+        label_operator.set_xalign(0.0);
+        grid_settings.attach(&label_operator, 0, 1, 1, 1);
+        grid_settings.attach(&self.entry_operator, 1, 1, 1, 1);
+        let box_prepare = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        box_prepare.pack_start(&label_prepare, false, false, 0);
+        box_prepare.pack_start(&self.label_old, false, false, 0);
+        box_prepare.pack_start(&grid_settings, false, false, 0);
+        self.stack.add_named(&box_prepare, Page::Prepare.name());
+
+        // Messen
+        self.label_step.set_xalign(0.0);
+        self.label_forced.set_xalign(0.0);
+        self.label_forced.set_line_wrap(true);
+        self.spin_button_measured.set_digits(2);
+        self.spin_button_measured.set_numeric(true);
+        let label_measured = gtk::Label::new(Some("Gemessener Strom [mA]"));
+        label_measured.set_xalign(0.0);
+        label_measured.set_hexpand(true);
+        let box_measured = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        box_measured.pack_start(&label_measured, true, true, 0);
+        box_measured.pack_end(&self.spin_button_measured, false, false, 0);
+        let box_measure = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        box_measure.pack_start(&self.label_step, false, false, 0);
+        box_measure.pack_start(&self.label_forced, false, false, 0);
+        box_measure.pack_start(&box_measured, false, false, 0);
+        self.stack.add_named(&box_measure, Page::Measure.name());
+
+        // Korrektur
+        self.label_correction.set_xalign(0.0);
+        self.label_correction.set_line_wrap(true);
+        self.label_correction.set_selectable(true);
+        self.stack
+            .add_named(&self.label_correction, Page::Correction.name());
+
+        // Schreiben
+        let box_writing = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        box_writing.pack_start(&self.spinner, false, false, 0);
+        box_writing.pack_start(&self.label_writing, false, false, 0);
+        self.stack.add_named(&box_writing, Page::Writing.name());
+
+        // Ergebnis
+        self.label_result.set_line_wrap(true);
+        self.label_result.set_xalign(0.0);
+        self.grid_result.set_row_spacing(6);
+        self.grid_result.set_column_spacing(12);
+        let box_result = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        box_result.pack_start(&self.label_result, false, false, 0);
+        box_result.pack_start(&self.grid_result, false, false, 0);
+        self.stack.add_named(&box_result, Page::Result.name());
+
+        let box_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        box_buttons.pack_end(&self.button_next, false, false, 0);
+        box_buttons.pack_end(&self.button_cancel, false, false, 0);
+        box_buttons.pack_start(&self.button_save, false, false, 0);
+        box_buttons.pack_start(&self.button_skip, false, false, 0);
+        // Nur auf der Korrektur- bzw. Ergebnisseite sichtbar
+        self.button_save.set_no_show_all(true);
+        self.button_skip.set_no_show_all(true);
+
+        let content = self.dialog.get_content_area();
+        content.set_spacing(12);
+        content.set_border_width(12);
+        content.pack_start(&self.stack, true, true, 0);
+        content.pack_end(&box_buttons, false, false, 0);
+
+        let loop_check_ui = self.clone();
+        let on_force_cancel = on_force.clone();
+        self.button_cancel.connect_clicked(move |_| {
+            if loop_check_ui.close() {
+                on_force_cancel(None);
+            }
+        });
+        let loop_check_ui = self.clone();
+        let on_force_delete = on_force.clone();
+        self.dialog.connect_delete_event(move |_, _| {
+            // Während des Schreibens kann nicht abgebrochen werden
+            if !loop_check_ui.writing() && loop_check_ui.close() {
+                on_force_delete(None);
+            }
+            gtk::Inhibit(true)
+        });
+        let loop_check_ui = self.clone();
+        let window = window.clone().upcast::<gtk::Window>();
+        self.button_save
+            .connect_clicked(move |_| loop_check_ui.save(&window));
+        let loop_check_ui = self.clone();
+        let on_force_skip = on_force.clone();
+        self.button_skip.connect_clicked(move |_| {
+            loop_check_ui.finish();
+            on_force_skip(None);
+        });
+        let loop_check_ui = self.clone();
+        self.button_next
+            .connect_clicked(move |_| match loop_check_ui.page() {
+                Some(Page::Prepare) => {
+                    if let Some(raw) = loop_check_ui.measure() {
+                        on_force(Some(raw));
+                    }
+                }
+                Some(Page::Measure) => match loop_check_ui.measured() {
+                    Some(Some(raw)) => on_force(Some(raw)),
+                    Some(None) => on_force(None),
+                    None => {}
+                },
+                Some(Page::Correction) => {
+                    if let Some(calibration) = loop_check_ui.write() {
+                        on_write(calibration);
+                    }
+                }
+                Some(Page::Writing) => {
+                    if let Some(raw) = loop_check_ui.verify() {
+                        on_force(Some(raw));
+                    }
+                }
+                _ => {
+                    if loop_check_ui.close() {
+                        on_force(None);
+                    }
+                }
+            });
+    }
+
+    /// Open the wizard
+    ///
+    /// The caller reads the calibration values of the output current, the result
+    /// is passed to `prepared`.
+    pub fn start(&self, modbus_address: u8) {
+        self.label_old
+            .set_text("Kalibrierwerte des Ausgangsstroms werden gelesen …");
+        *self.check.borrow_mut() = Some(Check {
+            page: Page::Prepare,
+            point: 0,
+            forced: false,
+            verify: false,
+            modbus_address,
+            old: None,
+            corrected: None,
+            result: None,
+        });
+        self.show_page(Page::Prepare);
+        self.button_next.set_sensitive(false);
+        self.dialog.show_all();
+        self.dialog.present();
+    }
+
+    /// Calibration values read before the check
+    pub fn prepared(&self, result: Result<OutputCalibration>) {
+        let mut check = self.check.borrow_mut();
+        let check = match &mut *check {
+            Some(check) if check.page == Page::Prepare => check,
+            _ => return,
+        };
+        match result {
+            Ok(old) => {
+                self.label_old.set_text(&format_calibration(&old));
+                check.old = Some(old);
+                self.button_next.set_sensitive(true);
+            }
+            Err(e) => self.label_old.set_text(&format!(
+                "Kalibrierwerte konnten nicht gelesen werden: {}",
+                e
+            )),
+        }
+    }
+
+    /// Result of forcing the output current
+    pub fn forced(&self, test_value: TestValue, result: &Result<u16>) {
+        if test_value != TestValue::OutputCurrent {
+            return;
+        }
+        let mut check = self.check.borrow_mut();
+        let check = match &mut *check {
+            Some(check) if check.page == Page::Measure && !check.forced => check,
+            _ => return,
+        };
+        match result {
+            // Das Ausschalten der Vorgabe bestätigt keinen Prüfpunkt
+            Ok(raw) if test_value.from_raw(*raw).is_none() => {}
+            Ok(_) => {
+                check.forced = true;
+                self.label_forced
+                    .set_text("Ausgangsstrom vorgegeben, jetzt den gemessenen Strom eintragen.");
+                self.button_next.set_sensitive(true);
+            }
+            Err(e) => self.label_forced.set_text(&format!(
+                "Ausgangsstrom konnte nicht vorgegeben werden: {}",
+                e
+            )),
+        }
+    }
+
+    /// Result of writing the corrected calibration values
+    ///
+    /// After writing the loop is checked again, the technician starts the second
+    /// pass with "Weiter".
+    pub fn written(&self, result: Result<OutputCalibration>) {
+        let error = {
+            let mut check = self.check.borrow_mut();
+            let check = match &mut *check {
+                Some(check) if check.page == Page::Writing => check,
+                _ => return,
+            };
+            match result {
+                Ok(new) => {
+                    if let Some(result) = &mut check.result {
+                        result.new = Some(new);
+                    }
+                    check.verify = true;
+                    self.spinner.stop();
+                    self.label_writing.set_text(&format!(
+                        "Kalibrierwerte geschrieben: {}\nMit \"Weiter\" wird die Stromschleife \
+                         erneut geprüft.",
+                        format_calibration(&new)
+                    ));
+                    self.button_next.set_sensitive(true);
+                    self.button_cancel.set_sensitive(true);
+                    return;
+                }
+                Err(e) => {
+                    check.page = Page::Result;
+                    format!("Kalibrierwerte konnten nicht geschrieben werden: {}", e)
+                }
+            }
+        };
+        self.show_result(Some(&error));
+    }
+
+    /// Start the second pass after writing, returns the raw value of the first
+    /// point
+    fn verify(&self) -> Option<u16> {
+        {
+            let mut check = self.check.borrow_mut();
+            let check = check.as_mut()?;
+            if !check.verify {
+                return None;
+            }
+            check.page = Page::Measure;
+            check.point = 0;
+            check.forced = false;
+        }
+        self.show_point(0, true);
+        self.show_page(Page::Measure);
+        TestValue::OutputCurrent.to_raw(LOOP_CHECK_POINTS[0]).ok()
+    }
+
+    /// Close the wizard, returns true if it was open
+    pub fn close(&self) -> bool {
+        let open = self.check.borrow_mut().take().is_some();
+        self.dialog.hide();
+        open
+    }
+
+    fn page(&self) -> Option<Page> {
+        self.check.borrow().as_ref().map(|check| check.page)
+    }
+
+    /// The corrected calibration values are being written
+    fn writing(&self) -> bool {
+        self.check
+            .borrow()
+            .as_ref()
+            .map_or(false, |check| check.page == Page::Writing && !check.verify)
+    }
+
+    /// Start the first pass, returns the raw value of the first point
+    fn measure(&self) -> Option<u16> {
+        {
+            let mut check = self.check.borrow_mut();
+            let check = check.as_mut()?;
+            let old = check.old?;
+            check.result = Some(LoopCheck::new(
+                &self.entry_operator.get_text(),
+                check.modbus_address,
+                self.spin_button_tolerance.get_value(),
+                old,
+                Local::now(),
+            ));
+            check.page = Page::Measure;
+            check.point = 0;
+            check.forced = false;
+        }
+        self.show_point(0, false);
+        self.show_page(Page::Measure);
+        TestValue::OutputCurrent.to_raw(LOOP_CHECK_POINTS[0]).ok()
+    }
+
+    /// Take the measured current of the point
+    ///
+    /// Returns the raw value of the next point, `Some(None)` after the last point
+    /// to switch the forced current off, `None` if the point is not forced yet.
+    fn measured(&self) -> Option<Option<u16>> {
+        let (next, verify) = {
+            let mut check = self.check.borrow_mut();
+            let check = check.as_mut()?;
+            if !check.forced {
+                return None;
+            }
+            let point = LoopPoint {
+                setpoint: LOOP_CHECK_POINTS[check.point],
+                measured: self.spin_button_measured.get_value(),
+            };
+            let result = check.result.as_mut()?;
+            if check.verify {
+                result.after.push(point);
+            } else {
+                result.before.push(point);
+            }
+            check.point += 1;
+            check.forced = false;
+            (check.point, check.verify)
+        };
+
+        if next < LOOP_CHECK_POINTS.len() {
+            self.show_point(next, verify);
+            return Some(
+                TestValue::OutputCurrent
+                    .to_raw(LOOP_CHECK_POINTS[next])
+                    .ok(),
+            );
+        }
+        if verify {
+            self.finish();
+        } else {
+            self.show_correction();
+        }
+        Some(None)
+    }
+
+    /// Show the setpoint of a point while it is forced
+    fn show_point(&self, point: usize, verify: bool) {
+        let setpoint = LOOP_CHECK_POINTS[point];
+        self.label_step.set_markup(&format!(
+            "<b>{} {} von {}: {}</b>",
+            if verify { "Nachprüfung" } else { "Prüfung" },
+            point + 1,
+            LOOP_CHECK_POINTS.len(),
+            TestValue::OutputCurrent.format(setpoint)
+        ));
+        self.label_forced
+            .set_text("Ausgangsstrom wird vorgegeben …");
+        self.spin_button_measured.set_value(setpoint);
+        self.button_next.set_sensitive(false);
+    }
+
+    /// First pass done, show the corrected calibration values
+    fn show_correction(&self) {
+        let text = {
+            let mut check = self.check.borrow_mut();
+            let check = match &mut *check {
+                Some(check) => check,
+                None => return,
+            };
+            check.page = Page::Correction;
+            let result = match &check.result {
+                Some(result) => result,
+                None => return,
+            };
+            let mut text = format!(
+                "{}\n\n{}",
+                format_points(&result.before, result.tolerance),
+                if result.passed() {
+                    "Alle Punkte liegen innerhalb der Toleranz, ein Abgleich ist nicht nötig."
+                } else {
+                    "Nicht alle Punkte liegen innerhalb der Toleranz."
+                }
+            );
+            match result.old.corrected(&result.before) {
+                Ok(corrected) => {
+                    text.push_str(&format!(
+                        "\n\nBisher: {}\nKorrigiert: {}",
+                        format_calibration(&result.old),
+                        format_calibration(&corrected)
+                    ));
+                    check.corrected = Some(corrected);
+                }
+                Err(e) => {
+                    text.push_str(&format!("\n\nKein Abgleich möglich: {}", e));
+                    check.corrected = None;
+                }
+            }
+            text
+        };
+        self.label_correction.set_text(&text);
+        let correctable = self
+            .check
+            .borrow()
+            .as_ref()
+            .map_or(false, |check| check.corrected.is_some());
+        self.show_page(Page::Correction);
+        self.button_next.set_label("Kalibrierwerte schreiben");
+        self.button_next.set_sensitive(correctable);
+        self.button_skip.set_visible(true);
+    }
+
+    /// Returns the corrected calibration values to write
+    fn write(&self) -> Option<OutputCalibration> {
+        let corrected = {
+            let mut check = self.check.borrow_mut();
+            let check = check.as_mut()?;
+            check.page = Page::Writing;
+            check.corrected?
+        };
+        self.label_writing
+            .set_text("Kalibrierwerte werden geschrieben …");
+        self.spinner.start();
+        self.button_next.set_sensitive(false);
+        self.button_cancel.set_sensitive(false);
+        self.button_skip.set_visible(false);
+        self.show_page(Page::Writing);
+        Some(corrected)
+    }
+
+    /// Check done, show pass or fail
+    fn finish(&self) {
+        if let Some(check) = &mut *self.check.borrow_mut() {
+            check.page = Page::Result;
+        }
+        self.show_result(None);
+    }
+
+    fn show_result(&self, error: Option<&str>) {
+        for child in self.grid_result.get_children() {
+            self.grid_result.remove(&child);
+        }
+        let check = self.check.borrow();
+        let result = match check.as_ref().and_then(|check| check.result.as_ref()) {
+            Some(result) => result,
+            None => return,
+        };
+        let mut text = if result.passed() {
+            "Prüfung bestanden".to_string()
+        } else {
+            "Prüfung nicht bestanden".to_string()
+        };
+        if let Some(max) = result.max_deviation() {
+            text.push_str(&format!(", größte Abweichung {:.2} mA", max));
+        }
+        if let Some(new) = &result.new {
+            text.push_str(&format!(
+                "\nBisher: {}\nNeu: {}",
+                format_calibration(&result.old),
+                format_calibration(new)
+            ));
+        }
+        if let Some(error) = error {
+            text.push_str(&format!("\n{}", error));
+        }
+        self.label_result.set_text(&text);
+        self.label_result
+            .get_style_context()
+            .remove_class("test-mode");
+        if !result.passed() {
+            self.label_result.get_style_context().add_class("test-mode");
+        }
+
+        for (column, title) in ["Vorgabe", "Vorher", "Abweichung", "Nachher", "Abweichung"]
+            .iter()
+            .enumerate()
+        {
+            let label = gtk::Label::new(Some(*title));
+            label.get_style_context().add_class("dim-label");
+            self.grid_result.attach(&label, column as i32, 0, 1, 1);
+        }
+        for (row, setpoint) in LOOP_CHECK_POINTS.iter().enumerate() {
+            let mut texts = vec![format!("{:.2} mA", setpoint)];
+            for points in &[&result.before, &result.after] {
+                match points.get(row) {
+                    Some(point) => {
+                        texts.push(format!("{:.2} mA", point.measured));
+                        texts.push(format!("{:+.2} mA", point.deviation()));
+                    }
+                    None => texts.extend(vec!["-".to_string(), "-".to_string()]),
+                }
+            }
+            for (column, text) in texts.iter().enumerate() {
+                let label = gtk::Label::new(Some(text.as_str()));
+                label.set_xalign(1.0);
+                self.grid_result
+                    .attach(&label, column as i32, row as i32 + 1, 1, 1);
+            }
+        }
+        self.grid_result.show_all();
+        self.show_page(Page::Result);
+        self.button_next.set_label("Schließen");
+        self.button_next.set_sensitive(true);
+        self.button_cancel.set_visible(false);
+        self.button_skip.set_visible(false);
+        self.button_save.set_visible(true);
+    }
+
+    /// Ask for the file name and save the result as JSON
+    fn save(&self, window: &gtk::Window) {
+        let result = match self
+            .check
+            .borrow()
+            .as_ref()
+            .and_then(|check| check.result.clone())
+        {
+            Some(result) => result,
+            None => return,
+        };
+
+        let dialog = gtk::FileChooserDialog::with_buttons(
+            Some("Ergebnis der Stromschleifenprüfung speichern"),
+            Some(window),
+            gtk::FileChooserAction::Save,
+            &[
+                ("_Abbrechen", gtk::ResponseType::Cancel),
+                ("_Speichern", gtk::ResponseType::Accept),
+            ],
+        );
+        dialog.set_do_overwrite_confirmation(true);
+        dialog.set_current_name(&result.file_name());
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("JSON Datei (*.json)"));
+        filter.add_pattern("*.json");
+        dialog.add_filter(&filter);
+
+        let path = match dialog.run() {
+            gtk::ResponseType::Accept => dialog.get_filename(),
+            _ => None,
+        };
+        dialog.close();
+
+        if let Some(path) = path {
+            let message = match result.save(&path) {
+                Ok(()) => format!("Ergebnis gespeichert: {}", path.display()),
+                Err(e) => format!("Ergebnis konnte nicht gespeichert werden: {}", e),
+            };
+            self.label_result.set_text(&message);
+        }
+    }
+
+    fn show_page(&self, page: Page) {
+        self.stack.set_visible_child_name(page.name());
+        if page == Page::Prepare || page == Page::Measure {
+            self.button_next.set_label("Weiter");
+            self.button_cancel.set_visible(true);
+            self.button_cancel.set_sensitive(true);
+            self.button_save.set_visible(false);
+            self.button_skip.set_visible(false);
+        }
+    }
+}
+
+/// Calibration values, e.g. "4 mA: 180, 20 mA: 900 (Rwreg 53 und 54)"
+fn format_calibration(calibration: &OutputCalibration) -> String {
+    format!(
+        "4 mA: {}, 20 mA: {} (Rwreg 53 und 54)",
+        calibration.low, calibration.high
+    )
+}
+
+/// One line per point with measured current and deviation
+fn format_points(points: &[LoopPoint], tolerance: f64) -> String {
+    points
+        .iter()
+        .map(|point| {
+            format!(
+                "{:.2} mA: gemessen {:.2} mA, Abweichung {:+.2} mA{}",
+                point.setpoint,
+                point.measured,
+                point.deviation(),
+                if point.passed(tolerance) { "" } else { " (!)" }
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
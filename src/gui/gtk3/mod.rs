@@ -1,27 +1,65 @@
-use crate::tokio_thread;
+use crate::error::{Error, Result};
+use crate::sensors::backup::{Backup, Change};
+use crate::sensors::calibration::CalibrationStep;
+use crate::sensors::calibration_report::{CalibrationRecord, CalibrationSnapshot};
+use crate::sensors::device_info::DeviceInfo;
+use crate::sensors::front_end::FrontEnd;
+use crate::sensors::loop_check::OutputCalibration;
+use crate::sensors::output_scaling::OutputScaling;
+use crate::sensors::ra_gas_ne4::{WorkingMode, CONNECTION_REGISTERS, NE4, RREGS};
+use crate::sensors::temperature_curve::TemperatureCurve;
+use crate::sensors::test_values::{Override, TestValue};
+use crate::tokio_thread::bus_scan::ScanResult;
+use crate::tokio_thread::hotplug::{PortEvent, PortInfo};
 use crate::tokio_thread::{TokioCommand, TokioThread};
+use bus_scan::BusScanUi;
+use calibration::CalibrationUi;
 use chrono::Local;
+use dashboard::DashboardUi;
+use device_info::DeviceInfoUi;
+use front_end::FrontEndUi;
 use gio::prelude::*;
 use glib::clone;
 use glib::{signal_handler_block, signal_handler_unblock};
 use gtk::prelude::*;
 use gtk::{Application, InfoBarExt};
+use line_settings::LineSettingsUi;
+use loop_check::LoopCheckUi;
+use output_scaling::OutputScalingUi;
+use recording::RecordingUi;
 use rwreg_store::RwregStore;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
+use temperature_curve::TemperatureCurveUi;
+use test_values::TestValuesUi;
+use trend::TrendUi;
 
 #[macro_use]
 pub mod macros;
+pub mod backup;
+pub mod bus_scan;
+pub mod calibration;
+pub mod dashboard;
+pub mod device_info;
+pub mod front_end;
+pub mod line_settings;
+pub mod loop_check;
+pub mod output_scaling;
+pub mod presets;
+pub mod recording;
 pub mod rwreg_store;
+pub mod temperature_curve;
+pub mod test_values;
 pub mod treestore_values;
+pub mod trend;
 
 const PKG_VERSION: &'static str = env!("CARGO_PKG_VERSION");
 const PKG_NAME: &'static str = env!("CARGO_PKG_NAME");
 const PKG_DESCRIPTION: &'static str = env!("CARGO_PKG_DESCRIPTION");
 
 pub struct Ui {
-    // application_window: gtk::ApplicationWindow,
+    application_window: gtk::ApplicationWindow,
     // combo_box_text_sensor_working_mode_map: HashMap<String, u16>,
     // toggle_button_connect_toggle_signal: glib::SignalHandlerId,
     button_messgas: gtk::Button,
@@ -29,40 +67,78 @@ pub struct Ui {
     button_nullpunkt: gtk::Button,
     button_reset: gtk::Button,
     button_sensor_working_mode: gtk::Button,
+    bus_scan_ui: BusScanUi,
+    calibration_ui: CalibrationUi,
     #[cfg(feature = "ra-gas")]
     check_button_mcs: gtk::CheckButton,
     combo_box_text_ports_changed_signal: glib::SignalHandlerId,
-    combo_box_text_ports_map: Rc<RefCell<HashMap<String, u32>>>,
     combo_box_text_ports: gtk::ComboBoxText,
     combo_box_text_sensor_working_mode: gtk::ComboBoxText,
+    dashboard_ui: DashboardUi,
+    device_info_ui: DeviceInfoUi,
     entry_modbus_address: gtk::Entry,
+    front_end_ui: FrontEndUi,
     infobar_info: gtk::InfoBar,
     label_sensor_ma_value: gtk::Label,
     label_sensor_type_value: gtk::Label,
+    label_sensor_value_si: gtk::Label,
     label_sensor_value_value: gtk::Label,
+    line_settings_ui: LineSettingsUi,
     list_store_sensor: gtk::ListStore,
+    loop_check_ui: LoopCheckUi,
+    output_scaling_ui: OutputScalingUi,
+    /// Serial ports in the order of the port selector
+    port_list: Rc<RefCell<Vec<PortInfo>>>,
+    recording_ui: RecordingUi,
     revealer_infobar_info: gtk::Revealer,
     statusbar_application: gtk::Statusbar,
     statusbar_contexts: HashMap<StatusContext, u32>,
+    temperature_curve_ui: TemperatureCurveUi,
+    test_values_ui: TestValuesUi,
     toggle_button_connect: gtk::ToggleButton,
+    trend_ui: TrendUi,
     #[cfg(feature = "ra-gas")]
     rwreg_store: RwregStore,
 }
 
 impl Ui {
-    fn select_port(&self, num: u32) {
-        // Restore selected serial interface
+    /// Fill the port selector
+    ///
+    /// The port named `active` stays selected if it is still available, the
+    /// selection doesn't depend on the position in the list.
+    fn update_ports(&self, ports: Vec<PortInfo>, active: Option<&str>) {
         signal_handler_block(
             &self.combo_box_text_ports,
             &self.combo_box_text_ports_changed_signal,
         );
-        &self.combo_box_text_ports.set_active(Some(num));
+        self.combo_box_text_ports.remove_all();
+        if ports.is_empty() {
+            self.combo_box_text_ports
+                .append(None, "Keine Schnittstelle gefunden");
+            self.combo_box_text_ports.set_active(Some(0));
+        } else {
+            for port in &ports {
+                self.combo_box_text_ports.append(None, &port.label());
+            }
+            let index = active
+                .and_then(|name| ports.iter().position(|port| port.name == name))
+                .unwrap_or(0);
+            self.combo_box_text_ports.set_active(Some(index as u32));
+        }
         signal_handler_unblock(
             &self.combo_box_text_ports,
             &self.combo_box_text_ports_changed_signal,
         );
-        &self.combo_box_text_ports.set_sensitive(true);
-        &self.toggle_button_connect.set_sensitive(true);
+
+        // Connected or scanning the port can't be changed
+        let scanning = self.bus_scan_ui.is_scanning();
+        let busy = scanning || self.toggle_button_connect.get_active();
+        self.combo_box_text_ports
+            .set_sensitive(!ports.is_empty() && !busy);
+        self.toggle_button_connect
+            .set_sensitive(!ports.is_empty() && !scanning);
+        *self.port_list.borrow_mut() = ports;
+        update_port_tooltip(&self.combo_box_text_ports, &self.port_list);
     }
 }
 
@@ -74,21 +150,44 @@ enum StatusContext {
 
 #[derive(Debug)]
 pub enum UiCommand {
+    Backup(Result<Backup>),
+    Calibrated(CalibrationStep, Result<CalibrationRecord>),
+    CalibrationRead(Result<(DeviceInfo, CalibrationSnapshot)>),
     DisableConnectUiElements,
     Disconnect,
     EnableConnectUiElements,
     Error(String),
-    Messgas(tokio::io::Result<()>),
-    NewModbusAddress(tokio::io::Result<()>),
-    NewWorkingMode(tokio::io::Result<()>),
-    Nullpunkt(tokio::io::Result<()>),
+    FrontEndRead(Result<FrontEnd>),
+    FrontEndWritten(Result<FrontEnd>),
+    NewModbusAddress(Result<()>),
+    NewWorkingMode(Result<()>),
+    OutputCalibrationRead(Result<OutputCalibration>),
+    OutputCalibrationWritten(Result<OutputCalibration>),
+    OutputScalingRead(Result<OutputScaling>),
+    OutputScalingWritten(Result<OutputScaling>),
+    /// The tokio thread has finished, the application can quit
+    Quit,
     // Reconnect,
+    Restore(Result<Vec<Change>>),
+    /// Differences to a backup, `true` if compared with another device id
+    RestoreDiff(Backup, bool, Result<Vec<Change>>),
+    ScanFinished(Result<()>),
+    ScanProgress(u8, Option<ScanResult>),
     ShowInfo(String),
-    UpdatePorts(Vec<String>),
+    TemperatureCurveRead(Result<TemperatureCurve>),
+    TemperatureCurveWritten(Result<TemperatureCurve>),
+    TestValue(TestValue, Result<u16>),
+    TestValuesReset(Result<()>),
+    /// Test values reset on disconnect, exit or after a lost port is back
+    TestValuesRestored(Result<Vec<Override>>),
+    UpdateDashboard(u8, Result<NE4>),
+    /// All available ports and the changes since the last update
+    UpdatePorts(Vec<PortInfo>, Vec<PortEvent>),
     UpdateSensorType(String),
     UpdateSensorValue(u16),
-    UpdateSensorValues(Result<Vec<u16>, mio_serial::Error>),
-    UpdateSensorRwregValues(Result<Vec<u16>, mio_serial::Error>),
+    UpdateSensorValues(Result<NE4>),
+    UpdateSensorRwregValues(Result<NE4>),
+    WriteRegister(u16, Result<u16>),
 }
 
 pub fn launch() {
@@ -166,38 +265,29 @@ fn ui_init(app: &gtk::Application) {
     // Statusbar
     let statusbar_application: gtk::Statusbar = build!(builder, "statusbar_application");
     let context_id_port_ops = statusbar_application.get_context_id("port operations");
-    let context_map: HashMap<StatusContext, u32> =
-        [(StatusContext::PortOperation, context_id_port_ops)]
-            .iter()
-            .cloned()
-            .collect();
+    let context_id_errors = statusbar_application.get_context_id("errors");
+    let context_map: HashMap<StatusContext, u32> = [
+        (StatusContext::PortOperation, context_id_port_ops),
+        (StatusContext::Error, context_id_errors),
+    ]
+    .iter()
+    .cloned()
+    .collect();
     // Serial port selector
     let combo_box_text_ports: gtk::ComboBoxText = build!(builder, "combo_box_text_ports");
-    let combo_box_text_ports_map = Rc::new(RefCell::new(HashMap::<String, u32>::new()));
-    scan_ports(&combo_box_text_ports, &combo_box_text_ports_map);
+    // Filled by the port watcher of the tokio thread, see `UiCommand::UpdatePorts`
+    let port_list = Rc::new(RefCell::new(Vec::<PortInfo>::new()));
 
     // Sensor Working Mode selector
     let combo_box_text_sensor_working_mode: gtk::ComboBoxText =
         build!(builder, "combo_box_text_sensor_working_mode");
     combo_box_text_sensor_working_mode.set_sensitive(false);
-    #[allow(unused_assignments)]
-    let mut combo_box_text_sensor_working_mode_map = HashMap::new();
-    combo_box_text_sensor_working_mode_map = [
-        ("Unkonfiguriert".to_string(), 0),
-        ("CO 1000 ppm".to_string(), 10),
-        ("CO 300 ppm".to_string(), 12),
-        ("NO 250 ppm".to_string(), 20),
-        ("NO2 20 ppm".to_string(), 30),
-        ("NH3 1000 ppm".to_string(), 40),
-        ("NH3 100 ppm".to_string(), 42),
-        ("CL2 10 ppm".to_string(), 50),
-        ("H2S 25 ppm".to_string(), 60),
-    ]
-    .iter()
-    .cloned()
-    .collect();
-    for (name, id) in combo_box_text_sensor_working_mode_map.clone() {
-        combo_box_text_sensor_working_mode.append(Some(&id.to_string()), &name);
+    // Reihenfolge und Bezeichnung aus dem Gaskatalog
+    for working_mode in WorkingMode::all() {
+        combo_box_text_sensor_working_mode.append(
+            Some(&working_mode.code().to_string()),
+            &working_mode.to_string(),
+        );
     }
 
     // Notebook
@@ -206,6 +296,12 @@ fn ui_init(app: &gtk::Application) {
     // Modbus Adresse
     let entry_modbus_address: gtk::Entry = build!(builder, "entry_modbus_address");
     let entry_new_modbus_address: gtk::Entry = build!(builder, "entry_new_modbus_address");
+    // Zielsensor aller Befehle der folgenden Seiten
+    let target_widgets = TargetWidgets {
+        combo_box_text_ports: combo_box_text_ports.clone(),
+        port_list: port_list.clone(),
+        entry_modbus_address: entry_modbus_address.clone(),
+    };
 
     // Reset Button
     let button_reset: gtk::Button = build!(builder, "button_reset");
@@ -218,6 +314,7 @@ fn ui_init(app: &gtk::Application) {
 
     // ListStore Sensor Values
     let list_store_sensor: gtk::ListStore = build!(builder, "list_store_sensor");
+    fill_list_store_sensor(&list_store_sensor);
 
     // Rwreg
     // This has to be declared outside of the following feature-block,
@@ -226,15 +323,263 @@ fn ui_init(app: &gtk::Application) {
     rwreg_store.fill_treestore();
     #[cfg(feature = "ra-gas")]
     {
-        let rwreg_window = rwreg_store.build_ui();
+        let rwreg_window = rwreg_store.build_ui(clone!(
+            @strong combo_box_text_ports,
+            @strong port_list,
+            @strong entry_modbus_address,
+            @strong tokio_thread_sender
+            => move |reg_nr, value| {
+                let port = active_port(&combo_box_text_ports, &port_list);
+                let modbus_address = entry_modbus_address.get_text().parse().unwrap_or(247);
+
+                send_tokio(&tokio_thread_sender, TokioCommand::WriteRegister(port, modbus_address, reg_nr, value));
+        }));
         let label = gtk::Label::new(Some("Rwreg Lese/Schreib(Read/Write)-Register"));
         notebook_sensor.append_page(&rwreg_window, Some(&label));
     }
 
+    let line_settings_ui = LineSettingsUi::new(&builder);
+    line_settings_ui.connect_changed(clone!(
+        @strong tokio_thread_sender => move |line_settings| {
+            send_tokio(&tokio_thread_sender, TokioCommand::SetLineSettings(line_settings));
+        }
+    ));
+    line_settings_ui.connect_poll_interval_changed(clone!(
+        @strong tokio_thread_sender => move |interval| {
+            send_tokio(&tokio_thread_sender, TokioCommand::SetPollInterval(interval));
+        }
+    ));
+    // Einstellungen aus der UI übernehmen
+    send_tokio(
+        &tokio_thread_sender,
+        TokioCommand::SetLineSettings(line_settings_ui.line_settings()),
+    );
+    send_tokio(
+        &tokio_thread_sender,
+        TokioCommand::SetPollInterval(line_settings_ui.poll_interval()),
+    );
+
     let toggle_button_connect: gtk::ToggleButton = build!(builder, "toggle_button_connect");
+
+    // Bus Scan
+    let bus_scan_ui = BusScanUi::new();
+    let bus_scan_box = bus_scan_ui.build_ui(
+        clone!(
+            @strong combo_box_text_ports,
+            @strong port_list,
+            @strong line_settings_ui,
+            @strong toggle_button_connect,
+            @strong tokio_thread_sender
+            => move || {
+                let port = active_port(&combo_box_text_ports, &port_list);
+                // Während des Scans wird die Schnittstelle exklusiv benötigt
+                toggle_button_connect.set_sensitive(false);
+                combo_box_text_ports.set_sensitive(false);
+                line_settings_ui.set_sensitive(false);
+                send_tokio(&tokio_thread_sender, TokioCommand::ScanBus(port));
+        }),
+        clone!(
+            @strong tokio_thread_sender
+            => move || {
+                send_tokio(&tokio_thread_sender, TokioCommand::CancelScan);
+        }),
+        clone!(
+            @strong entry_modbus_address,
+            @strong notebook_sensor,
+            @strong toggle_button_connect
+            => move |modbus_address| {
+                entry_modbus_address.set_text(&modbus_address.to_string());
+                notebook_sensor.set_current_page(Some(0));
+                // Der Verbinden Button übernimmt Schnittstelle und Modbus Adresse
+                toggle_button_connect.set_active(true);
+        }),
+    );
+    // Geräteinformation
+    let device_info_ui = DeviceInfoUi::new();
+    let device_info_box = device_info_ui.build_ui();
+    let label = gtk::Label::new(Some("Geräteinformation"));
+    notebook_sensor.append_page(&device_info_box, Some(&label));
+
+    // Verlauf
+    let trend_ui = TrendUi::new();
+    let trend_box = trend_ui.build_ui();
+    let label = gtk::Label::new(Some("Verlauf"));
+    notebook_sensor.append_page(&trend_box, Some(&label));
+
+    // Aufzeichnung
+    let recording_ui = RecordingUi::new();
+    let recording_box = recording_ui.build_ui(&application_window);
+    let label = gtk::Label::new(Some("Aufzeichnung"));
+    notebook_sensor.append_page(&recording_box, Some(&label));
+
+    // Stromschleife
+    let loop_check_ui = LoopCheckUi::new();
+    loop_check_ui.build_ui(
+        &application_window,
+        clone!(
+            @strong target_widgets,
+            @strong tokio_thread_sender
+            => move |raw| {
+                let (port, modbus_address) = target(&target_widgets);
+
+                send_tokio(&tokio_thread_sender, TokioCommand::SetTestValue(port, modbus_address, TestValue::OutputCurrent, raw));
+        }),
+        clone!(
+            @strong target_widgets,
+            @strong tokio_thread_sender
+            => move |calibration| {
+                let (port, modbus_address) = target(&target_widgets);
+
+                send_tokio(&tokio_thread_sender, TokioCommand::WriteOutputCalibration(port, modbus_address, calibration));
+        }),
+    );
+
+    // Testwerte
+    let test_values_ui = TestValuesUi::new();
+    let test_values_box = test_values_ui.build_ui(
+        clone!(
+            @strong target_widgets,
+            @strong tokio_thread_sender
+            => move |test_value, raw| {
+                let (port, modbus_address) = target(&target_widgets);
+
+                send_tokio(&tokio_thread_sender, TokioCommand::SetTestValue(port, modbus_address, test_value, raw));
+        }),
+        clone!(
+            @strong target_widgets,
+            @strong tokio_thread_sender
+            => move || {
+                let (port, modbus_address) = target(&target_widgets);
+
+                send_tokio(&tokio_thread_sender, TokioCommand::ResetTestValues(port, modbus_address));
+        }),
+        clone!(
+            @strong loop_check_ui,
+            @strong target_widgets,
+            @strong tokio_thread_sender
+            => move || {
+                let (port, modbus_address) = target(&target_widgets);
+
+                loop_check_ui.start(modbus_address);
+                send_tokio(&tokio_thread_sender, TokioCommand::ReadOutputCalibration(port, modbus_address));
+        }),
+    );
+    notebook_sensor.append_page(&test_values_box, Some(test_values_ui.tab_label()));
+
+    // Ausgangsstrom
+    let output_scaling_ui = OutputScalingUi::new();
+    let output_scaling_box = output_scaling_ui.build_ui(
+        clone!(
+            @strong target_widgets,
+            @strong tokio_thread_sender
+            => move || {
+                let (port, modbus_address) = target(&target_widgets);
+
+                send_tokio(&tokio_thread_sender, TokioCommand::ReadOutputScaling(port, modbus_address));
+        }),
+        clone!(
+            @strong target_widgets,
+            @strong tokio_thread_sender
+            => move |scaling| {
+                let (port, modbus_address) = target(&target_widgets);
+
+                send_tokio(&tokio_thread_sender, TokioCommand::WriteOutputScaling(port, modbus_address, scaling));
+        }),
+    );
+    let label = gtk::Label::new(Some("Ausgangsstrom"));
+    notebook_sensor.append_page(&output_scaling_box, Some(&label));
+
+    // Temperaturkennlinie
+    let temperature_curve_ui = TemperatureCurveUi::new();
+    let temperature_curve_box = temperature_curve_ui.build_ui(
+        clone!(
+            @strong target_widgets,
+            @strong tokio_thread_sender
+            => move || {
+                let (port, modbus_address) = target(&target_widgets);
+
+                send_tokio(&tokio_thread_sender, TokioCommand::ReadTemperatureCurve(port, modbus_address));
+        }),
+        clone!(
+            @strong target_widgets,
+            @strong tokio_thread_sender
+            => move |curve| {
+                let (port, modbus_address) = target(&target_widgets);
+
+                send_tokio(&tokio_thread_sender, TokioCommand::WriteTemperatureCurve(port, modbus_address, curve));
+        }),
+    );
+    let label = gtk::Label::new(Some("Temperaturkennlinie"));
+    notebook_sensor.append_page(&temperature_curve_box, Some(&label));
+
+    // Auswerte IC
+    let front_end_ui = FrontEndUi::new();
+    let front_end_box = front_end_ui.build_ui(
+        clone!(
+            @strong target_widgets,
+            @strong tokio_thread_sender
+            => move || {
+                let (port, modbus_address) = target(&target_widgets);
+
+                send_tokio(&tokio_thread_sender, TokioCommand::ReadFrontEnd(port, modbus_address));
+        }),
+        clone!(
+            @strong target_widgets,
+            @strong tokio_thread_sender
+            => move |front_end| {
+                let (port, modbus_address) = target(&target_widgets);
+
+                send_tokio(&tokio_thread_sender, TokioCommand::WriteFrontEnd(port, modbus_address, front_end));
+        }),
+    );
+    let label = gtk::Label::new(Some("Auswerte IC"));
+    notebook_sensor.append_page(&front_end_box, Some(&label));
+
+    let label = gtk::Label::new(Some("Bus Scan"));
+    notebook_sensor.append_page(&bus_scan_box, Some(&label));
+
+    // Übersicht, die Sensoren werden nur abgefragt solange die Seite sichtbar ist
+    let dashboard_page = notebook_sensor.get_n_pages();
+    let dashboard_ui = DashboardUi::new();
+    let dashboard_box = dashboard_ui.build_ui(
+        clone!(
+            @strong bus_scan_ui
+            => move || bus_scan_ui.found_addresses()
+        ),
+        clone!(
+            @strong combo_box_text_ports,
+            @strong port_list,
+            @strong notebook_sensor,
+            @strong toggle_button_connect,
+            @strong tokio_thread_sender
+            => move |modbus_addresses| {
+                // Neue Sensoren werden ab dem nächsten Umlauf abgefragt
+                if toggle_button_connect.get_active()
+                    && notebook_sensor.get_current_page() == Some(dashboard_page)
+                {
+                    let port = active_port(&combo_box_text_ports, &port_list);
+                    send_tokio(&tokio_thread_sender, TokioCommand::UpdateDashboard(port, modbus_addresses));
+                }
+        }),
+        clone!(
+            @strong entry_modbus_address,
+            @strong notebook_sensor,
+            @strong toggle_button_connect
+            => move |modbus_address| {
+                entry_modbus_address.set_text(&modbus_address.to_string());
+                notebook_sensor.set_current_page(Some(0));
+                toggle_button_connect.set_active(true);
+        }),
+    );
+    let label = gtk::Label::new(Some("Übersicht"));
+    notebook_sensor.append_page(&dashboard_box, Some(&label));
+
     let label_sensor_value_value: gtk::Label = build!(builder, "label_sensor_value_value");
+    let label_sensor_value_si: gtk::Label = build!(builder, "label_sensor_value_si");
     let label_sensor_ma_value: gtk::Label = build!(builder, "label_sensor_ma_value");
 
+    let menu_item_backup: gtk::MenuItem = build!(builder, "menu_item_backup");
+    let menu_item_restore: gtk::MenuItem = build!(builder, "menu_item_restore");
     let menu_item_quit: gtk::MenuItem = build!(builder, "menu_item_quit");
     let menu_item_about: gtk::MenuItem = build!(builder, "menu_item_about");
 
@@ -295,120 +640,128 @@ fn ui_init(app: &gtk::Application) {
     // => move |_| {
     //
     // }));
-    let combo_box_text_ports_changed_signal = combo_box_text_ports.connect_changed(move |_| {});
+    let combo_box_text_ports_changed_signal = combo_box_text_ports.connect_changed(clone!(
+        @strong port_list
+        => move |combo_box_text_ports| {
+            update_port_tooltip(combo_box_text_ports, &port_list);
+        }
+    ));
 
     toggle_button_connect.connect_clicked(clone!(
-            @strong combo_box_text_ports_map,
+            @strong port_list,
             @strong combo_box_text_ports,
+            @strong dashboard_ui,
             @strong entry_modbus_address,
+            @strong notebook_sensor,
+            @strong trend_ui,
             @strong tokio_thread_sender
             => move |s| {
                 if s.get_active() {
-                    // get port
-                    let active_port = combo_box_text_ports.get_active().unwrap_or(0);
+                    // Der Verlauf gehört zum vorher verbundenen Sensor
+                    trend_ui.clear();
 
-                    let mut port = None;
-                    for (p, i) in &*combo_box_text_ports_map.borrow() {
-                        if *i == active_port {
-                            port = Some(p.to_owned());
-                            break;
-                        }
-                    }
+                    // get port
+                    let port = active_port(&combo_box_text_ports, &port_list);
                     // get modbus_address
                     let modbus_address = entry_modbus_address.get_text().parse::<u8>().unwrap_or(247);
                     info!("port: {:?}, modbus_address: {:?}", &port, &modbus_address);
 
-                    tokio_thread_sender
-                        .clone()
-                        .try_send(TokioCommand::Connect)
-                        .expect("Failed to send tokio command");
-
-                    tokio_thread_sender
-                        .clone()
-                        .try_send(TokioCommand::UpdateSensor(port.clone(), modbus_address))
-                        .expect("Failed to send tokio command");
-
-                    // #[cfg(feature = "ra-gas")]
-                    // tokio_thread_sender
-                    //     .clone()
-                    //     .try_send(TokioCommand::UpdateSensorRwregValues(port.clone(), modbus_address))
-                    //     .expect("Failed to send tokio command");
+                    // Testwerte die beim letzten Trennen nicht zurückgesetzt werden konnten
+                    send_tokio(&tokio_thread_sender, TokioCommand::RestoreTestValues(port.clone()));
+                    send_tokio(&tokio_thread_sender, TokioCommand::Connect);
+                    send_tokio(&tokio_thread_sender, poll_command(
+                        port.clone(),
+                        notebook_sensor.get_current_page() == Some(dashboard_page),
+                        &dashboard_ui,
+                        modbus_address,
+                    ));
+
                 } else {
-                    tokio_thread_sender
-                        .clone()
-                        .try_send(TokioCommand::Disconnect)
-                        .expect("Failed to send tokio command");
+                    send_tokio(&tokio_thread_sender, TokioCommand::Disconnect);
+            }
+        }
+    ));
+
+    // Die Abfrage wechselt beim Öffnen und Verlassen der Übersicht
+    notebook_sensor.connect_switch_page(clone!(
+        @strong combo_box_text_ports,
+        @strong port_list,
+        @strong dashboard_ui,
+        @strong entry_modbus_address,
+        @strong toggle_button_connect,
+        @strong tokio_thread_sender
+        => move |notebook, _, page| {
+            // Die aktuelle Seite ist hier noch die vorherige
+            let was_dashboard = notebook.get_current_page() == Some(dashboard_page);
+            let is_dashboard = page == dashboard_page;
+            if toggle_button_connect.get_active() && was_dashboard != is_dashboard {
+                let port = active_port(&combo_box_text_ports, &port_list);
+                let modbus_address = entry_modbus_address.get_text().parse().unwrap_or(247);
+
+                send_tokio(&tokio_thread_sender, poll_command(port, is_dashboard, &dashboard_ui, modbus_address));
             }
         }
     ));
 
     button_new_modbus_address.connect_clicked(clone!(
         @strong combo_box_text_ports,
-        @strong combo_box_text_ports_map,
+        @strong port_list,
         @strong entry_modbus_address,
         @strong entry_new_modbus_address,
         @strong tokio_thread_sender
         => move |_| {
-            let active_port = combo_box_text_ports.get_active().unwrap_or(0);
-            let mut port = None;
-            for (p, i) in &*combo_box_text_ports_map.borrow() {
-                if *i == active_port {
-                    port = Some(p.to_owned());
-                    break;
-                }
-            }
+            let port = active_port(&combo_box_text_ports, &port_list);
             let modbus_address = entry_modbus_address.get_text(); // .unwrap_or("0".into());
             let new_modbus_address = entry_new_modbus_address.get_text(); // .unwrap_or("0".into());
 
-            tokio_thread_sender
-                .clone()
-                .try_send(TokioCommand::NewModbusAddress(port, modbus_address.to_owned().parse().unwrap_or(0), new_modbus_address.to_owned().parse().unwrap_or(0)))
-                .expect("Faild to send tokio command");
+            send_tokio(&tokio_thread_sender, TokioCommand::NewModbusAddress(port, modbus_address.to_owned().parse().unwrap_or(0), new_modbus_address.to_owned().parse().unwrap_or(0)));
         }
     ));
 
+    // Abgleich
+    let calibration_ui = CalibrationUi::new();
+    calibration_ui.build_ui(
+        &application_window,
+        clone!(
+            @strong combo_box_text_ports,
+            @strong port_list,
+            @strong entry_modbus_address,
+            @strong tokio_thread_sender
+            => move |step| {
+                let port = active_port(&combo_box_text_ports, &port_list);
+                let modbus_address = entry_modbus_address.get_text().parse().unwrap_or(247);
+
+                send_tokio(&tokio_thread_sender, TokioCommand::Calibrate(port, modbus_address, step));
+        }),
+    );
+
     button_nullpunkt.connect_clicked(clone!(
+        @strong calibration_ui,
         @strong combo_box_text_ports,
-        @strong combo_box_text_ports_map,
+        @strong port_list,
         @strong entry_modbus_address,
         @strong tokio_thread_sender
         => move |_| {
-            let active_port = combo_box_text_ports.get_active().unwrap_or(0);
-            let mut port = None;
-            for (p, i) in &*combo_box_text_ports_map.borrow() {
-                if *i == active_port {
-                    port = Some(p.to_owned());
-                    break;
-                }
-            }
-            let modbus_address = entry_modbus_address.get_text(); // .unwrap_or("0".into());
+            let port = active_port(&combo_box_text_ports, &port_list);
+            let modbus_address = entry_modbus_address.get_text().parse().unwrap_or(247);
 
-            tokio_thread_sender
-                .clone()
-                .try_send(TokioCommand::Nullpunkt(port, modbus_address.to_owned().parse().unwrap_or(0)))
-                .expect("Faild to send tokio command");
+            calibration_ui.start(CalibrationStep::Zero, modbus_address);
+            send_tokio(&tokio_thread_sender, TokioCommand::ReadCalibration(port, modbus_address));
     }));
 
     button_messgas.connect_clicked(clone!(
+        @strong calibration_ui,
         @strong combo_box_text_ports,
-        @strong combo_box_text_ports_map,
+        @strong port_list,
         @strong entry_modbus_address,
         @strong tokio_thread_sender
         => move |_| {
-            let active_port = combo_box_text_ports.get_active().unwrap_or(0);
-            let mut port = None;
-            for (p, i) in &*combo_box_text_ports_map.borrow() {
-                if *i == active_port {
-                    port = Some(p.to_owned());
-                    break;
-                }
-            }
-            let modbus_address = entry_modbus_address.get_text(); // .unwrap_or("0".into());
+            let port = active_port(&combo_box_text_ports, &port_list);
+            let modbus_address = entry_modbus_address.get_text().parse().unwrap_or(247);
 
-            tokio_thread_sender
-                .clone()
-                .try_send(TokioCommand::Messgas(port, modbus_address.to_owned().parse().unwrap_or(0)))
-                .expect("Faild to send tokio command");
+            // Die Prüfgaskonzentration wird im Assistenten eingestellt
+            calibration_ui.start(CalibrationStep::Span { concentration: 0 }, modbus_address);
+            send_tokio(&tokio_thread_sender, TokioCommand::ReadCalibration(port, modbus_address));
     }));
 
     button_reset.connect_clicked(clone!(
@@ -418,33 +771,86 @@ fn ui_init(app: &gtk::Application) {
 
     button_sensor_working_mode.connect_clicked(clone!(
         @strong entry_modbus_address,
-        @strong combo_box_text_ports_map,
+        @strong port_list,
         @strong combo_box_text_ports,
         @strong combo_box_text_sensor_working_mode,
         @strong tokio_thread_sender => move |_| {
-            let active_port = combo_box_text_ports.get_active().unwrap_or(0);
-            let mut port = None;
-            for (p, i) in &*combo_box_text_ports_map.borrow() {
-                if *i == active_port {
-                    port = Some(p.to_owned());
-                    break;
-                }
-            }
+            let port = active_port(&combo_box_text_ports, &port_list);
             let modbus_address = entry_modbus_address.get_text(); // .unwrap_or("247".into());
             let working_mode = combo_box_text_sensor_working_mode.get_active_id().unwrap_or("0".into());
 
-            tokio_thread_sender
-                .clone()
-                .try_send(TokioCommand::NewWorkingMode(port, modbus_address.to_owned().parse().unwrap_or(0), working_mode.to_owned().parse().unwrap_or(0)))
-                .expect("Faild to send tokio command");
+            send_tokio(&tokio_thread_sender, TokioCommand::NewWorkingMode(port, modbus_address.to_owned().parse().unwrap_or(0), working_mode.to_owned().parse().unwrap_or(0)));
     }));
 
+    menu_item_backup.connect_activate(clone!(
+        @strong combo_box_text_ports,
+        @strong port_list,
+        @strong entry_modbus_address,
+        @strong tokio_thread_sender
+        => move |_| {
+            let port = active_port(&combo_box_text_ports, &port_list);
+            let modbus_address = entry_modbus_address.get_text().parse().unwrap_or(247);
+
+            send_tokio(&tokio_thread_sender, TokioCommand::Backup(port, modbus_address));
+        }
+    ));
+
+    menu_item_restore.connect_activate(clone!(
+        @strong application_window,
+        @strong combo_box_text_ports,
+        @strong port_list,
+        @strong entry_modbus_address,
+        @strong tokio_thread_sender
+        => move |_| {
+            match backup::open_backup(&application_window) {
+                Ok(Some(backup)) => {
+                    let port = active_port(&combo_box_text_ports, &port_list);
+                    let modbus_address = entry_modbus_address.get_text().parse().unwrap_or(247);
+
+                    send_tokio(&tokio_thread_sender, TokioCommand::RestoreDiff(port, modbus_address, backup, false));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    let dialog = gtk::MessageDialog::new(
+                        Some(&application_window),
+                        gtk::DialogFlags::MODAL,
+                        gtk::MessageType::Error,
+                        gtk::ButtonsType::Ok,
+                        &e,
+                    );
+                    dialog.run();
+                    dialog.close();
+                }
+            }
+        }
+    ));
+
     menu_item_quit.connect_activate(clone!(
         @weak application_window => move |_| {
             application_window.close()
         }
     ));
 
+    // Vor dem Beenden setzt der Tokio Thread alle Testwerte zurück, er meldet sich
+    // mit `UiCommand::Quit`. Antwortet er nicht, wird trotzdem beendet.
+    let quitting = Rc::new(Cell::new(false));
+    application_window.connect_delete_event(clone!(
+        @strong tokio_thread_sender
+        => move |application_window, _| {
+            if quitting.replace(true) {
+                return gtk::Inhibit(false);
+            }
+            send_tokio(&tokio_thread_sender, TokioCommand::Shutdown);
+            glib::timeout_add_seconds_local(5, clone!(
+                @weak application_window => @default-return glib::Continue(false), move || {
+                    application_window.close();
+                    glib::Continue(false)
+                }
+            ));
+            gtk::Inhibit(true)
+        }
+    ));
+
     menu_item_about.connect_activate(clone!(
         @strong about_dialog => move |_| {
             about_dialog.show()
@@ -459,7 +865,7 @@ fn ui_init(app: &gtk::Application) {
 
     // Zugriff auf die Elemente der UI
     let ui = Ui {
-        // application_window: application_window.clone(),
+        application_window: application_window.clone(),
         // combo_box_text_sensor_working_mode_map,
         // toggle_button_connect_toggle_signal,
         button_messgas,
@@ -467,22 +873,35 @@ fn ui_init(app: &gtk::Application) {
         button_nullpunkt,
         button_reset,
         button_sensor_working_mode,
+        bus_scan_ui,
+        calibration_ui,
         #[cfg(feature = "ra-gas")]
         check_button_mcs: check_button_mcs.clone(),
         combo_box_text_ports_changed_signal,
-        combo_box_text_ports_map,
         combo_box_text_ports,
         combo_box_text_sensor_working_mode,
+        dashboard_ui,
+        device_info_ui,
         entry_modbus_address,
+        front_end_ui,
         infobar_info,
         label_sensor_ma_value,
         label_sensor_type_value,
+        label_sensor_value_si,
         label_sensor_value_value,
+        line_settings_ui,
         list_store_sensor,
+        loop_check_ui,
+        output_scaling_ui,
+        port_list,
+        recording_ui,
         revealer_infobar_info,
         statusbar_application,
         statusbar_contexts: context_map,
+        temperature_curve_ui,
+        test_values_ui,
         toggle_button_connect,
+        trend_ui,
         #[cfg(feature = "ra-gas")]
         rwreg_store,
     };
@@ -526,10 +945,7 @@ fn ui_init(app: &gtk::Application) {
                         &ui.entry_modbus_address.set_sensitive(true);
                         &ui.button_reset.set_sensitive(true);
                         // FIXME: Check if this is needed
-                        tokio_thread_sender
-                            .clone()
-                            .try_send(TokioCommand::Disconnect)
-                            .expect("Faild to send tokio command");
+                        send_tokio(&tokio_thread_sender, TokioCommand::Disconnect);
                         // log_status(
                         //     &ui,
                         //     StatusContext::PortOperation,
@@ -548,11 +964,26 @@ fn ui_init(app: &gtk::Application) {
                     }
                     UiCommand::Error(e) => {
                         info!("Execute event UiCommand::Error");
+                        log_status(&ui, StatusContext::PortOperation, &e);
+                    }
+                    UiCommand::FrontEndRead(result) => {
+                        info!("Execute event UiCommand::FrontEndRead");
+                        ui.front_end_ui.read(result);
+                    }
+                    UiCommand::FrontEndWritten(result) => {
+                        info!("Execute event UiCommand::FrontEndWritten");
                         log_status(
                             &ui,
                             StatusContext::PortOperation,
-                            &format!("Error: {:?}", e),
+                            &match &result {
+                                Ok(_) => "Einstellungen Auswerte IC geschrieben".to_string(),
+                                Err(e) => format!(
+                                    "Einstellungen Auswerte IC konnten nicht geschrieben werden: {}",
+                                    e
+                                ),
+                            },
                         );
+                        ui.front_end_ui.written(&result);
                     }
                     // UiCommand::Reconnect => {
                     //     tokio_thread_sender
@@ -565,69 +996,46 @@ fn ui_init(app: &gtk::Application) {
                     //         &format!("Neuverbindung!"),
                     //     );
                     // }
-                    UiCommand::UpdatePorts(ports) => {
-                        info!("Execute event UiCommand::UpdatePorts: {:?}", ports);
-                        let active_port = ui.combo_box_text_ports.get_active().unwrap_or(0);
-                        let old_num_ports = ui.combo_box_text_ports_map.borrow().len();
+                    UiCommand::UpdatePorts(ports, events) => {
+                        info!("Execute event UiCommand::UpdatePorts: {:?}", events);
+                        let selected_port = active_port(&ui.combo_box_text_ports, &ui.port_list);
+                        let connected = ui.toggle_button_connect.get_active();
+                        let had_ports = !ui.port_list.borrow().is_empty();
+                        let has_ports = !ports.is_empty();
                         // Update the port listing and other UI elements
-                        ui.combo_box_text_ports.remove_all();
-                        ui.combo_box_text_ports_map.borrow_mut().clear();
-                        if ports.is_empty() {
+                        ui.update_ports(ports, selected_port.as_deref());
+                        if !has_ports {
                             disable_ui_elements(&ui);
+                        } else if !had_ports && !connected {
+                            enable_ui_elements(&ui);
+                        }
 
-                            ui.combo_box_text_ports
-                                .append(None, "Keine Schnittstelle gefunden");
-                            ui.combo_box_text_ports.set_active(Some(0));
-                            ui.combo_box_text_ports.set_sensitive(false);
-                            ui.toggle_button_connect.set_sensitive(false);
-                        } else {
-                            for (i, p) in (0u32..).zip(ports.clone().into_iter()) {
-                                ui.combo_box_text_ports.append(None, &p);
-                                ui.combo_box_text_ports_map.borrow_mut().insert(p, i);
-                            }
-                            // More or Less ports
-                            let num_ports = ui.combo_box_text_ports_map.borrow().len();
-                            debug!(
-                                "current ports: {}, old num ports: {}",
-                                num_ports, old_num_ports
-                            );
-                            // serial ports lost
-                            if num_ports < old_num_ports {
-                                tokio_thread_sender
-                                    .clone()
-                                    .try_send(TokioCommand::Disconnect)
-                                    .expect("Faild to send tokio command");
-
-                                // Restore selected serial interface
-                                ui.select_port(0);
-
-                                // Tell the user
-                                log_status(
-                                    &ui,
-                                    StatusContext::PortOperation,
-                                    &format!(
-                                        "Schnittstelle verloren! Aktuelle Schnittstellen: {:?}",
-                                        ports
-                                    ),
-                                );
-                            // New serial port found
-                            } else if num_ports > old_num_ports {
-                                // Enable graphical elements
-                                enable_ui_elements(&ui);
-
-                                // Restore selected serial interface
-                                ui.select_port(active_port + 1);
-
-                                // Tell the user
-                                log_status(
-                                    &ui,
-                                    StatusContext::PortOperation,
-                                    &format!("Neue Schnittstelle gefunden: {:?}", ports),
-                                );
-                            } else if num_ports == old_num_ports {
-                                // Restore selected serial interface
-                                ui.select_port(active_port);
-                            }
+                        for event in events {
+                            let message = match event {
+                                PortEvent::Added(port) => {
+                                    // Testwerte einer verlorenen Schnittstelle zurücksetzen
+                                    if !connected && !ui.bus_scan_ui.is_scanning() {
+                                        send_tokio(
+                                            &tokio_thread_sender,
+                                            TokioCommand::RestoreTestValues(Some(
+                                                port.name.clone(),
+                                            )),
+                                        );
+                                    }
+                                    format!("Neue Schnittstelle gefunden: {}", port)
+                                }
+                                // Only the loss of the connected port ends the connection
+                                PortEvent::Removed(port)
+                                    if connected && Some(&port.name) == selected_port.as_ref() =>
+                                {
+                                    send_tokio(&tokio_thread_sender, TokioCommand::Disconnect);
+                                    format!("Schnittstelle verloren, Verbindung getrennt: {}", port)
+                                }
+                                PortEvent::Removed(port) => {
+                                    format!("Schnittstelle entfernt: {}", port)
+                                }
+                            };
+                            log_status(&ui, StatusContext::PortOperation, &message);
                         }
                     }
                     // FIXME: kann weg
@@ -645,79 +1053,378 @@ fn ui_init(app: &gtk::Application) {
                         log_status(
                             &ui,
                             StatusContext::PortOperation,
-                            &format!("Neue Modbus Adresse: {:?}", &value),
+                            &match &value {
+                                Ok(()) => "Neue Modbus Adresse gespeichert".to_string(),
+                                Err(e) => {
+                                    format!("Modbus Adresse konnte nicht gespeichert werden: {}", e)
+                                }
+                            },
                         );
                     }
                     UiCommand::NewWorkingMode(value) => {
                         log_status(
                             &ui,
                             StatusContext::PortOperation,
-                            &format!("Arbeitsweise: {:?}", &value),
+                            &match &value {
+                                Ok(()) => "Arbeitsweise gespeichert".to_string(),
+                                Err(e) => {
+                                    format!("Arbeitsweise konnte nicht gespeichert werden: {}", e)
+                                }
+                            },
+                        );
+                    }
+                    UiCommand::CalibrationRead(result) => {
+                        info!("Execute event UiCommand::CalibrationRead");
+                        ui.calibration_ui.prepared(result);
+                    }
+                    UiCommand::Calibrated(step, result) => {
+                        info!("Execute event UiCommand::Calibrated");
+                        log_status(
+                            &ui,
+                            StatusContext::PortOperation,
+                            &match &result {
+                                Ok(_) => format!("{} abgeglichen", step),
+                                Err(e) => format!("{} fehlgeschlagen: {}", step, e),
+                            },
                         );
+                        ui.calibration_ui.calibrated(step, result);
                     }
-                    UiCommand::Nullpunkt(value) => {
+                    UiCommand::OutputCalibrationRead(result) => {
+                        info!("Execute event UiCommand::OutputCalibrationRead");
+                        ui.loop_check_ui.prepared(result);
+                    }
+                    UiCommand::OutputCalibrationWritten(result) => {
+                        info!("Execute event UiCommand::OutputCalibrationWritten");
                         log_status(
                             &ui,
                             StatusContext::PortOperation,
-                            &format!("Nullpunkt: {:?}", &value),
+                            &match &result {
+                                Ok(calibration) => format!(
+                                    "Kalibrierwerte Ausgangsstrom geschrieben: 4 mA {}, 20 mA {}",
+                                    calibration.low, calibration.high
+                                ),
+                                Err(e) => format!(
+                                    "Kalibrierwerte Ausgangsstrom konnten nicht geschrieben werden: {}",
+                                    e
+                                ),
+                            },
                         );
+                        ui.loop_check_ui.written(result);
+                    }
+                    UiCommand::OutputScalingRead(result) => {
+                        info!("Execute event UiCommand::OutputScalingRead");
+                        ui.output_scaling_ui.read(result);
                     }
-                    UiCommand::Messgas(value) => {
+                    UiCommand::OutputScalingWritten(result) => {
+                        info!("Execute event UiCommand::OutputScalingWritten");
                         log_status(
                             &ui,
                             StatusContext::PortOperation,
-                            &format!("Messgas: {:?}", &value),
+                            &match &result {
+                                Ok(scaling) => format!(
+                                    "Skalierung Ausgangsstrom geschrieben: {} → {:.2} mA, {} → {:.2} mA",
+                                    scaling.low_value,
+                                    scaling.low_ma(),
+                                    scaling.high_value,
+                                    scaling.high_ma()
+                                ),
+                                Err(e) => format!(
+                                    "Skalierung Ausgangsstrom konnte nicht geschrieben werden: {}",
+                                    e
+                                ),
+                            },
                         );
+                        ui.output_scaling_ui.written(&result);
                     }
                     UiCommand::ShowInfo(msg) => {
                         show_info(&ui, &msg);
                     }
-                    UiCommand::UpdateSensorValues(values) => {
+                    UiCommand::Quit => {
+                        info!("Execute event UiCommand::Quit");
+                        ui.application_window.close();
+                    }
+                    UiCommand::TemperatureCurveRead(result) => {
+                        info!("Execute event UiCommand::TemperatureCurveRead");
+                        ui.temperature_curve_ui.read(result);
+                    }
+                    UiCommand::TemperatureCurveWritten(result) => {
+                        info!("Execute event UiCommand::TemperatureCurveWritten");
+                        log_status(
+                            &ui,
+                            StatusContext::PortOperation,
+                            &match &result {
+                                Ok(_) => "Temperaturkennlinie geschrieben".to_string(),
+                                Err(e) => format!(
+                                    "Temperaturkennlinie konnte nicht geschrieben werden: {}",
+                                    e
+                                ),
+                            },
+                        );
+                        ui.temperature_curve_ui.written(&result);
+                    }
+                    UiCommand::TestValue(test_value, result) => {
+                        info!("Execute event UiCommand::TestValue");
+                        ui.test_values_ui.written(test_value, &result);
+                        ui.loop_check_ui.forced(test_value, &result);
+                        log_status(
+                            &ui,
+                            StatusContext::PortOperation,
+                            &match &result {
+                                Ok(raw) => match test_value.from_raw(*raw) {
+                                    Some(value) => format!(
+                                        "Testwert vorgegeben: {} {}",
+                                        test_value,
+                                        test_value.format(value)
+                                    ),
+                                    None => format!("Testwert ausgeschaltet: {}", test_value),
+                                },
+                                Err(e) => {
+                                    format!("{} konnte nicht vorgegeben werden: {}", test_value, e)
+                                }
+                            },
+                        );
+                    }
+                    UiCommand::TestValuesReset(result) => {
+                        info!("Execute event UiCommand::TestValuesReset");
+                        log_status(
+                            &ui,
+                            StatusContext::PortOperation,
+                            &match &result {
+                                Ok(()) => "Alle Testwerte ausgeschaltet".to_string(),
+                                Err(e) => {
+                                    format!("Testwerte konnten nicht ausgeschaltet werden: {}", e)
+                                }
+                            },
+                        );
+                    }
+                    UiCommand::TestValuesRestored(result) => {
+                        info!("Execute event UiCommand::TestValuesRestored");
+                        let message = match &result {
+                            Ok(restored) => {
+                                let restored: Vec<String> = restored
+                                    .iter()
+                                    .map(|o| {
+                                        format!(
+                                            "{} (Modbus Adresse {})",
+                                            o.test_value, o.modbus_address
+                                        )
+                                    })
+                                    .collect();
+                                format!("Testwerte zurückgesetzt: {}", restored.join(", "))
+                            }
+                            Err(e) => format!(
+                                "Testwerte konnten nicht zurückgesetzt werden, neuer Versuch beim \
+                                 nächsten Verbinden: {}",
+                                e
+                            ),
+                        };
+                        log_status(&ui, StatusContext::PortOperation, &message);
+                    }
+                    UiCommand::UpdateDashboard(modbus_address, result) => {
+                        debug!("Execute event UiCommand::UpdateDashboard");
+                        ui.dashboard_ui.update(modbus_address, result);
+                    }
+                    UiCommand::UpdateSensorValues(ne4) => {
                         info!("Execute event UiCommand::UpdateSensorValues");
                         // show_info(&ui, "Not working jeat!");
-                        debug!("{:?}", values);
-                        match values {
-                            Ok(values) => {
+                        debug!("{:?}", ne4);
+                        match ne4 {
+                            Ok(ne4) => {
                                 // Update Sensor Typ
                                 &ui.label_sensor_type_value
                                     .set_text("RA-GAS GmbH - NE4_MOD_BUS");
                                 // Update Auswahlfeld Arbeitsweise
                                 &ui.combo_box_text_sensor_working_mode
-                                    .set_active_id(Some(&values[1].to_string()));
+                                    .set_active_id(Some(&ne4.work_mode().code().to_string()));
                                 // Update Sensor Wert
                                 &ui.label_sensor_value_value
-                                    .set_text(&sanitize_sensor_value(&values));
+                                    .set_text(&sanitize_sensor_value(&ne4));
+                                &ui.label_sensor_value_si
+                                    .set_text(ne4.work_mode().gas().map_or("ppm", |gas| gas.unit));
                                 // Update mA Wert
-                                &ui.label_sensor_ma_value.set_text(&sensor_ma(&values));
+                                &ui.label_sensor_ma_value.set_text(&sensor_ma(&ne4));
                                 // Update TreeStore
-                                update_treestore(&ui, &values);
+                                update_treestore(&ui, &ne4.rregs());
+                                // Update Geräteinformation
+                                ui.device_info_ui.update(&DeviceInfo::from_ne4(&ne4));
+                                // Update Verlauf
+                                ui.trend_ui.push(&ne4);
+                                // Abgleich
+                                ui.calibration_ui.update(&ne4);
+                                // Aufzeichnung
+                                if let Err(e) = ui.recording_ui.record(&ne4.rregs()) {
+                                    log_status(&ui, StatusContext::Error, &e);
+                                }
                             }
                             Err(err) => {
                                 // Status log
                                 log_status(
                                     &ui,
                                     StatusContext::Error,
-                                    &format!("Error while Sensor Update: {}", err),
+                                    &format!("Sensor konnte nicht abgefragt werden: {}", err),
                                 );
                             }
                         }
                     }
-                    UiCommand::UpdateSensorRwregValues(values) => {
+                    UiCommand::Backup(result) => {
+                        info!("Execute event UiCommand::Backup");
+                        let message = match result {
+                            Ok(backup) => {
+                                match backup::save_backup(&ui.application_window, &backup) {
+                                    Ok(Some(path)) => {
+                                        format!("Konfiguration gesichert: {}", path.display())
+                                    }
+                                    Ok(None) => "Sicherung abgebrochen".to_string(),
+                                    Err(e) => {
+                                        format!("Sicherung konnte nicht gespeichert werden: {}", e)
+                                    }
+                                }
+                            }
+                            Err(e) => format!("Konfiguration konnte nicht gelesen werden: {}", e),
+                        };
+                        log_status(&ui, StatusContext::PortOperation, &message);
+                    }
+                    UiCommand::RestoreDiff(backup, other_device, changes) => {
+                        info!("Execute event UiCommand::RestoreDiff");
+                        match changes {
+                            Err(e @ Error::OtherDevice { .. }) => {
+                                if backup::confirm_other_device(
+                                    &ui.application_window,
+                                    &e.to_string(),
+                                ) {
+                                    let port = active_port(&ui.combo_box_text_ports, &ui.port_list);
+                                    let modbus_address =
+                                        ui.entry_modbus_address.get_text().parse().unwrap_or(247);
+                                    send_tokio(
+                                        &tokio_thread_sender,
+                                        TokioCommand::RestoreDiff(
+                                            port,
+                                            modbus_address,
+                                            backup,
+                                            true,
+                                        ),
+                                    );
+                                }
+                            }
+                            Ok(changes) if changes.is_empty() => log_status(
+                                &ui,
+                                StatusContext::PortOperation,
+                                "Der Sensor stimmt mit der Sicherung überein",
+                            ),
+                            Ok(changes) => {
+                                if backup::confirm_restore(
+                                    &ui.application_window,
+                                    &backup,
+                                    &changes,
+                                ) {
+                                    let port = active_port(&ui.combo_box_text_ports, &ui.port_list);
+                                    let modbus_address =
+                                        ui.entry_modbus_address.get_text().parse().unwrap_or(247);
+                                    send_tokio(
+                                        &tokio_thread_sender,
+                                        TokioCommand::Restore(
+                                            port,
+                                            modbus_address,
+                                            backup,
+                                            other_device,
+                                        ),
+                                    );
+                                }
+                            }
+                            Err(e) => log_status(
+                                &ui,
+                                StatusContext::PortOperation,
+                                &format!("Konfiguration konnte nicht gelesen werden: {}", e),
+                            ),
+                        }
+                    }
+                    UiCommand::Restore(result) => {
+                        info!("Execute event UiCommand::Restore");
+                        let message = match result {
+                            Ok(changes) => format!(
+                                "Konfiguration wiederhergestellt, {} Register geschrieben und geprüft",
+                                changes.len()
+                            ),
+                            Err(e) => format!("Wiederherstellen fehlgeschlagen: {}", e),
+                        };
+                        log_status(&ui, StatusContext::PortOperation, &message);
+                    }
+                    UiCommand::ScanProgress(modbus_address, result) => {
+                        debug!("Execute event UiCommand::ScanProgress");
+                        if let Some(result) = &result {
+                            log_status(
+                                &ui,
+                                StatusContext::PortOperation,
+                                &format!(
+                                    "Sensor gefunden, Modbus Adresse: {}",
+                                    result.modbus_address
+                                ),
+                            );
+                        }
+                        ui.bus_scan_ui.progress(modbus_address, result);
+                    }
+                    UiCommand::ScanFinished(result) => {
+                        info!("Execute event UiCommand::ScanFinished");
+                        ui.bus_scan_ui.finished(&result);
+                        if !ui.port_list.borrow().is_empty() {
+                            ui.toggle_button_connect.set_sensitive(true);
+                            ui.combo_box_text_ports.set_sensitive(true);
+                            ui.line_settings_ui.set_sensitive(true);
+                        }
+                        log_status(
+                            &ui,
+                            StatusContext::PortOperation,
+                            &match &result {
+                                Ok(()) => "Bus Scan beendet".to_string(),
+                                Err(e) => format!("Bus Scan beendet: {}", e),
+                            },
+                        );
+                    }
+                    UiCommand::WriteRegister(reg_nr, result) => {
+                        info!("Execute event UiCommand::WriteRegister");
+                        #[cfg(feature = "ra-gas")]
+                        ui.rwreg_store.write_result(reg_nr, &result);
+                        log_status(
+                            &ui,
+                            StatusContext::PortOperation,
+                            &match &result {
+                                Ok(value) if CONNECTION_REGISTERS.contains(&reg_nr) => format!(
+                                    "Register {} geschrieben: {}, der Sensor antwortet erst nach dem Neuverbinden mit den neuen Einstellungen",
+                                    reg_nr, value
+                                ),
+                                Ok(value) => format!("Register {} geschrieben: {}", reg_nr, value),
+                                Err(e) => {
+                                    format!("Register {} schreiben fehlgeschlagen: {}", reg_nr, e)
+                                }
+                            },
+                        );
+                    }
+                    UiCommand::UpdateSensorRwregValues(ne4) => {
                         info!("Execute event UiCommand::UpdateSensorRwregValues");
                         // show_info(&ui, "Not working jeat!");
-                        debug!("{:?}", values);
-                        match values {
-                            Ok(values) => {
+                        debug!("{:?}", ne4);
+                        match ne4 {
+                            Ok(ne4) => {
+                                // Update Geräteinformation
+                                ui.device_info_ui.update(&DeviceInfo::from_ne4(&ne4));
+                                // Testwerte
+                                ui.test_values_ui.update(&ne4);
+                                // Ausgangsstrom
+                                ui.output_scaling_ui.update(&ne4);
+                                // Temperaturkennlinie
+                                ui.temperature_curve_ui.update(&ne4);
+                                // Auswerte IC
+                                ui.front_end_ui.update(&ne4);
                                 #[cfg(feature = "ra-gas")]
                                 // Update TreeStore
-                                &ui.rwreg_store.update_treestore(&ui, &values);
+                                &ui.rwreg_store.update_treestore(&ui, &ne4.rwregs());
                             }
                             Err(err) => {
                                 // Status log
                                 log_status(
                                     &ui,
                                     StatusContext::Error,
-                                    &format!("Error while Sensor Update: {}", err),
+                                    &format!("Sensor konnte nicht abgefragt werden: {}", err),
                                 );
                             }
                         }
@@ -737,6 +1444,8 @@ fn ui_init(app: &gtk::Application) {
 fn enable_ui_elements(ui: &Ui) {
     ui.toggle_button_connect.set_active(false);
     ui.combo_box_text_ports.set_sensitive(true);
+    ui.line_settings_ui.set_sensitive(true);
+    ui.bus_scan_ui.set_sensitive(true);
     ui.combo_box_text_sensor_working_mode.set_sensitive(true);
     ui.entry_modbus_address.set_sensitive(true);
     ui.button_reset.set_sensitive(true);
@@ -746,6 +1455,19 @@ fn enable_ui_elements(ui: &Ui) {
     ui.label_sensor_type_value.set_text("");
     ui.label_sensor_value_value.set_text("");
     ui.label_sensor_ma_value.set_text("");
+    ui.device_info_ui.clear();
+    ui.dashboard_ui.reset();
+    ui.test_values_ui.clear();
+    ui.test_values_ui.set_sensitive(false);
+    ui.output_scaling_ui.clear();
+    ui.output_scaling_ui.set_sensitive(false);
+    ui.temperature_curve_ui.clear();
+    ui.temperature_curve_ui.set_sensitive(false);
+    ui.front_end_ui.clear();
+    ui.front_end_ui.set_sensitive(false);
+    // Ohne Verbindung gibt es keine Messwerte für den Abgleich
+    ui.calibration_ui.abort();
+    ui.loop_check_ui.close();
     ui.button_nullpunkt.set_sensitive(true);
     ui.button_messgas.set_sensitive(true);
     ui.button_new_modbus_address.set_sensitive(true);
@@ -761,6 +1483,8 @@ fn enable_ui_elements(ui: &Ui) {
 fn disable_ui_elements(ui: &Ui) {
     // ui.toggle_button_connect.set_active(true);
     ui.combo_box_text_ports.set_sensitive(false);
+    ui.line_settings_ui.set_sensitive(false);
+    ui.bus_scan_ui.set_sensitive(false);
     ui.combo_box_text_sensor_working_mode.set_sensitive(false);
     ui.entry_modbus_address.set_sensitive(false);
     ui.button_reset.set_sensitive(false);
@@ -768,6 +1492,15 @@ fn disable_ui_elements(ui: &Ui) {
     ui.label_sensor_type_value.set_text("");
     ui.label_sensor_value_value.set_text("");
     ui.label_sensor_ma_value.set_text("");
+    ui.device_info_ui.clear();
+    ui.test_values_ui
+        .set_sensitive(ui.toggle_button_connect.get_active());
+    ui.output_scaling_ui
+        .set_sensitive(ui.toggle_button_connect.get_active());
+    ui.temperature_curve_ui
+        .set_sensitive(ui.toggle_button_connect.get_active());
+    ui.front_end_ui
+        .set_sensitive(ui.toggle_button_connect.get_active());
     ui.button_nullpunkt.set_sensitive(false);
     ui.button_messgas.set_sensitive(false);
     ui.button_new_modbus_address.set_sensitive(false);
@@ -799,27 +1532,80 @@ fn log_status(ui: &Ui, context: StatusContext, message: &str) {
     }
 }
 
-/// Scan available serial ports
+/// Send a command to the tokio thread
 ///
-/// Called once on program start
-fn scan_ports(
+/// A full or closed channel is logged instead of crashing the GUI.
+fn send_tokio(
+    tokio_thread_sender: &futures::channel::mpsc::Sender<TokioCommand>,
+    command: TokioCommand,
+) {
+    if let Err(e) = tokio_thread_sender.clone().try_send(command) {
+        error!("Failed to send tokio command: {}", e);
+    }
+}
+
+/// Serial port currently selected in the port selector
+fn active_port(
     combo_box_text_ports: &gtk::ComboBoxText,
-    combo_box_text_ports_map: &Rc<RefCell<HashMap<String, u32>>>,
+    port_list: &Rc<RefCell<Vec<PortInfo>>>,
+) -> Option<String> {
+    let active_port = combo_box_text_ports.get_active()?;
+    port_list
+        .borrow()
+        .get(active_port as usize)
+        .map(|port| port.name.clone())
+}
+
+/// Widgets selecting the sensor the commands are sent to
+#[derive(Clone)]
+struct TargetWidgets {
+    combo_box_text_ports: gtk::ComboBoxText,
+    port_list: Rc<RefCell<Vec<PortInfo>>>,
+    entry_modbus_address: gtk::Entry,
+}
+
+/// Serial port and Modbus address of the selected sensor
+fn target(target_widgets: &TargetWidgets) -> (Option<String>, u8) {
+    let port = active_port(
+        &target_widgets.combo_box_text_ports,
+        &target_widgets.port_list,
+    );
+    let modbus_address = target_widgets
+        .entry_modbus_address
+        .get_text()
+        .parse()
+        .unwrap_or(247);
+    (port, modbus_address)
+}
+
+/// Show the USB metadata of the selected port as tooltip
+fn update_port_tooltip(
+    combo_box_text_ports: &gtk::ComboBoxText,
+    port_list: &Rc<RefCell<Vec<PortInfo>>>,
 ) {
-    let mut combo_box_text_ports_map = combo_box_text_ports_map.borrow_mut();
-    let ports = tokio_thread::get_ports();
-    combo_box_text_ports.remove_all();
-    combo_box_text_ports_map.clear();
-    if !ports.is_empty() {
-        for (i, p) in (0u32..).zip(ports.into_iter()) {
-            combo_box_text_ports.append(None, &p);
-            combo_box_text_ports_map.insert(p, i);
-        }
+    let description = combo_box_text_ports.get_active().and_then(|active_port| {
+        port_list
+            .borrow()
+            .get(active_port as usize)
+            .and_then(PortInfo::description)
+    });
+    combo_box_text_ports.set_tooltip_text(description.as_deref());
+}
+
+/// Polling command for the visible page
+///
+/// The dashboard page polls all its sensors round-robin, every other page the
+/// sensor with `modbus_address`.
+fn poll_command(
+    port: Option<String>,
+    dashboard: bool,
+    dashboard_ui: &DashboardUi,
+    modbus_address: u8,
+) -> TokioCommand {
+    if dashboard {
+        TokioCommand::UpdateDashboard(port, dashboard_ui.addresses())
     } else {
-        let msg: &str = "Keine Schnittstelle gefunden";
-        combo_box_text_ports.append(None, msg);
-        combo_box_text_ports.set_active(Some(0));
-        combo_box_text_ports.set_sensitive(false);
+        TokioCommand::UpdateSensor(port, modbus_address)
     }
 }
 
@@ -828,46 +1614,63 @@ fn scan_ports(
 /// Ich habe auf eine Nachbearbeitung ersteinmal verzichtet, da dies ein Programmfehler im Sensor
 /// ist. Laut Dokumentation liefert der Sensor an diese Stelle nur Werte zwichen 0...10000
 /// 65535 ist definitiv zu hoch.
-fn sanitize_sensor_value(values: &[u16]) -> String {
-    let value = values[2];
-    value.to_string()
+///
+/// Werte über dem Messbereichsendwert des Gases werden markiert, ein nicht
+/// gelesener Wert wird als "–" angezeigt.
+fn sanitize_sensor_value(ne4: &NE4) -> String {
+    if ne4.rreg(2).is_none() {
+        return "–".to_string();
+    }
+    let value = ne4.concentration_gas();
+    match ne4.work_mode().gas() {
+        Some(gas) if !gas.contains(value) => format!("{} (> {})", value, gas.full_scale),
+        _ => value.to_string(),
+    }
 }
 
-/// mA Werte berechnen
-fn sensor_ma(values: &[u16]) -> String {
-    let sensor_ma: f32 = values[3] as f32 / 100.0;
-    let sensor_ma = format!("{:.02}", sensor_ma);
-    sensor_ma
+/// mA Werte anzeigen
+fn sensor_ma(ne4: &NE4) -> String {
+    match ne4.rreg(3) {
+        Some(_) => format!("{:.02}", ne4.output_current()),
+        None => "–".to_string(),
+    }
+}
+
+/// Fill the Rreg ListStore with all Rreg's of the register table
+fn fill_list_store_sensor(list_store_sensor: &gtk::ListStore) {
+    for reg in RREGS {
+        list_store_sensor.insert_with_values(
+            None,
+            &[0, 1, 2, 3],
+            &[&(reg.number as u32), &0u64, &0u64, &reg.description],
+        );
+    }
 }
 
 /// Update Treestore
-fn update_treestore(ui: &Ui, values: &[u16]) {
+///
+/// `values` is indexed by the register number, registers which couldn't be read
+/// keep the last value.
+fn update_treestore(ui: &Ui, values: &[Option<u16>]) {
     if let Some(iter) = &ui.list_store_sensor.get_iter_first() {
-        let _: Vec<u32> = values
-            .iter()
-            .enumerate()
-            .map(|(i, val)| {
-                let reg = &ui
-                    .list_store_sensor
-                    .get_value(&iter, 0)
-                    .get::<u32>()
-                    .unwrap_or(Some(0))
-                    .unwrap_or(0);
+        loop {
+            let reg = ui
+                .list_store_sensor
+                .get_value(&iter, 0)
+                .get::<u32>()
+                .unwrap_or(Some(0))
+                .unwrap_or(0);
+            if let Some(Some(val)) = values.get(reg as usize) {
                 // create the glib::value::Value from a u16 this is complicated (see supported types: https://gtk-rs.org/docs/glib/value/index.html)
-                let val = (*val as u32).to_value();
-                if i as u32 == *reg {
-                    &ui.list_store_sensor.set_value(&iter, 1, &val);
-                    &ui.list_store_sensor.iter_next(&iter);
-                }
-                0
-            })
-            .collect();
-        // Status log
-        log_status(
-            &ui,
-            StatusContext::PortOperation,
-            &format!("Sensor Update OK"),
-        );
+                let val = (*val as u64).to_value();
+                ui.list_store_sensor.set_value(&iter, 1, &val);
+            }
+            if !ui.list_store_sensor.iter_next(&iter) {
+                break;
+            }
+        }
+        // Kein "Update OK" pro Abfrage, das würde Fehlermeldungen in der
+        // Statusleiste sofort wieder verdecken
     } else {
         log_status(
             &ui,
@@ -0,0 +1,454 @@
+use crate::error::Result;
+/// Output scaling page
+///
+/// Edits the linear mapping of the gas concentration to the output current in
+/// Rwreg 15 to 18 and plots it against the gas range of the working mode. The
+/// current of the live reading is previewed next to the current the sensor
+/// reports in Rreg 3. All four registers are validated and written together.
+use crate::sensors::gas_catalogue::Gas;
+use crate::sensors::output_scaling::{OutputScaling, CURRENT_LIMITS};
+use crate::sensors::ra_gas_ne4::NE4;
+use gtk::prelude::*;
+use std::{cell::Cell, rc::Rc};
+
+/// Space for the current labels left of the plot
+const MARGIN_LEFT: f64 = 50.0;
+const MARGIN: f64 = 16.0;
+/// Space for the value labels below the plot
+const MARGIN_BOTTOM: f64 = 30.0;
+const FONT_SIZE: f64 = 11.0;
+const POINT_RADIUS: f64 = 5.0;
+/// Number of line segments of the plotted mapping
+const PLOT_STEPS: usize = 200;
+
+#[derive(Clone)]
+pub struct OutputScalingUi {
+    /// Edited scaling
+    scaling: Rc<Cell<OutputScaling>>,
+    /// Scaling in the sensor, from the last poll
+    sensor: Rc<Cell<Option<OutputScaling>>>,
+    /// The editor shows the scaling of the sensor
+    loaded: Rc<Cell<bool>>,
+    /// Gas concentration (Rreg 2) and output current (Rreg 3) of the sensor
+    live: Rc<Cell<Option<(f64, f64)>>>,
+    /// Gas of the working mode
+    gas: Rc<Cell<Option<&'static Gas>>>,
+    /// The spin buttons are set from the scaling
+    updating: Rc<Cell<bool>>,
+    drawing_area: gtk::DrawingArea,
+    spin_button_low_value: gtk::SpinButton,
+    spin_button_low_current: gtk::SpinButton,
+    spin_button_high_value: gtk::SpinButton,
+    spin_button_high_current: gtk::SpinButton,
+    /// Unit of the gas concentration next to both values
+    labels_unit: Vec<gtk::Label>,
+    label_preview: gtk::Label,
+    button_gas_range: gtk::Button,
+    button_read: gtk::Button,
+    button_write: gtk::Button,
+    label_status: gtk::Label,
+}
+
+impl OutputScalingUi {
+    pub fn new() -> Self {
+        let value_spin_button = || {
+            let spin_button = gtk::SpinButton::with_range(0.0, 10000.0, 1.0);
+            spin_button.set_numeric(true);
+            spin_button
+        };
+        let current_spin_button = || {
+            let spin_button = gtk::SpinButton::with_range(CURRENT_LIMITS.0, CURRENT_LIMITS.1, 0.01);
+            spin_button.set_digits(2);
+            spin_button.set_numeric(true);
+            spin_button
+        };
+
+        OutputScalingUi {
+            scaling: Rc::new(Cell::new(OutputScaling::default())),
+            sensor: Rc::new(Cell::new(None)),
+            loaded: Rc::new(Cell::new(false)),
+            live: Rc::new(Cell::new(None)),
+            gas: Rc::new(Cell::new(None)),
+            updating: Rc::new(Cell::new(false)),
+            drawing_area: gtk::DrawingArea::new(),
+            spin_button_low_value: value_spin_button(),
+            spin_button_low_current: current_spin_button(),
+            spin_button_high_value: value_spin_button(),
+            spin_button_high_current: current_spin_button(),
+            labels_unit: vec![gtk::Label::new(None), gtk::Label::new(None)],
+            label_preview: gtk::Label::new(None),
+            button_gas_range: gtk::Button::with_label("Messbereich übernehmen"),
+            button_read: gtk::Button::with_label("Aus Sensor lesen"),
+            button_write: gtk::Button::with_label("In Sensor schreiben"),
+            label_status: gtk::Label::new(None),
+        }
+    }
+
+    /// Build the output scaling page
+    ///
+    /// `on_read` reads the scaling from the sensor, the result is passed to `read`.
+    /// `on_write` writes all four registers.
+    pub fn build_ui<R, W>(&self, on_read: R, on_write: W) -> gtk::Box
+    where
+        R: Fn() + 'static,
+        W: Fn(OutputScaling) + 'static,
+    {
+        self.drawing_area.set_size_request(400, 250);
+        self.drawing_area.set_vexpand(true);
+        let output_scaling_ui = self.clone();
+        self.drawing_area.connect_draw(move |widget, cr| {
+            output_scaling_ui.draw(
+                cr,
+                f64::from(widget.get_allocated_width()),
+                f64::from(widget.get_allocated_height()),
+            );
+            gtk::Inhibit(false)
+        });
+
+        // Unterer und oberer Punkt
+        let grid = gtk::Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(6);
+        let rows = [
+            (
+                "Messwert unten (Rwreg 15)",
+                &self.spin_button_low_value,
+                Some(&self.labels_unit[0]),
+            ),
+            (
+                "Ausgangsstrom unten (Rwreg 16)",
+                &self.spin_button_low_current,
+                None,
+            ),
+            (
+                "Messwert oben (Rwreg 17)",
+                &self.spin_button_high_value,
+                Some(&self.labels_unit[1]),
+            ),
+            (
+                "Ausgangsstrom oben (Rwreg 18)",
+                &self.spin_button_high_current,
+                None,
+            ),
+        ];
+        for (index, (title, spin_button, label_unit)) in rows.iter().enumerate() {
+            let (row, column) = (index as i32 % 2, index as i32 / 2 * 3);
+            let label = gtk::Label::new(Some(title));
+            label.set_xalign(0.0);
+            grid.attach(&label, column, row, 1, 1);
+            grid.attach(*spin_button, column + 1, row, 1, 1);
+            let label_ma = gtk::Label::new(Some("mA"));
+            let label_unit = label_unit.unwrap_or(&label_ma);
+            label_unit.set_xalign(0.0);
+            label_unit.set_margin_end(12);
+            grid.attach(label_unit, column + 2, row, 1, 1);
+            let output_scaling_ui = self.clone();
+            spin_button.connect_value_changed(move |_| {
+                if !output_scaling_ui.updating.get() {
+                    output_scaling_ui.take_spin_buttons();
+                }
+            });
+        }
+
+        let output_scaling_ui = self.clone();
+        self.button_gas_range.connect_clicked(move |_| {
+            if let Some(gas) = output_scaling_ui.gas.get() {
+                output_scaling_ui.scaling.set(OutputScaling {
+                    low_value: 0,
+                    low_current: 400,
+                    high_value: gas.full_scale,
+                    high_current: 2000,
+                });
+                output_scaling_ui.show_scaling();
+            }
+        });
+        self.button_read.connect_clicked(move |_| on_read());
+        let output_scaling_ui = self.clone();
+        self.button_write.connect_clicked(move |_| {
+            let scaling = output_scaling_ui.scaling.get();
+            match scaling.validate() {
+                Ok(()) => {
+                    output_scaling_ui
+                        .label_status
+                        .set_text("Skalierung wird geschrieben …");
+                    on_write(scaling);
+                }
+                Err(e) => output_scaling_ui.label_status.set_text(&e.to_string()),
+            }
+        });
+        let box_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        box_buttons.pack_start(&self.button_gas_range, false, false, 0);
+        box_buttons.pack_end(&self.button_write, false, false, 0);
+        box_buttons.pack_end(&self.button_read, false, false, 0);
+
+        self.label_preview.set_xalign(0.0);
+        self.label_status.set_xalign(0.0);
+        self.label_status.set_line_wrap(true);
+
+        let box_main = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        box_main.set_border_width(12);
+        box_main.pack_start(&self.drawing_area, true, true, 0);
+        box_main.pack_start(&grid, false, false, 0);
+        box_main.pack_start(&self.label_preview, false, false, 0);
+        box_main.pack_start(&box_buttons, false, false, 0);
+        box_main.pack_start(&self.label_status, false, false, 0);
+        self.clear();
+        self.set_sensitive(false);
+        box_main
+    }
+
+    /// Take the live reading, the working mode and the scaling from a poll
+    ///
+    /// The first scaling read after connecting is loaded into the editor.
+    pub fn update(&self, ne4: &NE4) {
+        if ne4.has_rregs(&[2, 3]) {
+            self.live.set(Some((
+                f64::from(ne4.concentration_gas()),
+                ne4.output_current(),
+            )));
+        }
+        self.gas.set(ne4.work_mode().gas());
+        let sensor = OutputScaling::from_ne4(ne4);
+        self.sensor.set(sensor);
+        if let Some(scaling) = sensor {
+            if !self.loaded.get() {
+                self.loaded.set(true);
+                self.scaling.set(scaling);
+            }
+        }
+        self.show_scaling();
+    }
+
+    /// Result of reading the scaling from the sensor
+    pub fn read(&self, result: Result<OutputScaling>) {
+        match result {
+            Ok(scaling) => {
+                self.loaded.set(true);
+                self.sensor.set(Some(scaling));
+                self.scaling.set(scaling);
+                self.show_scaling();
+                self.label_status
+                    .set_text("Skalierung aus dem Sensor gelesen");
+            }
+            Err(e) => self
+                .label_status
+                .set_text(&format!("Skalierung konnte nicht gelesen werden: {}", e)),
+        }
+    }
+
+    /// Result of writing the scaling
+    pub fn written(&self, result: &Result<OutputScaling>) {
+        match result {
+            Ok(scaling) => {
+                self.sensor.set(Some(*scaling));
+                self.show_scaling();
+                self.label_status.set_text("Skalierung geschrieben");
+            }
+            Err(e) => self.label_status.set_text(&format!(
+                "Skalierung konnte nicht geschrieben werden: {}",
+                e
+            )),
+        }
+    }
+
+    /// Reading and writing only while connected
+    pub fn set_sensitive(&self, sensitive: bool) {
+        self.button_read.set_sensitive(sensitive);
+        self.button_write.set_sensitive(sensitive);
+    }
+
+    /// Forget the values of the sensor, e.g. after disconnecting
+    pub fn clear(&self) {
+        self.live.set(None);
+        self.gas.set(None);
+        self.sensor.set(None);
+        self.loaded.set(false);
+        self.show_scaling();
+    }
+
+    /// Edited scaling from the spin buttons
+    fn take_spin_buttons(&self) {
+        let current =
+            |spin_button: &gtk::SpinButton| (spin_button.get_value() * 100.0).round() as u16;
+        self.scaling.set(OutputScaling {
+            low_value: self.spin_button_low_value.get_value_as_int() as u16,
+            low_current: current(&self.spin_button_low_current),
+            high_value: self.spin_button_high_value.get_value_as_int() as u16,
+            high_current: current(&self.spin_button_high_current),
+        });
+        self.show_scaling();
+    }
+
+    /// Show the edited scaling in the spin buttons, the plot and the preview
+    fn show_scaling(&self) {
+        let scaling = self.scaling.get();
+        self.updating.set(true);
+        self.spin_button_low_value
+            .set_value(f64::from(scaling.low_value));
+        self.spin_button_low_current.set_value(scaling.low_ma());
+        self.spin_button_high_value
+            .set_value(f64::from(scaling.high_value));
+        self.spin_button_high_current.set_value(scaling.high_ma());
+        self.updating.set(false);
+
+        let gas = self.gas.get();
+        let unit = gas.map_or("ppm", |gas| gas.unit);
+        for label in &self.labels_unit {
+            label.set_text(unit);
+        }
+        self.button_gas_range.set_sensitive(gas.is_some());
+        if let Some(gas) = gas {
+            self.button_gas_range.set_tooltip_text(Some(&format!(
+                "0 … {} {} auf 4 … 20 mA",
+                gas.full_scale, gas.unit
+            )));
+        }
+
+        let mut text = match self.live.get() {
+            Some((value, current)) => format!(
+                "Messwert {} {}: Skalierung {:.2} mA, Sensor (Rreg 3) {:.2} mA",
+                value,
+                unit,
+                scaling.current_at(value),
+                current
+            ),
+            None => "Ausgangsstrom beim aktuellen Messwert: keine Messwerte".to_string(),
+        };
+        if let Err(e) = scaling.validate() {
+            text.push_str(&format!(" – {}", e));
+        } else if self.sensor.get().map_or(false, |sensor| sensor != scaling) {
+            text.push_str(" – Skalierung noch nicht geschrieben");
+        }
+        self.label_preview.set_text(&text);
+        self.drawing_area.queue_draw();
+    }
+
+    fn draw(&self, cr: &cairo::Context, width: f64, height: f64) {
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        cr.paint();
+        cr.set_font_size(FONT_SIZE);
+        let (left, top) = (MARGIN_LEFT, MARGIN);
+        let (plot_width, plot_height) = (
+            width - MARGIN_LEFT - MARGIN,
+            height - MARGIN - MARGIN_BOTTOM,
+        );
+        if plot_width <= 0.0 || plot_height <= 0.0 {
+            return;
+        }
+
+        // Die x-Achse zeigt den Messbereich des Gases und beide Punkte
+        let scaling = self.scaling.get();
+        let gas = self.gas.get();
+        let full_scale = gas.map_or(0.0, |gas| f64::from(gas.full_scale));
+        let x_max = full_scale
+            .max(f64::from(scaling.high_value) * 1.1)
+            .max(f64::from(scaling.low_value) * 1.1)
+            .max(10.0);
+        let x = |value: f64| left + value / x_max * plot_width;
+        let y = |current: f64| {
+            top + plot_height
+                - (current - CURRENT_LIMITS.0) / (CURRENT_LIMITS.1 - CURRENT_LIMITS.0) * plot_height
+        };
+
+        // Messbereich
+        if let Some(gas) = gas {
+            cr.set_source_rgb(0.92, 0.96, 1.0);
+            cr.rectangle(left, top, x(full_scale) - left, plot_height);
+            cr.fill();
+            cr.set_source_rgb(0.0, 0.0, 0.0);
+            cr.move_to(left + 4.0, top + FONT_SIZE + 2.0);
+            cr.show_text(&format!("{} {}", gas.name, gas));
+        }
+
+        // Raster mit Beschriftung
+        cr.set_line_width(1.0);
+        for current in (0..=24).step_by(4) {
+            let current = f64::from(current);
+            cr.set_source_rgb(0.85, 0.85, 0.85);
+            cr.move_to(left, y(current));
+            cr.line_to(left + plot_width, y(current));
+            cr.stroke();
+            let label = format!("{} mA", current);
+            let extents = cr.text_extents(&label);
+            cr.set_source_rgb(0.0, 0.0, 0.0);
+            cr.move_to(
+                left - extents.width - 4.0,
+                y(current) + extents.height / 2.0,
+            );
+            cr.show_text(&label);
+        }
+        let unit = gas.map_or("ppm", |gas| gas.unit);
+        for step in 0..=5 {
+            let value = (x_max / 5.0 * f64::from(step)).round();
+            cr.set_source_rgb(0.85, 0.85, 0.85);
+            cr.move_to(x(value), top);
+            cr.line_to(x(value), top + plot_height);
+            cr.stroke();
+            let label = format!("{} {}", value, unit);
+            let extents = cr.text_extents(&label);
+            cr.set_source_rgb(0.0, 0.0, 0.0);
+            cr.move_to(
+                (x(value) - extents.width / 2.0)
+                    .max(0.0)
+                    .min(width - extents.width),
+                height - MARGIN_BOTTOM / 2.0 + extents.height / 2.0,
+            );
+            cr.show_text(&label);
+        }
+        cr.set_source_rgb(0.6, 0.6, 0.6);
+        cr.rectangle(left, top, plot_width, plot_height);
+        cr.stroke();
+
+        // Skalierung, ungültige rot
+        if scaling.validate().is_ok() {
+            cr.set_source_rgb(0.10, 0.40, 0.80);
+        } else {
+            cr.set_source_rgb(0.80, 0.10, 0.10);
+        }
+        cr.set_line_width(2.0);
+        cr.move_to(x(0.0), y(scaling.current_at(0.0)));
+        for step in 1..=PLOT_STEPS {
+            let value = x_max * step as f64 / PLOT_STEPS as f64;
+            cr.line_to(x(value), y(scaling.current_at(value)));
+        }
+        cr.stroke();
+        for (value, current) in &[
+            (scaling.low_value, scaling.low_ma()),
+            (scaling.high_value, scaling.high_ma()),
+        ] {
+            cr.arc(
+                x(f64::from(*value)),
+                y(*current),
+                POINT_RADIUS,
+                0.0,
+                2.0 * std::f64::consts::PI,
+            );
+            cr.fill();
+        }
+
+        // Aktueller Messwert mit Strom laut Skalierung und Rreg 3
+        if let Some((value, current)) = self.live.get() {
+            let px = x(value.min(x_max));
+            cr.set_source_rgb(0.90, 0.50, 0.0);
+            cr.set_line_width(1.0);
+            cr.move_to(px, top);
+            cr.line_to(px, top + plot_height);
+            cr.stroke();
+            cr.arc(
+                px,
+                y(scaling.current_at(value)),
+                POINT_RADIUS - 1.0,
+                0.0,
+                2.0 * std::f64::consts::PI,
+            );
+            cr.fill();
+            let py = y(current.max(CURRENT_LIMITS.0).min(CURRENT_LIMITS.1));
+            cr.set_line_width(2.0);
+            cr.move_to(px - POINT_RADIUS, py - POINT_RADIUS);
+            cr.line_to(px + POINT_RADIUS, py + POINT_RADIUS);
+            cr.move_to(px - POINT_RADIUS, py + POINT_RADIUS);
+            cr.line_to(px + POINT_RADIUS, py - POINT_RADIUS);
+            cr.stroke();
+        }
+    }
+}
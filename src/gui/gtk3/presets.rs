@@ -0,0 +1,153 @@
+use crate::sensors::presets::{presets_path, Presets};
+/// Preset row of the editor pages
+///
+/// A combo box with entry for the preset name and buttons to load, save and
+/// delete presets. The presets of one kind are stored in the config directory
+/// of the user, see `sensors::presets`.
+use gtk::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+#[derive(Clone)]
+pub struct PresetsUi<T> {
+    presets: Rc<RefCell<Presets<T>>>,
+    /// `None` without config directory, the presets are then only kept in memory
+    path: Option<PathBuf>,
+    combo_box_text_presets: gtk::ComboBoxText,
+    button_load: gtk::Button,
+    button_save: gtk::Button,
+    button_delete: gtk::Button,
+}
+
+impl<T> PresetsUi<T>
+where
+    T: Clone + Serialize + DeserializeOwned + 'static,
+{
+    pub fn new(kind: &str) -> Self {
+        PresetsUi {
+            presets: Rc::new(RefCell::new(Presets::new())),
+            path: glib::get_user_config_dir().map(|config_dir| presets_path(&config_dir, kind)),
+            combo_box_text_presets: gtk::ComboBoxText::with_entry(),
+            button_load: gtk::Button::with_label("Laden"),
+            button_save: gtk::Button::with_label("Speichern"),
+            button_delete: gtk::Button::with_label("Löschen"),
+        }
+    }
+
+    /// Build the preset row
+    ///
+    /// `label` names what the presets are stored for, e.g. "Sensorzelle".
+    /// `current` returns the values to save, `on_load` gets the values of a loaded
+    /// preset. Results are reported to `on_message`.
+    pub fn build_ui<C, L, M>(&self, label: &str, current: C, on_load: L, on_message: M) -> gtk::Box
+    where
+        C: Fn() -> T + 'static,
+        L: Fn(T) + 'static,
+        M: Fn(&str) + 'static,
+    {
+        let on_message = Rc::new(on_message);
+        match &self.path {
+            Some(path) => match Presets::load(path) {
+                Ok(presets) => *self.presets.borrow_mut() = presets,
+                Err(e) => on_message(&format!("Vorlagen konnten nicht geladen werden: {}", e)),
+            },
+            None => on_message("Kein Konfigurationsverzeichnis, Vorlagen werden nicht gespeichert"),
+        }
+        self.fill();
+
+        let presets_ui = self.clone();
+        let message = on_message.clone();
+        self.button_load.connect_clicked(move |_| {
+            let name = presets_ui.name();
+            let values = presets_ui.presets.borrow().get(&name).cloned();
+            match values {
+                Some(values) => {
+                    on_load(values);
+                    message(&format!(
+                        "Vorlage \"{}\" geladen, noch nicht in den Sensor geschrieben",
+                        name
+                    ));
+                }
+                None => message(&format!("Keine Vorlage \"{}\" vorhanden", name)),
+            }
+        });
+        let presets_ui = self.clone();
+        let message = on_message.clone();
+        self.button_save.connect_clicked(move |_| {
+            let name = presets_ui.name();
+            if name.is_empty() {
+                message("Bitte einen Namen für die Vorlage angeben");
+                return;
+            }
+            presets_ui.presets.borrow_mut().insert(&name, current());
+            message(&presets_ui.store(&format!("Vorlage \"{}\" gespeichert", name)));
+        });
+        let presets_ui = self.clone();
+        self.button_delete.connect_clicked(move |_| {
+            let name = presets_ui.name();
+            if !presets_ui.presets.borrow_mut().remove(&name) {
+                on_message(&format!("Keine Vorlage \"{}\" vorhanden", name));
+                return;
+            }
+            on_message(&presets_ui.store(&format!("Vorlage \"{}\" gelöscht", name)));
+        });
+
+        let box_presets = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        box_presets.pack_start(&gtk::Label::new(Some(label)), false, false, 0);
+        box_presets.pack_start(&self.combo_box_text_presets, false, false, 0);
+        box_presets.pack_start(&self.button_load, false, false, 0);
+        box_presets.pack_start(&self.button_save, false, false, 0);
+        box_presets.pack_start(&self.button_delete, false, false, 0);
+        box_presets
+    }
+
+    /// Suggest a preset name, e.g. the gas of the sensor, unless one is entered
+    pub fn suggest_name(&self, name: &str) {
+        if self.name().is_empty() {
+            if let Some(entry) = self.entry() {
+                entry.set_text(name);
+            }
+        }
+    }
+
+    /// Entered or selected preset name
+    fn name(&self) -> String {
+        self.combo_box_text_presets
+            .get_active_text()
+            .map(|text| text.trim().to_string())
+            .unwrap_or_default()
+    }
+
+    fn entry(&self) -> Option<gtk::Entry> {
+        self.combo_box_text_presets
+            .get_child()
+            .and_then(|child| child.downcast::<gtk::Entry>().ok())
+    }
+
+    fn fill(&self) {
+        let name = self.name();
+        self.combo_box_text_presets.remove_all();
+        for preset in self.presets.borrow().names() {
+            self.combo_box_text_presets.append_text(preset);
+        }
+        if let Some(entry) = self.entry() {
+            entry.set_text(&name);
+        }
+        let has_presets = !self.presets.borrow().is_empty();
+        self.button_load.set_sensitive(has_presets);
+        self.button_delete.set_sensitive(has_presets);
+    }
+
+    /// Save the presets file, returns `message` on success
+    fn store(&self, message: &str) -> String {
+        self.fill();
+        let result = match &self.path {
+            Some(path) => self.presets.borrow().save(path),
+            None => Ok(()),
+        };
+        match result {
+            Ok(()) => message.to_string(),
+            Err(e) => format!("Vorlagen konnten nicht gespeichert werden: {}", e),
+        }
+    }
+}
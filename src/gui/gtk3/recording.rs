@@ -0,0 +1,239 @@
+use crate::sensors::ra_gas_ne4::RREGS;
+use crate::sensors::recording::{Recorder, RecordingSettings};
+/// Recording page
+///
+/// Writes the polled input registers to a CSV file. Interval and registers are
+/// selected before the recording starts.
+use chrono::Local;
+use gtk::prelude::*;
+use std::{cell::RefCell, fs::File, path::PathBuf, rc::Rc, time::Duration};
+
+#[derive(Clone)]
+pub struct RecordingUi {
+    store: gtk::ListStore,
+    treeview: gtk::TreeView,
+    spin_button_interval: gtk::SpinButton,
+    button_record: gtk::Button,
+    label_status: gtk::Label,
+    recording: Rc<RefCell<Option<(PathBuf, Recorder<File>)>>>,
+}
+
+impl RecordingUi {
+    pub fn new() -> Self {
+        let store = gtk::ListStore::new(&[glib::Type::Bool, glib::Type::U32, glib::Type::String]);
+        for reg in RREGS {
+            store.insert_with_values(
+                None,
+                &[0, 1, 2],
+                &[&true, &u32::from(reg.number), &reg.description],
+            );
+        }
+
+        RecordingUi {
+            treeview: gtk::TreeView::with_model(&store),
+            store,
+            spin_button_interval: gtk::SpinButton::with_range(1.0, 3600.0, 1.0),
+            button_record: gtk::Button::with_label("Aufzeichnung starten"),
+            label_status: gtk::Label::new(Some("Keine Aufzeichnung")),
+            recording: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Build the recording page
+    ///
+    /// `window` is the parent of the file chooser.
+    pub fn build_ui<W: IsA<gtk::Window>>(&self, window: &W) -> gtk::Box {
+        let renderer = gtk::CellRendererToggle::new();
+        let store = self.store.clone();
+        renderer.connect_toggled(move |_, path| {
+            if let Some(iter) = store.get_iter(&path) {
+                let active = store
+                    .get_value(&iter, 0)
+                    .get::<bool>()
+                    .unwrap_or(Some(false))
+                    .unwrap_or(false);
+                store.set_value(&iter, 0, &(!active).to_value());
+            }
+        });
+        let column = gtk::TreeViewColumn::new();
+        column.set_title("Aufzeichnen");
+        column.pack_start(&renderer, false);
+        column.add_attribute(&renderer, "active", 0);
+        self.treeview.append_column(&column);
+
+        for (i, title) in [(1, "Rreg Nr."), (2, "Messwerteigenschaft")].iter() {
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+
+            let renderer = gtk::CellRendererText::new();
+            column.pack_end(&renderer, true);
+            column.add_attribute(&renderer, "text", *i);
+
+            self.treeview.append_column(&column);
+        }
+
+        let scrolled_window = gtk::ScrolledWindow::new(gtk::NONE_ADJUSTMENT, gtk::NONE_ADJUSTMENT);
+        scrolled_window.set_vexpand(true);
+        scrolled_window.add(&self.treeview);
+
+        let default = RecordingSettings::default();
+        self.spin_button_interval
+            .set_value(default.interval.as_secs() as f64);
+        self.label_status.set_xalign(0.0);
+
+        let recording_ui = self.clone();
+        let window = window.clone().upcast::<gtk::Window>();
+        self.button_record.connect_clicked(move |_| {
+            if recording_ui.is_recording() {
+                recording_ui.stop();
+            } else {
+                recording_ui.start(&window);
+            }
+        });
+
+        let box_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        box_buttons.pack_start(&self.button_record, false, false, 0);
+        box_buttons.pack_start(&gtk::Label::new(Some("Intervall [s]")), false, false, 0);
+        box_buttons.pack_start(&self.spin_button_interval, false, false, 0);
+        box_buttons.pack_start(&self.label_status, true, true, 0);
+
+        let box_main = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        box_main.set_border_width(6);
+        box_main.pack_start(&box_buttons, false, false, 0);
+        box_main.pack_start(&scrolled_window, true, true, 0);
+
+        box_main
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.borrow().is_some()
+    }
+
+    /// Interval and registers selected in the UI
+    fn settings(&self) -> RecordingSettings {
+        let mut registers = vec![];
+        self.store.foreach(|model, _, iter| {
+            let active = model
+                .get_value(iter, 0)
+                .get::<bool>()
+                .unwrap_or(Some(false))
+                .unwrap_or(false);
+            if active {
+                if let Ok(Some(number)) = model.get_value(iter, 1).get::<u32>() {
+                    registers.push(number as u16);
+                }
+            }
+            false
+        });
+        RecordingSettings {
+            interval: Duration::from_secs(self.spin_button_interval.get_value_as_int() as u64),
+            registers,
+        }
+    }
+
+    /// Ask for the file name and start recording
+    fn start(&self, window: &gtk::Window) {
+        let settings = self.settings();
+        if settings.registers.is_empty() {
+            self.label_status.set_text("Keine Register ausgewählt");
+            return;
+        }
+
+        let dialog = gtk::FileChooserDialog::with_buttons(
+            Some("Aufzeichnung speichern"),
+            Some(window),
+            gtk::FileChooserAction::Save,
+            &[
+                ("_Abbrechen", gtk::ResponseType::Cancel),
+                ("_Speichern", gtk::ResponseType::Accept),
+            ],
+        );
+        dialog.set_do_overwrite_confirmation(true);
+        dialog.set_current_name(&format!(
+            "ne4_aufzeichnung_{}.csv",
+            Local::now().format("%Y-%m-%d_%H%M%S")
+        ));
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("CSV Datei (*.csv)"));
+        filter.add_pattern("*.csv");
+        dialog.add_filter(&filter);
+
+        let path = match dialog.run() {
+            gtk::ResponseType::Accept => dialog.get_filename(),
+            _ => None,
+        };
+        dialog.close();
+
+        if let Some(path) = path {
+            match Recorder::create(&path, &settings) {
+                Ok(recorder) => {
+                    self.label_status
+                        .set_text(&format!("Aufzeichnung in {}", path.display()));
+                    *self.recording.borrow_mut() = Some((path, recorder));
+                    self.set_recording(true);
+                }
+                Err(e) => {
+                    self.label_status.set_text(&format!(
+                        "{} konnte nicht angelegt werden: {}",
+                        path.display(),
+                        e
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Stop recording, the file is already complete
+    pub fn stop(&self) {
+        if let Some((path, recorder)) = self.recording.borrow_mut().take() {
+            self.label_status.set_text(&format!(
+                "{} Zeilen in {} aufgezeichnet",
+                recorder.rows(),
+                path.display()
+            ));
+        }
+        self.set_recording(false);
+    }
+
+    /// Record the values of a sensor update
+    ///
+    /// `values` is indexed by the register number. On a write error the recording
+    /// is stopped and the error returned.
+    pub fn record(&self, values: &[Option<u16>]) -> Result<(), String> {
+        let result = match &mut *self.recording.borrow_mut() {
+            Some((path, recorder)) => match recorder.record(Local::now(), values) {
+                Ok(true) => {
+                    self.label_status.set_text(&format!(
+                        "{} Zeilen in {}",
+                        recorder.rows(),
+                        path.display()
+                    ));
+                    Ok(())
+                }
+                Ok(false) => Ok(()),
+                Err(e) => Err(format!(
+                    "Aufzeichnung in {} abgebrochen: {}",
+                    path.display(),
+                    e
+                )),
+            },
+            None => Ok(()),
+        };
+        if let Err(e) = &result {
+            self.stop();
+            self.label_status.set_text(e);
+        }
+        result
+    }
+
+    /// Interval and registers can't be changed while recording
+    fn set_recording(&self, recording: bool) {
+        self.button_record.set_label(if recording {
+            "Aufzeichnung beenden"
+        } else {
+            "Aufzeichnung starten"
+        });
+        self.treeview.set_sensitive(!recording);
+        self.spin_button_interval.set_sensitive(!recording);
+    }
+}
@@ -1,7 +1,18 @@
+use crate::error::Result;
 use crate::gui::gtk3::Ui;
+use crate::sensors::ra_gas_ne4::{find_rwreg, CONNECTION_REGISTERS, RWREGS};
 /// Treestore and logic for Rwreg's
 use gio::prelude::*;
+use glib::clone;
 use gtk::prelude::*;
+use std::rc::Rc;
+
+/// Status text of a cell which was sent to the sensor
+const STATUS_PENDING: &str = "wird geschrieben …";
+/// Status text of a cell which was confirmed by the sensor
+const STATUS_CONFIRMED: &str = "bestätigt";
+/// Status text of a connection register, it can't be read back with the old settings
+const STATUS_RECONNECT: &str = "geschrieben, bitte neu verbinden";
 
 pub struct RwregStore {
     store: gtk::TreeStore,
@@ -14,376 +25,42 @@ impl RwregStore {
             glib::Type::String,
             glib::Type::String,
             glib::Type::String,
+            glib::Type::String,
+            glib::Type::Bool,
         ]);
 
         RwregStore { store }
     }
 
+    /// Fill the treestore with all Rwreg's of the register table
     pub fn fill_treestore(&self) {
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &0,
-                &"0 .. 65535 [0]",
-                &"",
-                &"Kundencode: zur freien Belegung z.B. Raumcode *",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &2,
-                &"0 … 10000 [11111]",
-                &"",
-                &"Messwertvorgabe für Testzwecke",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &3,
-                &"0 … 2500 [11111]",
-                &"",
-                &"Ausgangsstrom vorgeben für Testzwecke",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &4,
-                &"-200 … 600 [11111]",
-                &"",
-                &"Temperatur vorgeben für Testzwecke",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&10, &"0 … 16383", &"", &"Sensorspannung im Nullpunkt	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&11, &0, &"", &"Sensorwert Nullpunkt = 0	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &12,
-                &"0 … 16383",
-                &"",
-                &"Sensorspannung im Kalibrierpunkt ( bei Endwert) 	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &13,
-                &"0 … 10000",
-                &"",
-                &"Sensorwert im Kalibrierpunkt (bei Endwert) 	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &15,
-                &"0 … 10000 [0]",
-                &"",
-                &"Messwert unten für Ausgangsstrom unten	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &16,
-                &"0 … 2500 [400]",
-                &"",
-                &"Ausgangsstrom im unteren Punkt	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &17,
-                &"0 … 10000 [1000]",
-                &"",
-                &"Messwert oben für Ausgangsstrom oben	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &16,
-                &"0 … 2500 [2000]",
-                &"",
-                &"Ausgangsstrom im oberen Punkt	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &20,
-                &"0 / 1",
-                &"",
-                &"Status (Auswerte IC) (keine Eingabemöglichkeit)",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&21, &"0 / 1", &"", &"Lock (Auswerte IC) 	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&22, &"0 … 7", &"", &"TIA_GAIN (Auswerte IC) 	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&23, &"0 … 3", &"", &"RLOAD (Auswerte IC) 	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&24, &"0 / 1", &"", &"REF_Source (Auswerte IC) 	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&25, &"0 … 3", &"", &"INT_Z (Auswerte IC) 	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&26, &"0 / 1", &"", &"BIAS_Sign (Auswerte IC) 	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&27, &"0 … 13", &"", &"BIAS (Auswerte IC) 	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&28, &"0 / 1", &"", &"FET_Short (Auswerte IC) 	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&29, &"0 … 7", &"", &"OP_Mode (Auswerte IC) 	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &30,
-                &"50 ... 200",
-                &"",
-                &"Kennlinie vom Sensorhersteller bei -20°C	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &31,
-                &"50 ... 200",
-                &"",
-                &"Kennlinie vom Sensorhersteller bei 0°C	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &32,
-                &"50 ... 200",
-                &"",
-                &"Kennlinie vom Sensorhersteller bei 10°C	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &33,
-                &"50 ... 200",
-                &"",
-                &"Kennlinie vom Sensorhersteller bei 20°C	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &34,
-                &"50 ... 200",
-                &"",
-                &"Kennlinie vom Sensorhersteller bei 30°C	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &35,
-                &"50 ... 200",
-                &"",
-                &"Kennlinie vom Sensorhersteller bei 40°C	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &36,
-                &"50 ... 200",
-                &"",
-                &"Kennlinie vom Sensorhersteller bei 60°C	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &42,
-                &"0 … 16000 [11111]",
-                &"",
-                &"Sensor AD-Wert vorgeben für Testzwecke",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &49,
-                &"0 … 65535",
-                &"",
-                &"Neustart / Grunddaten / entsichern",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&50, &"1 … 247 [1]", &"", &"Modbus-Geräteadresse	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&51, &"0 … 3 [1]", &"", &"Modbus Baudrate	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&52, &"0 … 4 [0]", &"", &"Modbus Mode	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &53,
-                &"10 .. 1000 [180]",
-                &"",
-                &"Kalibrierwert Ausgangsstrom 4mA	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &54,
-                &"10 … 1000 [900]",
-                &"",
-                &"Kalibrierwert Ausgangsstrom 20mA	*",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[
-                &95,
-                &"0, 129 … 256 [90]",
-                &"",
-                &"Sensornummer für MCS4000 - Mode",
-            ],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&96, &"0 … 65535", &"", &"Einschaltzähler	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&97, &"0 … 65535", &"", &"Betriebsstunden	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&98, &"0 … 65535", &"", &"Gerätekennung vom Werk	*"],
-        );
-        self.store.insert_with_values(
-            None,
-            None,
-            &[0, 1, 2, 3],
-            &[&99, &"0 … 65535", &"", &"Arbeitsweise vom Werk	*"],
-        );
+        for reg in RWREGS {
+            let description = if reg.unlock {
+                format!("{} *", reg.description)
+            } else {
+                reg.description.to_string()
+            };
+            self.store.insert_with_values(
+                None,
+                None,
+                &[0, 1, 2, 3, 4, 5],
+                &[
+                    &(reg.number as u32),
+                    &reg.range_label(),
+                    &"",
+                    &description,
+                    &"",
+                    &reg.writable,
+                ],
+            );
+        }
     }
 
-    pub fn build_ui(&self) -> gtk::ScrolledWindow {
+    /// Build the Rwreg tree view
+    ///
+    /// `on_write` is called with register number and raw value of every edited cell
+    /// whose new value lies in the documented range of the register.
+    pub fn build_ui<F: Fn(u16, u16) + 'static>(&self, on_write: F) -> gtk::ScrolledWindow {
         // self.fill_treestore();
         //
         let sortable_store = gtk::TreeModelSort::new(&self.store);
@@ -417,22 +94,39 @@ impl RwregStore {
         column_value.set_title("Zugeordnete Größe und Einheit");
 
         let renderer = gtk::CellRendererText::new();
-        renderer.set_property_editable(true);
         column_value.pack_end(&renderer, true);
         column_value.add_attribute(&renderer, "text", 2);
+        // Read only registers like the Einschaltzähler are not editable
+        column_value.add_attribute(&renderer, "editable", 5);
 
         // let store = self.store.clone();
         // renderer.connect_editing_started(move |widget, path, text| {
         //     debug!("Edit started:\nwidget: {:?}\npath: {:?}\ntext: {:?}\n", widget, path, text);
         // });
         let store = self.store.clone();
-        renderer.connect_edited(move |widget, path, text| {
+        let on_write: Rc<dyn Fn(u16, u16)> = Rc::new(on_write);
+        renderer.connect_edited(clone!(
+            @strong sortable_store
+            => move |widget, path, text| {
             // debug!("Edited:\nwidget: {:?}\npath: {:?}\ntext: {:?}\n", widget, path, text);
-            edit_cell(&widget, &path, text, &store);
-        });
+            // The tree view shows the sorted model, the store needs the unsorted path
+            if let Some(path) = sortable_store.convert_path_to_child_path(&path) {
+                edit_cell(&widget, &path, text, &store, on_write.as_ref());
+            }
+        }));
 
         treeview.append_column(&column_value);
 
+        // Renderer Column 4
+        let column_status = gtk::TreeViewColumn::new();
+        column_status.set_title("Status");
+
+        let renderer = gtk::CellRendererText::new();
+        column_status.pack_end(&renderer, true);
+        column_status.add_attribute(&renderer, "text", 4);
+
+        treeview.append_column(&column_status);
+
         // Renderer Column 3
         let column_property = gtk::TreeViewColumn::new();
         column_property.set_title("Messwerteigenschaft");
@@ -451,42 +145,115 @@ impl RwregStore {
     }
 
     /// Update Treestore values with values received via modbus
-    pub fn update_treestore(&self, ui: &Ui, values: &[u16]) {
+    ///
+    /// `values` is indexed by the register number, registers which couldn't be
+    /// read keep the last value.
+    pub fn update_treestore(&self, _ui: &Ui, values: &[Option<u16>]) {
         debug!("{:?}", values);
         if let Some(iter) = self.store.get_iter_first() {
-            let _: Vec<u16> = values
-                .iter()
-                .enumerate()
-                .map(|(i, value)| {
-                    let reg_nr = self
-                        .store
-                        .get_value(&iter, 0)
-                        .get::<u32>()
-                        .unwrap_or(Some(0))
-                        .unwrap_or(0);
-                    debug!("i: {} reg_nr: {} value: {}", i, reg_nr, value);
-                    if i as u32 == reg_nr {
-                        let val = (*value as u32).to_value();
-                        self.store.set_value(&iter, 2, &val);
-                        self.store.iter_next(&iter);
-                    }
-                    *value
-                })
-                .collect();
+            loop {
+                let reg_nr = self
+                    .store
+                    .get_value(&iter, 0)
+                    .get::<u32>()
+                    .unwrap_or(Some(0))
+                    .unwrap_or(0);
+                if let Some(Some(value)) = values.get(reg_nr as usize) {
+                    debug!("reg_nr: {} value: {}", reg_nr, value);
+                    let value = match find_rwreg(reg_nr as u16) {
+                        Some(reg) => reg.raw_to_value(*value),
+                        None => *value as i32,
+                    };
+                    self.store
+                        .set_value(&iter, 2, &value.to_string().to_value());
+                }
+                if !self.store.iter_next(&iter) {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Mark the cell of the given register with the result of the write command
+    pub fn write_result(&self, reg_nr: u16, result: &Result<u16>) {
+        if let Some(iter) = self.find_iter(reg_nr) {
+            match result {
+                Ok(value) => {
+                    let value = match find_rwreg(reg_nr) {
+                        Some(reg) => reg.raw_to_value(*value),
+                        None => *value as i32,
+                    };
+                    self.store
+                        .set_value(&iter, 2, &value.to_string().to_value());
+                    let status = if CONNECTION_REGISTERS.contains(&reg_nr) {
+                        STATUS_RECONNECT
+                    } else {
+                        STATUS_CONFIRMED
+                    };
+                    self.store.set_value(&iter, 4, &status.to_value());
+                }
+                Err(e) => {
+                    self.store
+                        .set_value(&iter, 4, &format!("Fehler: {}", e).to_value());
+                }
+            }
+        }
+    }
+
+    /// Find the row of the given register number
+    fn find_iter(&self, reg_nr: u16) -> Option<gtk::TreeIter> {
+        let iter = self.store.get_iter_first()?;
+        loop {
+            let nr = self
+                .store
+                .get_value(&iter, 0)
+                .get::<u32>()
+                .unwrap_or(Some(0))
+                .unwrap_or(0);
+            if nr == reg_nr as u32 {
+                return Some(iter);
+            }
+            if !self.store.iter_next(&iter) {
+                return None;
+            }
         }
     }
 }
 
 /// callback called if a editable cell is updated with new value
+///
+/// The new value is checked against the documented range of the register first,
+/// values out of range are never sent to the sensor.
 fn edit_cell(
-    cell: &gtk::CellRendererText,
+    _cell: &gtk::CellRendererText,
     path: &gtk::TreePath,
     new_text: &str,
     model: &gtk::TreeStore,
+    on_write: &dyn Fn(u16, u16),
 ) {
     if let Some(iter) = model.get_iter(&path) {
-        let old_value = model.get_value(&iter, 2);
-        // debug!("{:?}", old_value.get::<String>());
-        model.set_value(&iter, 2, &new_text.to_value());
+        let reg_nr = model
+            .get_value(&iter, 0)
+            .get::<u32>()
+            .unwrap_or(Some(0))
+            .unwrap_or(0) as u16;
+        let reg = match find_rwreg(reg_nr) {
+            Some(reg) => reg,
+            None => return,
+        };
+        let status = match new_text.trim().parse::<i32>() {
+            Ok(value) if reg.accepts(value) => {
+                model.set_value(&iter, 2, &value.to_string().to_value());
+                on_write(reg.number, reg.value_to_raw(value));
+                STATUS_PENDING.to_string()
+            }
+            Ok(value) => format!(
+                "Fehler: {} liegt nicht im Wertebereich {}",
+                value,
+                reg.range_label()
+            ),
+            Err(_) => format!("Fehler: '{}' ist keine Zahl", new_text),
+        };
+        model.set_value(&iter, 4, &status.to_value());
     }
 }
@@ -0,0 +1,492 @@
+use crate::error::Result;
+use crate::gui::gtk3::presets::PresetsUi;
+use crate::sensors::ra_gas_ne4::NE4;
+/// Temperature curve page
+///
+/// Plots the factors of Rwreg 30 to 36 against the temperature. The points can be
+/// dragged with the mouse or typed in, the factors always stay within the register
+/// range. The gain of the edited curve at the internal temperature (Rreg 4) is
+/// compared with the gain the sensor reports in Rreg 44. Curves are stored as
+/// presets per sensor cell type and written to the sensor in one go.
+use crate::sensors::temperature_curve::{
+    factor_limits, TemperatureCurve, CURVE_PRESETS, CURVE_RWREGS, CURVE_TEMPERATURES,
+};
+use gtk::prelude::*;
+use std::{cell::Cell, rc::Rc};
+
+/// Space for the factor labels left of the plot
+const MARGIN_LEFT: f64 = 50.0;
+const MARGIN: f64 = 16.0;
+/// Space for the temperature labels below the plot
+const MARGIN_BOTTOM: f64 = 30.0;
+const FONT_SIZE: f64 = 11.0;
+/// Radius of the points, a point can be grabbed within twice the radius
+const POINT_RADIUS: f64 = 5.0;
+/// Shown temperature range in °C
+const TEMPERATURE_RANGE: (f64, f64) = (-25.0, 65.0);
+/// Difference between the curve and Rreg 44 which is reported
+const GAIN_TOLERANCE: f64 = 0.015;
+
+/// Plot area of the drawing area
+#[derive(Clone, Copy)]
+struct Plot {
+    left: f64,
+    top: f64,
+    width: f64,
+    height: f64,
+    /// Smallest and largest factor
+    limits: (f64, f64),
+}
+
+impl Plot {
+    fn new(width: f64, height: f64) -> Option<Self> {
+        let plot = Plot {
+            left: MARGIN_LEFT,
+            top: MARGIN,
+            width: width - MARGIN_LEFT - MARGIN,
+            height: height - MARGIN - MARGIN_BOTTOM,
+            limits: factor_limits(),
+        };
+        if plot.width <= 0.0 || plot.height <= 0.0 {
+            return None;
+        }
+        Some(plot)
+    }
+
+    fn x(&self, temperature: f64) -> f64 {
+        let (min, max) = TEMPERATURE_RANGE;
+        self.left + (temperature - min) / (max - min) * self.width
+    }
+
+    fn y(&self, factor: f64) -> f64 {
+        let (min, max) = self.limits;
+        self.top + self.height - (factor - min) / (max - min) * self.height
+    }
+
+    /// Factor at the y position
+    fn factor(&self, y: f64) -> f64 {
+        let (min, max) = self.limits;
+        min + (self.top + self.height - y) / self.height * (max - min)
+    }
+}
+
+#[derive(Clone)]
+pub struct TemperatureCurveUi {
+    /// Edited curve
+    curve: Rc<Cell<TemperatureCurve>>,
+    /// Curve in the sensor, from the last poll
+    sensor_curve: Rc<Cell<Option<TemperatureCurve>>>,
+    /// The editor shows a curve of the sensor or a preset
+    loaded: Rc<Cell<bool>>,
+    /// Internal temperature (Rreg 4) and gain reported by the sensor (Rreg 44)
+    live: Rc<Cell<Option<(f64, f64)>>>,
+    /// Index of the dragged point
+    drag: Rc<Cell<Option<usize>>>,
+    /// The spin buttons are set from the curve
+    updating: Rc<Cell<bool>>,
+    presets_ui: PresetsUi<TemperatureCurve>,
+    drawing_area: gtk::DrawingArea,
+    spin_buttons: Vec<gtk::SpinButton>,
+    button_read: gtk::Button,
+    button_write: gtk::Button,
+    label_gain: gtk::Label,
+    label_status: gtk::Label,
+}
+
+impl TemperatureCurveUi {
+    pub fn new() -> Self {
+        let (min, max) = factor_limits();
+        let spin_buttons = CURVE_TEMPERATURES
+            .iter()
+            .map(|_| {
+                let spin_button = gtk::SpinButton::with_range(min, max, 0.01);
+                spin_button.set_digits(2);
+                spin_button.set_numeric(true);
+                spin_button
+            })
+            .collect();
+
+        TemperatureCurveUi {
+            curve: Rc::new(Cell::new(TemperatureCurve::default())),
+            sensor_curve: Rc::new(Cell::new(None)),
+            loaded: Rc::new(Cell::new(false)),
+            live: Rc::new(Cell::new(None)),
+            drag: Rc::new(Cell::new(None)),
+            updating: Rc::new(Cell::new(false)),
+            presets_ui: PresetsUi::new(CURVE_PRESETS),
+            drawing_area: gtk::DrawingArea::new(),
+            spin_buttons,
+            button_read: gtk::Button::with_label("Aus Sensor lesen"),
+            button_write: gtk::Button::with_label("In Sensor schreiben"),
+            label_gain: gtk::Label::new(None),
+            label_status: gtk::Label::new(None),
+        }
+    }
+
+    /// Build the temperature curve page
+    ///
+    /// `on_read` reads the curve from the sensor, the result is passed to `read`.
+    /// `on_write` writes all seven factors.
+    pub fn build_ui<R, W>(&self, on_read: R, on_write: W) -> gtk::Box
+    where
+        R: Fn() + 'static,
+        W: Fn(TemperatureCurve) + 'static,
+    {
+        self.drawing_area.set_size_request(400, 250);
+        self.drawing_area.set_vexpand(true);
+        self.drawing_area.add_events(
+            gdk::EventMask::BUTTON_PRESS_MASK
+                | gdk::EventMask::BUTTON_RELEASE_MASK
+                | gdk::EventMask::BUTTON1_MOTION_MASK,
+        );
+        let temperature_curve_ui = self.clone();
+        self.drawing_area.connect_draw(move |widget, cr| {
+            temperature_curve_ui.draw(
+                cr,
+                f64::from(widget.get_allocated_width()),
+                f64::from(widget.get_allocated_height()),
+            );
+            gtk::Inhibit(false)
+        });
+        let temperature_curve_ui = self.clone();
+        self.drawing_area
+            .connect_button_press_event(move |widget, event| {
+                if event.get_button() == 1 {
+                    let (x, y) = event.get_position();
+                    let point = temperature_curve_ui.point_at(widget, x, y);
+                    temperature_curve_ui.drag.set(point);
+                }
+                gtk::Inhibit(false)
+            });
+        let temperature_curve_ui = self.clone();
+        self.drawing_area
+            .connect_motion_notify_event(move |widget, event| {
+                if let Some(index) = temperature_curve_ui.drag.get() {
+                    if let Some(plot) = plot(widget) {
+                        let (_, y) = event.get_position();
+                        temperature_curve_ui.set_factor(index, plot.factor(y));
+                    }
+                }
+                gtk::Inhibit(false)
+            });
+        let temperature_curve_ui = self.clone();
+        self.drawing_area.connect_button_release_event(move |_, _| {
+            temperature_curve_ui.drag.set(None);
+            gtk::Inhibit(false)
+        });
+
+        // Stützpunkte
+        let grid = gtk::Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(6);
+        grid.set_column_homogeneous(true);
+        for (column, (spin_button, temperature)) in self
+            .spin_buttons
+            .iter()
+            .zip(CURVE_TEMPERATURES.iter())
+            .enumerate()
+        {
+            let label = gtk::Label::new(Some(&format!(
+                "{} °C (Rwreg {})",
+                temperature, CURVE_RWREGS[column]
+            )));
+            grid.attach(&label, column as i32, 0, 1, 1);
+            grid.attach(spin_button, column as i32, 1, 1, 1);
+            let temperature_curve_ui = self.clone();
+            spin_button.connect_value_changed(move |spin_button| {
+                if !temperature_curve_ui.updating.get() {
+                    temperature_curve_ui.set_factor(column, spin_button.get_value());
+                }
+            });
+        }
+
+        // Vorlagen
+        let temperature_curve_ui = self.clone();
+        let on_load = move |curve| {
+            temperature_curve_ui.loaded.set(true);
+            temperature_curve_ui.curve.set(curve);
+            temperature_curve_ui.show_curve();
+        };
+        let curve = self.curve.clone();
+        let label_status = self.label_status.clone();
+        let box_presets = self.presets_ui.build_ui(
+            "Sensorzelle",
+            move || curve.get(),
+            on_load,
+            move |message| label_status.set_text(message),
+        );
+
+        self.button_read.connect_clicked(move |_| on_read());
+        let temperature_curve_ui = self.clone();
+        self.button_write.connect_clicked(move |_| {
+            let curve = temperature_curve_ui.curve.get();
+            match curve.validate() {
+                Ok(()) => {
+                    temperature_curve_ui
+                        .label_status
+                        .set_text("Kennlinie wird geschrieben …");
+                    on_write(curve);
+                }
+                Err(e) => temperature_curve_ui.label_status.set_text(&e.to_string()),
+            }
+        });
+        box_presets.pack_end(&self.button_write, false, false, 0);
+        box_presets.pack_end(&self.button_read, false, false, 0);
+
+        self.label_gain.set_xalign(0.0);
+        self.label_status.set_xalign(0.0);
+        self.label_status.set_line_wrap(true);
+
+        let box_main = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        box_main.set_border_width(12);
+        box_main.pack_start(&self.drawing_area, true, true, 0);
+        box_main.pack_start(&grid, false, false, 0);
+        box_main.pack_start(&self.label_gain, false, false, 0);
+        box_main.pack_start(&box_presets, false, false, 0);
+        box_main.pack_start(&self.label_status, false, false, 0);
+        self.show_curve();
+        self.set_sensitive(false);
+        box_main
+    }
+
+    /// Take temperature, Rreg 44 and the curve of the sensor from a poll
+    ///
+    /// The first curve read after connecting is loaded into the editor.
+    pub fn update(&self, ne4: &NE4) {
+        if ne4.has_rregs(&[4, 44]) {
+            self.live
+                .set(Some((ne4.int_temp(), ne4.amplification_temp())));
+        }
+        let sensor_curve = TemperatureCurve::from_ne4(ne4).filter(|curve| curve.validate().is_ok());
+        self.sensor_curve.set(sensor_curve);
+        if let Some(curve) = sensor_curve {
+            if !self.loaded.get() {
+                self.loaded.set(true);
+                self.curve.set(curve);
+            }
+        }
+        self.show_curve();
+    }
+
+    /// Result of reading the curve from the sensor
+    pub fn read(&self, result: Result<TemperatureCurve>) {
+        match result {
+            Ok(curve) => {
+                self.loaded.set(true);
+                self.sensor_curve.set(Some(curve));
+                self.curve.set(curve);
+                self.show_curve();
+                self.label_status
+                    .set_text("Kennlinie aus dem Sensor gelesen");
+            }
+            Err(e) => self
+                .label_status
+                .set_text(&format!("Kennlinie konnte nicht gelesen werden: {}", e)),
+        }
+    }
+
+    /// Result of writing the curve
+    pub fn written(&self, result: &Result<TemperatureCurve>) {
+        match result {
+            Ok(curve) => {
+                self.sensor_curve.set(Some(*curve));
+                self.show_curve();
+                self.label_status.set_text("Kennlinie geschrieben");
+            }
+            Err(e) => self
+                .label_status
+                .set_text(&format!("Kennlinie konnte nicht geschrieben werden: {}", e)),
+        }
+    }
+
+    /// Reading and writing only while connected, the presets can always be edited
+    pub fn set_sensitive(&self, sensitive: bool) {
+        self.button_read.set_sensitive(sensitive);
+        self.button_write.set_sensitive(sensitive);
+    }
+
+    /// Forget the values of the sensor, e.g. after disconnecting
+    pub fn clear(&self) {
+        self.live.set(None);
+        self.sensor_curve.set(None);
+        self.loaded.set(false);
+        self.show_curve();
+    }
+
+    /// Set one factor of the edited curve, clamped to the register range
+    fn set_factor(&self, index: usize, factor: f64) {
+        let mut curve = self.curve.get();
+        curve.set_factor(index, factor);
+        self.curve.set(curve);
+        self.show_curve();
+    }
+
+    /// Show the edited curve in the spin buttons, the plot and the gain label
+    fn show_curve(&self) {
+        let curve = self.curve.get();
+        self.updating.set(true);
+        for (index, spin_button) in self.spin_buttons.iter().enumerate() {
+            spin_button.set_value(curve.factor(index));
+        }
+        self.updating.set(false);
+
+        let text = match self.live.get() {
+            Some((temperature, sensor_gain)) => {
+                let gain = curve.gain_at(temperature);
+                let mut text = format!(
+                    "Verstärkung bei {:.1} °C: Kennlinie {:.2}, Sensor (Rreg 44) {:.2}",
+                    temperature, gain, sensor_gain
+                );
+                match self.sensor_curve.get() {
+                    Some(sensor_curve) if sensor_curve != curve => {
+                        text.push_str(" – Kennlinie noch nicht geschrieben")
+                    }
+                    Some(_) if (gain - sensor_gain).abs() > GAIN_TOLERANCE => {
+                        text.push_str(" – Sensor weicht von der Kennlinie ab")
+                    }
+                    _ => {}
+                }
+                text
+            }
+            None => "Verstärkung bei der aktuellen Temperatur: keine Messwerte".to_string(),
+        };
+        self.label_gain.set_text(&text);
+        self.drawing_area.queue_draw();
+    }
+
+    /// Index of the point near the position
+    fn point_at(&self, widget: &gtk::DrawingArea, x: f64, y: f64) -> Option<usize> {
+        let plot = plot(widget)?;
+        let curve = self.curve.get();
+        CURVE_TEMPERATURES
+            .iter()
+            .enumerate()
+            .map(|(index, temperature)| {
+                let dx = plot.x(*temperature) - x;
+                let dy = plot.y(curve.factor(index)) - y;
+                (index, (dx * dx + dy * dy).sqrt())
+            })
+            .filter(|(_, distance)| *distance <= 2.0 * POINT_RADIUS)
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(index, _)| index)
+    }
+
+    fn draw(&self, cr: &cairo::Context, width: f64, height: f64) {
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        cr.paint();
+        cr.set_font_size(FONT_SIZE);
+        let plot = match Plot::new(width, height) {
+            Some(plot) => plot,
+            None => return,
+        };
+
+        // Raster mit Beschriftung
+        cr.set_line_width(1.0);
+        let (min, max) = plot.limits;
+        let mut factor = min;
+        while factor <= max + 1e-9 {
+            cr.set_source_rgb(0.85, 0.85, 0.85);
+            cr.move_to(plot.left, plot.y(factor));
+            cr.line_to(plot.left + plot.width, plot.y(factor));
+            cr.stroke();
+            let label = format!("{:.2}", factor);
+            let extents = cr.text_extents(&label);
+            cr.set_source_rgb(0.0, 0.0, 0.0);
+            cr.move_to(
+                plot.left - extents.width - 4.0,
+                plot.y(factor) + extents.height / 2.0,
+            );
+            cr.show_text(&label);
+            factor += 0.25;
+        }
+        for temperature in CURVE_TEMPERATURES.iter() {
+            cr.set_source_rgb(0.85, 0.85, 0.85);
+            cr.move_to(plot.x(*temperature), plot.top);
+            cr.line_to(plot.x(*temperature), plot.top + plot.height);
+            cr.stroke();
+            let label = format!("{} °C", temperature);
+            let extents = cr.text_extents(&label);
+            cr.set_source_rgb(0.0, 0.0, 0.0);
+            cr.move_to(
+                plot.x(*temperature) - extents.width / 2.0,
+                height - MARGIN_BOTTOM / 2.0 + extents.height / 2.0,
+            );
+            cr.show_text(&label);
+        }
+        cr.set_source_rgb(0.6, 0.6, 0.6);
+        cr.rectangle(plot.left, plot.top, plot.width, plot.height);
+        cr.stroke();
+
+        let curve = self.curve.get();
+        let polyline = |curve: &TemperatureCurve| {
+            cr.move_to(plot.x(TEMPERATURE_RANGE.0), plot.y(curve.factor(0)));
+            for (index, temperature) in CURVE_TEMPERATURES.iter().enumerate() {
+                cr.line_to(plot.x(*temperature), plot.y(curve.factor(index)));
+            }
+            cr.line_to(
+                plot.x(TEMPERATURE_RANGE.1),
+                plot.y(curve.factor(CURVE_TEMPERATURES.len() - 1)),
+            );
+            cr.stroke();
+        };
+
+        // Kennlinie im Sensor, wenn sie von der bearbeiteten abweicht
+        if let Some(sensor_curve) = self.sensor_curve.get() {
+            if sensor_curve != curve {
+                cr.set_source_rgb(0.6, 0.6, 0.6);
+                cr.set_line_width(1.5);
+                cr.set_dash(&[4.0, 4.0], 0.0);
+                polyline(&sensor_curve);
+                cr.set_dash(&[], 0.0);
+            }
+        }
+
+        // Bearbeitete Kennlinie
+        cr.set_source_rgb(0.10, 0.40, 0.80);
+        cr.set_line_width(2.0);
+        polyline(&curve);
+        for (index, temperature) in CURVE_TEMPERATURES.iter().enumerate() {
+            cr.arc(
+                plot.x(*temperature),
+                plot.y(curve.factor(index)),
+                POINT_RADIUS,
+                0.0,
+                2.0 * std::f64::consts::PI,
+            );
+            cr.fill();
+        }
+
+        // Aktuelle Temperatur mit Verstärkung laut Kennlinie und Rreg 44
+        if let Some((temperature, sensor_gain)) = self.live.get() {
+            let x = plot.x(temperature);
+            cr.set_source_rgb(0.90, 0.50, 0.0);
+            cr.set_line_width(1.0);
+            cr.move_to(x, plot.top);
+            cr.line_to(x, plot.top + plot.height);
+            cr.stroke();
+            cr.arc(
+                x,
+                plot.y(curve.gain_at(temperature)),
+                POINT_RADIUS - 1.0,
+                0.0,
+                2.0 * std::f64::consts::PI,
+            );
+            cr.fill();
+            let y = plot.y(sensor_gain.max(min).min(max));
+            cr.set_line_width(2.0);
+            cr.move_to(x - POINT_RADIUS, y - POINT_RADIUS);
+            cr.line_to(x + POINT_RADIUS, y + POINT_RADIUS);
+            cr.move_to(x - POINT_RADIUS, y + POINT_RADIUS);
+            cr.line_to(x + POINT_RADIUS, y - POINT_RADIUS);
+            cr.stroke();
+        }
+    }
+}
+
+/// Plot area of the widget
+fn plot(widget: &gtk::DrawingArea) -> Option<Plot> {
+    Plot::new(
+        f64::from(widget.get_allocated_width()),
+        f64::from(widget.get_allocated_height()),
+    )
+}
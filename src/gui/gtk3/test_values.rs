@@ -0,0 +1,205 @@
+use crate::error::Result;
+use crate::sensors::ra_gas_ne4::NE4;
+/// Test value page
+///
+/// Forces gas concentration, output current, temperature and sensor AD value
+/// (Rwreg 2, 3, 4 and 42) in real units. The current overrides are taken from
+/// the polled Rwreg's, an active override is shown on the page and in the tab
+/// label. The tokio thread resets all overrides it has set on disconnect and on
+/// exit, see `Ne4Client::restore_test_values`.
+use crate::sensors::test_values::{active_test_values, TestValue};
+use gtk::prelude::*;
+
+/// Widgets of one test value
+#[derive(Clone)]
+struct Row {
+    test_value: TestValue,
+    spin_button: gtk::SpinButton,
+    button_set: gtk::Button,
+    button_off: gtk::Button,
+    label_current: gtk::Label,
+}
+
+#[derive(Clone)]
+pub struct TestValuesUi {
+    rows: Vec<Row>,
+    grid: gtk::Grid,
+    button_reset: gtk::Button,
+    button_loop_check: gtk::Button,
+    label_status: gtk::Label,
+    label_tab: gtk::Label,
+}
+
+impl TestValuesUi {
+    pub fn new() -> Self {
+        let grid = gtk::Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(12);
+
+        let mut rows = vec![];
+        for (row, test_value) in TestValue::ALL.iter().enumerate() {
+            let (min, max) = test_value.limits();
+            let decimals = test_value.register().decimals();
+            let step = 1.0 / 10f64.powi(decimals as i32);
+            let spin_button = gtk::SpinButton::with_range(min, max, step);
+            spin_button.set_digits(decimals as u32);
+            spin_button.set_numeric(true);
+            let label_title = gtk::Label::new(Some(&format!(
+                "{} (Rwreg {})",
+                test_value,
+                test_value.register_number()
+            )));
+            label_title.set_xalign(1.0);
+            let label_unit = gtk::Label::new(Some(test_value.unit()));
+            label_unit.set_xalign(0.0);
+            let label_current = gtk::Label::new(Some("-"));
+            label_current.set_xalign(0.0);
+            label_current.set_width_chars(12);
+
+            let row = row as i32;
+            let button_set = gtk::Button::with_label("Vorgeben");
+            let button_off = gtk::Button::with_label("Aus");
+            grid.attach(&label_title, 0, row, 1, 1);
+            grid.attach(&spin_button, 1, row, 1, 1);
+            grid.attach(&label_unit, 2, row, 1, 1);
+            grid.attach(&button_set, 3, row, 1, 1);
+            grid.attach(&button_off, 4, row, 1, 1);
+            grid.attach(&label_current, 5, row, 1, 1);
+
+            rows.push(Row {
+                test_value: *test_value,
+                spin_button,
+                button_set,
+                button_off,
+                label_current,
+            });
+        }
+
+        TestValuesUi {
+            rows,
+            grid,
+            button_reset: gtk::Button::with_label("Alle Testwerte aus"),
+            button_loop_check: gtk::Button::with_label("Stromschleife prüfen …"),
+            label_status: gtk::Label::new(None),
+            label_tab: gtk::Label::new(Some("Testwerte")),
+        }
+    }
+
+    /// Build the test value page
+    ///
+    /// `on_set` is called with the test value and the raw value to force, `None`
+    /// switches the override off. `on_reset` switches all overrides off.
+    /// `on_loop_check` opens the loop check wizard.
+    pub fn build_ui<S, R, L>(&self, on_set: S, on_reset: R, on_loop_check: L) -> gtk::Box
+    where
+        S: Fn(TestValue, Option<u16>) + Clone + 'static,
+        R: Fn() + 'static,
+        L: Fn() + 'static,
+    {
+        for row in &self.rows {
+            let test_values_ui = self.clone();
+            let spin_button = row.spin_button.clone();
+            let test_value = row.test_value;
+            let on_set_value = on_set.clone();
+            row.button_set.connect_clicked(move |_| {
+                match test_value.to_raw(spin_button.get_value()) {
+                    Ok(raw) => on_set_value(test_value, Some(raw)),
+                    Err(e) => test_values_ui.label_status.set_text(&e.to_string()),
+                }
+            });
+            let on_set_off = on_set.clone();
+            row.button_off
+                .connect_clicked(move |_| on_set_off(test_value, None));
+        }
+        self.button_reset.connect_clicked(move |_| on_reset());
+        self.button_loop_check
+            .connect_clicked(move |_| on_loop_check());
+
+        let label_info = gtk::Label::new(Some(
+            "Vorgegebene Werte ersetzen die Messung des Sensors, z.B. um die Auswertung \
+             der Zentrale zu prüfen. Beim Trennen der Verbindung und beim Beenden werden \
+             alle Vorgaben ausgeschaltet.",
+        ));
+        label_info.set_line_wrap(true);
+        label_info.set_xalign(0.0);
+        self.label_status.set_xalign(0.0);
+        self.label_status.set_line_wrap(true);
+
+        let box_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        box_buttons.pack_start(&self.button_reset, false, false, 0);
+        box_buttons.pack_start(&self.button_loop_check, false, false, 0);
+
+        let box_main = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        box_main.set_border_width(12);
+        box_main.pack_start(&label_info, false, false, 0);
+        box_main.pack_start(&self.grid, false, false, 0);
+        box_main.pack_start(&box_buttons, false, false, 0);
+        box_main.pack_start(&self.label_status, false, false, 0);
+        self.set_sensitive(false);
+        box_main
+    }
+
+    /// Label of the notebook tab, marks active overrides
+    pub fn tab_label(&self) -> &gtk::Label {
+        &self.label_tab
+    }
+
+    /// Show the overrides of the polled Rwreg's
+    pub fn update(&self, ne4: &NE4) {
+        for row in &self.rows {
+            row.label_current
+                .set_text(&match row.test_value.current(ne4) {
+                    Some(value) => row.test_value.format(value),
+                    None => "aus".to_string(),
+                });
+        }
+        let active = active_test_values(ne4);
+        let style_context = self.label_tab.get_style_context();
+        if active.is_empty() {
+            self.label_tab.set_text("Testwerte");
+            style_context.remove_class("test-mode");
+            self.label_status.set_text("Keine Testwerte vorgegeben");
+        } else {
+            let names: Vec<String> = active.iter().map(|t| t.to_string()).collect();
+            self.label_tab.set_text("Testwerte (aktiv)");
+            style_context.add_class("test-mode");
+            self.label_status
+                .set_text(&format!("Testmodus aktiv: {}", names.join(", ")));
+        }
+    }
+
+    /// Result of writing a test value
+    pub fn written(&self, test_value: TestValue, result: &Result<u16>) {
+        match result {
+            Ok(raw) => {
+                if let Some(row) = self.rows.iter().find(|row| row.test_value == test_value) {
+                    row.label_current
+                        .set_text(&match test_value.from_raw(*raw) {
+                            Some(value) => test_value.format(value),
+                            None => "aus".to_string(),
+                        });
+                }
+            }
+            Err(e) => self.label_status.set_text(&format!(
+                "{} konnte nicht vorgegeben werden: {}",
+                test_value, e
+            )),
+        }
+    }
+
+    /// The overrides can only be set while connected
+    pub fn set_sensitive(&self, sensitive: bool) {
+        self.grid.set_sensitive(sensitive);
+        self.button_reset.set_sensitive(sensitive);
+        self.button_loop_check.set_sensitive(sensitive);
+    }
+
+    /// Forget the shown overrides, e.g. after disconnecting
+    pub fn clear(&self) {
+        for row in &self.rows {
+            row.label_current.set_text("-");
+        }
+        self.label_tab.set_text("Testwerte");
+        self.label_tab.get_style_context().remove_class("test-mode");
+    }
+}
@@ -0,0 +1,338 @@
+use crate::sensors::history::{value_range, History, Sample};
+use crate::sensors::ra_gas_ne4::NE4;
+/// Trend chart page
+///
+/// Draws the history of gas concentration, output current and internal temperature
+/// in three panels with a common time axis. The time window is selectable, the
+/// chart can be paused and the time axis zoomed with the buttons or the mouse wheel.
+use gtk::prelude::*;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// Selectable time windows in seconds
+const WINDOWS: &[(&str, u64)] = &[
+    ("1 Minute", 60),
+    ("5 Minuten", 300),
+    ("15 Minuten", 900),
+    ("1 Stunde", 3600),
+];
+
+/// Maximum zoom factor of the time axis
+const MAX_ZOOM: u32 = 16;
+
+/// Space for the value labels left of the panels
+const MARGIN_LEFT: f64 = 60.0;
+const MARGIN: f64 = 10.0;
+/// Space for the time labels below the panels
+const MARGIN_BOTTOM: f64 = 24.0;
+const FONT_SIZE: f64 = 11.0;
+
+/// One panel of the chart
+struct Series {
+    title: &'static str,
+    unit: &'static str,
+    decimals: usize,
+    /// Smallest value range of the axis
+    min_span: f64,
+    color: (f64, f64, f64),
+    value: fn(&Sample) -> f64,
+}
+
+const SERIES: &[Series] = &[
+    Series {
+        title: "Gaskonzentration",
+        unit: "ppm",
+        decimals: 0,
+        min_span: 10.0,
+        color: (0.80, 0.20, 0.10),
+        value: gas,
+    },
+    Series {
+        title: "Ausgangsstrom",
+        unit: "mA",
+        decimals: 2,
+        min_span: 0.5,
+        color: (0.10, 0.40, 0.80),
+        value: ma,
+    },
+    Series {
+        title: "Interne Temperatur",
+        unit: "°C",
+        decimals: 1,
+        min_span: 2.0,
+        color: (0.20, 0.60, 0.20),
+        value: temp,
+    },
+];
+
+fn gas(sample: &Sample) -> f64 {
+    sample.gas
+}
+
+fn ma(sample: &Sample) -> f64 {
+    sample.ma
+}
+
+fn temp(sample: &Sample) -> f64 {
+    sample.temp
+}
+
+#[derive(Clone)]
+pub struct TrendUi {
+    history: Rc<RefCell<History>>,
+    drawing_area: gtk::DrawingArea,
+    combo_box_text_window: gtk::ComboBoxText,
+    toggle_button_pause: gtk::ToggleButton,
+    button_zoom_in: gtk::Button,
+    button_zoom_out: gtk::Button,
+    /// End of the shown time window while paused
+    paused_at: Rc<Cell<Option<Instant>>>,
+    zoom: Rc<Cell<u32>>,
+}
+
+impl TrendUi {
+    pub fn new() -> Self {
+        let (_, longest) = WINDOWS[WINDOWS.len() - 1];
+        TrendUi {
+            history: Rc::new(RefCell::new(History::new(Duration::from_secs(longest)))),
+            drawing_area: gtk::DrawingArea::new(),
+            combo_box_text_window: gtk::ComboBoxText::new(),
+            toggle_button_pause: gtk::ToggleButton::with_label("Pause"),
+            button_zoom_in: gtk::Button::with_label("Zoom +"),
+            button_zoom_out: gtk::Button::with_label("Zoom −"),
+            paused_at: Rc::new(Cell::new(None)),
+            zoom: Rc::new(Cell::new(1)),
+        }
+    }
+
+    /// Build the trend chart page
+    pub fn build_ui(&self) -> gtk::Box {
+        for (label, secs) in WINDOWS {
+            self.combo_box_text_window
+                .append(Some(&secs.to_string()), label);
+        }
+        self.combo_box_text_window.set_active(Some(0));
+        let trend_ui = self.clone();
+        self.combo_box_text_window.connect_changed(move |_| {
+            trend_ui.zoom.set(1);
+            trend_ui.update_zoom_buttons();
+        });
+
+        let trend_ui = self.clone();
+        self.toggle_button_pause.connect_toggled(move |button| {
+            if button.get_active() {
+                trend_ui.paused_at.set(Some(Instant::now()));
+            } else {
+                trend_ui.paused_at.set(None);
+            }
+            trend_ui.drawing_area.queue_draw();
+        });
+
+        let trend_ui = self.clone();
+        self.button_zoom_in
+            .connect_clicked(move |_| trend_ui.set_zoom(trend_ui.zoom.get() * 2));
+        let trend_ui = self.clone();
+        self.button_zoom_out
+            .connect_clicked(move |_| trend_ui.set_zoom(trend_ui.zoom.get() / 2));
+        self.update_zoom_buttons();
+
+        self.drawing_area.set_size_request(400, 300);
+        self.drawing_area.set_vexpand(true);
+        self.drawing_area.add_events(gdk::EventMask::SCROLL_MASK);
+        let trend_ui = self.clone();
+        self.drawing_area.connect_scroll_event(move |_, event| {
+            match event.get_direction() {
+                gdk::ScrollDirection::Up => trend_ui.set_zoom(trend_ui.zoom.get() * 2),
+                gdk::ScrollDirection::Down => trend_ui.set_zoom(trend_ui.zoom.get() / 2),
+                _ => {}
+            }
+            gtk::Inhibit(true)
+        });
+        let trend_ui = self.clone();
+        self.drawing_area.connect_draw(move |widget, cr| {
+            trend_ui.draw(
+                cr,
+                f64::from(widget.get_allocated_width()),
+                f64::from(widget.get_allocated_height()),
+            );
+            gtk::Inhibit(false)
+        });
+
+        let box_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        box_buttons.pack_start(&gtk::Label::new(Some("Zeitfenster")), false, false, 0);
+        box_buttons.pack_start(&self.combo_box_text_window, false, false, 0);
+        box_buttons.pack_start(&self.toggle_button_pause, false, false, 0);
+        box_buttons.pack_end(&self.button_zoom_in, false, false, 0);
+        box_buttons.pack_end(&self.button_zoom_out, false, false, 0);
+
+        let box_main = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        box_main.set_border_width(6);
+        box_main.pack_start(&box_buttons, false, false, 0);
+        box_main.pack_start(&self.drawing_area, true, true, 0);
+
+        box_main
+    }
+
+    /// Append the values of a sensor update, skipped if a value couldn't be read
+    pub fn push(&self, ne4: &NE4) {
+        let sample = match Sample::from_ne4(Instant::now(), ne4) {
+            Some(sample) => sample,
+            None => return,
+        };
+        self.history.borrow_mut().push(sample);
+        if self.paused_at.get().is_none() {
+            self.drawing_area.queue_draw();
+        }
+    }
+
+    /// Forget the history, e.g. when another sensor is connected
+    pub fn clear(&self) {
+        self.history.borrow_mut().clear();
+        self.drawing_area.queue_draw();
+    }
+
+    fn set_zoom(&self, zoom: u32) {
+        if zoom >= 1 && zoom <= MAX_ZOOM {
+            self.zoom.set(zoom);
+            self.update_zoom_buttons();
+        }
+    }
+
+    fn update_zoom_buttons(&self) {
+        let zoom = self.zoom.get();
+        self.button_zoom_in.set_sensitive(zoom < MAX_ZOOM);
+        self.button_zoom_out.set_sensitive(zoom > 1);
+        self.drawing_area.queue_draw();
+    }
+
+    /// Shown time span, the selected window divided by the zoom factor
+    fn span(&self) -> Duration {
+        let window = self
+            .combo_box_text_window
+            .get_active_id()
+            .and_then(|id| id.parse().ok())
+            .unwrap_or(WINDOWS[0].1);
+        Duration::from_secs(window) / self.zoom.get()
+    }
+
+    fn draw(&self, cr: &cairo::Context, width: f64, height: f64) {
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        cr.paint();
+        cr.set_font_size(FONT_SIZE);
+
+        let end = self.paused_at.get().unwrap_or_else(Instant::now);
+        let span = self.span();
+        let samples = self.history.borrow().visible(end, span);
+
+        let plot_width = width - MARGIN_LEFT - MARGIN;
+        let panel_height = (height - MARGIN_BOTTOM) / SERIES.len() as f64;
+        if plot_width <= 0.0 || panel_height <= 2.0 * MARGIN {
+            return;
+        }
+        // x position of a sample, the newest value is on the right
+        let x = |sample: &Sample| {
+            let age = end
+                .checked_duration_since(sample.time)
+                .unwrap_or_default()
+                .as_secs_f64();
+            MARGIN_LEFT + plot_width * (1.0 - age / span.as_secs_f64())
+        };
+
+        for (i, series) in SERIES.iter().enumerate() {
+            let top = i as f64 * panel_height + MARGIN;
+            let bottom = (i + 1) as f64 * panel_height;
+            let plot_height = bottom - top;
+
+            // Frame
+            cr.set_source_rgb(0.6, 0.6, 0.6);
+            cr.set_line_width(1.0);
+            cr.rectangle(MARGIN_LEFT, top, plot_width, plot_height);
+            cr.stroke();
+
+            // Title with the newest value
+            let title = match samples.last() {
+                Some(sample) => format!(
+                    "{}: {:.*} {}",
+                    series.title,
+                    series.decimals,
+                    (series.value)(sample),
+                    series.unit
+                ),
+                None => format!("{} [{}]", series.title, series.unit),
+            };
+            cr.set_source_rgb(0.0, 0.0, 0.0);
+            cr.move_to(MARGIN_LEFT + 4.0, top + FONT_SIZE + 2.0);
+            cr.show_text(&title);
+
+            let (min, max) = match value_range(samples.iter().map(series.value), series.min_span) {
+                Some(range) => range,
+                None => continue,
+            };
+            let y = |value: f64| bottom - (value - min) / (max - min) * plot_height;
+
+            // Value labels
+            for value in &[min, max] {
+                let label = format!("{:.*}", series.decimals, value);
+                let extents = cr.text_extents(&label);
+                cr.move_to(
+                    MARGIN_LEFT - extents.width - 4.0,
+                    y(*value) + extents.height / 2.0,
+                );
+                cr.show_text(&label);
+            }
+
+            // Curve
+            cr.save();
+            cr.rectangle(MARGIN_LEFT, top, plot_width, plot_height);
+            cr.clip();
+            let (r, g, b) = series.color;
+            cr.set_source_rgb(r, g, b);
+            cr.set_line_width(1.5);
+            for (n, sample) in samples.iter().enumerate() {
+                if n == 0 {
+                    cr.move_to(x(sample), y((series.value)(sample)));
+                } else {
+                    cr.line_to(x(sample), y((series.value)(sample)));
+                }
+            }
+            cr.stroke();
+            cr.restore();
+        }
+
+        // Time axis, seconds before the end of the window
+        cr.set_source_rgb(0.0, 0.0, 0.0);
+        let ticks = 4;
+        for tick in 0..=ticks {
+            let offset = span.as_secs_f64() * f64::from(ticks - tick) / f64::from(ticks);
+            let label = if tick == ticks && self.paused_at.get().is_some() {
+                "Pause".to_string()
+            } else {
+                format_offset(offset)
+            };
+            let extents = cr.text_extents(&label);
+            let tick_x = MARGIN_LEFT + plot_width * f64::from(tick) / f64::from(ticks);
+            cr.move_to(
+                (tick_x - extents.width / 2.0)
+                    .max(0.0)
+                    .min(width - extents.width),
+                height - MARGIN_BOTTOM / 2.0 + extents.height / 2.0,
+            );
+            cr.show_text(&label);
+        }
+    }
+}
+
+/// Label of the time axis, e.g. "-1:30" or "-20 s"
+fn format_offset(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    if secs == 0 {
+        "jetzt".to_string()
+    } else if secs < 60 {
+        format!("-{} s", secs)
+    } else {
+        format!("-{}:{:02}", secs / 60, secs % 60)
+    }
+}
@@ -2,12 +2,30 @@
 extern crate log;
 extern crate nom;
 
+pub mod error;
+
 pub mod gui {
     pub mod gtk3;
 }
 
 pub mod sensors {
+    pub mod backup;
+    pub mod calibration;
+    pub mod calibration_report;
+    pub mod dashboard;
+    pub mod device_info;
+    pub mod front_end;
+    pub mod gas_catalogue;
+    pub mod history;
+    pub mod loop_check;
+    pub mod output_scaling;
+    pub mod presets;
     pub mod ra_gas_ne4;
+    pub mod recording;
+    pub mod temperature_curve;
+    pub mod test_values;
 }
 
+pub mod simulator;
+
 pub mod tokio_thread;
@@ -0,0 +1,358 @@
+/// Sicherung und Wiederherstellung der Konfiguration eines NE4
+///
+/// Eine Sicherung enthält alle beschreibbaren Rwreg zusammen mit Schnittstelle,
+/// Modbus Adresse, Geräte ID, Softwarestand und Zeitstempel. Gespeichert wird sie
+/// als JSON Datei mit Versionsnummer.
+use super::calibration::CALIBRATION_COMMAND;
+use super::ra_gas_ne4::{find_rwreg, register_value, Register, CONNECTION_REGISTERS, RWREGS};
+use crate::error::{self, Error};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io, path::Path};
+
+/// Version des Dateiformats
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Rwreg 49 ist ein Kommando (Neustart, Grunddaten, entsichern) und wird nicht gesichert
+const COMMAND_REGISTERS: &[u16] = &[49];
+
+/// Geräte ID und Arbeitsweise vom Werk gehören zum einzelnen Sensor und werden
+/// nicht wiederhergestellt
+const FACTORY_REGISTERS: &[u16] = &[98, 99];
+
+/// Rohwerte die bei einem Register ein Kommando sind
+///
+/// 11111 in Rwreg 10 bzw. 12 übernimmt den aktuellen AD-Wert als Nullpunkt bzw.
+/// Messgas, statt den gesicherten Wert zu schreiben.
+const COMMAND_VALUES: &[(u16, u16)] = &[(10, CALIBRATION_COMMAND), (12, CALIBRATION_COMMAND)];
+
+/// Rwreg die gesichert werden
+pub fn backup_registers() -> impl Iterator<Item = &'static Register> {
+    RWREGS
+        .iter()
+        .filter(|reg| reg.writable && !COMMAND_REGISTERS.contains(&reg.number))
+}
+
+/// Rwreg die wiederhergestellt werden
+///
+/// Ohne Verbindungsparameter, Werkseinstellungen und Testwertvorgaben, eine beim
+/// Sichern aktive Vorgabe würde sonst dauerhaft gesetzt.
+pub fn restore_registers() -> impl Iterator<Item = &'static Register> {
+    backup_registers().filter(|reg| {
+        !CONNECTION_REGISTERS.contains(&reg.number)
+            && !FACTORY_REGISTERS.contains(&reg.number)
+            && !reg.test_value
+    })
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BackupRegister {
+    pub number: u16,
+    /// Rohwert
+    pub value: u16,
+    /// Messwerteigenschaft, nur zur Information
+    pub description: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Backup {
+    pub version: u32,
+    /// Zeitpunkt der Sicherung (RFC 3339)
+    pub timestamp: String,
+    pub port: String,
+    pub modbus_address: u8,
+    /// Gerätekennung vom Werk, Rwreg 98
+    pub device_id: Option<u16>,
+    /// Softwaredatum, Rreg 49
+    pub software_date: Option<u16>,
+    pub registers: Vec<BackupRegister>,
+}
+
+/// Unterschied zwischen Sicherung und Sensor
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub number: u16,
+    /// Wert im Sensor, `None` wenn das Register nicht gelesen wurde
+    pub sensor: Option<u16>,
+    /// Wert in der Sicherung
+    pub backup: u16,
+}
+
+impl Change {
+    /// Prüft ob der gesicherte Wert geschrieben werden darf
+    ///
+    /// Der Wert muss im Wertebereich liegen und darf kein Kommando sein.
+    pub fn check(&self) -> error::Result<()> {
+        let reg = find_rwreg(self.number).ok_or(Error::UnknownRegister(self.number))?;
+        if COMMAND_VALUES.contains(&(self.number, self.backup)) {
+            return Err(Error::CommandValue {
+                register: self.number,
+                value: self.backup,
+            });
+        }
+        reg.check_raw(self.backup)
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = |raw: u16| match find_rwreg(self.number) {
+            Some(reg) => reg.raw_to_value(raw).to_string(),
+            None => raw.to_string(),
+        };
+        let sensor = self.sensor.map_or_else(|| "-".to_string(), value);
+        let description = find_rwreg(self.number).map_or("", |reg| reg.description);
+        write!(
+            f,
+            "Rwreg {}: {} → {} ({})",
+            self.number,
+            sensor,
+            value(self.backup),
+            description
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum BackupError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    /// Die Datei wurde mit einer neueren Version geschrieben
+    Version(u32),
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackupError::Io(e) => write!(f, "{}", e),
+            BackupError::Format(e) => write!(f, "Ungültige Sicherungsdatei: {}", e),
+            BackupError::Version(version) => write!(
+                f,
+                "Sicherungsdatei Version {} wird nicht unterstützt (maximal {})",
+                version, FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl From<io::Error> for BackupError {
+    fn from(e: io::Error) -> Self {
+        BackupError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for BackupError {
+    fn from(e: serde_json::Error) -> Self {
+        BackupError::Format(e)
+    }
+}
+
+impl Backup {
+    /// Neue Sicherung aus den gelesenen Registern
+    ///
+    /// `values` sind Paare aus Registernummer und Rohwert.
+    pub fn new(
+        port: &str,
+        modbus_address: u8,
+        software_date: Option<u16>,
+        values: &[(u16, u16)],
+    ) -> Self {
+        let registers = values
+            .iter()
+            .filter_map(|&(number, value)| {
+                let reg = backup_registers().find(|reg| reg.number == number)?;
+                Some(BackupRegister {
+                    number,
+                    value,
+                    description: reg.description.to_string(),
+                })
+            })
+            .collect();
+        Backup {
+            version: FORMAT_VERSION,
+            timestamp: Local::now().to_rfc3339(),
+            port: port.to_string(),
+            modbus_address,
+            device_id: register_value(values, 98),
+            software_date,
+            registers,
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, BackupError> {
+        let backup: Backup = serde_json::from_str(json)?;
+        if backup.version > FORMAT_VERSION {
+            return Err(BackupError::Version(backup.version));
+        }
+        Ok(backup)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Backup is always serializable")
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, BackupError> {
+        Backup::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), BackupError> {
+        fs::write(path, self.to_json())?;
+        Ok(())
+    }
+
+    /// Vorgeschlagener Dateiname, z.B. `ne4_247_12345_2020-08-20.json`
+    pub fn file_name(&self) -> String {
+        format!(
+            "ne4_{}_{}_{}.json",
+            self.modbus_address,
+            self.device_id.unwrap_or(0),
+            self.timestamp.get(..10).unwrap_or("")
+        )
+    }
+
+    /// Prüft ob die Sicherung von diesem Sensor stammt
+    ///
+    /// `device_id` ist die Geräte ID (Rwreg 98) des angeschlossenen Sensors.
+    pub fn check_device(&self, device_id: Option<u16>) -> error::Result<()> {
+        if self.device_id == device_id {
+            Ok(())
+        } else {
+            Err(Error::OtherDevice {
+                backup: self.device_id,
+                sensor: device_id,
+            })
+        }
+    }
+
+    /// Register die sich im Sensor von der Sicherung unterscheiden
+    ///
+    /// Nur die Register aus `restore_registers` werden verglichen.
+    pub fn diff(&self, sensor: &[(u16, u16)]) -> Vec<Change> {
+        self.registers
+            .iter()
+            .filter(|backup| restore_registers().any(|reg| reg.number == backup.number))
+            .filter_map(|backup| {
+                let sensor = register_value(sensor, backup.number);
+                if sensor == Some(backup.value) {
+                    None
+                } else {
+                    Some(Change {
+                        number: backup.number,
+                        sensor,
+                        backup: backup.value,
+                    })
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backup() -> Backup {
+        Backup::new(
+            "/dev/ttyUSB0",
+            247,
+            Some(9040),
+            &[(0, 7), (13, 1000), (20, 1), (50, 247), (98, 12345)],
+        )
+    }
+
+    #[test]
+    fn registers() {
+        assert!(backup_registers().all(|reg| reg.writable));
+        assert!(backup_registers().all(|reg| reg.number != 49));
+        assert!(backup_registers().any(|reg| reg.number == 50));
+        assert!(restore_registers().all(|reg| reg.number != 50));
+        for number in &[2, 3, 4, 42, 98, 99] {
+            assert!(backup_registers().any(|reg| reg.number == *number));
+            assert!(restore_registers().all(|reg| reg.number != *number));
+        }
+    }
+
+    #[test]
+    fn check() {
+        let change = |number, backup| Change {
+            number,
+            sensor: Some(0),
+            backup,
+        };
+        assert!(change(10, 812).check().is_ok());
+        match change(10, CALIBRATION_COMMAND).check() {
+            Err(Error::CommandValue { register: 10, .. }) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        match change(12, CALIBRATION_COMMAND).check() {
+            Err(Error::CommandValue { register: 12, .. }) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        match change(13, 10001).check() {
+            Err(Error::OutOfRange { register: 13, .. }) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn check_device() {
+        let backup = backup();
+        assert!(backup.check_device(Some(12345)).is_ok());
+        match backup.check_device(Some(4711)) {
+            Err(Error::OtherDevice {
+                backup: Some(12345),
+                sensor: Some(4711),
+            }) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(backup.check_device(None).is_err());
+    }
+
+    #[test]
+    fn new_skips_read_only() {
+        let backup = backup();
+        let numbers: Vec<u16> = backup.registers.iter().map(|reg| reg.number).collect();
+        assert_eq!(numbers, vec![0, 13, 50, 98]);
+        assert_eq!(backup.device_id, Some(12345));
+        assert_eq!(
+            backup.file_name().len(),
+            "ne4_247_12345_2020-08-20.json".len()
+        );
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let backup = backup();
+        assert_eq!(Backup::from_json(&backup.to_json()).unwrap(), backup);
+    }
+
+    #[test]
+    fn newer_version_rejected() {
+        let mut backup = backup();
+        backup.version = FORMAT_VERSION + 1;
+        match Backup::from_json(&backup.to_json()) {
+            Err(BackupError::Version(version)) => assert_eq!(version, FORMAT_VERSION + 1),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn diff() {
+        let backup = backup();
+        let changes = backup.diff(&[(0, 7), (13, 500), (50, 12), (98, 4711)]);
+        assert_eq!(
+            changes,
+            vec![Change {
+                number: 13,
+                sensor: Some(500),
+                backup: 1000
+            }]
+        );
+        assert_eq!(
+            changes[0].to_string(),
+            "Rwreg 13: 500 → 1000 (Sensorwert im Kalibrierpunkt (bei Endwert))"
+        );
+    }
+}
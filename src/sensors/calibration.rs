@@ -0,0 +1,261 @@
+/// Nullpunkt- und Messgasabgleich
+///
+/// Der Abgleich wird erst ausgelöst wenn der Messwert über eine einstellbare Zeit
+/// innerhalb einer Toleranz stabil ist. Vorher und nachher werden die
+/// Kalibrierpunkte in Rwreg 10 bis 13 gelesen.
+use super::ra_gas_ne4::{find_rwreg, register_value};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Lese/Schreib(Read/Write)-Register der Kalibrierpunkte
+pub const CALIBRATION_RWREGS: &[u16] = &[10, 11, 12, 13];
+/// Befehlswert für Nullpunkt und Messgas
+pub const CALIBRATION_COMMAND: u16 = 11111;
+/// Rreg mit dem AD-Wert des Sensors, dieser Wert wird beim Abgleich übernommen
+pub const CALIBRATION_RREG: u16 = 42;
+
+/// Vorgabe der Toleranz in AD Werten
+pub const DEFAULT_TOLERANCE: u16 = 10;
+/// Vorgabe der Zeit die der Messwert stabil sein muss
+pub const DEFAULT_STABLE_TIME: Duration = Duration::from_secs(30);
+
+/// Schritt des Abgleichs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CalibrationStep {
+    /// Nullpunkt mit Nullgas (saubere Luft), Rwreg 10
+    Zero,
+    /// Kalibrierpunkt mit Prüfgas der angegebenen Konzentration in ppm, Rwreg 12 und 13
+    Span { concentration: u16 },
+}
+
+impl CalibrationStep {
+    /// Register in das der Befehlswert geschrieben wird
+    pub fn register(&self) -> u16 {
+        match self {
+            CalibrationStep::Zero => 10,
+            CalibrationStep::Span { .. } => 12,
+        }
+    }
+
+    /// Anweisung für den Techniker
+    pub fn instruction(&self) -> String {
+        match self {
+            CalibrationStep::Zero => {
+                "Nullgas (saubere Luft) auf den Sensor geben und warten bis der Messwert stabil ist."
+                    .to_string()
+            }
+            CalibrationStep::Span { concentration } => format!(
+                "Prüfgas mit {} ppm auf den Sensor geben und warten bis der Messwert stabil ist.",
+                concentration
+            ),
+        }
+    }
+}
+
+impl fmt::Display for CalibrationStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CalibrationStep::Zero => write!(f, "Nullpunkt"),
+            CalibrationStep::Span { .. } => write!(f, "Messgas"),
+        }
+    }
+}
+
+/// Kalibrierpunkte, Rwreg 10 bis 13
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct CalibrationPoints {
+    /// Sensorspannung im Nullpunkt, Rwreg 10
+    pub zero_adc: u16,
+    /// Sensorwert im Nullpunkt in ppm, Rwreg 11
+    pub zero_ppm: u16,
+    /// Sensorspannung im Kalibrierpunkt, Rwreg 12
+    pub span_adc: u16,
+    /// Sensorwert im Kalibrierpunkt in ppm, Rwreg 13
+    pub span_ppm: u16,
+}
+
+impl CalibrationPoints {
+    /// Kalibrierpunkte aus Rwreg 10 bis 13, `None` wenn eines fehlt
+    pub fn from_registers(rwregs: &[(u16, u16)]) -> Option<Self> {
+        Some(CalibrationPoints {
+            zero_adc: register_value(rwregs, 10)?,
+            zero_ppm: register_value(rwregs, 11)?,
+            span_adc: register_value(rwregs, 12)?,
+            span_ppm: register_value(rwregs, 13)?,
+        })
+    }
+
+    /// Registernummer und Rohwert
+    pub fn registers(&self) -> [(u16, u16); 4] {
+        [
+            (10, self.zero_adc),
+            (11, self.zero_ppm),
+            (12, self.span_adc),
+            (13, self.span_ppm),
+        ]
+    }
+
+    /// Vergleich mit den neuen Kalibrierpunkten
+    ///
+    /// Liefert Registernummer, Beschreibung, alten und neuen Wert.
+    pub fn compare(&self, new: &CalibrationPoints) -> Vec<(u16, &'static str, u16, u16)> {
+        self.registers()
+            .iter()
+            .zip(new.registers().iter())
+            .map(|((number, old), (_, new))| {
+                let description = find_rwreg(*number).map_or("", |reg| reg.description);
+                (*number, description, *old, *new)
+            })
+            .collect()
+    }
+}
+
+/// Überwacht ob ein Messwert stabil ist
+///
+/// Der Messwert ist stabil wenn alle Werte der letzten `stable_time` höchstens
+/// `tolerance` auseinander liegen.
+#[derive(Debug)]
+pub struct StabilityMonitor {
+    tolerance: f64,
+    stable_time: Duration,
+    samples: VecDeque<(Instant, f64)>,
+}
+
+impl StabilityMonitor {
+    pub fn new(tolerance: f64, stable_time: Duration) -> Self {
+        StabilityMonitor {
+            tolerance,
+            stable_time,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Messwert anhängen, nicht mehr benötigte Werte werden verworfen
+    pub fn push(&mut self, time: Instant, value: f64) {
+        self.samples.push_back((time, value));
+        // Der älteste Wert vor dem Zeitfenster wird behalten, damit das Fenster
+        // vollständig abgedeckt ist
+        while self.samples.len() > 1 {
+            match time.checked_duration_since(self.samples[1].0) {
+                Some(age) if age >= self.stable_time => {
+                    self.samples.pop_front();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Zeit seit der der Messwert ohne Unterbrechung innerhalb der Toleranz liegt
+    pub fn stable_for(&self) -> Duration {
+        let (newest, value) = match self.samples.back() {
+            Some(sample) => *sample,
+            None => return Duration::from_secs(0),
+        };
+        let (mut min, mut max) = (value, value);
+        let mut since = newest;
+        for (time, value) in self.samples.iter().rev() {
+            min = f64::min(min, *value);
+            max = f64::max(max, *value);
+            if max - min > self.tolerance {
+                break;
+            }
+            since = *time;
+        }
+        newest.checked_duration_since(since).unwrap_or_default()
+    }
+
+    /// Fortschritt bis zum stabilen Messwert, 0.0 … 1.0
+    pub fn progress(&self) -> f64 {
+        if self.stable_time == Duration::from_secs(0) {
+            return if self.samples.is_empty() { 0.0 } else { 1.0 };
+        }
+        (self.stable_for().as_secs_f64() / self.stable_time.as_secs_f64()).min(1.0)
+    }
+
+    pub fn is_stable(&self) -> bool {
+        !self.samples.is_empty() && self.stable_for() >= self.stable_time
+    }
+
+    pub fn stable_time(&self) -> Duration {
+        self.stable_time
+    }
+
+    /// Von vorne beginnen, z.B. nach einem Abbruch
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor_with(values: &[f64]) -> StabilityMonitor {
+        let start = Instant::now();
+        let mut monitor = StabilityMonitor::new(5.0, Duration::from_secs(10));
+        for (i, value) in values.iter().enumerate() {
+            monitor.push(start + Duration::from_secs(i as u64), *value);
+        }
+        monitor
+    }
+
+    #[test]
+    fn stable_after_time() {
+        let monitor = monitor_with(&[800.0; 10]);
+        assert_eq!(monitor.stable_for(), Duration::from_secs(9));
+        assert!(!monitor.is_stable());
+        assert_eq!(monitor.progress(), 0.9);
+
+        let monitor = monitor_with(&[800.0; 11]);
+        assert!(monitor.is_stable());
+        assert_eq!(monitor.progress(), 1.0);
+    }
+
+    #[test]
+    fn unstable_value_restarts() {
+        let mut values = vec![800.0; 20];
+        values.extend(&[900.0, 902.0, 898.0, 903.0]);
+        let monitor = monitor_with(&values);
+        // 898 … 903 liegt noch in der Toleranz, 900 … 902 ist drei Sekunden alt
+        assert_eq!(monitor.stable_for(), Duration::from_secs(3));
+        assert!(!monitor.is_stable());
+        assert!(monitor.samples.len() <= 12);
+    }
+
+    #[test]
+    fn empty_and_reset() {
+        let mut monitor = monitor_with(&[]);
+        assert!(!monitor.is_stable());
+        assert_eq!(monitor.progress(), 0.0);
+        monitor.push(Instant::now(), 1.0);
+        monitor.reset();
+        assert_eq!(monitor.stable_for(), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn calibration_points() {
+        let old = CalibrationPoints::from_registers(&[(10, 800), (11, 0), (12, 4800), (13, 1000)])
+            .unwrap();
+        let new = CalibrationPoints {
+            zero_adc: 812,
+            ..old
+        };
+        let compare = old.compare(&new);
+        assert_eq!(compare[0], (10, "Sensorspannung im Nullpunkt", 800, 812));
+        assert_eq!(compare[3].0, 13);
+        assert_eq!(compare[3].2, compare[3].3);
+    }
+
+    #[test]
+    fn step() {
+        assert_eq!(CalibrationStep::Zero.register(), 10);
+        let span = CalibrationStep::Span { concentration: 500 };
+        assert_eq!(span.register(), 12);
+        assert_eq!(span.to_string(), "Messgas");
+        assert!(span.instruction().contains("500 ppm"));
+    }
+}
@@ -0,0 +1,373 @@
+/// Kalibrierprotokoll
+///
+/// Ein Protokoll enthält die Kennung des Sensors, den Prüfer und für jeden
+/// Abgleich die AD-Werte und Kalibrierpunkte vorher und nachher. Es wird als
+/// druckbarer HTML Bericht und als JSON Datei gespeichert.
+use super::calibration::{CalibrationPoints, CalibrationStep, CALIBRATION_RREG};
+use super::device_info::DeviceInfo;
+use super::ra_gas_ne4::{find_rreg, find_rwreg, register_value, WorkingMode};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Write, fs, io, path::Path};
+
+/// Version des Dateiformats
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Rreg mit dem korrigierten AD-Wert des Sensors
+pub const CORRECTED_ADC_RREG: u16 = 45;
+
+/// Zustand des Sensors zu einem Zeitpunkt des Abgleichs
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CalibrationSnapshot {
+    /// Zeitpunkt (RFC 3339)
+    pub timestamp: String,
+    /// AD-Wert des Sensors, Rreg 42
+    pub sensor_adc: u16,
+    /// Korrigierter AD-Wert des Sensors, Rreg 45
+    pub corrected_adc: u16,
+    /// Kalibrierpunkte, Rwreg 10 bis 13
+    pub points: CalibrationPoints,
+}
+
+impl CalibrationSnapshot {
+    /// Zustand aus gelesenen Rreg's und Rwreg's, `None` wenn eines fehlt
+    pub fn from_registers(
+        timestamp: DateTime<Local>,
+        rregs: &[(u16, u16)],
+        rwregs: &[(u16, u16)],
+    ) -> Option<Self> {
+        Some(CalibrationSnapshot {
+            timestamp: timestamp.to_rfc3339(),
+            sensor_adc: register_value(rregs, CALIBRATION_RREG)?,
+            corrected_adc: register_value(rregs, CORRECTED_ADC_RREG)?,
+            points: CalibrationPoints::from_registers(rwregs)?,
+        })
+    }
+}
+
+/// Ein Nullpunkt- oder Messgasabgleich
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CalibrationRecord {
+    /// "Nullpunkt" oder "Messgas"
+    pub step: String,
+    /// Konzentration des Prüfgases in ppm, nur beim Messgas
+    pub test_gas_ppm: Option<u16>,
+    pub before: CalibrationSnapshot,
+    pub after: CalibrationSnapshot,
+}
+
+impl CalibrationRecord {
+    pub fn new(
+        step: CalibrationStep,
+        before: CalibrationSnapshot,
+        after: CalibrationSnapshot,
+    ) -> Self {
+        CalibrationRecord {
+            step: step.to_string(),
+            test_gas_ppm: match step {
+                CalibrationStep::Zero => None,
+                CalibrationStep::Span { concentration } => Some(concentration),
+            },
+            before,
+            after,
+        }
+    }
+}
+
+/// Protokoll einer Kalibrierung mit einem oder mehreren Abgleichen
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CalibrationReport {
+    pub version: u32,
+    /// Name des Prüfers
+    pub operator: String,
+    pub modbus_address: u8,
+    /// Gerätekennung vom Werk, Rwreg 98
+    pub device_id: Option<u16>,
+    /// Kundencode, Rreg 0
+    pub customer_code: Option<u16>,
+    /// Arbeitsweise des Sensors, Rreg 1
+    pub working_mode: Option<u16>,
+    /// Beginn der Kalibrierung (RFC 3339)
+    pub started: String,
+    /// Ende des letzten Abgleichs (RFC 3339)
+    pub finished: Option<String>,
+    pub records: Vec<CalibrationRecord>,
+}
+
+impl CalibrationReport {
+    pub fn new(
+        operator: &str,
+        modbus_address: u8,
+        device_info: &DeviceInfo,
+        started: DateTime<Local>,
+    ) -> Self {
+        CalibrationReport {
+            version: FORMAT_VERSION,
+            operator: operator.to_string(),
+            modbus_address,
+            device_id: device_info.device_id,
+            customer_code: device_info.customer_code,
+            working_mode: device_info.working_mode.map(WorkingMode::code),
+            started: started.to_rfc3339(),
+            finished: None,
+            records: vec![],
+        }
+    }
+
+    /// Abgleich anhängen
+    pub fn push(&mut self, record: CalibrationRecord) {
+        self.finished = Some(record.after.timestamp.clone());
+        self.records.push(record);
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Calibration report is always serializable")
+    }
+
+    /// Druckbarer HTML Bericht
+    pub fn to_html(&self) -> String {
+        fn optional<T: ToString>(value: Option<T>) -> String {
+            value.map_or_else(|| "-".to_string(), |value| value.to_string())
+        }
+
+        let mut html = String::new();
+        let title = format!("Kalibrierprotokoll NE4 {}", optional(self.device_id));
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"de\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+            escape(&title),
+            STYLE,
+            escape(&title)
+        );
+
+        html.push_str("<table class=\"info\">\n");
+        for (title, value) in &[
+            ("Gerätekennung vom Werk", optional(self.device_id)),
+            ("Kundencode", optional(self.customer_code)),
+            (
+                "Arbeitsweise",
+                optional(self.working_mode.map(WorkingMode::from)),
+            ),
+            ("Modbus Adresse", self.modbus_address.to_string()),
+            ("Prüfer", self.operator.clone()),
+            ("Beginn", format_timestamp(&self.started)),
+            (
+                "Ende",
+                self.finished
+                    .as_ref()
+                    .map_or_else(|| "-".to_string(), |t| format_timestamp(t)),
+            ),
+        ] {
+            let _ = writeln!(
+                html,
+                "<tr><th>{}</th><td>{}</td></tr>",
+                escape(title),
+                escape(value)
+            );
+        }
+        html.push_str("</table>\n");
+
+        for record in &self.records {
+            let _ = writeln!(html, "<h2>{}</h2>", escape(&record.step));
+            if let Some(ppm) = record.test_gas_ppm {
+                let _ = writeln!(html, "<p>Prüfgaskonzentration: {} ppm</p>", ppm);
+            }
+            html.push_str(
+                "<table class=\"values\">\n<tr><th></th><th>Vorher</th><th>Nachher</th></tr>\n",
+            );
+            let mut rows = vec![
+                (
+                    "Zeitpunkt".to_string(),
+                    format_timestamp(&record.before.timestamp),
+                    format_timestamp(&record.after.timestamp),
+                ),
+                (
+                    rreg_title(CALIBRATION_RREG),
+                    record.before.sensor_adc.to_string(),
+                    record.after.sensor_adc.to_string(),
+                ),
+                (
+                    rreg_title(CORRECTED_ADC_RREG),
+                    record.before.corrected_adc.to_string(),
+                    record.after.corrected_adc.to_string(),
+                ),
+            ];
+            for ((number, before), (_, after)) in record
+                .before
+                .points
+                .registers()
+                .iter()
+                .zip(record.after.points.registers().iter())
+            {
+                let description = find_rwreg(*number).map_or("", |reg| reg.description);
+                rows.push((
+                    format!("Rwreg {}: {}", number, description),
+                    before.to_string(),
+                    after.to_string(),
+                ));
+            }
+            for (title, before, after) in rows {
+                let class = if before != after {
+                    " class=\"changed\""
+                } else {
+                    ""
+                };
+                let _ = writeln!(
+                    html,
+                    "<tr{}><th>{}</th><td>{}</td><td>{}</td></tr>",
+                    class,
+                    escape(&title),
+                    escape(&before),
+                    escape(&after)
+                );
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("<p class=\"signature\">Datum, Unterschrift Prüfer</p>\n</body>\n</html>\n");
+        html
+    }
+
+    /// JSON Datei und HTML Bericht speichern
+    ///
+    /// `path` ist der HTML Bericht, die JSON Datei bekommt den gleichen Namen mit
+    /// der Endung `.json`. Liefert den Pfad der JSON Datei.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<std::path::PathBuf> {
+        let path = path.as_ref();
+        let json_path = path.with_extension("json");
+        fs::write(path, self.to_html())?;
+        fs::write(&json_path, self.to_json())?;
+        Ok(json_path)
+    }
+
+    /// Vorgeschlagener Dateiname, z.B. `kalibrierprotokoll_12345_2020-08-20.html`
+    pub fn file_name(&self) -> String {
+        format!(
+            "kalibrierprotokoll_{}_{}.html",
+            self.device_id.unwrap_or(0),
+            self.started.get(..10).unwrap_or("")
+        )
+    }
+}
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { border: 1px solid #999; padding: 0.3em 0.8em; text-align: left; }
+td { text-align: right; }
+table.info td { text-align: left; }
+tr.changed td { font-weight: bold; }
+p.signature { margin-top: 4em; border-top: 1px solid #000; width: 20em; }
+";
+
+/// Beschriftung eines Rreg, z.B. "Rreg 42: AD-Wert des Sensors"
+fn rreg_title(number: u16) -> String {
+    format!(
+        "Rreg {}: {}",
+        number,
+        find_rreg(number).map_or("", |reg| reg.description)
+    )
+}
+
+/// Zeitstempel für den Bericht, z.B. "20.08.2020 14:30:00"
+fn format_timestamp(timestamp: &str) -> String {
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(timestamp) => timestamp.format("%d.%m.%Y %H:%M:%S").to_string(),
+        Err(_) => timestamp.to_string(),
+    }
+}
+
+/// Sonderzeichen für HTML maskieren
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn snapshot(hour: u32, sensor_adc: u16, zero_adc: u16) -> CalibrationSnapshot {
+        CalibrationSnapshot::from_registers(
+            Local.ymd(2020, 8, 20).and_hms(hour, 0, 0),
+            &[(42, sensor_adc), (45, sensor_adc)],
+            &[(10, zero_adc), (11, 0), (12, 4800), (13, 1000)],
+        )
+        .unwrap()
+    }
+
+    fn report() -> CalibrationReport {
+        let device_info = DeviceInfo {
+            customer_code: Some(4711),
+            working_mode: Some(WorkingMode::from(10)),
+            device_id: Some(12345),
+            ..DeviceInfo::default()
+        };
+        let mut report = CalibrationReport::new(
+            "Max <Muster> & Co",
+            247,
+            &device_info,
+            Local.ymd(2020, 8, 20).and_hms(9, 0, 0),
+        );
+        report.push(CalibrationRecord::new(
+            CalibrationStep::Zero,
+            snapshot(10, 812, 800),
+            snapshot(11, 812, 812),
+        ));
+        report
+    }
+
+    #[test]
+    fn snapshot_from_registers() {
+        let snapshot = snapshot(10, 812, 800);
+        assert_eq!(snapshot.sensor_adc, 812);
+        assert_eq!(snapshot.points.zero_adc, 800);
+        assert_eq!(
+            CalibrationSnapshot::from_registers(Local::now(), &[(42, 1)], &[]),
+            None
+        );
+    }
+
+    #[test]
+    fn json() {
+        let report = report();
+        assert_eq!(
+            report.finished,
+            Some(report.records[0].after.timestamp.clone())
+        );
+        assert_eq!(report.working_mode, Some(10));
+        let json = report.to_json();
+        let read: CalibrationReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, report);
+        assert!(json.contains("\"zero_adc\": 812"));
+        assert_eq!(
+            report.file_name(),
+            "kalibrierprotokoll_12345_2020-08-20.html"
+        );
+    }
+
+    #[test]
+    fn html() {
+        let html = report().to_html();
+        assert!(html.contains("<title>Kalibrierprotokoll NE4 12345</title>"));
+        assert!(html.contains("<td>Max &lt;Muster&gt; &amp; Co</td>"));
+        assert!(html.contains("<td>CO 1000 ppm</td>"));
+        assert!(html.contains("<h2>Nullpunkt</h2>"));
+        assert!(!html.contains("Prüfgaskonzentration"));
+        assert!(html.contains(
+            "<tr class=\"changed\"><th>Rwreg 10: Sensorspannung im Nullpunkt</th><td>800</td><td>812</td></tr>"
+        ));
+        assert!(html.contains("<td>20.08.2020 10:00:00</td><td>20.08.2020 11:00:00</td>"));
+    }
+}
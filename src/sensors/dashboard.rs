@@ -0,0 +1,307 @@
+use super::ra_gas_ne4::{WorkingMode, NE4};
+use crate::error::Result;
+/// Übersicht mehrerer Sensoren an einer Leitung
+///
+/// Die Sensoren der Übersicht werden reihum über die gemeinsame Modbus Sitzung
+/// abgefragt. Pro Sensor werden nur Arbeitsweise, Gaskonzentration und
+/// Ausgangsstrom gelesen (Rreg 1 bis 3), damit ein Umlauf auch bei vielen
+/// Sensoren kurz bleibt.
+use chrono::{DateTime, Local};
+use std::fmt;
+
+/// Erstes Rreg der Übersicht, Arbeitsweise
+pub const DASHBOARD_RREG_START: u16 = 1;
+/// Anzahl der Rreg der Übersicht, Arbeitsweise, Gaskonzentration und Ausgangsstrom
+pub const DASHBOARD_RREG_COUNT: u16 = 3;
+/// Nach so vielen Fehlern in Folge gilt ein Sensor als nicht erreichbar
+pub const OFFLINE_ERRORS: u32 = 3;
+
+/// Kommunikationsstatus eines Sensors der Übersicht
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommStatus {
+    /// Noch nicht abgefragt
+    Waiting,
+    /// Letzte Abfrage erfolgreich
+    Ok,
+    /// Die letzten `count` Abfragen sind fehlgeschlagen
+    Error { message: String, count: u32 },
+}
+
+impl CommStatus {
+    /// Der Sensor hat mehrmals in Folge nicht geantwortet
+    pub fn is_offline(&self) -> bool {
+        match self {
+            CommStatus::Error { count, .. } => *count >= OFFLINE_ERRORS,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for CommStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommStatus::Waiting => write!(f, "Warte auf Abfrage"),
+            CommStatus::Ok => write!(f, "OK"),
+            CommStatus::Error { message, .. } if self.is_offline() => {
+                write!(f, "Nicht erreichbar: {}", message)
+            }
+            CommStatus::Error { message, count } => write!(f, "Fehler ({}): {}", count, message),
+        }
+    }
+}
+
+/// Ein Sensor der Übersicht
+#[derive(Clone, Debug)]
+pub struct DashboardSensor {
+    pub modbus_address: u8,
+    /// Werte der letzten erfolgreichen Abfrage
+    pub ne4: Option<NE4>,
+    pub status: CommStatus,
+    /// Zeitpunkt der letzten erfolgreichen Abfrage
+    pub last_seen: Option<DateTime<Local>>,
+}
+
+impl DashboardSensor {
+    pub fn new(modbus_address: u8) -> Self {
+        DashboardSensor {
+            modbus_address,
+            ne4: None,
+            status: CommStatus::Waiting,
+            last_seen: None,
+        }
+    }
+
+    /// Ergebnis einer Abfrage übernehmen
+    ///
+    /// Bei einem Fehler bleiben die Werte der letzten erfolgreichen Abfrage
+    /// erhalten.
+    pub fn update(&mut self, time: DateTime<Local>, result: Result<NE4>) {
+        match result {
+            Ok(ne4) => {
+                self.ne4 = Some(ne4);
+                self.status = CommStatus::Ok;
+                self.last_seen = Some(time);
+            }
+            Err(e) => {
+                let count = match &self.status {
+                    CommStatus::Error { count, .. } => count + 1,
+                    _ => 1,
+                };
+                self.status = CommStatus::Error {
+                    message: e.to_string(),
+                    count,
+                };
+            }
+        }
+    }
+
+    /// Gaskonzentration mit Einheit, "-" ohne Messwert
+    pub fn concentration(&self) -> String {
+        match &self.ne4 {
+            Some(ne4) => match ne4.work_mode().gas() {
+                Some(gas) => gas.format_value(ne4.concentration_gas()),
+                None => ne4.concentration_gas().to_string(),
+            },
+            None => "-".to_string(),
+        }
+    }
+
+    /// Ausgangsstrom in mA, "-" ohne Messwert
+    pub fn output_current(&self) -> String {
+        self.ne4.as_ref().map_or_else(
+            || "-".to_string(),
+            |ne4| format!("{:.02} mA", ne4.output_current()),
+        )
+    }
+
+    /// Arbeitsweise, `None` ohne Messwert
+    pub fn working_mode(&self) -> Option<WorkingMode> {
+        self.ne4.as_ref().map(|ne4| ne4.work_mode())
+    }
+
+    /// Gaskonzentration über dem Messbereichsendwert
+    pub fn is_over_range(&self) -> bool {
+        self.ne4.as_ref().map_or(false, |ne4| {
+            ne4.work_mode()
+                .gas()
+                .map_or(false, |gas| !gas.contains(ne4.concentration_gas()))
+        })
+    }
+}
+
+/// Sensoren der Übersicht, in der Reihenfolge der Abfrage
+#[derive(Clone, Debug, Default)]
+pub struct Dashboard {
+    sensors: Vec<DashboardSensor>,
+}
+
+impl Dashboard {
+    pub fn new() -> Self {
+        Dashboard::default()
+    }
+
+    /// Sensor hinzufügen
+    ///
+    /// Modbus Adressen außerhalb 1 … 247 und doppelte Adressen werden abgelehnt.
+    pub fn add(&mut self, modbus_address: u8) -> std::result::Result<(), String> {
+        if modbus_address < 1 || modbus_address > 247 {
+            return Err(format!("Ungültige Modbus Adresse: {}", modbus_address));
+        }
+        if self.sensor(modbus_address).is_some() {
+            return Err(format!(
+                "Modbus Adresse {} ist bereits in der Übersicht",
+                modbus_address
+            ));
+        }
+        self.sensors.push(DashboardSensor::new(modbus_address));
+        Ok(())
+    }
+
+    pub fn remove(&mut self, modbus_address: u8) {
+        self.sensors
+            .retain(|sensor| sensor.modbus_address != modbus_address);
+    }
+
+    pub fn sensor(&self, modbus_address: u8) -> Option<&DashboardSensor> {
+        self.sensors
+            .iter()
+            .find(|sensor| sensor.modbus_address == modbus_address)
+    }
+
+    pub fn sensors(&self) -> &[DashboardSensor] {
+        &self.sensors
+    }
+
+    /// Modbus Adressen in der Reihenfolge der Abfrage
+    pub fn addresses(&self) -> Vec<u8> {
+        self.sensors
+            .iter()
+            .map(|sensor| sensor.modbus_address)
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sensors.is_empty()
+    }
+
+    /// Ergebnis einer Abfrage übernehmen, `None` wenn der Sensor nicht (mehr) in
+    /// der Übersicht ist
+    pub fn update(
+        &mut self,
+        modbus_address: u8,
+        time: DateTime<Local>,
+        result: Result<NE4>,
+    ) -> Option<&DashboardSensor> {
+        let sensor = self
+            .sensors
+            .iter_mut()
+            .find(|sensor| sensor.modbus_address == modbus_address)?;
+        sensor.update(time, result);
+        Some(sensor)
+    }
+
+    /// Alle Werte verwerfen, z.B. nach dem Trennen der Verbindung
+    pub fn reset(&mut self) {
+        for sensor in &mut self.sensors {
+            *sensor = DashboardSensor::new(sensor.modbus_address);
+        }
+    }
+}
+
+/// Modbus Adressen aus einer Eingabe wie "1, 5, 10-12"
+pub fn parse_addresses(text: &str) -> std::result::Result<Vec<u8>, String> {
+    let mut addresses = vec![];
+    for part in text.split(|c| c == ',' || c == ';' || char::is_whitespace(c)) {
+        if part.is_empty() {
+            continue;
+        }
+        let invalid = || format!("Ungültige Modbus Adresse: {}", part);
+        let (first, last) = match part.find('-') {
+            Some(i) => (&part[..i], &part[i + 1..]),
+            None => (part, part),
+        };
+        let first: u8 = first.trim().parse().map_err(|_| invalid())?;
+        let last: u8 = last.trim().parse().map_err(|_| invalid())?;
+        if first < 1 || last > 247 || first > last {
+            return Err(invalid());
+        }
+        addresses.extend(first..=last);
+    }
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use chrono::TimeZone;
+
+    fn ne4(working_mode: u16, concentration: u16) -> NE4 {
+        let mut ne4 = NE4::new();
+        ne4.set_rregs(&[0, working_mode, concentration, 1200]);
+        ne4
+    }
+
+    #[test]
+    fn add_and_remove() {
+        let mut dashboard = Dashboard::new();
+        assert!(dashboard.add(5).is_ok());
+        assert!(dashboard.add(1).is_ok());
+        assert!(dashboard.add(5).is_err());
+        assert!(dashboard.add(0).is_err());
+        assert!(dashboard.add(248).is_err());
+        assert_eq!(dashboard.addresses(), vec![5, 1]);
+        dashboard.remove(5);
+        assert_eq!(dashboard.addresses(), vec![1]);
+        assert!(dashboard.update(5, Local::now(), Ok(NE4::new())).is_none());
+    }
+
+    #[test]
+    fn update_status() {
+        let mut dashboard = Dashboard::new();
+        dashboard.add(3).unwrap();
+        assert_eq!(dashboard.sensor(3).unwrap().status, CommStatus::Waiting);
+        assert_eq!(dashboard.sensor(3).unwrap().concentration(), "-");
+
+        let time = Local.ymd(2020, 9, 1).and_hms(10, 0, 0);
+        let sensor = dashboard.update(3, time, Ok(ne4(30, 12))).unwrap();
+        assert_eq!(sensor.status, CommStatus::Ok);
+        assert_eq!(sensor.concentration(), "12 ppm");
+        assert_eq!(sensor.output_current(), "12.00 mA");
+        assert_eq!(sensor.working_mode(), Some(WorkingMode::from(30)));
+        assert!(!sensor.is_over_range());
+
+        for count in 1..=OFFLINE_ERRORS {
+            let sensor = dashboard
+                .update(3, Local::now(), Err(Error::Timeout))
+                .unwrap();
+            assert_eq!(sensor.status.is_offline(), count == OFFLINE_ERRORS);
+        }
+        let sensor = dashboard.sensor(3).unwrap();
+        assert_eq!(
+            sensor.status.to_string(),
+            "Nicht erreichbar: Der Sensor antwortet nicht (Zeitüberschreitung)"
+        );
+        // Die letzten Werte bleiben erhalten
+        assert_eq!(sensor.concentration(), "12 ppm");
+        assert_eq!(sensor.last_seen, Some(time));
+
+        dashboard.update(3, Local::now(), Ok(ne4(30, 25)));
+        assert!(dashboard.sensor(3).unwrap().is_over_range());
+        dashboard.reset();
+        assert_eq!(dashboard.sensor(3).unwrap().status, CommStatus::Waiting);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_addresses("1, 5 10-12;247"),
+            Ok(vec![1, 5, 10, 11, 12, 247])
+        );
+        assert_eq!(parse_addresses(""), Ok(vec![]));
+        assert!(parse_addresses("0").is_err());
+        assert!(parse_addresses("12-10").is_err());
+        assert!(parse_addresses("5-300").is_err());
+        assert!(parse_addresses("a").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+/// Geräteinformationen eines NE4
+///
+/// Kennung, Arbeitsweise, Softwarestand und Lebensdauerzähler aus den Lese(Read)-
+/// und Lese/Schreib(Read/Write)-Registern.
+use super::ra_gas_ne4::{register_value, WorkingMode, NE4};
+use chrono::NaiveDate;
+
+/// Lese(Read)-Register der Geräteinformation: Kundencode, Arbeitsweise, Softwaredatum
+pub const DEVICE_INFO_RREGS: &[u16] = &[0, 1, 49];
+/// Lese/Schreib(Read/Write)-Register der Geräteinformation: Einschaltzähler,
+/// Betriebsstunden, Gerätekennung und Arbeitsweise vom Werk
+pub const DEVICE_INFO_RWREGS: &[u16] = &[96, 97, 98, 99];
+
+/// Softwaredatum (Rreg 49) dekodieren
+///
+/// Das Datum ist als `TTMMJ` abgelegt, die Jahresziffer zählt ab 2020. 9040 ist
+/// also der 09.04.2020, der größte Wert 31129 der 31.12.2029. `None` wenn der Wert
+/// kein gültiges Datum ist.
+pub fn decode_software_date(raw: u16) -> Option<NaiveDate> {
+    let day = u32::from(raw / 1000);
+    let month = u32::from(raw / 10 % 100);
+    let year = 2020 + i32::from(raw % 10);
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+/// Geräteinformationen, nicht gelesene Register sind `None`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeviceInfo {
+    /// Kundencode, Rreg 0 (entspricht Rwreg 0)
+    pub customer_code: Option<u16>,
+    /// Arbeitsweise des Sensors, Rreg 1
+    pub working_mode: Option<WorkingMode>,
+    /// Softwaredatum als Rohwert, Rreg 49
+    pub software_date: Option<u16>,
+    /// Einschaltzähler, Rwreg 96
+    pub power_on_count: Option<u16>,
+    /// Betriebsstunden, Rwreg 97
+    pub operating_hours: Option<u16>,
+    /// Gerätekennung vom Werk, Rwreg 98
+    pub device_id: Option<u16>,
+    /// Arbeitsweise vom Werk, Rwreg 99
+    pub factory_working_mode: Option<WorkingMode>,
+}
+
+impl DeviceInfo {
+    /// Geräteinformation aus gelesenen Rreg's und Rwreg's
+    pub fn from_registers(rregs: &[(u16, u16)], rwregs: &[(u16, u16)]) -> Self {
+        DeviceInfo {
+            customer_code: register_value(rregs, 0),
+            working_mode: register_value(rregs, 1).map(WorkingMode::from),
+            software_date: register_value(rregs, 49),
+            power_on_count: register_value(rwregs, 96),
+            operating_hours: register_value(rwregs, 97),
+            device_id: register_value(rwregs, 98),
+            factory_working_mode: register_value(rwregs, 99).map(WorkingMode::from),
+        }
+    }
+
+    /// Geräteinformation aus den zuletzt abgefragten Registern
+    ///
+    /// Register die seit dem Verbinden noch nicht (vollständig) gelesen wurden
+    /// sind `None` und werden als "-" angezeigt, nicht als 0.
+    pub fn from_ne4(ne4: &NE4) -> Self {
+        DeviceInfo {
+            customer_code: ne4.rreg(0),
+            working_mode: ne4.rreg(1).map(WorkingMode::from),
+            software_date: ne4.rreg(49),
+            power_on_count: ne4.rwreg(96),
+            operating_hours: ne4.rwreg(97),
+            device_id: ne4.rwreg(98),
+            factory_working_mode: ne4.rwreg(99).map(WorkingMode::from),
+        }
+    }
+
+    /// Dekodiertes Softwaredatum
+    pub fn software_date_decoded(&self) -> Option<NaiveDate> {
+        self.software_date.and_then(decode_software_date)
+    }
+
+    /// Beschriftung und Wert für die Anzeige, nicht gelesene Werte als "-"
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        fn optional<T: ToString>(value: Option<T>) -> String {
+            value.map_or_else(|| "-".to_string(), |value| value.to_string())
+        }
+        let software_date = match (self.software_date, self.software_date_decoded()) {
+            (Some(raw), Some(date)) => format!("{} ({})", date.format("%d.%m.%Y"), raw),
+            (Some(raw), None) => format!("ungültig ({})", raw),
+            (None, _) => "-".to_string(),
+        };
+        vec![
+            ("Kundencode", optional(self.customer_code)),
+            ("Arbeitsweise", optional(self.working_mode)),
+            ("Gerätekennung vom Werk", optional(self.device_id)),
+            ("Arbeitsweise vom Werk", optional(self.factory_working_mode)),
+            ("Softwaredatum", software_date),
+            ("Einschaltzähler", optional(self.power_on_count)),
+            (
+                "Betriebsstunden",
+                self.operating_hours
+                    .map_or_else(|| "-".to_string(), |hours| format!("{} h", hours)),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn software_date() {
+        assert_eq!(
+            decode_software_date(9040),
+            Some(NaiveDate::from_ymd(2020, 4, 9))
+        );
+        assert_eq!(
+            decode_software_date(31129),
+            Some(NaiveDate::from_ymd(2029, 12, 31))
+        );
+        assert_eq!(decode_software_date(0), None);
+        assert_eq!(decode_software_date(31020), None);
+    }
+
+    #[test]
+    fn from_registers() {
+        let info = DeviceInfo::from_registers(
+            &[(0, 4711), (1, 30), (49, 9040)],
+            &[(96, 12), (97, 345), (98, 12345)],
+        );
+        assert_eq!(info.customer_code, Some(4711));
+        assert_eq!(info.working_mode, Some(WorkingMode::from(30)));
+        assert_eq!(info.device_id, Some(12345));
+        assert_eq!(info.factory_working_mode, None);
+        assert_eq!(
+            info.rows(),
+            vec![
+                ("Kundencode", "4711".to_string()),
+                ("Arbeitsweise", "NO2 20 ppm".to_string()),
+                ("Gerätekennung vom Werk", "12345".to_string()),
+                ("Arbeitsweise vom Werk", "-".to_string()),
+                ("Softwaredatum", "09.04.2020 (9040)".to_string()),
+                ("Einschaltzähler", "12".to_string()),
+                ("Betriebsstunden", "345 h".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn from_ne4() {
+        let mut ne4 = NE4::new();
+        ne4.set_rregs(&[1, 10]);
+        let mut rwregs = vec![0; 100];
+        rwregs[99] = 12;
+        ne4.set_rwregs(&rwregs);
+        let info = DeviceInfo::from_ne4(&ne4);
+        assert_eq!(info.customer_code, Some(1));
+        assert_eq!(info.working_mode, Some(WorkingMode::from(10)));
+        assert_eq!(info.factory_working_mode, Some(WorkingMode::from(12)));
+        assert_eq!(info.software_date_decoded(), None);
+    }
+
+    #[test]
+    fn from_ne4_unread() {
+        let info = DeviceInfo::from_ne4(&NE4::new());
+        assert_eq!(info, DeviceInfo::default());
+        assert!(info.rows().iter().all(|(_, value)| value == "-"));
+
+        let mut ne4 = NE4::new();
+        ne4.set_rregs(&[4711, 30]);
+        let info = DeviceInfo::from_ne4(&ne4);
+        assert_eq!(info.customer_code, Some(4711));
+        assert_eq!(info.software_date, None);
+        assert_eq!(info.power_on_count, None);
+    }
+}
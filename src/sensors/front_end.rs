@@ -0,0 +1,234 @@
+/// Einstellungen des Auswerte IC
+///
+/// Rwreg 21 bis 29 konfigurieren den analogen Eingang des Sensors (Auswerte IC).
+/// Jedes Register ist ein kleines Auswahlfeld, z.B. der Verstärkungswiderstand
+/// des Transimpedanzverstärkers (TIA_GAIN) oder die Vorspannung der Messzelle
+/// (BIAS). Rwreg 20 meldet ob das IC bereit ist und kann nicht geschrieben werden.
+use super::ra_gas_ne4::{find_rwreg, register_pairs, register_value, NE4};
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+
+/// Status des Auswerte IC, nur lesbar
+pub const FRONT_END_STATUS: u16 = 20;
+/// Lese/Schreib(Read/Write)-Register der Einstellungen
+pub const FRONT_END_RWREGS: [u16; 9] = [21, 22, 23, 24, 25, 26, 27, 28, 29];
+/// Art der Vorlagen, siehe `presets::presets_path`
+pub const FRONT_END_PRESETS: &str = "auswerte_ic";
+
+/// Ein Feld des Auswerte IC mit den Bezeichnungen aller Rohwerte
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Field {
+    pub number: u16,
+    /// Name im Datenblatt des IC
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Bezeichnung je Rohwert, der Index ist der Rohwert
+    pub options: &'static [&'static str],
+}
+
+/// Alle Felder in der Reihenfolge der Register
+pub const FIELDS: [Field; 9] = [
+    Field {
+        number: 21,
+        name: "Lock",
+        description: "Schreibschutz der Einstellungen im IC",
+        options: &["Beschreibbar", "Gesperrt"],
+    },
+    Field {
+        number: 22,
+        name: "TIA_GAIN",
+        description: "Verstärkungswiderstand des Transimpedanzverstärkers",
+        options: &[
+            "Externer Widerstand",
+            "2.75 kΩ",
+            "3.5 kΩ",
+            "7 kΩ",
+            "14 kΩ",
+            "35 kΩ",
+            "120 kΩ",
+            "350 kΩ",
+        ],
+    },
+    Field {
+        number: 23,
+        name: "RLOAD",
+        description: "Lastwiderstand der Messzelle",
+        options: &["10 Ω", "33 Ω", "50 Ω", "100 Ω"],
+    },
+    Field {
+        number: 24,
+        name: "REF_Source",
+        description: "Quelle der Referenzspannung",
+        options: &["Intern (Versorgungsspannung)", "Extern"],
+    },
+    Field {
+        number: 25,
+        name: "INT_Z",
+        description: "Interner Nullpunkt, Anteil der Referenzspannung",
+        options: &["20 %", "50 %", "67 %", "Überbrückt"],
+    },
+    Field {
+        number: 26,
+        name: "BIAS_Sign",
+        description: "Vorzeichen der Vorspannung",
+        options: &["Negativ", "Positiv"],
+    },
+    Field {
+        number: 27,
+        name: "BIAS",
+        description: "Vorspannung der Messzelle, Anteil der Referenzspannung",
+        options: &[
+            "0 %", "1 %", "2 %", "4 %", "6 %", "8 %", "10 %", "12 %", "14 %", "16 %", "18 %",
+            "20 %", "22 %", "24 %",
+        ],
+    },
+    Field {
+        number: 28,
+        name: "FET_Short",
+        description: "Kurzschluss von Arbeits- und Referenzelektrode",
+        options: &["Aus", "Ein"],
+    },
+    Field {
+        number: 29,
+        name: "OP_Mode",
+        description: "Betriebsart",
+        options: &[
+            "Tiefschlaf",
+            "2-Elektroden Zelle",
+            "Standby",
+            "3-Elektroden Zelle",
+            "Reserviert (4)",
+            "Reserviert (5)",
+            "Temperaturmessung (TIA aus)",
+            "Temperaturmessung (TIA an)",
+        ],
+    },
+];
+
+impl Field {
+    /// Bezeichnung des Rohwerts, z.B. "35 kΩ"
+    pub fn label(&self, raw: u16) -> String {
+        match self.options.get(usize::from(raw)) {
+            Some(label) => label.to_string(),
+            None => format!("Ungültig ({})", raw),
+        }
+    }
+}
+
+/// Bezeichnung des Status aus Rwreg 20
+pub fn status_label(raw: u16) -> &'static str {
+    match raw {
+        0 => "Nicht bereit",
+        1 => "Bereit",
+        _ => "Unbekannt",
+    }
+}
+
+/// Einstellungen des Auswerte IC als Rohwerte, in der Reihenfolge von `FIELDS`
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct FrontEnd {
+    pub values: [u16; 9],
+}
+
+impl FrontEnd {
+    /// Einstellungen aus gelesenen Registern, `None` wenn eines fehlt
+    pub fn from_registers(rwregs: &[(u16, u16)]) -> Option<Self> {
+        let mut values = [0; 9];
+        for (value, number) in values.iter_mut().zip(FRONT_END_RWREGS.iter()) {
+            *value = register_value(rwregs, *number)?;
+        }
+        Some(FrontEnd { values })
+    }
+
+    /// Einstellungen aus den abgefragten Rwreg's
+    pub fn from_ne4(ne4: &NE4) -> Option<Self> {
+        FrontEnd::from_registers(&ne4.rwreg_pairs(&FRONT_END_RWREGS)?)
+    }
+
+    /// Registernummer und Rohwert
+    pub fn registers(&self) -> Vec<(u16, u16)> {
+        register_pairs(&FRONT_END_RWREGS, &self.values)
+    }
+
+    /// Registernummer und Rohwert in der Reihenfolge zum Schreiben
+    ///
+    /// Solange das IC gesperrt ist (Rwreg 21), übernimmt es keine Einstellungen.
+    /// Das Entsperren wird deshalb zuerst geschrieben, das Sperren zuletzt.
+    pub fn write_order(&self) -> Vec<(u16, u16)> {
+        let mut registers = self.registers();
+        if self.values[0] != 0 {
+            registers.rotate_left(1);
+        }
+        registers
+    }
+
+    /// Alle Werte liegen im Wertebereich der Register
+    pub fn validate(&self) -> Result<()> {
+        for (number, value) in self.registers() {
+            find_rwreg(number)
+                .expect("Auswerte IC sind Rwreg's")
+                .check_raw(value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn fields_match_registers() {
+        for (field, number) in FIELDS.iter().zip(FRONT_END_RWREGS.iter()) {
+            assert_eq!(field.number, *number);
+            let reg = find_rwreg(field.number).unwrap();
+            assert!(reg.description.starts_with(field.name), "{}", field.name);
+            let (_, max) = reg.limits();
+            assert_eq!(field.options.len() as f64, max + 1.0, "{}", field.name);
+        }
+        assert!(!find_rwreg(FRONT_END_STATUS).unwrap().writable);
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(FIELDS[1].label(5), "35 kΩ");
+        assert_eq!(FIELDS[6].label(13), "24 %");
+        assert_eq!(FIELDS[6].label(14), "Ungültig (14)");
+        assert_eq!(status_label(1), "Bereit");
+    }
+
+    #[test]
+    fn write_order() {
+        let mut front_end = FrontEnd {
+            values: [0, 5, 1, 0, 1, 0, 3, 0, 3],
+        };
+        let rwregs = front_end.write_order();
+        assert_eq!(rwregs, front_end.registers());
+        assert_eq!(rwregs[0], (21, 0));
+
+        // Gesperrt wird erst nach allen Einstellungen
+        front_end.values[0] = 1;
+        let rwregs = front_end.write_order();
+        assert_eq!(rwregs[0], (22, 5));
+        assert_eq!(rwregs[7], (29, 3));
+        assert_eq!(rwregs[8], (21, 1));
+    }
+
+    #[test]
+    fn validate() {
+        let mut front_end = FrontEnd {
+            values: [1, 5, 1, 0, 1, 0, 3, 0, 3],
+        };
+        assert!(front_end.validate().is_ok());
+        let rwregs = front_end.registers();
+        assert_eq!(rwregs[0], (21, 1));
+        assert_eq!(FrontEnd::from_registers(&rwregs), Some(front_end));
+
+        front_end.values[6] = 14;
+        match front_end.validate() {
+            Err(Error::OutOfRange { register, .. }) => assert_eq!(register, 27),
+            other => panic!("{:?}", other),
+        }
+    }
+}
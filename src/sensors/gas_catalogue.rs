@@ -0,0 +1,132 @@
+/// Gaskatalog
+///
+/// Jede Arbeitsweise des NE4 steht für ein Gas mit Messbereich. Der Katalog ordnet
+/// dem Wert in Rreg 1 bzw. Rwreg 99 Gas, Summenformel, Messbereichsendwert und
+/// Einheit zu. Ein neues Gas braucht nur einen neuen Eintrag in `GASES`.
+use std::fmt;
+
+/// Ein Gas mit Messbereich
+#[derive(Debug, Eq, PartialEq)]
+pub struct Gas {
+    /// Arbeitsweise, Wert in Rreg 1 bzw. Rwreg 99
+    pub working_mode: u16,
+    /// Name des Gases
+    pub name: &'static str,
+    /// Summenformel
+    pub formula: &'static str,
+    /// Messbereichsendwert, der Messbereich beginnt immer bei 0
+    pub full_scale: u16,
+    /// Einheit der Gaskonzentration
+    pub unit: &'static str,
+}
+
+/// Alle Gase, in der Reihenfolge der Auswahl
+pub const GASES: &[Gas] = &[
+    Gas {
+        working_mode: 10,
+        name: "Kohlenmonoxid",
+        formula: "CO",
+        full_scale: 1000,
+        unit: "ppm",
+    },
+    Gas {
+        working_mode: 12,
+        name: "Kohlenmonoxid",
+        formula: "CO",
+        full_scale: 300,
+        unit: "ppm",
+    },
+    Gas {
+        working_mode: 20,
+        name: "Stickstoffmonoxid",
+        formula: "NO",
+        full_scale: 250,
+        unit: "ppm",
+    },
+    Gas {
+        working_mode: 30,
+        name: "Stickstoffdioxid",
+        formula: "NO2",
+        full_scale: 20,
+        unit: "ppm",
+    },
+    Gas {
+        working_mode: 40,
+        name: "Ammoniak",
+        formula: "NH3",
+        full_scale: 1000,
+        unit: "ppm",
+    },
+    Gas {
+        working_mode: 42,
+        name: "Ammoniak",
+        formula: "NH3",
+        full_scale: 100,
+        unit: "ppm",
+    },
+    Gas {
+        working_mode: 50,
+        name: "Chlor",
+        formula: "Cl2",
+        full_scale: 10,
+        unit: "ppm",
+    },
+    Gas {
+        working_mode: 60,
+        name: "Schwefelwasserstoff",
+        formula: "H2S",
+        full_scale: 25,
+        unit: "ppm",
+    },
+];
+
+/// Gas einer Arbeitsweise, `None` bei unbekannter Arbeitsweise
+pub fn find_gas(working_mode: u16) -> Option<&'static Gas> {
+    GASES.iter().find(|gas| gas.working_mode == working_mode)
+}
+
+impl Gas {
+    /// Liegt die Gaskonzentration im Messbereich?
+    pub fn contains(&self, value: u16) -> bool {
+        value <= self.full_scale
+    }
+
+    /// Gaskonzentration mit Einheit, z.B. "25 ppm"
+    pub fn format_value(&self, value: u16) -> String {
+        format!("{} {}", value, self.unit)
+    }
+}
+
+/// Kurzbezeichnung wie auf dem Typenschild, z.B. "CO 1000 ppm"
+impl fmt::Display for Gas {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.formula, self.full_scale, self.unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_working_modes() {
+        for (i, gas) in GASES.iter().enumerate() {
+            assert_ne!(gas.working_mode, 0, "0 ist unkonfiguriert");
+            assert!(GASES[i + 1..]
+                .iter()
+                .all(|other| other.working_mode != gas.working_mode));
+        }
+    }
+
+    #[test]
+    fn find() {
+        let gas = find_gas(30).unwrap();
+        assert_eq!(gas.name, "Stickstoffdioxid");
+        assert_eq!(gas.to_string(), "NO2 20 ppm");
+        assert!(gas.contains(20));
+        assert!(!gas.contains(21));
+        assert_eq!(gas.format_value(12), "12 ppm");
+        assert_eq!(find_gas(0), None);
+        assert_eq!(find_gas(31), None);
+    }
+}
@@ -0,0 +1,174 @@
+/// Verlauf der Messwerte für die Trendanzeige
+///
+/// Gaskonzentration, Ausgangsstrom und interne Temperatur werden bei jeder
+/// Abfrage in einem Ringpuffer abgelegt. Werte die älter als die maximale
+/// Dauer sind werden verworfen.
+use super::ra_gas_ne4::NE4;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Ein Messpunkt, umgerechnet in die Einheiten der Register
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    pub time: Instant,
+    /// Gaskonzentration in ppm, Rreg 2
+    pub gas: f64,
+    /// Ausgangsstrom in mA, Rreg 3
+    pub ma: f64,
+    /// Interne Leiterplattentemperatur in °C, Rreg 4
+    pub temp: f64,
+}
+
+/// Lese(Read)-Register eines Messpunkts
+pub const SAMPLE_RREGS: [u16; 3] = [2, 3, 4];
+
+impl Sample {
+    /// Messpunkt aus den Werten einer Abfrage
+    ///
+    /// `None` wenn eines der Register bei der Abfrage nicht gelesen werden konnte.
+    pub fn from_ne4(time: Instant, ne4: &NE4) -> Option<Self> {
+        if !ne4.has_rregs(&SAMPLE_RREGS) {
+            return None;
+        }
+        Some(Sample {
+            time,
+            gas: f64::from(ne4.concentration_gas()),
+            ma: ne4.output_current(),
+            temp: ne4.int_temp(),
+        })
+    }
+}
+
+/// Ringpuffer der Messpunkte
+#[derive(Debug)]
+pub struct History {
+    samples: VecDeque<Sample>,
+    max_age: Duration,
+}
+
+impl History {
+    pub fn new(max_age: Duration) -> Self {
+        History {
+            samples: VecDeque::new(),
+            max_age,
+        }
+    }
+
+    /// Messpunkt anhängen und zu alte Messpunkte verwerfen
+    pub fn push(&mut self, sample: Sample) {
+        self.samples.push_back(sample);
+        while let Some(first) = self.samples.front() {
+            match sample.time.checked_duration_since(first.time) {
+                Some(age) if age > self.max_age => {
+                    self.samples.pop_front();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Messpunkte im Zeitfenster `span` vor `end`
+    pub fn visible(&self, end: Instant, span: Duration) -> Vec<Sample> {
+        self.samples
+            .iter()
+            .filter(|sample| match end.checked_duration_since(sample.time) {
+                Some(age) => age <= span,
+                None => false,
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Wertebereich einer Messreihe für die Skalierung der Achse
+///
+/// Der Bereich ist mindestens `min_span` groß, damit konstante Werte nicht auf
+/// dem Rand liegen und das Rauschen nicht übertrieben dargestellt wird.
+pub fn value_range<I: IntoIterator<Item = f64>>(values: I, min_span: f64) -> Option<(f64, f64)> {
+    let (min, max) = values.into_iter().fold(None, |range, value| match range {
+        None => Some((value, value)),
+        Some((min, max)) => Some((f64::min(min, value), f64::max(max, value))),
+    })?;
+    if max - min < min_span {
+        let center = (min + max) / 2.0;
+        Some((center - min_span / 2.0, center + min_span / 2.0))
+    } else {
+        Some((min, max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(time: Instant, gas: f64) -> Sample {
+        Sample {
+            time,
+            gas,
+            ma: 4.0,
+            temp: 20.0,
+        }
+    }
+
+    #[test]
+    fn from_ne4() {
+        let mut values = vec![Some(0u16); 50];
+        values[2] = Some(25);
+        values[3] = Some(1234);
+        values[4] = Some(0xFF38);
+        let mut ne4 = NE4::new();
+        ne4.update_rregs(&values);
+        let sample = Sample::from_ne4(Instant::now(), &ne4).unwrap();
+        assert_eq!(sample.gas, 25.0);
+        assert_eq!(sample.ma, 12.34);
+        assert_eq!(sample.temp, -20.0);
+
+        values[3] = None;
+        ne4.update_rregs(&values);
+        assert_eq!(Sample::from_ne4(Instant::now(), &ne4), None);
+    }
+
+    #[test]
+    fn old_samples_dropped() {
+        let start = Instant::now();
+        let mut history = History::new(Duration::from_secs(10));
+        for i in 0..=20 {
+            history.push(sample(start + Duration::from_secs(i), i as f64));
+        }
+        assert_eq!(history.len(), 11);
+        assert_eq!(history.samples.front().unwrap().gas, 10.0);
+    }
+
+    #[test]
+    fn visible() {
+        let start = Instant::now();
+        let mut history = History::new(Duration::from_secs(60));
+        for i in 0..10 {
+            history.push(sample(start + Duration::from_secs(i), i as f64));
+        }
+        let visible = history.visible(start + Duration::from_secs(5), Duration::from_secs(2));
+        let gas: Vec<f64> = visible.iter().map(|sample| sample.gas).collect();
+        assert_eq!(gas, vec![3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(value_range(vec![], 1.0), None);
+        assert_eq!(value_range(vec![4.0, 20.0, 12.0], 1.0), Some((4.0, 20.0)));
+        assert_eq!(value_range(vec![10.0, 10.0], 2.0), Some((9.0, 11.0)));
+    }
+}
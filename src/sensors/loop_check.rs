@@ -0,0 +1,288 @@
+/// Prüfung und Abgleich der 4-20 mA Stromschleife
+///
+/// Der Ausgangsstrom wird über Rwreg 3 nacheinander auf 4, 8, 12, 16 und 20 mA
+/// vorgegeben, der Techniker trägt jeweils den Wert seines Multimeters oder der
+/// SPS ein. Aus den Abweichungen werden neue Kalibrierwerte für 4 mA und 20 mA
+/// (Rwreg 53 und 54) berechnet. Nach dem Schreiben wird die Schleife erneut
+/// geprüft, das Ergebnis mit der Abweichung pro Punkt als JSON gespeichert.
+use super::ra_gas_ne4::{find_rwreg, register_value};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+/// Version des Dateiformats
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Vorgegebene Ausgangsströme in mA
+pub const LOOP_CHECK_POINTS: [f64; 5] = [4.0, 8.0, 12.0, 16.0, 20.0];
+/// Lese/Schreib(Read/Write)-Register der Kalibrierwerte für 4 mA und 20 mA
+pub const OUTPUT_CALIBRATION_RWREGS: [u16; 2] = [53, 54];
+/// Vorgabe der zulässigen Abweichung in mA
+pub const DEFAULT_LOOP_TOLERANCE: f64 = 0.1;
+
+/// Kalibrierwerte des Ausgangsstroms, Rwreg 53 und 54
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct OutputCalibration {
+    /// Kalibrierwert für 4 mA, Rwreg 53
+    pub low: u16,
+    /// Kalibrierwert für 20 mA, Rwreg 54
+    pub high: u16,
+}
+
+impl OutputCalibration {
+    /// Kalibrierwerte aus gelesenen Registern, siehe `register_value`
+    pub fn from_registers(rwregs: &[(u16, u16)]) -> Option<Self> {
+        Some(OutputCalibration {
+            low: register_value(rwregs, 53)?,
+            high: register_value(rwregs, 54)?,
+        })
+    }
+
+    /// Registernummer und Rohwert
+    pub fn registers(&self) -> [(u16, u16); 2] {
+        [(53, self.low), (54, self.high)]
+    }
+
+    /// Kalibrierwert mit dem der Sensor den Ausgangsstrom `setpoint` erzeugt
+    ///
+    /// Zwischen 4 mA und 20 mA wird linear interpoliert.
+    fn value_at(&self, setpoint: f64) -> f64 {
+        let (low, high) = (f64::from(self.low), f64::from(self.high));
+        low + (setpoint - 4.0) * (high - low) / 16.0
+    }
+
+    /// Korrigierte Kalibrierwerte aus den gemessenen Strömen
+    ///
+    /// Der gemessene Strom wird als Gerade über dem Kalibrierwert angenähert
+    /// (kleinste Quadrate), die neuen Werte sind die Kalibrierwerte bei denen
+    /// diese Gerade 4 mA und 20 mA ergibt.
+    pub fn corrected(&self, points: &[LoopPoint]) -> Result<Self, String> {
+        if points.len() < 2 {
+            return Err("Zu wenige Messpunkte für einen Abgleich".to_string());
+        }
+        let n = points.len() as f64;
+        let xs: Vec<f64> = points.iter().map(|p| self.value_at(p.setpoint)).collect();
+        let mean_x = xs.iter().sum::<f64>() / n;
+        let mean_y = points.iter().map(|p| p.measured).sum::<f64>() / n;
+        let (mut covariance, mut variance) = (0.0, 0.0);
+        for (x, point) in xs.iter().zip(points) {
+            covariance += (x - mean_x) * (point.measured - mean_y);
+            variance += (x - mean_x) * (x - mean_x);
+        }
+        let slope = covariance / variance;
+        if !slope.is_finite() || slope <= 0.0 {
+            return Err(
+                "Der gemessene Strom steigt nicht mit der Vorgabe, Verdrahtung prüfen".to_string(),
+            );
+        }
+        let offset = mean_y - slope * mean_x;
+        let value = |number: u16, current: f64| {
+            let value = ((current - offset) / slope).round();
+            match find_rwreg(number) {
+                Some(reg) if value.is_finite() && reg.contains(value as i32) => Ok(value as u16),
+                Some(reg) => Err(format!(
+                    "Korrekturwert {:.0} für Rwreg {} liegt nicht im Wertebereich {}",
+                    value,
+                    number,
+                    reg.range_label()
+                )),
+                None => Err(format!("Rwreg {} fehlt in der Registertabelle", number)),
+            }
+        };
+        Ok(OutputCalibration {
+            low: value(53, 4.0)?,
+            high: value(54, 20.0)?,
+        })
+    }
+}
+
+/// Ein Prüfpunkt der Stromschleife
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct LoopPoint {
+    /// Vorgegebener Ausgangsstrom in mA
+    pub setpoint: f64,
+    /// Gemessener Strom in mA
+    pub measured: f64,
+}
+
+impl LoopPoint {
+    /// Abweichung in mA
+    pub fn deviation(&self) -> f64 {
+        self.measured - self.setpoint
+    }
+
+    pub fn passed(&self, tolerance: f64) -> bool {
+        self.deviation().abs() <= tolerance
+    }
+}
+
+/// Ergebnis einer Prüfung der Stromschleife
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct LoopCheck {
+    pub version: u32,
+    pub operator: String,
+    pub modbus_address: u8,
+    /// Beginn der Prüfung (RFC 3339)
+    pub started: String,
+    /// Zulässige Abweichung in mA
+    pub tolerance: f64,
+    /// Kalibrierwerte vor der Prüfung
+    pub old: OutputCalibration,
+    /// Prüfpunkte vor dem Abgleich
+    pub before: Vec<LoopPoint>,
+    /// Geschriebene Kalibrierwerte, `None` ohne Abgleich
+    pub new: Option<OutputCalibration>,
+    /// Prüfpunkte nach dem Abgleich
+    pub after: Vec<LoopPoint>,
+}
+
+impl LoopCheck {
+    pub fn new(
+        operator: &str,
+        modbus_address: u8,
+        tolerance: f64,
+        old: OutputCalibration,
+        started: DateTime<Local>,
+    ) -> Self {
+        LoopCheck {
+            version: FORMAT_VERSION,
+            operator: operator.to_string(),
+            modbus_address,
+            started: started.to_rfc3339(),
+            tolerance,
+            old,
+            before: vec![],
+            new: None,
+            after: vec![],
+        }
+    }
+
+    /// Prüfpunkte die über das Ergebnis entscheiden, nach dem Abgleich die
+    /// erneute Prüfung
+    pub fn final_points(&self) -> &[LoopPoint] {
+        if self.new.is_some() {
+            &self.after
+        } else {
+            &self.before
+        }
+    }
+
+    /// Alle Prüfpunkte gemessen und innerhalb der Toleranz
+    pub fn passed(&self) -> bool {
+        let points = self.final_points();
+        points.len() == LOOP_CHECK_POINTS.len()
+            && points.iter().all(|point| point.passed(self.tolerance))
+    }
+
+    /// Größte Abweichung der entscheidenden Prüfpunkte in mA
+    pub fn max_deviation(&self) -> Option<f64> {
+        self.final_points()
+            .iter()
+            .map(|point| point.deviation().abs())
+            .fold(None, |max, deviation| {
+                Some(max.map_or(deviation, |max: f64| max.max(deviation)))
+            })
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Loop check is always serializable")
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+
+    /// Vorgeschlagener Dateiname, z.B. `stromschleife_247_2020-09-01.json`
+    pub fn file_name(&self) -> String {
+        format!(
+            "stromschleife_{}_{}.json",
+            self.modbus_address,
+            self.started.get(..10).unwrap_or("")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const FACTORY: OutputCalibration = OutputCalibration {
+        low: 180,
+        high: 900,
+    };
+
+    /// Stromschleife mit Steigung und Versatz gegenüber der Werkseinstellung
+    fn measure(calibration: &OutputCalibration, slope: f64, offset: f64) -> Vec<LoopPoint> {
+        LOOP_CHECK_POINTS
+            .iter()
+            .map(|setpoint| LoopPoint {
+                setpoint: *setpoint,
+                measured: slope * calibration.value_at(*setpoint) + offset,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn corrected() {
+        // Exakte Schleife, nichts zu korrigieren
+        let points = measure(&FACTORY, 16.0 / 720.0, 0.0);
+        assert_eq!(FACTORY.corrected(&points), Ok(FACTORY));
+
+        let points = measure(&FACTORY, 0.0225, -0.2);
+        assert!((points[0].deviation() + 0.15).abs() < 1e-9);
+        let new = FACTORY.corrected(&points).unwrap();
+        assert_eq!(
+            new,
+            OutputCalibration {
+                low: 187,
+                high: 898
+            }
+        );
+        for point in measure(&new, 0.0225, -0.2) {
+            assert!(point.passed(0.05), "{:?}", point);
+        }
+    }
+
+    #[test]
+    fn not_correctable() {
+        let points = measure(&FACTORY, 0.0, 4.0);
+        assert!(FACTORY.corrected(&points).is_err());
+        let points = measure(&FACTORY, 0.002, 0.0);
+        assert!(FACTORY.corrected(&points).is_err());
+        assert!(FACTORY.corrected(&points[..1]).is_err());
+    }
+
+    #[test]
+    fn result() {
+        let started = Local.ymd(2020, 9, 1).and_hms(10, 0, 0);
+        let mut check = LoopCheck::new("Prüfer", 247, 0.1, FACTORY, started);
+        assert!(!check.passed());
+        assert_eq!(check.max_deviation(), None);
+        check.before = measure(&FACTORY, 0.0225, -0.2);
+        assert!(!check.passed());
+        check.new = FACTORY.corrected(&check.before).ok();
+        check.after = measure(&check.new.unwrap(), 0.0225, -0.2);
+        assert!(check.passed());
+        assert!(check.max_deviation().unwrap() < 0.05);
+        assert_eq!(check.file_name(), "stromschleife_247_2020-09-01.json");
+
+        // Gleitkommazahlen sind nach dem Einlesen nicht unbedingt bitgleich
+        let json = check.to_json();
+        let read: LoopCheck = serde_json::from_str(&json).unwrap();
+        let same_points = |read: &[LoopPoint], points: &[LoopPoint]| {
+            read.len() == points.len()
+                && read.iter().zip(points).all(|(read, point)| {
+                    (read.setpoint - point.setpoint).abs() < 1e-9
+                        && (read.measured - point.measured).abs() < 1e-9
+                })
+        };
+        assert_eq!(read.started, check.started);
+        assert_eq!(read.old, check.old);
+        assert_eq!(read.new, check.new);
+        assert!((read.tolerance - check.tolerance).abs() < 1e-9);
+        assert!(same_points(&read.before, &check.before));
+        assert!(same_points(&read.after, &check.after));
+        assert_eq!(read.passed(), check.passed());
+    }
+}
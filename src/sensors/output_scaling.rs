@@ -0,0 +1,197 @@
+/// Skalierung des Ausgangsstroms
+///
+/// Rwreg 15 bis 18 bilden die Gaskonzentration linear auf den Ausgangsstrom ab:
+/// der Messwert unten (Rwreg 15) ergibt den Strom unten (Rwreg 16), der Messwert
+/// oben (Rwreg 17) den Strom oben (Rwreg 18). Die Ströme sind in 1/100 mA
+/// gespeichert, außerhalb der beiden Punkte wird die Gerade bis 0 bzw. 25 mA
+/// fortgesetzt.
+use super::ra_gas_ne4::{find_rwreg, register_value, NE4};
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// Lese/Schreib(Read/Write)-Register der Skalierung
+pub const OUTPUT_SCALING_RWREGS: [u16; 4] = [15, 16, 17, 18];
+/// Kleinster und größter Ausgangsstrom in mA
+pub const CURRENT_LIMITS: (f64, f64) = (0.0, 25.0);
+
+/// Skalierung des Ausgangsstroms, Rwreg 15 bis 18
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct OutputScaling {
+    /// Messwert unten, Rwreg 15
+    pub low_value: u16,
+    /// Ausgangsstrom im unteren Punkt in 1/100 mA, Rwreg 16
+    pub low_current: u16,
+    /// Messwert oben, Rwreg 17
+    pub high_value: u16,
+    /// Ausgangsstrom im oberen Punkt in 1/100 mA, Rwreg 18
+    pub high_current: u16,
+}
+
+impl Default for OutputScaling {
+    /// Vorgabewerte der Registertabelle, 0 … 1000 ppm auf 4 … 20 mA
+    fn default() -> Self {
+        OutputScaling {
+            low_value: 0,
+            low_current: 400,
+            high_value: 1000,
+            high_current: 2000,
+        }
+    }
+}
+
+impl OutputScaling {
+    /// Skalierung aus gelesenen Registern, siehe `register_value`
+    pub fn from_registers(rwregs: &[(u16, u16)]) -> Option<Self> {
+        Some(OutputScaling {
+            low_value: register_value(rwregs, 15)?,
+            low_current: register_value(rwregs, 16)?,
+            high_value: register_value(rwregs, 17)?,
+            high_current: register_value(rwregs, 18)?,
+        })
+    }
+
+    /// Skalierung aus den abgefragten Rwreg's
+    pub fn from_ne4(ne4: &NE4) -> Option<Self> {
+        OutputScaling::from_registers(&ne4.rwreg_pairs(&OUTPUT_SCALING_RWREGS)?)
+    }
+
+    /// Registernummer und Rohwert
+    pub fn registers(&self) -> [(u16, u16); 4] {
+        [
+            (15, self.low_value),
+            (16, self.low_current),
+            (17, self.high_value),
+            (18, self.high_current),
+        ]
+    }
+
+    /// Registernummer und Rohwert in der Reihenfolge zum Schreiben
+    ///
+    /// Liegt der neue untere Messwert über dem bisherigen oberen, wird der obere
+    /// Punkt zuerst geschrieben. So ist der obere Messwert im Sensor auch
+    /// zwischen den einzelnen Registern immer größer als der untere.
+    pub fn write_order(&self, old: &OutputScaling) -> [(u16, u16); 4] {
+        let [low_value, low_current, high_value, high_current] = self.registers();
+        if self.low_value >= old.high_value {
+            [high_value, high_current, low_value, low_current]
+        } else {
+            [low_value, low_current, high_value, high_current]
+        }
+    }
+
+    /// Alle Werte liegen im Wertebereich und der obere Messwert über dem unteren
+    pub fn validate(&self) -> Result<()> {
+        for (number, value) in self.registers().iter() {
+            find_rwreg(*number)
+                .expect("Skalierung sind Rwreg's")
+                .check_raw(*value)?;
+        }
+        if self.high_value <= self.low_value {
+            let (_, max) = find_rwreg(17).expect("Skalierung sind Rwreg's").limits();
+            return Err(Error::OutOfRange {
+                register: 17,
+                value: i32::from(self.high_value),
+                range: format!("{} … {}", i32::from(self.low_value) + 1, max),
+            });
+        }
+        Ok(())
+    }
+
+    /// Ausgangsstrom im unteren Punkt in mA
+    pub fn low_ma(&self) -> f64 {
+        f64::from(self.low_current) / 100.0
+    }
+
+    /// Ausgangsstrom im oberen Punkt in mA
+    pub fn high_ma(&self) -> f64 {
+        f64::from(self.high_current) / 100.0
+    }
+
+    /// Ausgangsstrom in mA beim Messwert `value`, begrenzt auf 0 … 25 mA
+    ///
+    /// Ohne gültige Skalierung (oben nicht über unten) gilt der Strom unten.
+    pub fn current_at(&self, value: f64) -> f64 {
+        let (low, high) = (f64::from(self.low_value), f64::from(self.high_value));
+        if high <= low {
+            return self.low_ma();
+        }
+        let current =
+            self.low_ma() + (value - low) * (self.high_ma() - self.low_ma()) / (high - low);
+        current.max(CURRENT_LIMITS.0).min(CURRENT_LIMITS.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_match_registers() {
+        for (number, value) in OutputScaling::default().registers().iter() {
+            let default = find_rwreg(*number).unwrap().default;
+            assert_eq!(default, Some(i32::from(*value)), "Rwreg {}", number);
+        }
+    }
+
+    #[test]
+    fn current() {
+        let scaling = OutputScaling::default();
+        assert_eq!(scaling.current_at(0.0), 4.0);
+        assert_eq!(scaling.current_at(500.0), 12.0);
+        assert_eq!(scaling.current_at(1000.0), 20.0);
+        assert_eq!(scaling.current_at(2000.0), 25.0);
+
+        let scaling = OutputScaling {
+            low_value: 500,
+            ..scaling
+        };
+        assert_eq!(scaling.current_at(0.0), 0.0);
+        let scaling = OutputScaling {
+            high_value: 100,
+            ..scaling
+        };
+        assert_eq!(scaling.current_at(500.0), 4.0);
+    }
+
+    #[test]
+    fn validate() {
+        let scaling = OutputScaling::default();
+        assert!(scaling.validate().is_ok());
+        let invalid = OutputScaling {
+            high_value: 0,
+            ..scaling
+        };
+        match invalid.validate() {
+            Err(Error::OutOfRange {
+                register, range, ..
+            }) => {
+                assert_eq!(register, 17);
+                assert_eq!(range, "1 … 10000");
+            }
+            other => panic!("{:?}", other),
+        }
+        let invalid = OutputScaling {
+            high_current: 2501,
+            ..scaling
+        };
+        match invalid.validate() {
+            Err(Error::OutOfRange { register, .. }) => assert_eq!(register, 18),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn write_order() {
+        let old = OutputScaling::default();
+        let new = OutputScaling {
+            low_value: 2000,
+            high_value: 5000,
+            ..old
+        };
+        assert_eq!(new.write_order(&old)[0].0, 17);
+        assert_eq!(old.write_order(&new)[0].0, 15);
+        let rwregs = new.registers();
+        assert_eq!(rwregs[3], (18, 2000));
+        assert_eq!(OutputScaling::from_registers(&rwregs), Some(new));
+    }
+}
@@ -0,0 +1,169 @@
+/// Vorlagen für zusammengehörige Register
+///
+/// Eine Vorlage speichert die Werte einer Registergruppe unter einem Namen, z.B.
+/// die Temperaturkennlinie unter dem Typ der Sensorzelle. Alle Vorlagen einer
+/// Art liegen zusammen in einer JSON Datei im Konfigurationsverzeichnis des
+/// Benutzers.
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Version des Dateiformats
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Unterverzeichnis im Konfigurationsverzeichnis des Benutzers
+const CONFIG_DIR: &str = "ne4_konfig";
+
+/// Pfad der Vorlagendatei, z.B. `~/.config/ne4_konfig/vorlagen_kennlinie.json`
+pub fn presets_path(config_dir: &Path, kind: &str) -> PathBuf {
+    config_dir
+        .join(CONFIG_DIR)
+        .join(format!("vorlagen_{}.json", kind))
+}
+
+/// Eine benannte Vorlage
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Preset<T> {
+    pub name: String,
+    pub values: T,
+}
+
+/// Alle Vorlagen einer Art, nach Namen sortiert
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Presets<T> {
+    pub version: u32,
+    presets: Vec<Preset<T>>,
+}
+
+impl<T> Presets<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    pub fn new() -> Self {
+        Presets {
+            version: FORMAT_VERSION,
+            presets: vec![],
+        }
+    }
+
+    pub fn from_json(json: &str) -> io::Result<Self> {
+        let presets: Presets<T> = serde_json::from_str(json)?;
+        if presets.version > FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Vorlagendatei Version {} wird nicht unterstützt (maximal {})",
+                    presets.version, FORMAT_VERSION
+                ),
+            ));
+        }
+        Ok(presets)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Presets are always serializable")
+    }
+
+    /// Vorlagen laden, eine fehlende Datei ergibt keine Vorlagen
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(json) => Presets::from_json(&json),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Presets::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Vorlagen speichern, das Verzeichnis wird bei Bedarf angelegt
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_json())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.presets
+            .iter()
+            .map(|preset| preset.name.as_str())
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&T> {
+        self.presets
+            .iter()
+            .find(|preset| preset.name == name)
+            .map(|preset| &preset.values)
+    }
+
+    /// Vorlage ablegen, eine Vorlage mit gleichem Namen wird ersetzt
+    pub fn insert(&mut self, name: &str, values: T) {
+        self.remove(name);
+        let index = self
+            .presets
+            .iter()
+            .position(|preset| preset.name.as_str() > name)
+            .unwrap_or_else(|| self.presets.len());
+        self.presets.insert(
+            index,
+            Preset {
+                name: name.to_string(),
+                values,
+            },
+        );
+    }
+
+    /// Vorlage löschen, `false` wenn es sie nicht gab
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.presets.len();
+        self.presets.retain(|preset| preset.name != name);
+        self.presets.len() != len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.presets.is_empty()
+    }
+}
+
+impl<T> Default for Presets<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn default() -> Self {
+        Presets::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_remove() {
+        let mut presets = Presets::new();
+        presets.insert("NH3", vec![1u16, 2]);
+        presets.insert("CO", vec![3]);
+        presets.insert("NH3", vec![4]);
+        assert_eq!(presets.names(), vec!["CO", "NH3"]);
+        assert_eq!(presets.get("NH3"), Some(&vec![4]));
+        assert!(presets.remove("CO"));
+        assert!(!presets.remove("CO"));
+        assert_eq!(presets.get("CO"), None);
+
+        let json = presets.to_json();
+        assert_eq!(Presets::<Vec<u16>>::from_json(&json).unwrap(), presets);
+        let json = json.replace("\"version\": 1", "\"version\": 2");
+        assert!(Presets::<Vec<u16>>::from_json(&json).is_err());
+    }
+
+    #[test]
+    fn missing_file() {
+        let path = presets_path(Path::new("/nonexistent"), "kennlinie");
+        assert_eq!(
+            path,
+            Path::new("/nonexistent/ne4_konfig/vorlagen_kennlinie.json")
+        );
+        assert!(Presets::<Vec<u16>>::load(&path).unwrap().is_empty());
+    }
+}
@@ -1,28 +1,722 @@
+use super::gas_catalogue::{find_gas, Gas, GASES};
+use super::test_values::TEST_VALUE_OFF;
+use crate::error::{self, Error};
 use std::fmt;
 use tokio::time::{timeout, Duration};
 use tokio_modbus::prelude::*;
 use tokio_serial::{Serial, SerialPortSettings};
 
+/// Modbus Funktionscode mit dem ein Register gelesen wird
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FunctionCode {
+    /// Read Holding Registers (0x03), geschrieben wird mit 0x06
+    ReadHoldingRegisters = 0x03,
+    /// Read Input Registers (0x04)
+    ReadInputRegisters = 0x04,
+}
+
+/// Beschreibung eines einzelnen Registers des 'NE4-MOD-BUS' Sensors
+///
+/// Alle Wertebereiche und Vorgabewerte sind Rohwerte wie sie über Modbus übertragen
+/// werden. Der Wert in der jeweiligen Einheit ergibt sich aus `Rohwert / scale`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Register {
+    /// Registernummer (Adresse)
+    pub number: u16,
+    /// Funktionscode zum Lesen des Registers
+    pub function: FunctionCode,
+    /// Gültige Wertebereiche (Rohwerte, jeweils inklusive)
+    pub ranges: &'static [(i32, i32)],
+    /// Vorgabewert (Rohwert)
+    pub default: Option<i32>,
+    /// Einheit des skalierten Wertes
+    pub unit: &'static str,
+    /// Skalierungsfaktor, z.B. 100 für zwei Kommastellen
+    pub scale: u16,
+    /// Rohwert ist vorzeichenbehaftet (Zweierkomplement)
+    pub signed: bool,
+    /// Register kann mit Funktionscode 0x06 geschrieben werden
+    pub writable: bool,
+    /// Sensor muss vor dem Schreiben über Register 49 entsperrt werden
+    pub unlock: bool,
+    /// Testregister, 11111 schaltet die Vorgabe aus, siehe `test_values`
+    pub test_value: bool,
+    /// Messwerteigenschaft
+    pub description: &'static str,
+}
+
+/// Lese(Read)-Register
+const fn input(
+    number: u16,
+    ranges: &'static [(i32, i32)],
+    unit: &'static str,
+    scale: u16,
+    description: &'static str,
+) -> Register {
+    Register {
+        number,
+        function: FunctionCode::ReadInputRegisters,
+        ranges,
+        default: None,
+        unit,
+        scale,
+        signed: false,
+        writable: false,
+        unlock: false,
+        test_value: false,
+        description,
+    }
+}
+
+/// Lese/Schreib(Read/Write)-Register, standardmäßig nur entsperrt beschreibbar
+const fn holding(
+    number: u16,
+    ranges: &'static [(i32, i32)],
+    default: Option<i32>,
+    unit: &'static str,
+    scale: u16,
+    description: &'static str,
+) -> Register {
+    Register {
+        number,
+        function: FunctionCode::ReadHoldingRegisters,
+        ranges,
+        default,
+        unit,
+        scale,
+        signed: false,
+        writable: true,
+        unlock: true,
+        test_value: false,
+        description,
+    }
+}
+
+impl Register {
+    const fn signed(self) -> Self {
+        Register {
+            signed: true,
+            ..self
+        }
+    }
+
+    const fn read_only(self) -> Self {
+        Register {
+            writable: false,
+            unlock: false,
+            ..self
+        }
+    }
+
+    const fn without_unlock(self) -> Self {
+        Register {
+            unlock: false,
+            ..self
+        }
+    }
+
+    const fn test_value(self) -> Self {
+        Register {
+            test_value: true,
+            ..self
+        }
+    }
+
+    /// Rohwert in einen vorzeichenrichtigen Wert umwandeln
+    pub fn raw_to_value(&self, raw: u16) -> i32 {
+        if self.signed {
+            i32::from(raw as i16)
+        } else {
+            i32::from(raw)
+        }
+    }
+
+    /// Vorzeichenrichtigen Wert in den Rohwert für Modbus umwandeln
+    pub fn value_to_raw(&self, value: i32) -> u16 {
+        value as u16
+    }
+
+    /// Rohwert in die Einheit des Registers umrechnen
+    pub fn scaled(&self, raw: u16) -> f64 {
+        f64::from(self.raw_to_value(raw)) / f64::from(self.scale)
+    }
+
+    /// Anzahl der Nachkommastellen, z.B. 2 bei `scale` 100
+    pub fn decimals(&self) -> usize {
+        let mut decimals = 0;
+        let mut scale = self.scale;
+        while scale >= 10 {
+            scale /= 10;
+            decimals += 1;
+        }
+        decimals
+    }
+
+    /// Rohwert in der Einheit des Registers mit den passenden Nachkommastellen,
+    /// z.B. "12.34" für 1234 bei Rreg 3
+    pub fn format(&self, raw: u16) -> String {
+        format!("{:.*}", self.decimals(), self.scaled(raw))
+    }
+
+    /// Prüft ob der Wert im dokumentierten Wertebereich liegt
+    ///
+    /// Der Vorgabewert in eckigen Klammern gehört nicht dazu, er kann außerhalb
+    /// liegen (z.B. Rwreg 95).
+    pub fn contains(&self, value: i32) -> bool {
+        self.ranges
+            .iter()
+            .any(|(min, max)| *min <= value && value <= *max)
+    }
+
+    /// Prüft ob der Wert geschrieben werden darf
+    ///
+    /// Das ist der Wertebereich, bei den Testregistern zusätzlich 11111 um die
+    /// Vorgabe auszuschalten.
+    pub fn accepts(&self, value: i32) -> bool {
+        self.contains(value) || (self.test_value && value == i32::from(TEST_VALUE_OFF))
+    }
+
+    /// Kleinster und größter Wert in der Einheit des Registers, z.B. (0.5, 2.0)
+    /// bei Rwreg 30
+    pub fn limits(&self) -> (f64, f64) {
+        let min = self.ranges.iter().map(|(min, _)| *min).min().unwrap_or(0);
+        let max = self.ranges.iter().map(|(_, max)| *max).max().unwrap_or(0);
+        let scale = f64::from(self.scale);
+        (f64::from(min) / scale, f64::from(max) / scale)
+    }
+
+    /// Wie `accepts` für einen Rohwert, mit dem Wertebereich im Fehler
+    pub fn check_raw(&self, raw: u16) -> error::Result<()> {
+        let value = self.raw_to_value(raw);
+        if self.accepts(value) {
+            Ok(())
+        } else {
+            Err(Error::OutOfRange {
+                register: self.number,
+                value,
+                range: self.range_label(),
+            })
+        }
+    }
+
+    /// Wertebereich wie in der Dokumentation, z.B. "0 … 2500 [400]"
+    pub fn range_label(&self) -> String {
+        let ranges: Vec<String> = self
+            .ranges
+            .iter()
+            .map(|(min, max)| match max - min {
+                0 => format!("{}", min),
+                1 => format!("{} / {}", min, max),
+                _ => format!("{} … {}", min, max),
+            })
+            .collect();
+        match self.default {
+            Some(default) => format!("{} [{}]", ranges.join(", "), default),
+            None => ranges.join(", "),
+        }
+    }
+}
+
+/// Alle Lese(Read)-Register (Fcode: 0x04)
+pub const RREGS: &[Register] = &[
+    input(0, &[(0, 65535)], "", 1, "Gerätekennung Kunden"),
+    input(1, &[(0, 65535)], "", 1, "Arbeitsweise (Sensor)"),
+    input(2, &[(0, 10000)], "ppm", 1, "Gaskonzentration im ppm"),
+    input(
+        3,
+        &[(0, 2500)],
+        "mA",
+        100,
+        "Berechneter Ausgangsstrom in mA (mit zwei Kommastellen)",
+    ),
+    input(
+        4,
+        &[(-200, 600)],
+        "°C",
+        10,
+        "Interne Leiterplattentemperatur in °C (mit Kommastelle)",
+    )
+    .signed(),
+    input(40, &[(0, 65535)], "", 1, "AD-Wert der Temperaturmessung"),
+    input(41, &[(0, 65535)], "", 1, "AD-Wert des Potentiometers"),
+    input(42, &[(0, 16383)], "", 1, "AD-Wert des Sensors"),
+    input(
+        43,
+        &[(0, 65535)],
+        "",
+        100,
+        "Verstärkungsfaktor durch Poti (100 = 1,00)",
+    ),
+    input(
+        44,
+        &[(0, 65535)],
+        "",
+        100,
+        "Verstärkungsfaktor durch Temperaturkennlinie (100 = 1,00)",
+    ),
+    input(45, &[(0, 65535)], "", 1, "Korrigierter AD-Wert des Sensors"),
+    input(
+        46,
+        &[(0, 10000)],
+        "ppm",
+        1,
+        "berechnete Gaskonzentration im ppm",
+    ),
+    input(49, &[(0, 65535)], "", 1, "Softwaredatum bis 31.12.2029"),
+];
+
+/// Alle Lese/Schreib(Read/Write)-Register (Fcode: 0x03, 0x06)
+pub const RWREGS: &[Register] = &[
+    holding(
+        0,
+        &[(0, 65535)],
+        Some(0),
+        "",
+        1,
+        "Kundencode: zur freien Belegung z.B. Raumcode",
+    ),
+    holding(
+        2,
+        &[(0, 10000)],
+        Some(11111),
+        "ppm",
+        1,
+        "Messwertvorgabe für Testzwecke",
+    )
+    .without_unlock()
+    .test_value(),
+    holding(
+        3,
+        &[(0, 2500)],
+        Some(11111),
+        "mA",
+        100,
+        "Ausgangsstrom vorgeben für Testzwecke",
+    )
+    .without_unlock()
+    .test_value(),
+    holding(
+        4,
+        &[(-200, 600)],
+        Some(11111),
+        "°C",
+        10,
+        "Temperatur vorgeben für Testzwecke",
+    )
+    .signed()
+    .without_unlock()
+    .test_value(),
+    holding(
+        10,
+        &[(0, 16383)],
+        None,
+        "",
+        1,
+        "Sensorspannung im Nullpunkt",
+    ),
+    holding(11, &[(0, 0)], None, "ppm", 1, "Sensorwert Nullpunkt = 0"),
+    holding(
+        12,
+        &[(0, 16383)],
+        None,
+        "",
+        1,
+        "Sensorspannung im Kalibrierpunkt (bei Endwert)",
+    ),
+    holding(
+        13,
+        &[(0, 10000)],
+        None,
+        "ppm",
+        1,
+        "Sensorwert im Kalibrierpunkt (bei Endwert)",
+    ),
+    holding(
+        15,
+        &[(0, 10000)],
+        Some(0),
+        "ppm",
+        1,
+        "Messwert unten für Ausgangsstrom unten",
+    ),
+    holding(
+        16,
+        &[(0, 2500)],
+        Some(400),
+        "mA",
+        100,
+        "Ausgangsstrom im unteren Punkt",
+    ),
+    holding(
+        17,
+        &[(0, 10000)],
+        Some(1000),
+        "ppm",
+        1,
+        "Messwert oben für Ausgangsstrom oben",
+    ),
+    holding(
+        18,
+        &[(0, 2500)],
+        Some(2000),
+        "mA",
+        100,
+        "Ausgangsstrom im oberen Punkt",
+    ),
+    holding(
+        20,
+        &[(0, 1)],
+        None,
+        "",
+        1,
+        "Status (Auswerte IC) (keine Eingabemöglichkeit)",
+    )
+    .read_only(),
+    holding(21, &[(0, 1)], None, "", 1, "Lock (Auswerte IC)"),
+    holding(22, &[(0, 7)], None, "", 1, "TIA_GAIN (Auswerte IC)"),
+    holding(23, &[(0, 3)], None, "", 1, "RLOAD (Auswerte IC)"),
+    holding(24, &[(0, 1)], None, "", 1, "REF_Source (Auswerte IC)"),
+    holding(25, &[(0, 3)], None, "", 1, "INT_Z (Auswerte IC)"),
+    holding(26, &[(0, 1)], None, "", 1, "BIAS_Sign (Auswerte IC)"),
+    holding(27, &[(0, 13)], None, "", 1, "BIAS (Auswerte IC)"),
+    holding(28, &[(0, 1)], None, "", 1, "FET_Short (Auswerte IC)"),
+    holding(29, &[(0, 7)], None, "", 1, "OP_Mode (Auswerte IC)"),
+    holding(
+        30,
+        &[(50, 200)],
+        None,
+        "",
+        100,
+        "Kennlinie vom Sensorhersteller bei -20°C",
+    ),
+    holding(
+        31,
+        &[(50, 200)],
+        None,
+        "",
+        100,
+        "Kennlinie vom Sensorhersteller bei 0°C",
+    ),
+    holding(
+        32,
+        &[(50, 200)],
+        None,
+        "",
+        100,
+        "Kennlinie vom Sensorhersteller bei 10°C",
+    ),
+    holding(
+        33,
+        &[(50, 200)],
+        None,
+        "",
+        100,
+        "Kennlinie vom Sensorhersteller bei 20°C",
+    ),
+    holding(
+        34,
+        &[(50, 200)],
+        None,
+        "",
+        100,
+        "Kennlinie vom Sensorhersteller bei 30°C",
+    ),
+    holding(
+        35,
+        &[(50, 200)],
+        None,
+        "",
+        100,
+        "Kennlinie vom Sensorhersteller bei 40°C",
+    ),
+    holding(
+        36,
+        &[(50, 200)],
+        None,
+        "",
+        100,
+        "Kennlinie vom Sensorhersteller bei 60°C",
+    ),
+    holding(
+        42,
+        &[(0, 16000)],
+        Some(11111),
+        "",
+        1,
+        "Sensor AD-Wert vorgeben für Testzwecke",
+    )
+    .without_unlock()
+    .test_value(),
+    holding(
+        49,
+        &[(0, 65535)],
+        None,
+        "",
+        1,
+        "Neustart / Grunddaten / entsichern",
+    )
+    .without_unlock(),
+    holding(50, &[(1, 247)], Some(1), "", 1, "Modbus-Geräteadresse"),
+    holding(51, &[(0, 3)], Some(1), "", 1, "Modbus Baudrate"),
+    holding(52, &[(0, 4)], Some(0), "", 1, "Modbus Mode"),
+    holding(
+        53,
+        &[(10, 1000)],
+        Some(180),
+        "",
+        1,
+        "Kalibrierwert Ausgangsstrom 4mA",
+    ),
+    holding(
+        54,
+        &[(10, 1000)],
+        Some(900),
+        "",
+        1,
+        "Kalibrierwert Ausgangsstrom 20mA",
+    ),
+    holding(
+        95,
+        &[(0, 0), (129, 256)],
+        Some(90),
+        "",
+        1,
+        "Sensornummer für MCS4000 - Mode",
+    )
+    .without_unlock(),
+    holding(96, &[(0, 65535)], None, "", 1, "Einschaltzähler").read_only(),
+    holding(97, &[(0, 65535)], None, "h", 1, "Betriebsstunden").read_only(),
+    holding(98, &[(0, 65535)], None, "", 1, "Gerätekennung vom Werk"),
+    holding(99, &[(0, 65535)], None, "", 1, "Arbeitsweise vom Werk"),
+];
+
+/// Modbus Adresse, Baudrate und Modus (Rwreg 50 bis 52)
+///
+/// Nach dem Schreiben antwortet der Sensor nur noch mit den neuen Einstellungen,
+/// sie werden weder zurückgelesen noch wiederhergestellt.
+pub const CONNECTION_REGISTERS: &[u16] = &[50, 51, 52];
+
+/// Lese(Read)-Register mit der gegebenen Nummer finden
+pub fn find_rreg(number: u16) -> Option<&'static Register> {
+    RREGS.iter().find(|reg| reg.number == number)
+}
+
+/// Lese/Schreib(Read/Write)-Register mit der gegebenen Nummer finden
+pub fn find_rwreg(number: u16) -> Option<&'static Register> {
+    RWREGS.iter().find(|reg| reg.number == number)
+}
+
+/// Rohwert des Registers `number` aus gelesenen Registern
+///
+/// `registers` sind Paare aus Registernummer und Rohwert, wie sie beim einmaligen
+/// Lesen von Registern entstehen. `None` wenn das Register fehlt.
+pub fn register_value(registers: &[(u16, u16)], number: u16) -> Option<u16> {
+    registers
+        .iter()
+        .find(|(n, _)| *n == number)
+        .map(|(_, value)| *value)
+}
+
+/// Paare aus Registernummer und Rohwert, das Gegenstück zu `register_value`
+pub fn register_pairs(numbers: &[u16], values: &[u16]) -> Vec<(u16, u16)> {
+    numbers
+        .iter()
+        .cloned()
+        .zip(values.iter().cloned())
+        .collect()
+}
+
+/// Anzahl der Lese(Read)-Register, indiziert nach der Registernummer
+pub const RREG_COUNT: usize = 50;
+/// Anzahl der Lese/Schreib(Read/Write)-Register, indiziert nach der Registernummer
+pub const RWREG_COUNT: usize = 100;
+
+/// Arbeitsweise des Sensors, Rreg 1 bzw. Rwreg 99
+///
+/// Die Arbeitsweisen der Gase kommen aus dem Gaskatalog.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WorkingMode {
+    Unkonfiguriert,
+    Gas(&'static Gas),
+    /// Arbeitsweise die diese Version nicht kennt
+    Unknown(u16),
+}
+
+impl WorkingMode {
+    /// Alle bekannten Arbeitsweisen, in der Reihenfolge der Auswahl
+    pub fn all() -> impl Iterator<Item = WorkingMode> {
+        std::iter::once(WorkingMode::Unkonfiguriert).chain(GASES.iter().map(WorkingMode::Gas))
+    }
+
+    /// Wert im Register
+    pub fn code(self) -> u16 {
+        match self {
+            WorkingMode::Unkonfiguriert => 0,
+            WorkingMode::Gas(gas) => gas.working_mode,
+            WorkingMode::Unknown(code) => code,
+        }
+    }
+
+    /// Gas der Arbeitsweise, `None` wenn unkonfiguriert oder unbekannt
+    pub fn gas(self) -> Option<&'static Gas> {
+        match self {
+            WorkingMode::Gas(gas) => Some(gas),
+            _ => None,
+        }
+    }
+}
+
+impl From<u16> for WorkingMode {
+    fn from(code: u16) -> Self {
+        match (code, find_gas(code)) {
+            (0, _) => WorkingMode::Unkonfiguriert,
+            (_, Some(gas)) => WorkingMode::Gas(gas),
+            (code, None) => WorkingMode::Unknown(code),
+        }
+    }
+}
+
+impl fmt::Display for WorkingMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WorkingMode::Unkonfiguriert => write!(f, "Unkonfiguriert"),
+            WorkingMode::Gas(gas) => write!(f, "{}", gas),
+            WorkingMode::Unknown(code) => write!(f, "Unbekannt ({})", code),
+        }
+    }
+}
+
 /// Representation des 'NE4-MOD-BUS' Sensors
 ///
+/// Enthält beide Registerbänke, jeweils nach der Registernummer indiziert. Die
+/// Abfrage im Tokio Thread füllt die Register, die GUI liest die umgerechneten Werte.
+/// Register die noch nie oder bei der letzten Abfrage nicht gelesen werden konnten
+/// sind ungültig, `rreg` und `rwreg` liefern für sie `None`. Die umgerechneten
+/// Werte behalten den zuletzt gelesenen Wert.
+///
 /// Quelle:
 /// [RA-GAS-Modbus-Systembeschreibung: Stand 09.04.2020 (Firmware 9040)](09-04-2020_Beschreibung_RA-GAS_Sensor-MB.docx)
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct NE4 {
     rreg: Vec<u16>,
+    rwreg: Vec<u16>,
+    /// Gültige Lese(Read)-Register, nach der Registernummer indiziert
+    rreg_valid: Vec<bool>,
+    /// Gültige Lese/Schreib(Read/Write)-Register, nach der Registernummer indiziert
+    rwreg_valid: Vec<bool>,
+}
+
+impl Default for NE4 {
+    fn default() -> Self {
+        NE4::new()
+    }
 }
 
 impl NE4 {
     pub fn new() -> Self {
         NE4 {
-            rreg: vec![0u16; 50],
+            rreg: vec![0u16; RREG_COUNT],
+            rwreg: vec![0u16; RWREG_COUNT],
+            rreg_valid: vec![false; RREG_COUNT],
+            rwreg_valid: vec![false; RWREG_COUNT],
         }
     }
 
-    pub async fn update(&mut self) -> Result<(), std::io::Error> {
-        for (i, reg) in self.rreg.iter_mut().enumerate() {
-            let value = timeout(Duration::from_millis(100), read_register(i)).await?;
-            *reg = value.unwrap()[0];
+    /// Lese(Read)-Register übernehmen, `values` ist nach der Registernummer indiziert
+    pub fn set_rregs(&mut self, values: &[u16]) {
+        let values: Vec<Option<u16>> = values.iter().cloned().map(Some).collect();
+        self.update_rregs(&values);
+    }
+
+    /// Lese/Schreib(Read/Write)-Register übernehmen, `values` ist nach der
+    /// Registernummer indiziert
+    pub fn set_rwregs(&mut self, values: &[u16]) {
+        let values: Vec<Option<u16>> = values.iter().cloned().map(Some).collect();
+        self.update_rwregs(&values);
+    }
+
+    /// Lese(Read)-Register einer Abfrage übernehmen
+    ///
+    /// `values` ist nach der Registernummer indiziert, nicht gelesene Register
+    /// (`None`) werden ungültig.
+    pub fn update_rregs(&mut self, values: &[Option<u16>]) {
+        update_registers(&mut self.rreg, &mut self.rreg_valid, values);
+    }
+
+    /// Lese/Schreib(Read/Write)-Register einer Abfrage übernehmen
+    ///
+    /// `values` ist nach der Registernummer indiziert, nicht gelesene Register
+    /// (`None`) werden ungültig.
+    pub fn update_rwregs(&mut self, values: &[Option<u16>]) {
+        update_registers(&mut self.rwreg, &mut self.rwreg_valid, values);
+    }
+
+    /// Alle Lese(Read)-Register, nach der Registernummer indiziert, `None` wenn ungültig
+    pub fn rregs(&self) -> Vec<Option<u16>> {
+        (0..self.rreg.len()).map(|n| self.rreg(n as u16)).collect()
+    }
+
+    /// Alle Lese/Schreib(Read/Write)-Register, nach der Registernummer indiziert,
+    /// `None` wenn ungültig
+    pub fn rwregs(&self) -> Vec<Option<u16>> {
+        (0..self.rwreg.len())
+            .map(|n| self.rwreg(n as u16))
+            .collect()
+    }
+
+    /// Rohwert eines Lese(Read)-Registers, `None` wenn ungültig
+    pub fn rreg(&self, number: u16) -> Option<u16> {
+        match self.rreg_valid.get(number as usize) {
+            Some(true) => Some(self.rreg[number as usize]),
+            _ => None,
+        }
+    }
+
+    /// Rohwert eines Lese/Schreib(Read/Write)-Registers, `None` wenn ungültig
+    pub fn rwreg(&self, number: u16) -> Option<u16> {
+        match self.rwreg_valid.get(number as usize) {
+            Some(true) => Some(self.rwreg[number as usize]),
+            _ => None,
+        }
+    }
+
+    /// Paare aus Registernummer und Rohwert der Lese/Schreib(Read/Write)-Register
+    /// `numbers`, `None` wenn eines nicht gültig ist
+    pub fn rwreg_pairs(&self, numbers: &[u16]) -> Option<Vec<(u16, u16)>> {
+        numbers
+            .iter()
+            .map(|number| Some((*number, self.rwreg(*number)?)))
+            .collect()
+    }
+
+    /// Alle Lese(Read)-Register `numbers` sind gültig
+    pub fn has_rregs(&self, numbers: &[u16]) -> bool {
+        numbers.iter().all(|number| self.rreg(*number).is_some())
+    }
+
+    /// Lese(Read)-Register in der Einheit aus der Registertabelle
+    fn scaled_rreg(&self, number: u16) -> f64 {
+        let raw = self.rreg[number as usize];
+        match find_rreg(number) {
+            Some(reg) => reg.scaled(raw),
+            None => f64::from(raw),
+        }
+    }
+
+    pub async fn update(
+        &mut self,
+        tty_path: &str,
+        modbus_address: u8,
+        settings: &SerialPortSettings,
+    ) -> Result<(), std::io::Error> {
+        for reg in RREGS {
+            let value = timeout(
+                Duration::from_millis(100),
+                read_register(tty_path, modbus_address, settings, reg),
+            )
+            .await?;
+            self.rreg[reg.number as usize] = value?[0];
+            self.rreg_valid[reg.number as usize] = true;
         }
         Ok(())
     }
@@ -34,8 +728,8 @@ impl NE4 {
     }
 
     /// Arbeitsweise (Sensor)
-    pub fn work_mode(&self) -> u16 {
-        self.rreg[1]
+    pub fn work_mode(&self) -> WorkingMode {
+        WorkingMode::from(self.rreg[1])
     }
 
     /// Gaskonzentration in ppm
@@ -43,14 +737,14 @@ impl NE4 {
         self.rreg[2]
     }
 
-    /// Berechneter Ausgangsstrom in mA (mit zwei Kommastellen)
-    pub fn output_current(&self) -> u16 {
-        self.rreg[3]
+    /// Berechneter Ausgangsstrom in mA
+    pub fn output_current(&self) -> f64 {
+        self.scaled_rreg(3)
     }
 
-    /// Interne Leiterplattentemperatur in °C (mit Kommastelle)
-    pub fn int_temp(&self) -> u16 {
-        self.rreg[4]
+    /// Interne Leiterplattentemperatur in °C (-20,0 … 60,0)
+    pub fn int_temp(&self) -> f64 {
+        self.scaled_rreg(4)
     }
 
     /// AD-Wert der Temperaturmessung
@@ -68,14 +762,14 @@ impl NE4 {
         self.rreg[42]
     }
 
-    /// Verstärkungsfaktor durch Poti (100 = 1,00)
-    pub fn amplification_poti(&self) -> u16 {
-        self.rreg[43]
+    /// Verstärkungsfaktor durch Poti (1.0 = 100)
+    pub fn amplification_poti(&self) -> f64 {
+        self.scaled_rreg(43)
     }
 
-    /// Verstärkungsfaktor durch Temperaturkennlinie (100 = 1,00)
-    pub fn amplification_temp(&self) -> u16 {
-        self.rreg[44]
+    /// Verstärkungsfaktor durch Temperaturkennlinie (1.0 = 100)
+    pub fn amplification_temp(&self) -> f64 {
+        self.scaled_rreg(44)
     }
 
     /// Korrigierter AD-Wert des Sensors
@@ -94,21 +788,39 @@ impl NE4 {
     }
 }
 
+/// Werte einer Abfrage übernehmen, siehe `NE4::update_rregs`
+fn update_registers(registers: &mut [u16], valid: &mut [bool], values: &[Option<u16>]) {
+    for ((register, valid), value) in registers.iter_mut().zip(valid.iter_mut()).zip(values) {
+        match value {
+            Some(value) => {
+                *register = *value;
+                *valid = true;
+            }
+            None => *valid = false,
+        }
+    }
+}
+
 impl fmt::Display for NE4 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "NE4-MOD-BUS")
     }
 }
 
-async fn read_register(i: usize) -> Result<Vec<u16>, futures::io::Error> {
-    let tty_path = "/dev/ttyUSB0";
-    let slave = Slave(247);
-    let mut settings = SerialPortSettings::default();
-    settings.baud_rate = 9600;
-    let port = Serial::from_path(tty_path, &settings).unwrap();
+async fn read_register(
+    tty_path: &str,
+    modbus_address: u8,
+    settings: &SerialPortSettings,
+    reg: &Register,
+) -> Result<Vec<u16>, futures::io::Error> {
+    let slave = Slave(modbus_address);
+    let port = Serial::from_path(tty_path, settings)?;
 
     let mut ctx = rtu::connect_slave(port, slave).await?;
-    ctx.read_holding_registers(i as u16, 1).await
+    match reg.function {
+        FunctionCode::ReadHoldingRegisters => ctx.read_holding_registers(reg.number, 1).await,
+        FunctionCode::ReadInputRegisters => ctx.read_input_registers(reg.number, 1).await,
+    }
 }
 
 pub const VALUE_RREG_START: u16 = 0x0000;
@@ -135,17 +847,202 @@ mod tests {
     fn wiring() {
         let ne4 = NE4::new();
         assert_eq!(ne4.rreg.len(), 50);
+        assert_eq!(ne4.rwreg.len(), 100);
+    }
+
+    #[test]
+    fn register_numbers_unique() {
+        for table in &[RREGS, RWREGS] {
+            let mut numbers: Vec<u16> = table.iter().map(|reg| reg.number).collect();
+            numbers.sort();
+            numbers.dedup();
+            assert_eq!(numbers.len(), table.len());
+        }
+    }
+
+    #[test]
+    fn register_numbers_sorted() {
+        for table in &[RREGS, RWREGS] {
+            assert!(table.windows(2).all(|w| w[0].number < w[1].number));
+        }
+    }
+
+    #[test]
+    fn rreg_fit_into_sensor() {
+        let ne4 = NE4::new();
+        assert!(RREGS
+            .iter()
+            .all(|reg| (reg.number as usize) < ne4.rreg.len()));
+        assert!(RWREGS
+            .iter()
+            .all(|reg| (reg.number as usize) < ne4.rwreg.len()));
+    }
+
+    #[test]
+    fn set_registers() {
+        let mut ne4 = NE4::new();
+        ne4.set_rregs(&[1, 2, 3]);
+        ne4.set_rwregs(&vec![7; 200]);
+        assert_eq!(&ne4.rregs()[..4], &[Some(1), Some(2), Some(3), None]);
+        assert_eq!(ne4.rreg(2), Some(3));
+        assert_eq!(ne4.rreg(50), None);
+        assert_eq!(ne4.rwregs().len(), 100);
+        assert_eq!(ne4.rwreg(99), Some(7));
     }
 
-    // #[tokio::test]
-    // async fn update() {
-    //     let mut ne4 = NE4::new();
-    //     assert_eq!(*ne4.rreg.first().unwrap(), 0u16);
-    //     assert_eq!(*ne4.rreg.last().unwrap(), 0u16);
-    //     ne4.update().await.unwrap();
-    //     assert_eq!(*ne4.rreg.first().unwrap(), 0);
-    //     assert_eq!(*ne4.rreg.last().unwrap(), 0);
-    // }
+    #[test]
+    fn rwreg_pairs() {
+        let mut ne4 = NE4::new();
+        assert_eq!(ne4.rwreg_pairs(&[15, 16]), None);
+        ne4.set_rwregs(&[0, 1, 2, 3]);
+        assert_eq!(ne4.rwreg_pairs(&[3, 1]), Some(vec![(3, 3), (1, 1)]));
+        assert_eq!(ne4.rwreg_pairs(&[3, 4]), None);
+        assert_eq!(register_pairs(&[3, 1], &[3, 1]), vec![(3, 3), (1, 1)]);
+    }
+
+    #[test]
+    fn limits() {
+        assert_eq!(find_rwreg(16).unwrap().limits(), (0.0, 25.0));
+        assert_eq!(find_rwreg(17).unwrap().limits(), (0.0, 10000.0));
+    }
+
+    #[test]
+    fn invalid_registers() {
+        let mut ne4 = NE4::new();
+        assert_eq!(ne4.rreg(0), None);
+        assert!(!ne4.has_rregs(&[2]));
+        ne4.update_rregs(&[Some(10), Some(20), Some(30)]);
+        assert!(ne4.has_rregs(&[0, 1, 2]));
+        // Block mit Rreg 1 nicht gelesen
+        ne4.update_rregs(&[Some(11), None, Some(31)]);
+        assert_eq!(ne4.rreg(0), Some(11));
+        assert_eq!(ne4.rreg(1), None);
+        assert!(!ne4.has_rregs(&[0, 1]));
+        // Der umgerechnete Wert behält den letzten gelesenen Wert
+        assert_eq!(ne4.work_mode().code(), 20);
+        ne4.update_rregs(&[None, Some(21)]);
+        assert_eq!(ne4.rreg(0), None);
+        assert_eq!(ne4.rreg(1), Some(21));
+        assert_eq!(ne4.rreg(2), Some(31));
+    }
+
+    #[test]
+    fn working_mode() {
+        assert!(WorkingMode::all().all(|mode| WorkingMode::from(mode.code()) == mode));
+        assert_eq!(WorkingMode::all().count(), GASES.len() + 1);
+        assert_eq!(WorkingMode::from(0), WorkingMode::Unkonfiguriert);
+        assert_eq!(WorkingMode::from(30).gas().unwrap().formula, "NO2");
+        assert_eq!(WorkingMode::from(31), WorkingMode::Unknown(31));
+        assert_eq!(WorkingMode::from(30).to_string(), "NO2 20 ppm");
+    }
+
+    #[test]
+    fn rwreg_output_current_upper_point() {
+        let reg = find_rwreg(18).unwrap();
+        assert_eq!(reg.default, Some(2000));
+        assert_eq!(reg.unit, "mA");
+        assert_eq!(find_rwreg(16).unwrap().default, Some(400));
+    }
+
+    #[test]
+    fn function_codes() {
+        assert!(RREGS
+            .iter()
+            .all(|reg| reg.function == FunctionCode::ReadInputRegisters && !reg.writable));
+        assert!(RWREGS
+            .iter()
+            .all(|reg| reg.function == FunctionCode::ReadHoldingRegisters));
+    }
+
+    #[test]
+    fn read_only_rwregs() {
+        let read_only: Vec<u16> = RWREGS
+            .iter()
+            .filter(|reg| !reg.writable)
+            .map(|reg| reg.number)
+            .collect();
+        assert_eq!(read_only, vec![20, 96, 97]);
+    }
+
+    #[test]
+    fn format() {
+        let reg = find_rreg(3).unwrap();
+        assert_eq!(reg.decimals(), 2);
+        assert_eq!(reg.format(1234), "12.34");
+        let reg = find_rreg(4).unwrap();
+        assert_eq!(reg.decimals(), 1);
+        assert_eq!(reg.format(0xFF38), "-20.0");
+        assert_eq!(find_rreg(2).unwrap().format(25), "25");
+    }
+
+    #[test]
+    fn range_label() {
+        assert_eq!(find_rwreg(16).unwrap().range_label(), "0 … 2500 [400]");
+        assert_eq!(find_rwreg(20).unwrap().range_label(), "0 / 1");
+        assert_eq!(find_rwreg(95).unwrap().range_label(), "0, 129 … 256 [90]");
+    }
+
+    #[test]
+    fn contains() {
+        let reg = find_rwreg(4).unwrap();
+        assert!(reg.contains(-200));
+        assert!(reg.contains(600));
+        assert!(!reg.contains(11111));
+        assert!(!reg.contains(-201));
+        assert!(!reg.contains(601));
+        assert!(reg.accepts(11111));
+        assert!(!reg.accepts(601));
+
+        let reg = find_rwreg(95).unwrap();
+        assert!(!reg.contains(90));
+        assert!(!reg.accepts(90));
+        assert!(reg.accepts(129));
+        assert!(reg.check_raw(129).is_ok());
+        match reg.check_raw(90) {
+            Err(Error::OutOfRange {
+                register: 95,
+                value: 90,
+                ..
+            }) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn register_values() {
+        let registers = [(15, 0), (16, 400), (18, 2000)];
+        assert_eq!(register_value(&registers, 16), Some(400));
+        assert_eq!(register_value(&registers, 17), None);
+    }
+
+    #[test]
+    fn signed_temperature() {
+        let reg = find_rreg(4).unwrap();
+        assert_eq!(reg.raw_to_value(0xFF38), -200);
+        assert_eq!(reg.value_to_raw(-200), 0xFF38);
+        assert_eq!(reg.scaled(0xFF38), -20.0);
+        assert_eq!(reg.scaled(235), 23.5);
+    }
+
+    /// Runs against the NE4 simulator on a pty
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn update() {
+        use crate::simulator::{model::Ne4Model, model::SOFTWARE_DATE, Faults, Simulator};
+
+        let simulator = Simulator::new(Ne4Model::new(247), Faults::default(), 1).unwrap();
+        let tty_path = simulator.path().to_string();
+        simulator.spawn();
+
+        let mut ne4 = NE4::new();
+        assert_eq!(*ne4.rreg.first().unwrap(), 0u16);
+        assert_eq!(*ne4.rreg.last().unwrap(), 0u16);
+        ne4.update(&tty_path, 247, &SerialPortSettings::default())
+            .await
+            .unwrap();
+        assert_eq!(*ne4.rreg.first().unwrap(), 0);
+        assert_eq!(*ne4.rreg.last().unwrap(), SOFTWARE_DATE);
+    }
 
     /// Test Rreg functions
     #[test]
@@ -158,8 +1055,9 @@ mod tests {
     #[test]
     fn work_mode() {
         let mut ne4 = NE4::new();
-        ne4.rreg[1] = 0x1000;
-        assert_eq!(ne4.work_mode(), 4096);
+        ne4.rreg[1] = 60;
+        assert_eq!(ne4.work_mode(), WorkingMode::from(60));
+        assert_eq!(ne4.work_mode().gas().unwrap().formula, "H2S");
     }
     #[test]
     fn concentration_gas() {
@@ -170,14 +1068,16 @@ mod tests {
     #[test]
     fn output_current() {
         let mut ne4 = NE4::new();
-        ne4.rreg[3] = 0x1000;
-        assert_eq!(ne4.output_current(), 4096);
+        ne4.rreg[3] = 1234;
+        assert_eq!(ne4.output_current(), 12.34);
     }
     #[test]
     fn int_temp() {
         let mut ne4 = NE4::new();
-        ne4.rreg[4] = 0x1000;
-        assert_eq!(ne4.int_temp(), 4096);
+        ne4.rreg[4] = 235;
+        assert_eq!(ne4.int_temp(), 23.5);
+        ne4.rreg[4] = 0xFF38;
+        assert_eq!(ne4.int_temp(), -20.0);
     }
     #[test]
     fn adc_temp() {
@@ -200,14 +1100,14 @@ mod tests {
     #[test]
     fn amplification_poti() {
         let mut ne4 = NE4::new();
-        ne4.rreg[43] = 0x1000;
-        assert_eq!(ne4.amplification_poti(), 4096);
+        ne4.rreg[43] = 150;
+        assert_eq!(ne4.amplification_poti(), 1.5);
     }
     #[test]
     fn amplification_temp() {
         let mut ne4 = NE4::new();
-        ne4.rreg[44] = 0x1000;
-        assert_eq!(ne4.amplification_temp(), 4096);
+        ne4.rreg[44] = 95;
+        assert_eq!(ne4.amplification_temp(), 0.95);
     }
     #[test]
     fn adc_sensor_corrected() {
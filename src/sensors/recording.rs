@@ -0,0 +1,205 @@
+/// Aufzeichnung der Messwerte als CSV Datei
+///
+/// Jede Zeile enthält einen Zeitstempel und die ausgewählten Lese(Read)-Register,
+/// umgerechnet in die Einheit des Registers (z.B. mA mit zwei Kommastellen).
+use super::ra_gas_ne4::{Register, RREGS};
+use chrono::{DateTime, Local};
+use std::{fs::File, io, path::Path, time::Duration};
+
+/// Einstellungen einer Aufzeichnung
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordingSettings {
+    /// Mindestabstand zwischen zwei Zeilen
+    pub interval: Duration,
+    /// Nummern der aufgezeichneten Lese(Read)-Register
+    pub registers: Vec<u16>,
+}
+
+impl Default for RecordingSettings {
+    /// Alle Lese(Read)-Register, eine Zeile pro Sekunde
+    fn default() -> Self {
+        RecordingSettings {
+            interval: Duration::from_secs(1),
+            registers: RREGS.iter().map(|reg| reg.number).collect(),
+        }
+    }
+}
+
+/// Schreibt die Messwerte in eine CSV Datei
+pub struct Recorder<W: io::Write> {
+    writer: csv::Writer<W>,
+    registers: Vec<&'static Register>,
+    interval: chrono::Duration,
+    last: Option<DateTime<Local>>,
+    rows: usize,
+}
+
+impl Recorder<File> {
+    /// Neue Datei anlegen, eine vorhandene Datei wird überschrieben
+    pub fn create<P: AsRef<Path>>(path: P, settings: &RecordingSettings) -> csv::Result<Self> {
+        Recorder::new(File::create(path)?, settings)
+    }
+}
+
+impl<W: io::Write> Recorder<W> {
+    /// Aufzeichnung beginnen, die Kopfzeile wird sofort geschrieben
+    pub fn new(writer: W, settings: &RecordingSettings) -> csv::Result<Self> {
+        let mut recorder = Recorder {
+            writer: csv::Writer::from_writer(writer),
+            registers: RREGS
+                .iter()
+                .filter(|reg| settings.registers.contains(&reg.number))
+                .collect(),
+            interval: chrono::Duration::from_std(settings.interval)
+                .unwrap_or_else(|_| chrono::Duration::zero()),
+            last: None,
+            rows: 0,
+        };
+        let mut header = vec!["Zeitstempel".to_string()];
+        header.extend(recorder.registers.iter().map(|reg| column_name(reg)));
+        recorder.writer.write_record(&header)?;
+        recorder.writer.flush()?;
+        Ok(recorder)
+    }
+
+    /// Messwerte aufzeichnen
+    ///
+    /// `values` ist nach der Registernummer indiziert, z.B. `NE4::rregs()`.
+    /// Ungültige Register (`None`) bleiben leer. Liegt die letzte Zeile noch keinen
+    /// Intervall zurück oder ist keines der aufgezeichneten Register gültig, wird
+    /// nichts geschrieben und `false` zurückgegeben.
+    pub fn record(
+        &mut self,
+        timestamp: DateTime<Local>,
+        values: &[Option<u16>],
+    ) -> csv::Result<bool> {
+        if let Some(last) = self.last {
+            if timestamp - last < self.interval {
+                return Ok(false);
+            }
+        }
+        let value = |reg: &Register| values.get(reg.number as usize).cloned().flatten();
+        if self.registers.iter().all(|reg| value(reg).is_none()) {
+            return Ok(false);
+        }
+
+        let mut row = vec![timestamp.format("%Y-%m-%d %H:%M:%S%.3f").to_string()];
+        row.extend(
+            self.registers
+                .iter()
+                .map(|reg| value(reg).map_or_else(String::new, |raw| reg.format(raw))),
+        );
+        self.writer.write_record(&row)?;
+        // Jede Zeile sofort schreiben, damit bei einem Absturz nichts verloren geht
+        self.writer.flush()?;
+
+        self.last = Some(timestamp);
+        self.rows += 1;
+        Ok(true)
+    }
+
+    /// Anzahl der geschriebenen Zeilen ohne Kopfzeile
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+}
+
+/// Spaltenüberschrift, z.B. "Rreg 2 Gaskonzentration im ppm"
+pub fn column_name(reg: &Register) -> String {
+    format!("Rreg {} {}", reg.number, reg.description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn settings() -> RecordingSettings {
+        RecordingSettings {
+            interval: Duration::from_secs(2),
+            registers: vec![2, 3, 4, 43],
+        }
+    }
+
+    fn output(recorder: &Recorder<Vec<u8>>) -> String {
+        String::from_utf8(recorder.writer.get_ref().clone()).unwrap()
+    }
+
+    #[test]
+    fn default_records_all_rregs() {
+        assert_eq!(RecordingSettings::default().registers.len(), RREGS.len());
+    }
+
+    #[test]
+    fn header() {
+        let recorder = Recorder::new(vec![], &settings()).unwrap();
+        assert_eq!(
+            output(&recorder).lines().next().unwrap(),
+            "Zeitstempel,Rreg 2 Gaskonzentration im ppm,\
+             Rreg 3 Berechneter Ausgangsstrom in mA (mit zwei Kommastellen),\
+             Rreg 4 Interne Leiterplattentemperatur in °C (mit Kommastelle),\
+             \"Rreg 43 Verstärkungsfaktor durch Poti (100 = 1,00)\""
+        );
+    }
+
+    #[test]
+    fn scaled_values() {
+        let mut values = vec![Some(0u16); 50];
+        values[2] = Some(25);
+        values[3] = Some(1234);
+        values[4] = Some(0xFF38);
+        values[43] = Some(100);
+        let mut recorder = Recorder::new(vec![], &settings()).unwrap();
+        let timestamp = Local.ymd(2020, 8, 20).and_hms_milli(12, 0, 0, 500);
+        assert!(recorder.record(timestamp, &values).unwrap());
+        assert_eq!(
+            output(&recorder).lines().nth(1).unwrap(),
+            "2020-08-20 12:00:00.500,25,12.34,-20.0,1.00"
+        );
+    }
+
+    #[test]
+    fn interval() {
+        let values = vec![Some(0u16); 50];
+        let mut recorder = Recorder::new(vec![], &settings()).unwrap();
+        let start = Local.ymd(2020, 8, 20).and_hms(12, 0, 0);
+        assert!(recorder.record(start, &values).unwrap());
+        assert!(!recorder
+            .record(start + chrono::Duration::seconds(1), &values)
+            .unwrap());
+        assert!(recorder
+            .record(start + chrono::Duration::seconds(2), &values)
+            .unwrap());
+        assert_eq!(recorder.rows(), 2);
+        assert_eq!(output(&recorder).lines().count(), 3);
+    }
+
+    #[test]
+    fn missing_values_are_empty() {
+        let mut recorder = Recorder::new(vec![], &settings()).unwrap();
+        let timestamp = Local.ymd(2020, 8, 20).and_hms(12, 0, 0);
+        recorder.record(timestamp, &[None, None, Some(7)]).unwrap();
+        assert_eq!(
+            output(&recorder).lines().nth(1).unwrap(),
+            "2020-08-20 12:00:00.000,7,,,"
+        );
+    }
+
+    #[test]
+    fn invalid_values_are_skipped() {
+        let mut values = vec![Some(0u16); 50];
+        values[3] = None;
+        let mut recorder = Recorder::new(vec![], &settings()).unwrap();
+        let start = Local.ymd(2020, 8, 20).and_hms(12, 0, 0);
+        assert!(recorder.record(start, &values).unwrap());
+        assert_eq!(
+            output(&recorder).lines().nth(1).unwrap(),
+            "2020-08-20 12:00:00.000,0,,0.0,0.00"
+        );
+        // Abfrage komplett fehlgeschlagen, keine Zeile
+        assert!(!recorder
+            .record(start + chrono::Duration::seconds(2), &[None; 50])
+            .unwrap());
+        assert_eq!(recorder.rows(), 1);
+    }
+}
@@ -0,0 +1,156 @@
+/// Temperaturkennlinie der Sensorzelle
+///
+/// Rwreg 30 bis 36 enthalten die Kennlinie des Sensorherstellers bei -20, 0, 10,
+/// 20, 30, 40 und 60 °C als Verstärkungsfaktoren von 50 bis 200 (0,50 … 2,00).
+/// Zwischen den Stützpunkten wird linear interpoliert, außerhalb gilt der Wert des
+/// nächsten Stützpunkts. Der Sensor meldet die Verstärkung bei der aktuellen
+/// Leiterplattentemperatur in Rreg 44.
+use super::ra_gas_ne4::{find_rwreg, register_pairs, register_value, Register, NE4};
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+
+/// Lese/Schreib(Read/Write)-Register der Kennlinie
+pub const CURVE_RWREGS: [u16; 7] = [30, 31, 32, 33, 34, 35, 36];
+/// Temperatur der Stützpunkte in °C
+pub const CURVE_TEMPERATURES: [f64; 7] = [-20.0, 0.0, 10.0, 20.0, 30.0, 40.0, 60.0];
+/// Art der Vorlagen, siehe `presets::presets_path`
+pub const CURVE_PRESETS: &str = "kennlinie";
+
+/// Verstärkungsfaktoren der Kennlinie als Rohwerte (100 = 1,00)
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct TemperatureCurve {
+    pub factors: [u16; 7],
+}
+
+impl Default for TemperatureCurve {
+    /// Kennlinie ohne Temperaturkompensation
+    fn default() -> Self {
+        TemperatureCurve { factors: [100; 7] }
+    }
+}
+
+impl TemperatureCurve {
+    /// Kennlinie aus gelesenen Registern, `None` ohne alle sieben Stützpunkte
+    pub fn from_registers(rwregs: &[(u16, u16)]) -> Option<Self> {
+        let mut factors = [0; 7];
+        for (factor, number) in factors.iter_mut().zip(CURVE_RWREGS.iter()) {
+            *factor = register_value(rwregs, *number)?;
+        }
+        Some(TemperatureCurve { factors })
+    }
+
+    /// Kennlinie aus den abgefragten Rwreg's
+    pub fn from_ne4(ne4: &NE4) -> Option<Self> {
+        TemperatureCurve::from_registers(&ne4.rwreg_pairs(&CURVE_RWREGS)?)
+    }
+
+    /// Registernummer und Rohwert
+    pub fn registers(&self) -> Vec<(u16, u16)> {
+        register_pairs(&CURVE_RWREGS, &self.factors)
+    }
+
+    /// Alle Faktoren liegen im Wertebereich der Register
+    pub fn validate(&self) -> Result<()> {
+        for (number, value) in self.registers() {
+            curve_register(number).check_raw(value)?;
+        }
+        Ok(())
+    }
+
+    /// Verstärkungsfaktor eines Stützpunkts, z.B. 1.05
+    pub fn factor(&self, index: usize) -> f64 {
+        f64::from(self.factors[index]) / 100.0
+    }
+
+    /// Verstärkungsfaktor eines Stützpunkts setzen
+    ///
+    /// Der Wert wird gerundet und auf den Wertebereich der Register begrenzt.
+    pub fn set_factor(&mut self, index: usize, factor: f64) {
+        let (min, max) = factor_limits();
+        let factor = if factor.is_finite() { factor } else { 1.0 };
+        self.factors[index] = (factor.max(min).min(max) * 100.0).round() as u16;
+    }
+
+    /// Interpolierter Verstärkungsfaktor bei der Temperatur `temperature` in °C
+    pub fn gain_at(&self, temperature: f64) -> f64 {
+        let last = CURVE_TEMPERATURES.len() - 1;
+        if temperature <= CURVE_TEMPERATURES[0] {
+            return self.factor(0);
+        }
+        if temperature >= CURVE_TEMPERATURES[last] {
+            return self.factor(last);
+        }
+        let index = CURVE_TEMPERATURES
+            .windows(2)
+            .position(|pair| temperature < pair[1])
+            .unwrap_or(last - 1);
+        let (t0, t1) = (CURVE_TEMPERATURES[index], CURVE_TEMPERATURES[index + 1]);
+        let (f0, f1) = (self.factor(index), self.factor(index + 1));
+        f0 + (temperature - t0) * (f1 - f0) / (t1 - t0)
+    }
+}
+
+/// Kleinster und größter Verstärkungsfaktor, z.B. (0.5, 2.0)
+pub fn factor_limits() -> (f64, f64) {
+    curve_register(CURVE_RWREGS[0]).limits()
+}
+
+fn curve_register(number: u16) -> &'static Register {
+    find_rwreg(number).expect("Kennlinie sind Rwreg's")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    const CURVE: TemperatureCurve = TemperatureCurve {
+        factors: [150, 120, 110, 100, 95, 90, 80],
+    };
+
+    #[test]
+    fn gain() {
+        assert_eq!(CURVE.gain_at(-30.0), 1.5);
+        assert_eq!(CURVE.gain_at(-20.0), 1.5);
+        assert!((CURVE.gain_at(-10.0) - 1.35).abs() < 1e-9);
+        assert_eq!(CURVE.gain_at(20.0), 1.0);
+        assert!((CURVE.gain_at(25.0) - 0.975).abs() < 1e-9);
+        assert!((CURVE.gain_at(50.0) - 0.85).abs() < 1e-9);
+        assert_eq!(CURVE.gain_at(60.0), 0.8);
+        assert_eq!(CURVE.gain_at(70.0), 0.8);
+    }
+
+    #[test]
+    fn factors() {
+        let mut curve = TemperatureCurve::default();
+        assert!(curve.validate().is_ok());
+        curve.set_factor(0, 1.234);
+        curve.set_factor(1, 3.0);
+        curve.set_factor(2, 0.1);
+        curve.set_factor(3, f64::NAN);
+        assert_eq!(curve.factors, [123, 200, 50, 100, 100, 100, 100]);
+
+        curve.factors[6] = 201;
+        match curve.validate() {
+            Err(Error::OutOfRange { register, .. }) => assert_eq!(register, 36),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(factor_limits(), (0.5, 2.0));
+    }
+
+    #[test]
+    fn registers() {
+        let rwregs = CURVE.registers();
+        assert_eq!(rwregs[0], (30, 150));
+        assert_eq!(rwregs[6], (36, 80));
+        assert_eq!(TemperatureCurve::from_registers(&rwregs), Some(CURVE));
+
+        let mut ne4 = NE4::new();
+        let mut values = vec![0u16; 37];
+        for (number, value) in &rwregs {
+            values[*number as usize] = *value;
+        }
+        ne4.set_rwregs(&values);
+        assert_eq!(TemperatureCurve::from_ne4(&ne4), Some(CURVE));
+    }
+}
@@ -0,0 +1,242 @@
+/// Testwertvorgaben
+///
+/// Über Rwreg 2, 3, 4 und 42 kann der Sensor Gaskonzentration, Ausgangsstrom,
+/// Temperatur und Sensor AD-Wert vorgegeben bekommen, z.B. um die Auswertung
+/// einer Zentrale zu prüfen. 11111 schaltet die Vorgabe aus. Ein Sensor darf nie
+/// mit aktiver Vorgabe im Feld bleiben, deshalb werden alle gesetzten Vorgaben
+/// gemerkt und beim Trennen zurückgesetzt.
+use super::ra_gas_ne4::{find_rwreg, Register, NE4};
+use crate::error::{Error, Result};
+use std::fmt;
+
+/// Rohwert der die Vorgabe ausschaltet
+pub const TEST_VALUE_OFF: u16 = 11111;
+
+/// Vorgebbarer Messwert
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TestValue {
+    /// Gaskonzentration in ppm, Rwreg 2
+    Concentration,
+    /// Ausgangsstrom in mA, Rwreg 3
+    OutputCurrent,
+    /// Leiterplattentemperatur in °C, Rwreg 4
+    Temperature,
+    /// AD-Wert des Sensors, Rwreg 42
+    SensorAdc,
+}
+
+impl TestValue {
+    /// Alle Vorgaben, in der Reihenfolge der Anzeige
+    pub const ALL: [TestValue; 4] = [
+        TestValue::Concentration,
+        TestValue::OutputCurrent,
+        TestValue::Temperature,
+        TestValue::SensorAdc,
+    ];
+
+    /// Rwreg der Vorgabe
+    pub fn register_number(self) -> u16 {
+        match self {
+            TestValue::Concentration => 2,
+            TestValue::OutputCurrent => 3,
+            TestValue::Temperature => 4,
+            TestValue::SensorAdc => 42,
+        }
+    }
+
+    /// Eintrag der Registertabelle
+    pub fn register(self) -> &'static Register {
+        find_rwreg(self.register_number()).expect("Testwerte sind Rwreg's")
+    }
+
+    /// Vorgabe zum Rwreg, `None` wenn das Register keine Vorgabe ist
+    pub fn from_register(number: u16) -> Option<Self> {
+        TestValue::ALL
+            .iter()
+            .find(|test_value| test_value.register_number() == number)
+            .cloned()
+    }
+
+    /// Einheit, z.B. "mA"
+    pub fn unit(self) -> &'static str {
+        self.register().unit
+    }
+
+    /// Kleinster und größter Wert in der Einheit der Vorgabe
+    pub fn limits(self) -> (f64, f64) {
+        self.register().limits()
+    }
+
+    /// Wert in der Einheit der Vorgabe in den Rohwert umrechnen
+    ///
+    /// Werte außerhalb des Wertebereichs werden abgelehnt, ebenso Werte die
+    /// zufällig dem Rohwert 11111 entsprechen.
+    pub fn to_raw(self, value: f64) -> Result<u16> {
+        let reg = self.register();
+        let scaled = (value * f64::from(reg.scale)).round();
+        let out_of_range = || Error::OutOfRange {
+            register: reg.number,
+            value: scaled as i32,
+            range: reg.range_label(),
+        };
+        if !scaled.is_finite() || scaled < f64::from(i32::MIN) || scaled > f64::from(i32::MAX) {
+            return Err(out_of_range());
+        }
+        let raw = reg.value_to_raw(scaled as i32);
+        let in_range = reg
+            .ranges
+            .iter()
+            .any(|(min, max)| *min <= scaled as i32 && scaled as i32 <= *max);
+        if !in_range || raw == TEST_VALUE_OFF {
+            return Err(out_of_range());
+        }
+        Ok(raw)
+    }
+
+    /// Rohwert in die Einheit der Vorgabe umrechnen, `None` wenn die Vorgabe aus ist
+    pub fn from_raw(self, raw: u16) -> Option<f64> {
+        match raw {
+            TEST_VALUE_OFF => None,
+            raw => Some(self.register().scaled(raw)),
+        }
+    }
+
+    /// Wert mit Einheit, z.B. "12.00 mA"
+    pub fn format(self, value: f64) -> String {
+        let value = format!("{:.*}", self.register().decimals(), value);
+        match self.unit() {
+            "" => value,
+            unit => format!("{} {}", value, unit),
+        }
+    }
+
+    /// Aktuelle Vorgabe des Sensors, `None` wenn sie aus ist
+    pub fn current(self, ne4: &NE4) -> Option<f64> {
+        ne4.rwreg(self.register_number())
+            .and_then(|raw| self.from_raw(raw))
+    }
+}
+
+impl fmt::Display for TestValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TestValue::Concentration => write!(f, "Gaskonzentration"),
+            TestValue::OutputCurrent => write!(f, "Ausgangsstrom"),
+            TestValue::Temperature => write!(f, "Temperatur"),
+            TestValue::SensorAdc => write!(f, "Sensor AD-Wert"),
+        }
+    }
+}
+
+/// Vorgaben die der Sensor gerade aktiv hat
+pub fn active_test_values(ne4: &NE4) -> Vec<TestValue> {
+    TestValue::ALL
+        .iter()
+        .filter(|test_value| test_value.current(ne4).is_some())
+        .cloned()
+        .collect()
+}
+
+/// Eine gesetzte Vorgabe
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Override {
+    pub port: String,
+    pub modbus_address: u8,
+    pub test_value: TestValue,
+}
+
+/// Alle gesetzten Vorgaben, die noch zurückgesetzt werden müssen
+#[derive(Clone, Debug, Default)]
+pub struct Overrides {
+    overrides: Vec<Override>,
+}
+
+impl Overrides {
+    pub fn new() -> Self {
+        Overrides::default()
+    }
+
+    /// Vorgabe merken, jede Vorgabe wird nur einmal geführt
+    pub fn insert(&mut self, port: &str, modbus_address: u8, test_value: TestValue) {
+        let new = Override {
+            port: port.to_string(),
+            modbus_address,
+            test_value,
+        };
+        if !self.overrides.contains(&new) {
+            self.overrides.push(new);
+        }
+    }
+
+    /// Vorgabe ist zurückgesetzt
+    pub fn remove(&mut self, port: &str, modbus_address: u8, test_value: TestValue) {
+        self.overrides.retain(|o| {
+            !(o.port == port && o.modbus_address == modbus_address && o.test_value == test_value)
+        });
+    }
+
+    /// Vorgaben an der Schnittstelle
+    pub fn on_port(&self, port: &str) -> Vec<Override> {
+        self.overrides
+            .iter()
+            .filter(|o| o.port == port)
+            .cloned()
+            .collect()
+    }
+
+    pub fn all(&self) -> &[Override] {
+        &self.overrides
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_values() {
+        assert_eq!(TestValue::OutputCurrent.to_raw(12.0).unwrap(), 1200);
+        assert_eq!(TestValue::Temperature.to_raw(-5.0).unwrap(), 0xFFCE);
+        assert_eq!(TestValue::Temperature.from_raw(0xFFCE), Some(-5.0));
+        assert_eq!(TestValue::Concentration.from_raw(TEST_VALUE_OFF), None);
+        assert!(TestValue::Concentration.to_raw(10001.0).is_err());
+        assert!(TestValue::OutputCurrent.to_raw(f64::NAN).is_err());
+        assert_eq!(TestValue::Temperature.limits(), (-20.0, 60.0));
+        assert_eq!(TestValue::OutputCurrent.format(4.0), "4.00 mA");
+        assert_eq!(TestValue::SensorAdc.format(800.0), "800");
+        assert_eq!(TestValue::from_register(42), Some(TestValue::SensorAdc));
+        assert_eq!(TestValue::from_register(10), None);
+    }
+
+    #[test]
+    fn active() {
+        let mut ne4 = NE4::new();
+        let mut rwregs = vec![0u16; 43];
+        for number in &[2, 3, 4, 42] {
+            rwregs[*number] = TEST_VALUE_OFF;
+        }
+        ne4.set_rwregs(&rwregs);
+        assert!(active_test_values(&ne4).is_empty());
+        rwregs[3] = 400;
+        ne4.set_rwregs(&rwregs);
+        assert_eq!(active_test_values(&ne4), vec![TestValue::OutputCurrent]);
+        assert_eq!(TestValue::OutputCurrent.current(&ne4), Some(4.0));
+    }
+
+    #[test]
+    fn overrides() {
+        let mut overrides = Overrides::new();
+        overrides.insert("/dev/ttyUSB0", 247, TestValue::Concentration);
+        overrides.insert("/dev/ttyUSB0", 247, TestValue::Concentration);
+        overrides.insert("/dev/ttyUSB1", 1, TestValue::OutputCurrent);
+        assert_eq!(overrides.all().len(), 2);
+        assert_eq!(overrides.on_port("/dev/ttyUSB1").len(), 1);
+        overrides.remove("/dev/ttyUSB0", 247, TestValue::Concentration);
+        overrides.remove("/dev/ttyUSB1", 1, TestValue::OutputCurrent);
+        assert!(overrides.is_empty());
+    }
+}
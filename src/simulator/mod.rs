@@ -0,0 +1,77 @@
+/// NE4 simulator
+///
+/// A simulated NE4 acting as Modbus RTU slave on a pseudo terminal. Clients open
+/// the slave side of the pty like a real serial port, e.g. `/dev/pts/3`. Used by
+/// the integration tests and for demos without a sensor.
+pub mod model;
+pub mod rtu;
+
+#[cfg(target_os = "linux")]
+mod pty;
+#[cfg(target_os = "linux")]
+pub use pty::Simulator;
+
+use model::Rng;
+
+/// Fault injection
+///
+/// Probabilities in 0.0 … 1.0 per request.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Faults {
+    /// Request isn't answered at all
+    pub timeout: f64,
+    /// Request is answered with the exception "slave device busy"
+    pub exception: f64,
+    /// Response with wrong CRC
+    pub crc_error: f64,
+}
+
+/// Fault chosen for one request
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Fault {
+    Timeout,
+    Exception,
+    CrcError,
+}
+
+impl Faults {
+    /// Roll the dice for one request
+    pub fn choose(&self, rng: &mut Rng) -> Option<Fault> {
+        let dice = rng.next_f64();
+        if dice < self.timeout {
+            Some(Fault::Timeout)
+        } else if dice < self.timeout + self.exception {
+            Some(Fault::Exception)
+        } else if dice < self.timeout + self.exception + self.crc_error {
+            Some(Fault::CrcError)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_faults() {
+        let mut rng = Rng::new(7);
+        assert!((0..1000).all(|_| Faults::default().choose(&mut rng).is_none()));
+    }
+
+    #[test]
+    fn fault_rates() {
+        let faults = Faults {
+            timeout: 0.1,
+            exception: 0.2,
+            crc_error: 0.3,
+        };
+        let mut rng = Rng::new(7);
+        let chosen: Vec<Option<Fault>> = (0..10000).map(|_| faults.choose(&mut rng)).collect();
+        let count = |fault| chosen.iter().filter(|f| **f == fault).count();
+        assert!((800..1200).contains(&count(Some(Fault::Timeout))));
+        assert!((1800..2200).contains(&count(Some(Fault::Exception))));
+        assert!((2700..3300).contains(&count(Some(Fault::CrcError))));
+    }
+}
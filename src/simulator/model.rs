@@ -0,0 +1,385 @@
+/// Register model of a simulated NE4
+///
+/// Models the documented semantics of the sensor: unlocking with 9876 on
+/// Rwreg 49, zero point (Rwreg 10) and span gas (Rwreg 12) with 11111, the
+/// change of the Modbus address (Rwreg 50) and the test value overrides on
+/// Rwreg 2, 3, 4 and 42 (11111 = no override). Rreg 44 follows the temperature
+/// curve in Rwreg 30 to 36. Registers outside of the register tables are rejected
+/// with an exception, like the firmware does.
+use super::rtu::*;
+use crate::sensors::output_scaling::OutputScaling;
+use crate::sensors::ra_gas_ne4::{find_rreg, find_rwreg, RWREGS};
+use crate::sensors::temperature_curve::TemperatureCurve;
+
+/// Password for Rwreg 49
+pub const UNLOCK: u16 = 9876;
+/// Command value for zero point and span gas, also "no override" for test values
+pub const COMMAND: u16 = 11111;
+/// Software date reported in Rreg 49
+pub const SOFTWARE_DATE: u16 = 9040;
+
+/// Sensor AD value in clean air
+const ADC_CLEAN_AIR: f64 = 800.0;
+/// Temperature of the simulated environment
+const TEMPERATURE: f64 = 22.0;
+
+/// Small xorshift generator, reproducible with the same seed
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    /// Random number in 0.0 … 1.0
+    pub fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Ne4Model {
+    holding: Vec<u16>,
+    unlocked: bool,
+    /// Drifting sensor AD value
+    sensor_adc: f64,
+    /// Drifting temperature in °C
+    temperature: f64,
+    /// Gas applied to the sensor, AD counts above clean air
+    gas_adc: f64,
+    /// Registers which reject every write, see `reject_writes`
+    rejected: Vec<u16>,
+}
+
+impl Ne4Model {
+    /// Factory configured NE4 with the given Modbus address
+    pub fn new(modbus_address: u8) -> Self {
+        let mut holding = vec![0u16; 100];
+        for reg in RWREGS {
+            if let Some(default) = reg.default {
+                holding[reg.number as usize] = reg.value_to_raw(default);
+            }
+        }
+        for reg in 30..=36 {
+            holding[reg] = 100;
+        }
+        // Auswerte IC bereit, 3-Elektroden Zelle mit 35 kΩ und 33 Ω
+        holding[20..=29].copy_from_slice(&[1, 1, 5, 1, 0, 1, 0, 0, 0, 3]);
+        holding[10] = ADC_CLEAN_AIR as u16;
+        holding[12] = 4800;
+        holding[13] = 1000;
+        holding[50] = u16::from(modbus_address);
+        holding[96] = 42;
+        holding[97] = 1234;
+        holding[98] = 12345;
+        holding[99] = 10;
+
+        Ne4Model {
+            holding,
+            unlocked: false,
+            sensor_adc: ADC_CLEAN_AIR,
+            temperature: TEMPERATURE,
+            gas_adc: 0.0,
+            rejected: vec![],
+        }
+    }
+
+    pub fn modbus_address(&self) -> u8 {
+        self.holding[50] as u8
+    }
+
+    /// Apply gas to the sensor, in AD counts above clean air
+    pub fn set_gas_adc(&mut self, gas_adc: f64) {
+        self.gas_adc = gas_adc;
+    }
+
+    /// Answer writes to the register with "slave device failure", like a
+    /// defective EEPROM cell
+    pub fn reject_writes(&mut self, number: u16) {
+        self.rejected.push(number);
+    }
+
+    /// Plausible drift of sensor and temperature over `seconds`
+    pub fn drift(&mut self, rng: &mut Rng, seconds: f64) {
+        let target = ADC_CLEAN_AIR + self.gas_adc;
+        self.sensor_adc += (target - self.sensor_adc) * (0.5 * seconds).min(1.0)
+            + (rng.next_f64() - 0.5) * 4.0 * seconds;
+        self.temperature += (TEMPERATURE - self.temperature) * (0.05 * seconds).min(1.0)
+            + (rng.next_f64() - 0.5) * 0.2 * seconds;
+    }
+
+    /// Sensor AD value, Rreg 42
+    fn sensor_adc(&self) -> u16 {
+        match self.holding[42] {
+            COMMAND => self.sensor_adc.max(0.0).min(16383.0).round() as u16,
+            value => value,
+        }
+    }
+
+    /// Calculated gas concentration in ppm, Rreg 46
+    fn concentration(&self) -> u16 {
+        let zero = f64::from(self.holding[10]);
+        let span = f64::from(self.holding[12]);
+        let span_ppm = f64::from(self.holding[13]);
+        if span <= zero {
+            return 0;
+        }
+        let ppm = (f64::from(self.sensor_adc()) - zero) * span_ppm / (span - zero);
+        ppm.max(0.0).min(10000.0).round() as u16
+    }
+
+    /// Output current in 1/100 mA, linear between the lower and the upper point
+    fn output_current(&self, ppm: u16) -> u16 {
+        let scaling = OutputScaling {
+            low_value: self.holding[15],
+            low_current: self.holding[16],
+            high_value: self.holding[17],
+            high_current: self.holding[18],
+        };
+        (scaling.current_at(f64::from(ppm)) * 100.0).round() as u16
+    }
+
+    /// Gain of the temperature curve at the internal temperature, Rreg 44
+    fn temperature_gain(&self) -> u16 {
+        let mut curve = TemperatureCurve::default();
+        curve.factors.copy_from_slice(&self.holding[30..=36]);
+        let temperature = match self.input_register(4) {
+            Some(raw) => f64::from(raw as i16) / 10.0,
+            None => TEMPERATURE,
+        };
+        (curve.gain_at(temperature) * 100.0).round() as u16
+    }
+
+    /// Value of an input register, `None` if the register doesn't exist
+    pub fn input_register(&self, number: u16) -> Option<u16> {
+        find_rreg(number)?;
+        let value = match number {
+            0 => self.holding[0],
+            1 => self.holding[99],
+            2 => match self.holding[2] {
+                COMMAND => self.concentration(),
+                value => value,
+            },
+            3 => match self.holding[3] {
+                COMMAND => self.output_current(self.input_register(2)?),
+                value => value,
+            },
+            4 => match self.holding[4] {
+                COMMAND => (self.temperature * 10.0).round() as i16 as u16,
+                value => value,
+            },
+            40 => (2000.0 + self.temperature * 10.0).round() as u16,
+            41 => 512,
+            42 => self.sensor_adc(),
+            43 => 100,
+            44 => self.temperature_gain(),
+            45 => self.sensor_adc(),
+            46 => self.concentration(),
+            49 => SOFTWARE_DATE,
+            _ => 0,
+        };
+        Some(value)
+    }
+
+    /// Value of a holding register, `None` if the register doesn't exist
+    pub fn holding_register(&self, number: u16) -> Option<u16> {
+        find_rwreg(number)?;
+        Some(self.holding[number as usize])
+    }
+
+    /// Write a holding register, returns the exception code on failure
+    fn write_register(&mut self, number: u16, value: u16) -> Result<(), u8> {
+        let reg = find_rwreg(number).ok_or(ILLEGAL_DATA_ADDRESS)?;
+        if !reg.writable {
+            return Err(ILLEGAL_DATA_ADDRESS);
+        }
+        if number == 49 {
+            self.unlocked = value == UNLOCK;
+            return Ok(());
+        }
+        if (reg.unlock && !self.unlocked) || self.rejected.contains(&number) {
+            return Err(SLAVE_DEVICE_FAILURE);
+        }
+        match (number, value) {
+            // Nullpunkt
+            (10, COMMAND) => {
+                self.holding[10] = self.sensor_adc();
+                self.holding[11] = 0;
+            }
+            // Messgas
+            (12, COMMAND) => self.holding[12] = self.sensor_adc(),
+            _ if !reg.accepts(reg.raw_to_value(value)) => return Err(ILLEGAL_DATA_VALUE),
+            _ => self.holding[number as usize] = value,
+        }
+        Ok(())
+    }
+
+    /// Answer a request
+    ///
+    /// A new Modbus address is answered with the old address and used from the
+    /// next request on.
+    pub fn handle(&mut self, request: Request) -> Response {
+        let function = request.function();
+        let exception = |code| Response::Exception { function, code };
+        match request {
+            Request::ReadHoldingRegisters { addr, count }
+            | Request::ReadInputRegisters { addr, count } => {
+                if count == 0 || count > 125 {
+                    return exception(ILLEGAL_DATA_VALUE);
+                }
+                let values: Option<Vec<u16>> = (addr..addr.saturating_add(count))
+                    .map(|number| match request {
+                        Request::ReadHoldingRegisters { .. } => self.holding_register(number),
+                        _ => self.input_register(number),
+                    })
+                    .collect();
+                match values {
+                    Some(values) => Response::Registers { function, values },
+                    None => exception(ILLEGAL_DATA_ADDRESS),
+                }
+            }
+            Request::WriteSingleRegister { addr, value } => {
+                match self.write_register(addr, value) {
+                    Ok(()) => Response::WriteSingleRegister { addr, value },
+                    Err(code) => exception(code),
+                }
+            }
+            Request::Other(_) => exception(ILLEGAL_FUNCTION),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(model: &mut Ne4Model, addr: u16, value: u16) -> Response {
+        model.handle(Request::WriteSingleRegister { addr, value })
+    }
+
+    fn read_input(model: &mut Ne4Model, addr: u16) -> u16 {
+        match model.handle(Request::ReadInputRegisters { addr, count: 1 }) {
+            Response::Registers { values, .. } => values[0],
+            response => panic!("unexpected response {:?}", response),
+        }
+    }
+
+    #[test]
+    fn locked() {
+        let mut model = Ne4Model::new(247);
+        assert_eq!(
+            write(&mut model, 13, 500),
+            Response::Exception {
+                function: 0x06,
+                code: SLAVE_DEVICE_FAILURE
+            }
+        );
+        write(&mut model, 49, UNLOCK);
+        assert_eq!(
+            write(&mut model, 13, 500),
+            Response::WriteSingleRegister {
+                addr: 13,
+                value: 500
+            }
+        );
+        assert_eq!(model.holding_register(13), Some(500));
+
+        model.reject_writes(13);
+        assert_eq!(
+            write(&mut model, 13, 600),
+            Response::Exception {
+                function: 0x06,
+                code: SLAVE_DEVICE_FAILURE
+            }
+        );
+        assert_eq!(model.holding_register(13), Some(500));
+    }
+
+    #[test]
+    fn zero_and_span() {
+        let mut model = Ne4Model::new(247);
+        write(&mut model, 49, UNLOCK);
+        write(&mut model, 42, 1000);
+        write(&mut model, 10, COMMAND);
+        assert_eq!(model.holding_register(10), Some(1000));
+        assert_eq!(read_input(&mut model, 2), 0);
+
+        write(&mut model, 42, 3000);
+        write(&mut model, 12, COMMAND);
+        assert_eq!(model.holding_register(12), Some(3000));
+        assert_eq!(read_input(&mut model, 2), 1000);
+        // Factory output current 4 … 20 mA for 0 … 1000 ppm
+        assert_eq!(read_input(&mut model, 3), 2000);
+    }
+
+    #[test]
+    fn test_value_overrides() {
+        let mut model = Ne4Model::new(247);
+        write(&mut model, 2, 123);
+        write(&mut model, 3, 1200);
+        write(&mut model, 4, (-50i16) as u16);
+        assert_eq!(read_input(&mut model, 2), 123);
+        assert_eq!(read_input(&mut model, 3), 1200);
+        assert_eq!(read_input(&mut model, 4), 0xFFCE);
+        write(&mut model, 2, COMMAND);
+        assert_eq!(read_input(&mut model, 2), 0);
+    }
+
+    #[test]
+    fn temperature_gain() {
+        let mut model = Ne4Model::new(247);
+        assert_eq!(read_input(&mut model, 44), 100);
+        write(&mut model, 49, UNLOCK);
+        write(&mut model, 33, 100);
+        write(&mut model, 34, 80);
+        write(&mut model, 4, 250);
+        assert_eq!(read_input(&mut model, 44), 90);
+    }
+
+    #[test]
+    fn new_modbus_address() {
+        let mut model = Ne4Model::new(247);
+        write(&mut model, 49, UNLOCK);
+        write(&mut model, 50, 12);
+        assert_eq!(model.modbus_address(), 12);
+        assert_eq!(
+            write(&mut model, 50, 0),
+            Response::Exception {
+                function: 0x06,
+                code: ILLEGAL_DATA_VALUE
+            }
+        );
+    }
+
+    #[test]
+    fn gaps_rejected() {
+        let mut model = Ne4Model::new(247);
+        assert_eq!(
+            model.handle(Request::ReadInputRegisters { addr: 0, count: 50 }),
+            Response::Exception {
+                function: 0x04,
+                code: ILLEGAL_DATA_ADDRESS
+            }
+        );
+        match model.handle(Request::ReadInputRegisters { addr: 40, count: 7 }) {
+            Response::Registers { values, .. } => assert_eq!(values.len(), 7),
+            response => panic!("unexpected response {:?}", response),
+        }
+    }
+
+    #[test]
+    fn drift_stays_plausible() {
+        let mut model = Ne4Model::new(247);
+        let mut rng = Rng::new(1);
+        for _ in 0..1000 {
+            model.drift(&mut rng, 0.1);
+        }
+        assert!(read_input(&mut model, 2) < 10);
+        let temperature = read_input(&mut model, 4) as i16;
+        assert!(200 < temperature && temperature < 240);
+    }
+}
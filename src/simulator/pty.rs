@@ -0,0 +1,176 @@
+/// Pseudo terminal of the simulator
+use super::{
+    model::{Ne4Model, Rng},
+    rtu::{decode_request, is_complete, Response, REQUEST_LEN, SLAVE_DEVICE_BUSY},
+    Fault, Faults,
+};
+use nix::{
+    errno::Errno,
+    fcntl::OFlag,
+    poll::{poll, PollFd, PollFlags},
+    pty::{grantpt, posix_openpt, ptsname_r, unlockpt, PtyMaster},
+    sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg},
+    unistd::{read, write},
+};
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Silence on the line which ends a frame of unknown length, in milliseconds
+const FRAME_GAP: i32 = 5;
+/// Interval of the drift without requests, in milliseconds
+const DRIFT_INTERVAL: i32 = 100;
+
+fn io_error(e: nix::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e)
+}
+
+pub struct Simulator {
+    master: PtyMaster,
+    /// The slave side is kept open, otherwise reading the master fails with EIO
+    /// every time the last client closed the port.
+    _slave: File,
+    path: String,
+    model: Ne4Model,
+    faults: Faults,
+    rng: Rng,
+}
+
+impl Simulator {
+    /// Create the pseudo terminal
+    ///
+    /// The same `seed` gives the same drift and the same faults.
+    pub fn new(model: Ne4Model, faults: Faults, seed: u64) -> io::Result<Self> {
+        let master = posix_openpt(OFlag::O_RDWR | OFlag::O_NOCTTY).map_err(io_error)?;
+        grantpt(&master).map_err(io_error)?;
+        unlockpt(&master).map_err(io_error)?;
+        let path = ptsname_r(&master).map_err(io_error)?;
+
+        let slave = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(nix::libc::O_NOCTTY)
+            .open(&path)?;
+        // No echo and no line editing, the bytes are passed through unchanged
+        let mut termios = tcgetattr(slave.as_raw_fd()).map_err(io_error)?;
+        cfmakeraw(&mut termios);
+        tcsetattr(slave.as_raw_fd(), SetArg::TCSANOW, &termios).map_err(io_error)?;
+
+        Ok(Simulator {
+            master,
+            _slave: slave,
+            path,
+            model,
+            faults,
+            rng: Rng::new(seed),
+        })
+    }
+
+    /// Path of the serial port clients connect to
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Run the simulator in its own thread
+    pub fn spawn(self) -> thread::JoinHandle<io::Result<()>> {
+        thread::spawn(move || self.run())
+    }
+
+    /// Answer requests until an I/O error occurs
+    pub fn run(mut self) -> io::Result<()> {
+        info!("NE4 simulator listening on {}", self.path);
+        let fd = self.master.as_raw_fd();
+        let mut buffer: Vec<u8> = vec![];
+        let mut last_drift = Instant::now();
+
+        loop {
+            let timeout = if buffer.is_empty() {
+                DRIFT_INTERVAL
+            } else {
+                FRAME_GAP
+            };
+            let mut fds = [PollFd::new(fd, PollFlags::POLLIN)];
+            let ready = poll(&mut fds, timeout).map_err(io_error)?;
+
+            let now = Instant::now();
+            self.model
+                .drift(&mut self.rng, (now - last_drift).as_secs_f64());
+            last_drift = now;
+
+            if ready > 0 {
+                let mut chunk = [0u8; 256];
+                match read(fd, &mut chunk) {
+                    Ok(len) => buffer.extend_from_slice(&chunk[..len]),
+                    Err(nix::Error::Sys(Errno::EAGAIN)) | Err(nix::Error::Sys(Errno::EIO)) => {
+                        thread::sleep(Duration::from_millis(10));
+                        continue;
+                    }
+                    Err(e) => return Err(io_error(e)),
+                }
+                if !is_complete(&buffer) {
+                    continue;
+                }
+            } else if buffer.is_empty() {
+                continue;
+            }
+
+            // Either a complete request or the silence after a frame of unknown length
+            let len = if is_complete(&buffer) {
+                REQUEST_LEN
+            } else {
+                buffer.len()
+            };
+            let frame: Vec<u8> = buffer.drain(..len).collect();
+            if let Some(response) = self.answer(&frame) {
+                self.write_all(&response)?;
+            }
+        }
+    }
+
+    /// Response to a request frame, `None` if nothing is sent back
+    fn answer(&mut self, frame: &[u8]) -> Option<Vec<u8>> {
+        let (slave, request) = match decode_request(frame) {
+            Ok(request) => request,
+            Err(e) => {
+                debug!("Invalid frame {:02X?}: {:?}", frame, e);
+                return None;
+            }
+        };
+        if slave != self.model.modbus_address() {
+            return None;
+        }
+        let fault = self.faults.choose(&mut self.rng);
+        debug!("Request {:?}, fault: {:?}", request, fault);
+        match fault {
+            Some(Fault::Timeout) => None,
+            Some(Fault::Exception) => Some(
+                Response::Exception {
+                    function: request.function(),
+                    code: SLAVE_DEVICE_BUSY,
+                }
+                .encode(slave),
+            ),
+            fault => {
+                let mut response = self.model.handle(request).encode(slave);
+                if fault == Some(Fault::CrcError) {
+                    if let Some(crc) = response.last_mut() {
+                        *crc ^= 0xFF;
+                    }
+                }
+                Some(response)
+            }
+        }
+    }
+
+    fn write_all(&self, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            let len = write(self.master.as_raw_fd(), data).map_err(io_error)?;
+            data = &data[len..];
+        }
+        Ok(())
+    }
+}
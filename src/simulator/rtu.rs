@@ -0,0 +1,180 @@
+/// Modbus RTU framing of the simulator
+///
+/// Only the functions the NE4 knows are decoded, every other function is answered
+/// with an exception.
+
+/// Modbus exception codes
+pub const ILLEGAL_FUNCTION: u8 = 0x01;
+pub const ILLEGAL_DATA_ADDRESS: u8 = 0x02;
+pub const ILLEGAL_DATA_VALUE: u8 = 0x03;
+pub const SLAVE_DEVICE_FAILURE: u8 = 0x04;
+pub const SLAVE_DEVICE_BUSY: u8 = 0x06;
+
+/// Length of the requests 0x03, 0x04 and 0x06
+pub const REQUEST_LEN: usize = 8;
+
+/// Modbus CRC16, the low byte is transmitted first
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for byte in data {
+        crc ^= u16::from(*byte);
+        for _ in 0..8 {
+            if crc & 1 == 1 {
+                crc = (crc >> 1) ^ 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+/// Append the CRC to the frame
+fn with_crc(mut frame: Vec<u8>) -> Vec<u8> {
+    let crc = crc16(&frame);
+    frame.push((crc & 0xFF) as u8);
+    frame.push((crc >> 8) as u8);
+    frame
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Request {
+    ReadHoldingRegisters {
+        addr: u16,
+        count: u16,
+    },
+    ReadInputRegisters {
+        addr: u16,
+        count: u16,
+    },
+    WriteSingleRegister {
+        addr: u16,
+        value: u16,
+    },
+    /// Function the NE4 doesn't know
+    Other(u8),
+}
+
+impl Request {
+    pub fn function(&self) -> u8 {
+        match self {
+            Request::ReadHoldingRegisters { .. } => 0x03,
+            Request::ReadInputRegisters { .. } => 0x04,
+            Request::WriteSingleRegister { .. } => 0x06,
+            Request::Other(function) => *function,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Response {
+    Registers { function: u8, values: Vec<u16> },
+    WriteSingleRegister { addr: u16, value: u16 },
+    Exception { function: u8, code: u8 },
+}
+
+impl Response {
+    /// RTU frame of the response
+    pub fn encode(&self, slave: u8) -> Vec<u8> {
+        let mut frame = vec![slave];
+        match self {
+            Response::Registers { function, values } => {
+                frame.push(*function);
+                frame.push((values.len() * 2) as u8);
+                for value in values {
+                    frame.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+            Response::WriteSingleRegister { addr, value } => {
+                frame.push(0x06);
+                frame.extend_from_slice(&addr.to_be_bytes());
+                frame.extend_from_slice(&value.to_be_bytes());
+            }
+            Response::Exception { function, code } => {
+                frame.push(function | 0x80);
+                frame.push(*code);
+            }
+        }
+        with_crc(frame)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FrameError {
+    TooShort,
+    Crc,
+}
+
+/// Decode a request frame, returns slave address and request
+pub fn decode_request(frame: &[u8]) -> Result<(u8, Request), FrameError> {
+    if frame.len() < 4 {
+        return Err(FrameError::TooShort);
+    }
+    let (data, crc) = frame.split_at(frame.len() - 2);
+    if crc16(data) != u16::from_le_bytes([crc[0], crc[1]]) {
+        return Err(FrameError::Crc);
+    }
+    let slave = data[0];
+    let function = data[1];
+    if data.len() != REQUEST_LEN - 2 {
+        return Ok((slave, Request::Other(function)));
+    }
+    let addr = u16::from_be_bytes([data[2], data[3]]);
+    let word = u16::from_be_bytes([data[4], data[5]]);
+    let request = match function {
+        0x03 => Request::ReadHoldingRegisters { addr, count: word },
+        0x04 => Request::ReadInputRegisters { addr, count: word },
+        0x06 => Request::WriteSingleRegister { addr, value: word },
+        function => Request::Other(function),
+    };
+    Ok((slave, request))
+}
+
+/// Known request with fixed length, no need to wait for the end of the frame
+pub fn is_complete(buffer: &[u8]) -> bool {
+    buffer.len() >= REQUEST_LEN && [0x03, 0x04, 0x06].contains(&buffer[1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01]), 0x0A84);
+    }
+
+    #[test]
+    fn decode_read_request() {
+        let frame = [0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A];
+        assert!(is_complete(&frame));
+        assert_eq!(
+            decode_request(&frame),
+            Ok((1, Request::ReadHoldingRegisters { addr: 0, count: 1 }))
+        );
+    }
+
+    #[test]
+    fn decode_crc_error() {
+        let frame = [0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0B];
+        assert_eq!(decode_request(&frame), Err(FrameError::Crc));
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        let frame = Response::Registers {
+            function: 0x04,
+            values: vec![0x1234],
+        }
+        .encode(247);
+        assert_eq!(&frame[..5], &[247, 0x04, 2, 0x12, 0x34]);
+        assert_eq!(crc16(&frame), 0);
+
+        let frame = Response::Exception {
+            function: 0x03,
+            code: ILLEGAL_DATA_ADDRESS,
+        }
+        .encode(247);
+        assert_eq!(&frame[..3], &[247, 0x83, 0x02]);
+    }
+}
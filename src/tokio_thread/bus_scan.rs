@@ -0,0 +1,115 @@
+/// Modbus bus scan
+///
+/// Probes every Modbus address of a RS-485 line with a short timeout. An address
+/// which answers, even with a Modbus exception, is a sensor.
+use super::connection::Connection;
+use super::LineSettings;
+use crate::error::Result;
+use std::ops::RangeInclusive;
+use tokio::time::Duration;
+
+/// All valid Modbus slave addresses
+pub const SCAN_ADDRESSES: RangeInclusive<u8> = 1..=247;
+
+/// Characters of the first scan request and its response, including the silent
+/// interval of 3.5 characters after each frame
+///
+/// Read Input Registers: 8 bytes request, 9 bytes response for two registers.
+const SCAN_FRAME_CHARACTERS: u32 = 8 + 9 + 7;
+
+/// Time the sensor needs to start answering
+const SCAN_TURNAROUND: Duration = Duration::from_millis(30);
+
+/// Response timeout while scanning
+///
+/// Much shorter than the normal timeout, most of the addresses never answer. It
+/// covers the transmission of request and response at the baud rate of the
+/// line plus the turnaround time of the sensor, but is never longer than the
+/// configured timeout.
+pub fn scan_timeout(line_settings: &LineSettings) -> Duration {
+    let timeout = line_settings.character_time() * SCAN_FRAME_CHARACTERS + SCAN_TURNAROUND;
+    timeout.min(line_settings.timeout)
+}
+
+/// Sensor found by the bus scan
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScanResult {
+    pub modbus_address: u8,
+    /// Kundencode, Rreg 0
+    pub customer_code: Option<u16>,
+    /// Arbeitsweise, Rreg 1
+    pub working_mode: Option<u16>,
+    /// Geräte ID (Werk), Rwreg 98
+    pub device_id: Option<u16>,
+    /// Softwarestand, Rreg 49
+    pub software_date: Option<u16>,
+}
+
+/// Probe one Modbus address
+///
+/// Returns `None` if nothing answered within the timeout. The identification
+/// registers of a responding sensor are read one after the other, registers the
+/// sensor rejects stay empty. Other errors, e.g. a serial port which can't be
+/// opened, abort the scan.
+pub async fn scan_address(
+    connection: &Connection,
+    modbus_address: u8,
+) -> Result<Option<ScanResult>> {
+    let (customer_code, working_mode) =
+        match connection.read_input_registers(modbus_address, 0, 2).await {
+            Ok(values) => (values.get(0).cloned(), values.get(1).cloned()),
+            Err(e) if e.is_exception() => (None, None),
+            // Timeout or garbled answer, e.g. a collision on the bus
+            Err(e) if e.is_no_answer() => return Ok(None),
+            Err(e) => return Err(e),
+        };
+    let software_date = connection
+        .read_input_registers(modbus_address, 49, 1)
+        .await
+        .ok()
+        .and_then(|values| values.first().cloned());
+    let device_id = connection
+        .read_holding_registers(modbus_address, 98, 1)
+        .await
+        .ok()
+        .and_then(|values| values.first().cloned());
+
+    Ok(Some(ScanResult {
+        modbus_address,
+        customer_code,
+        working_mode,
+        device_id,
+        software_date,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_serial::Parity;
+
+    #[test]
+    fn timeout_depends_on_baud_rate() {
+        let line_settings = LineSettings::default();
+        // 9600 8N1: 24 characters of 1.04 ms
+        assert_eq!(scan_timeout(&line_settings), Duration::from_micros(54_984));
+        let slow = LineSettings {
+            baud_rate: 1200,
+            parity: Parity::Even,
+            timeout: Duration::from_millis(1000),
+            ..line_settings
+        };
+        assert_eq!(scan_timeout(&slow), Duration::from_micros(249_984));
+        let fast = LineSettings {
+            baud_rate: 115_200,
+            ..line_settings
+        };
+        assert!(scan_timeout(&fast) < Duration::from_millis(33));
+        // Never longer than the configured timeout
+        let short = LineSettings {
+            baud_rate: 1200,
+            ..line_settings
+        };
+        assert_eq!(scan_timeout(&short), line_settings.timeout);
+    }
+}
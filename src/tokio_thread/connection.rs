@@ -0,0 +1,207 @@
+/// Connection manager
+///
+/// One `Connection` owns the single Modbus RTU context of a serial port. All
+/// commands and the polling use the same session, the bus is locked for the
+/// duration of each request. After an I/O error or a timeout the context is
+/// dropped, the next request reconnects transparently.
+use super::SerialConfig;
+use crate::error::{Error, Result};
+use std::{cell::RefCell, io, rc::Rc};
+use tokio::{
+    sync::Mutex,
+    time::{timeout, Duration},
+};
+use tokio_modbus::client::{
+    util::{reconnect_shared_context, SharedContext},
+    Context,
+};
+use tokio_modbus::prelude::*;
+
+/// Characters of a request: address, function, start, count or value and CRC
+const REQUEST_CHARACTERS: u32 = 8;
+/// Characters of a response besides the registers: address, function, byte
+/// count and CRC
+const RESPONSE_CHARACTERS: u32 = 5;
+
+pub struct Connection {
+    serial_config: SerialConfig,
+    shared_context: Rc<RefCell<SharedContext>>,
+    bus: Mutex<()>,
+    timeout: Duration,
+    /// Transmission time of one character on the line
+    character_time: Duration,
+}
+
+impl Connection {
+    /// `timeout` is the response timeout, the transmission time of request and
+    /// response at `character_time` per character is added per request
+    pub(super) fn new(
+        serial_config: SerialConfig,
+        timeout: Duration,
+        character_time: Duration,
+    ) -> Self {
+        let shared_context = Rc::new(RefCell::new(SharedContext::new(
+            None, // no initial context, i.e. not connected
+            Box::new(serial_config.clone()),
+        )));
+        Connection {
+            serial_config,
+            shared_context,
+            bus: Mutex::new(()),
+            timeout,
+            character_time,
+        }
+    }
+
+    /// Path of the serial port
+    pub fn path(&self) -> &str {
+        &self.serial_config.path
+    }
+
+    /// Shared context, connects if not connected yet
+    async fn context(&self) -> Result<Rc<RefCell<Context>>> {
+        if !self.shared_context.borrow().is_connected() {
+            debug!("Connect to {}", self.path());
+            reconnect_shared_context(&self.shared_context)
+                .await
+                .map_err(|source| Error::Open {
+                    port: self.path().to_string(),
+                    source,
+                })?;
+        }
+        self.shared_context
+            .borrow()
+            .share_context()
+            .ok_or_else(|| Error::Io(io::Error::new(io::ErrorKind::NotConnected, "Not connected")))
+    }
+
+    /// Drop the current context
+    ///
+    /// The serial port is closed as soon as no request uses the context anymore.
+    fn reset(&self) {
+        debug!("Reset connection to {}", self.path());
+        *self.shared_context.borrow_mut() =
+            SharedContext::new(None, Box::new(self.serial_config.clone()));
+    }
+
+    /// Check the result of a request, drop the context after I/O errors
+    ///
+    /// A timeout drops the context too, a late answer of the sensor would
+    /// otherwise be taken as the response to the next request.
+    fn check<T>(
+        &self,
+        result: std::result::Result<io::Result<T>, tokio::time::Elapsed>,
+    ) -> Result<T> {
+        match result {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(e)) => {
+                let e = Error::from(e);
+                if !e.is_exception() {
+                    self.reset();
+                }
+                Err(e)
+            }
+            Err(_) => {
+                self.reset();
+                Err(Error::Timeout)
+            }
+        }
+    }
+
+    fn timeout(&self, count: u16) -> Duration {
+        request_timeout(self.timeout, self.character_time, count)
+    }
+
+    /// Read Holding Registers 0x03
+    pub async fn read_holding_registers(
+        &self,
+        modbus_address: u8,
+        addr: u16,
+        count: u16,
+    ) -> Result<Vec<u16>> {
+        let _bus = self.bus.lock().await;
+        let context = self.context().await?;
+        let mut context = context.borrow_mut();
+        context.set_slave(Slave(modbus_address));
+        let result = timeout(
+            self.timeout(count),
+            context.read_holding_registers(addr, count),
+        )
+        .await;
+        drop(context);
+        self.check(result)
+    }
+
+    /// Read Input Registers 0x04
+    pub async fn read_input_registers(
+        &self,
+        modbus_address: u8,
+        addr: u16,
+        count: u16,
+    ) -> Result<Vec<u16>> {
+        let _bus = self.bus.lock().await;
+        let context = self.context().await?;
+        let mut context = context.borrow_mut();
+        context.set_slave(Slave(modbus_address));
+        let result = timeout(
+            self.timeout(count),
+            context.read_input_registers(addr, count),
+        )
+        .await;
+        drop(context);
+        self.check(result)
+    }
+
+    /// Write Single Register 0x06
+    pub async fn write_single_register(
+        &self,
+        modbus_address: u8,
+        addr: u16,
+        value: u16,
+    ) -> Result<()> {
+        let _bus = self.bus.lock().await;
+        let context = self.context().await?;
+        let mut context = context.borrow_mut();
+        context.set_slave(Slave(modbus_address));
+        let result = timeout(self.timeout(1), context.write_single_register(addr, value)).await;
+        drop(context);
+        self.check(result)
+    }
+}
+
+/// Response timeout plus the transmission time of a request for `count`
+/// registers and its response
+fn request_timeout(timeout: Duration, character_time: Duration, count: u16) -> Duration {
+    let characters = REQUEST_CHARACTERS + RESPONSE_CHARACTERS + 2 * u32::from(count);
+    timeout + character_time * characters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokio_thread::LineSettings;
+
+    #[test]
+    fn timeout_depends_on_baud_rate() {
+        let line_settings = LineSettings {
+            baud_rate: 4800,
+            ..LineSettings::default()
+        };
+        // 4800 8N1: 100 Rwreg's are 213 characters of 2.083 ms
+        let timeout = request_timeout(line_settings.timeout, line_settings.character_time(), 100);
+        assert_eq!(timeout, Duration::from_micros(100_000 + 213 * 2083));
+        assert!(timeout > Duration::from_millis(530));
+
+        let slow = LineSettings {
+            baud_rate: 1200,
+            ..line_settings
+        };
+        let timeout = request_timeout(slow.timeout, slow.character_time(), 100);
+        assert_eq!(timeout, Duration::from_micros(100_000 + 213 * 8333));
+        // A single register at 1200 baud
+        assert_eq!(
+            request_timeout(slow.timeout, slow.character_time(), 1),
+            Duration::from_micros(100_000 + 15 * 8333)
+        );
+    }
+}
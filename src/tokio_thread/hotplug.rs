@@ -0,0 +1,233 @@
+/// Serial port hotplug tracking
+///
+/// The port watcher enumerates the serial ports periodically and reports which
+/// ports were added or removed. Ports are identified by name and USB metadata,
+/// never by their position in the list.
+use std::fmt;
+use tokio_serial::{SerialPortInfo, SerialPortType};
+
+/// Ports which are never offered, e.g. the on-board UART under Linux
+const IGNORED_PORTS: &[&str] = &["/dev/ttyS0"];
+
+/// USB metadata of a serial adapter
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UsbInfo {
+    pub vid: u16,
+    pub pid: u16,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+/// Serial port with its USB metadata, if it is an USB adapter
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PortInfo {
+    pub name: String,
+    pub usb: Option<UsbInfo>,
+}
+
+impl PortInfo {
+    pub fn new(name: &str) -> Self {
+        PortInfo {
+            name: name.to_string(),
+            usb: None,
+        }
+    }
+
+    /// Text of the port selector, e.g. "/dev/ttyUSB0 - FT232R USB UART"
+    pub fn label(&self) -> String {
+        let usb = match &self.usb {
+            Some(usb) => usb,
+            None => return self.name.clone(),
+        };
+        match usb.product.as_ref().or_else(|| usb.manufacturer.as_ref()) {
+            Some(product) => format!("{} - {}", self.name, product),
+            None => format!("{} - USB {:04x}:{:04x}", self.name, usb.vid, usb.pid),
+        }
+    }
+
+    /// USB metadata, one line per value, `None` if the port isn't an USB adapter
+    pub fn description(&self) -> Option<String> {
+        let usb = self.usb.as_ref()?;
+        let mut lines = vec![];
+        if let Some(manufacturer) = &usb.manufacturer {
+            lines.push(format!("Hersteller: {}", manufacturer));
+        }
+        if let Some(product) = &usb.product {
+            lines.push(format!("Produkt: {}", product));
+        }
+        if let Some(serial_number) = &usb.serial_number {
+            lines.push(format!("Seriennummer: {}", serial_number));
+        }
+        lines.push(format!("USB ID: {:04x}:{:04x}", usb.vid, usb.pid));
+        Some(lines.join("\n"))
+    }
+}
+
+impl fmt::Display for PortInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.usb.as_ref().and_then(|usb| usb.serial_number.as_ref()) {
+            Some(serial_number) => write!(f, "{} (Seriennummer {})", self.label(), serial_number),
+            None => write!(f, "{}", self.label()),
+        }
+    }
+}
+
+impl From<SerialPortInfo> for PortInfo {
+    fn from(info: SerialPortInfo) -> Self {
+        let usb = match info.port_type {
+            SerialPortType::UsbPort(usb) => Some(UsbInfo {
+                vid: usb.vid,
+                pid: usb.pid,
+                manufacturer: usb.manufacturer,
+                product: usb.product,
+                serial_number: usb.serial_number,
+            }),
+            _ => None,
+        };
+        PortInfo {
+            name: info.port_name,
+            usb,
+        }
+    }
+}
+
+/// Change of the available serial ports
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PortEvent {
+    Added(PortInfo),
+    Removed(PortInfo),
+}
+
+/// Compares the enumerated ports with the previous enumeration
+#[derive(Debug, Default)]
+pub struct PortWatcher {
+    ports: Vec<PortInfo>,
+}
+
+impl PortWatcher {
+    pub fn new() -> Self {
+        PortWatcher::default()
+    }
+
+    /// Ports of the last update
+    pub fn ports(&self) -> &[PortInfo] {
+        &self.ports
+    }
+
+    /// Take the current ports, returns the removed and added ports
+    ///
+    /// A port whose USB metadata changed, i.e. another adapter got the same
+    /// name, is reported as removed and added.
+    pub fn update(&mut self, ports: Vec<PortInfo>) -> Vec<PortEvent> {
+        let ports = filter_ports(ports);
+        let removed = self
+            .ports
+            .iter()
+            .filter(|port| !ports.contains(port))
+            .cloned()
+            .map(PortEvent::Removed);
+        let added = ports
+            .iter()
+            .filter(|port| !self.ports.contains(port))
+            .cloned()
+            .map(PortEvent::Added);
+        let events = removed.chain(added).collect();
+        self.ports = ports;
+        events
+    }
+}
+
+/// Sort the ports by name and remove unwanted ports
+pub fn filter_ports(mut ports: Vec<PortInfo>) -> Vec<PortInfo> {
+    ports.retain(|port| !IGNORED_PORTS.contains(&port.name.as_str()));
+    ports.sort_by(|a, b| a.name.cmp(&b.name));
+    ports.dedup_by(|a, b| a.name == b.name);
+    ports
+}
+
+/// Available serial ports, sorted by name
+///
+/// Enumeration errors are logged and reported as no ports. Windows e.g. fails
+/// to enumerate if the last port was removed.
+pub fn available_ports() -> Vec<PortInfo> {
+    match tokio_serial::available_ports() {
+        Ok(ports) => filter_ports(ports.into_iter().map(PortInfo::from).collect()),
+        Err(e) => {
+            debug!("Failed to enumerate serial ports: {}", e);
+            vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usb(name: &str, serial_number: &str) -> PortInfo {
+        PortInfo {
+            name: name.to_string(),
+            usb: Some(UsbInfo {
+                vid: 0x0403,
+                pid: 0x6001,
+                manufacturer: Some("FTDI".to_string()),
+                product: Some("FT232R USB UART".to_string()),
+                serial_number: Some(serial_number.to_string()),
+            }),
+        }
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let mut watcher = PortWatcher::new();
+        let events = watcher.update(vec![usb("/dev/ttyUSB1", "B"), PortInfo::new("/dev/ttyS0")]);
+        assert_eq!(events, vec![PortEvent::Added(usb("/dev/ttyUSB1", "B"))]);
+        assert!(watcher.update(vec![usb("/dev/ttyUSB1", "B")]).is_empty());
+
+        // Added before the selected port, the selection must not move
+        let events = watcher.update(vec![usb("/dev/ttyUSB1", "B"), usb("/dev/ttyUSB0", "A")]);
+        assert_eq!(events, vec![PortEvent::Added(usb("/dev/ttyUSB0", "A"))]);
+        assert_eq!(watcher.ports()[0].name, "/dev/ttyUSB0");
+
+        // One removed and one added within the same interval
+        let events = watcher.update(vec![usb("/dev/ttyUSB0", "A"), usb("/dev/ttyUSB2", "C")]);
+        assert_eq!(
+            events,
+            vec![
+                PortEvent::Removed(usb("/dev/ttyUSB1", "B")),
+                PortEvent::Added(usb("/dev/ttyUSB2", "C")),
+            ]
+        );
+
+        // Another adapter with the same name
+        let events = watcher.update(vec![usb("/dev/ttyUSB0", "D"), usb("/dev/ttyUSB2", "C")]);
+        assert_eq!(
+            events,
+            vec![
+                PortEvent::Removed(usb("/dev/ttyUSB0", "A")),
+                PortEvent::Added(usb("/dev/ttyUSB0", "D")),
+            ]
+        );
+
+        assert_eq!(watcher.update(vec![]).len(), 2);
+        assert!(watcher.ports().is_empty());
+    }
+
+    #[test]
+    fn labels() {
+        let port = usb("/dev/ttyUSB0", "A50285BI");
+        assert_eq!(port.label(), "/dev/ttyUSB0 - FT232R USB UART");
+        assert_eq!(
+            port.to_string(),
+            "/dev/ttyUSB0 - FT232R USB UART (Seriennummer A50285BI)"
+        );
+        assert_eq!(
+            port.description().unwrap(),
+            "Hersteller: FTDI\nProdukt: FT232R USB UART\nSeriennummer: A50285BI\nUSB ID: 0403:6001"
+        );
+
+        let port = PortInfo::new("COM3");
+        assert_eq!(port.to_string(), "COM3");
+        assert_eq!(port.description(), None);
+    }
+}
@@ -1,70 +1,159 @@
 use super::gui::gtk3::UiCommand;
+use crate::error::{Error, Result};
+use crate::sensors::backup::{backup_registers, restore_registers, Backup, Change};
+use crate::sensors::calibration::{
+    CalibrationPoints, CalibrationStep, CALIBRATION_COMMAND, CALIBRATION_RREG, CALIBRATION_RWREGS,
+};
+use crate::sensors::calibration_report::{
+    CalibrationRecord, CalibrationSnapshot, CORRECTED_ADC_RREG,
+};
+use crate::sensors::dashboard::{DASHBOARD_RREG_COUNT, DASHBOARD_RREG_START};
+use crate::sensors::device_info::{DeviceInfo, DEVICE_INFO_RREGS, DEVICE_INFO_RWREGS};
+use crate::sensors::front_end::{FrontEnd, FRONT_END_RWREGS};
+use crate::sensors::loop_check::{OutputCalibration, OUTPUT_CALIBRATION_RWREGS};
+use crate::sensors::output_scaling::{OutputScaling, OUTPUT_SCALING_RWREGS};
+use crate::sensors::ra_gas_ne4::{
+    find_rwreg, register_value, FunctionCode, Register, CONNECTION_REGISTERS, NE4,
+};
+use crate::sensors::temperature_curve::{TemperatureCurve, CURVE_RWREGS};
+use crate::sensors::test_values::{Override, Overrides, TestValue, TEST_VALUE_OFF};
+use bus_scan::{scan_address, scan_timeout, SCAN_ADDRESSES};
+use connection::Connection;
 use futures::channel::mpsc::*;
 use futures::prelude::*;
-use tokio::time::{timeout, Duration};
-use tokio_modbus::client::{
-    util::{reconnect_shared_context, NewContext, SharedContext},
-    Context,
-};
+use hotplug::{available_ports, PortWatcher};
+use poller::{PollTarget, Poller, DEFAULT_POLL_INTERVAL};
+use register_blocks::{contiguous_blocks, Block, BlockRead};
+use tokio::time::Duration;
+use tokio_modbus::client::{util::NewContext, Context};
 use tokio_modbus::prelude::*;
 // use tokio_serial::{Serial, SerialPortSettings};
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
+    collections::HashMap,
     future::Future,
-    io::Error,
+    io,
     pin::Pin,
     rc::Rc,
-    sync::{Arc, Mutex},
 };
 use tokio_serial::*;
 
+pub mod bus_scan;
+pub mod connection;
+pub mod hotplug;
+pub mod poller;
+pub mod register_blocks;
+
 /// Tokio thread commands
 ///
 /// This command can the tokio/ serial thread process.
 #[derive(Debug)]
 pub enum TokioCommand {
+    Backup(Option<String>, u8),
+    Calibrate(Option<String>, u8, CalibrationStep),
+    CancelScan,
     Connect,
     Disconnect,
-    Messgas(Option<String>, u8),
     NewWorkingMode(Option<String>, u8, u16),
     NewModbusAddress(Option<String>, u8, u8),
-    Nullpunkt(Option<String>, u8),
+    ReadCalibration(Option<String>, u8),
+    ReadFrontEnd(Option<String>, u8),
+    ReadOutputCalibration(Option<String>, u8),
+    ReadOutputScaling(Option<String>, u8),
+    ReadTemperatureCurve(Option<String>, u8),
+    /// Restore a backup, `true` also onto a sensor with another device id
+    Restore(Option<String>, u8, Backup, bool),
+    /// Compare a backup with the sensor, `true` also for another device id
+    RestoreDiff(Option<String>, u8, Backup, bool),
+    /// Reset the test values set on the port, e.g. after it is back
+    RestoreTestValues(Option<String>),
+    /// Switch all test values of the sensor off
+    ResetTestValues(Option<String>, u8),
+    ScanBus(Option<String>),
+    /// Force a test value, `None` switches it off
+    SetTestValue(Option<String>, u8, TestValue, Option<u16>),
+    /// The application quits, answered with `UiCommand::Quit`
+    Shutdown,
+    UpdateDashboard(Option<String>, Vec<u8>),
     UpdateSensor(Option<String>, u8),
-    UpdateSensorRwregValues(Option<String>, u8),
+    WriteFrontEnd(Option<String>, u8, FrontEnd),
+    WriteOutputCalibration(Option<String>, u8, OutputCalibration),
+    WriteOutputScaling(Option<String>, u8, OutputScaling),
+    WriteTemperatureCurve(Option<String>, u8, TemperatureCurve),
+    SetLineSettings(LineSettings),
+    SetPollInterval(Duration),
+    WriteRegister(Option<String>, u8, u16, u16),
 }
 
-/// State of the tokio thread
+/// Serial line settings
 ///
-/// Possible states the tokio thread acts in.
-#[derive(Debug, PartialEq)]
-enum TokioState {
-    Connected,
-    Disconnected,
+/// Selected in the connection header and applied to every new session.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineSettings {
+    pub baud_rate: u32,
+    pub parity: Parity,
+    pub data_bits: DataBits,
+    pub stop_bits: StopBits,
+    /// Modbus response timeout
+    pub timeout: Duration,
 }
 
-/// Serial Configuration
-#[derive(Debug)]
-struct SerialConfig {
-    path: String,
-    settings: SerialPortSettings,
+impl Default for LineSettings {
+    /// Factory settings of the NE4, 9600 baud 8N1
+    fn default() -> Self {
+        LineSettings {
+            baud_rate: 9600,
+            parity: Parity::None,
+            data_bits: DataBits::Eight,
+            stop_bits: StopBits::One,
+            timeout: Duration::from_millis(100),
+        }
+    }
 }
 
-/// Implementation Serial Configuration
-impl SerialConfig {
-    fn new() -> Self {
-        SerialConfig {
-            path: "/dev/ttyUSB0".into(),
-            settings: SerialPortSettings {
-                baud_rate: 9600,
-                ..Default::default()
-            },
+impl LineSettings {
+    /// Settings for the serial port
+    pub fn serial_port_settings(&self) -> SerialPortSettings {
+        SerialPortSettings {
+            baud_rate: self.baud_rate,
+            parity: self.parity,
+            data_bits: self.data_bits,
+            stop_bits: self.stop_bits,
+            ..Default::default()
         }
     }
+
+    /// Transmission time of one character, start, data, parity and stop bits
+    pub fn character_time(&self) -> Duration {
+        let data_bits = match self.data_bits {
+            DataBits::Five => 5,
+            DataBits::Six => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        };
+        let parity_bits = match self.parity {
+            Parity::None => 0,
+            Parity::Odd | Parity::Even => 1,
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        let bits = 1 + data_bits + parity_bits + stop_bits;
+        Duration::from_micros(1_000_000 * bits / u64::from(self.baud_rate.max(1)))
+    }
+}
+
+/// Serial Configuration
+#[derive(Clone, Debug)]
+struct SerialConfig {
+    path: String,
+    settings: SerialPortSettings,
 }
 
 /// Shared Context Serial Configuration
 impl NewContext for SerialConfig {
-    fn new_context(&self) -> Pin<Box<dyn Future<Output = std::result::Result<Context, Error>>>> {
+    fn new_context(&self) -> Pin<Box<dyn Future<Output = io::Result<Context>>>> {
         let serial = Serial::from_path(&self.path, &self.settings);
         Box::pin(async {
             let port = serial?;
@@ -73,290 +162,885 @@ impl NewContext for SerialConfig {
     }
 }
 
-struct Ne4Client {
-    serial_config: SerialConfig,
+/// Block plans which are known to work, per port, modbus address and function code
+type BlockPlans = Rc<RefCell<HashMap<(String, u8, FunctionCode), Vec<Block>>>>;
+
+/// NE4 Modbus client
+///
+/// Used by the tokio thread of the GUI and by the command line tool `ne4_cli`.
+pub struct Ne4Client {
+    line_settings: Cell<LineSettings>,
+    connection: RefCell<Option<Rc<Connection>>>,
+    block_plans: BlockPlans,
+    /// Test values set by this client which aren't reset yet
+    overrides: RefCell<Overrides>,
 }
 
 impl Ne4Client {
-    fn new() -> Self {
+    pub fn new(line_settings: LineSettings) -> Self {
+        Ne4Client {
+            line_settings: Cell::new(line_settings),
+            connection: RefCell::new(None),
+            block_plans: Rc::new(RefCell::new(HashMap::new())),
+            overrides: RefCell::new(Overrides::new()),
+        }
+    }
+
+    /// Apply new serial line settings
+    ///
+    /// An open session is closed, the next command connects with the new settings.
+    pub fn set_line_settings(&self, line_settings: LineSettings) {
+        if self.line_settings.replace(line_settings) != line_settings {
+            self.disconnect();
+        }
+    }
+
+    /// Session of the given serial port
+    ///
+    /// The session stays open, all commands on the same port share it.
+    /// Selecting another port closes the old session.
+    fn connection(&self, port: Option<String>) -> Result<Rc<Connection>> {
+        let tty_path = port.ok_or(Error::NoPort)?;
+        let mut connection = self.connection.borrow_mut();
+        match &*connection {
+            Some(connection) if connection.path() == tty_path => Ok(connection.clone()),
+            _ => {
+                let line_settings = self.line_settings.get();
+                let serial_config = SerialConfig {
+                    path: tty_path,
+                    settings: line_settings.serial_port_settings(),
+                };
+                let new_connection = Rc::new(Connection::new(
+                    serial_config,
+                    line_settings.timeout,
+                    line_settings.character_time(),
+                ));
+                *connection = Some(new_connection.clone());
+                Ok(new_connection)
+            }
+        }
+    }
+
+    /// Session for the bus scan
+    ///
+    /// The scan needs the serial port on its own, an open session is closed. The
+    /// scan session uses the line settings but the short scan timeout.
+    pub fn scan_connection(&self, port: Option<String>) -> Result<Connection> {
+        let tty_path = port.ok_or(Error::NoPort)?;
+        self.disconnect();
+        let line_settings = self.line_settings.get();
         let serial_config = SerialConfig {
-            path: "/dev/ttyUSB0".into(),
-            settings: SerialPortSettings {
-                baud_rate: 9600,
-                ..Default::default()
-            },
+            path: tty_path,
+            settings: line_settings.serial_port_settings(),
         };
-        Ne4Client { serial_config }
+        // The scan timeout covers the transmission of the short scan frames
+        Ok(Connection::new(
+            serial_config,
+            scan_timeout(&line_settings),
+            Duration::from_secs(0),
+        ))
+    }
+
+    /// Close the session
+    ///
+    /// The serial port is closed as soon as the running poller has stopped.
+    pub fn disconnect(&self) {
+        self.connection.borrow_mut().take();
     }
 
     /// Nullpunkt action
     ///
-    /// This action is fired if the user clicks the Nullpunkt button.
-    async fn nullpunkt(&self, port: Option<String>, modbus_address: u8) -> tokio::io::Result<()> {
-        if let Some(tty_path) = port {
-            let slave = Slave(modbus_address);
-            let port = Serial::from_path(tty_path, &self.serial_config.settings)?;
-            let mut ctx = rtu::connect_slave(port, slave).await?;
-            ctx.set_slave(slave);
-            ctx.write_single_register(10, 11111).await
-        } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "No serial port found",
-            ))
-        }
+    /// Takes the current sensor AD value as zero point (Rwreg 10).
+    pub async fn nullpunkt(&self, port: Option<String>, modbus_address: u8) -> Result<()> {
+        let connection = self.connection(port)?;
+        // Entsperren
+        connection
+            .write_single_register(modbus_address, 49, 9876)
+            .await?;
+        connection
+            .write_single_register(modbus_address, 10, CALIBRATION_COMMAND)
+            .await
+            .map_err(|e| e.locked(10))
     }
 
     /// Messgas action
     ///
-    /// This action is fired if the user clicks the Messgas button.
-    async fn messgas(&self, port: Option<String>, modbus_address: u8) -> tokio::io::Result<()> {
-        if let Some(tty_path) = port {
-            let slave = Slave(modbus_address);
-            let port = Serial::from_path(tty_path, &self.serial_config.settings)?;
-            let mut ctx = rtu::connect_slave(port, slave).await?;
-            ctx.set_slave(slave);
-            ctx.write_single_register(12, 11111).await
-        } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "No serial port found",
-            ))
+    /// Takes the current sensor AD value as calibration point (Rwreg 12).
+    pub async fn messgas(&self, port: Option<String>, modbus_address: u8) -> Result<()> {
+        let connection = self.connection(port)?;
+        // Entsperren
+        connection
+            .write_single_register(modbus_address, 49, 9876)
+            .await?;
+        connection
+            .write_single_register(modbus_address, 12, CALIBRATION_COMMAND)
+            .await
+            .map_err(|e| e.locked(12))
+    }
+
+    /// Calibration points, Rwreg 10 to 13
+    pub async fn calibration_points(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+    ) -> Result<CalibrationPoints> {
+        let values = self
+            .read_registers_once(
+                port,
+                modbus_address,
+                FunctionCode::ReadHoldingRegisters,
+                CALIBRATION_RWREGS,
+            )
+            .await?;
+        CalibrationPoints::from_registers(&values).ok_or(Error::Incomplete("Kalibrierpunkte"))
+    }
+
+    /// Identity and calibration state of the sensor before a calibration
+    pub async fn calibration_start(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+    ) -> Result<(DeviceInfo, CalibrationSnapshot)> {
+        let device_info = self.device_info(port.clone(), modbus_address).await?;
+        let snapshot = self.calibration_snapshot(port, modbus_address).await?;
+        Ok((device_info, snapshot))
+    }
+
+    /// Sensor AD values and calibration points for the calibration report
+    pub async fn calibration_snapshot(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+    ) -> Result<CalibrationSnapshot> {
+        let rregs = self
+            .read_registers_once(
+                port.clone(),
+                modbus_address,
+                FunctionCode::ReadInputRegisters,
+                &[CALIBRATION_RREG, CORRECTED_ADC_RREG],
+            )
+            .await?;
+        let rwregs = self
+            .read_registers_once(
+                port,
+                modbus_address,
+                FunctionCode::ReadHoldingRegisters,
+                CALIBRATION_RWREGS,
+            )
+            .await?;
+        CalibrationSnapshot::from_registers(chrono::Local::now(), &rregs, &rwregs)
+            .ok_or(Error::Incomplete("Kalibrierpunkte"))
+    }
+
+    /// Zero point or span gas calibration
+    ///
+    /// For the span gas the concentration of the test gas is written to Rwreg 13
+    /// first. AD values and calibration points are read before and afterwards.
+    pub async fn calibrate(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+        step: CalibrationStep,
+    ) -> Result<CalibrationRecord> {
+        let before = self
+            .calibration_snapshot(port.clone(), modbus_address)
+            .await?;
+        match step {
+            CalibrationStep::Zero => self.nullpunkt(port.clone(), modbus_address).await?,
+            CalibrationStep::Span { concentration } => {
+                self.write_register(port.clone(), modbus_address, 13, concentration)
+                    .await?;
+                self.messgas(port.clone(), modbus_address).await?;
+            }
         }
+        let after = self.calibration_snapshot(port, modbus_address).await?;
+        Ok(CalibrationRecord::new(step, before, after))
     }
 
     /// New working mode action
     ///
     /// This action is fired if the user selects a new working mode (Arbeitsweise in german)
     /// and hits the update button.
-    async fn new_working_mode(
+    pub async fn new_working_mode(
         &self,
         port: Option<String>,
         modbus_address: u8,
         working_mode: u16,
-    ) -> tokio::io::Result<()> {
-        if let Some(tty_path) = port {
-            let slave = Slave(modbus_address);
-            let port = Serial::from_path(tty_path, &self.serial_config.settings)?;
-            let mut ctx = rtu::connect_slave(port, slave).await?;
-            ctx.set_slave(slave);
-            // Entsperren
-            let _ = ctx.write_single_register(49, 9876).await;
-            // Save new working mode
-            ctx.write_single_register(99, working_mode).await
-        } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "No serial port found",
-            ))
-        }
+    ) -> Result<()> {
+        let connection = self.connection(port)?;
+        // Entsperren
+        connection
+            .write_single_register(modbus_address, 49, 9876)
+            .await?;
+        // Save new working mode
+        connection
+            .write_single_register(modbus_address, 99, working_mode)
+            .await
+            .map_err(|e| e.locked(99))
     }
 
     /// Set new modbus
     ///
     /// This function sets a new modbus address on sensor platine.
     /// **The plaitne has to been unlocked first!**
-    async fn new_modbus_address(
+    pub async fn new_modbus_address(
         &self,
         port: Option<String>,
         modbus_address: u8,
         new_modbus_address: u8,
-    ) -> tokio::io::Result<()> {
-        if let Some(tty_path) = port {
-            let slave = Slave(modbus_address);
-            let port = Serial::from_path(tty_path, &self.serial_config.settings)?;
-            let mut ctx = rtu::connect_slave(port, slave).await?;
-            ctx.set_slave(slave);
+    ) -> Result<()> {
+        let connection = self.connection(port)?;
+        // Entsperren
+        connection
+            .write_single_register(modbus_address, 49, 9876)
+            .await?;
+        // Save new modbus address
+        connection
+            .write_single_register(modbus_address, 50, new_modbus_address.into())
+            .await
+            .map_err(|e| e.locked(50))
+    }
+
+    /// Write a single Rwreg 0x06
+    ///
+    /// The value is checked against the documented range of the register first.
+    /// Registers marked in the register table are unlocked via register 49 before.
+    /// After writing the register is read back, the read back value is returned.
+    /// Modbus address, baud rate and mode (Rwreg 50 to 52) are not read back, the
+    /// sensor answers with the new settings only, the written value is returned.
+    pub async fn write_register(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+        reg_nr: u16,
+        value: u16,
+    ) -> Result<u16> {
+        let reg = find_rwreg(reg_nr).ok_or(Error::UnknownRegister(reg_nr))?;
+        if !reg.writable {
+            return Err(Error::ReadOnly(reg_nr));
+        }
+        reg.check_raw(value)?;
+
+        let connection = self.connection(port)?;
+        if reg.unlock {
             // Entsperren
-            let _ = ctx.write_single_register(49, 9876).await;
-            // Save new modbus address
-            ctx.write_single_register(50, new_modbus_address.into())
+            connection
+                .write_single_register(modbus_address, 49, 9876)
+                .await?;
+        }
+        connection
+            .write_single_register(modbus_address, reg_nr, value)
+            .await
+            .map_err(|e| if reg.unlock { e.locked(reg_nr) } else { e })?;
+        if CONNECTION_REGISTERS.contains(&reg_nr) {
+            return Ok(value);
+        }
+        // Read back
+        let read_back = connection
+            .read_holding_registers(modbus_address, reg_nr, 1)
+            .await?;
+        match read_back.first() {
+            Some(read_back) if *read_back == value => Ok(*read_back),
+            read_back => Err(Error::ReadBack {
+                register: reg_nr,
+                written: value,
+                read: read_back.cloned(),
+            }),
+        }
+    }
+
+    /// Write several Rwreg's in the given order, see `write_register`
+    ///
+    /// The NE4 only knows Write Single Register, a failure in between would leave
+    /// a mix of old and new values in the sensor. So if a register fails, the
+    /// registers written so far are set back to their `old` values in reverse
+    /// order, and the error of the failing register is returned.
+    /// Registers which couldn't be set back are reported as `NotRestored`.
+    async fn write_registers(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+        registers: &[(u16, u16)],
+        old: &[(u16, u16)],
+    ) -> Result<()> {
+        for (index, (number, value)) in registers.iter().enumerate() {
+            let e = match self
+                .write_register(port.clone(), modbus_address, *number, *value)
                 .await
-        } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "No serial port found",
-            ))
+            {
+                Ok(_) => continue,
+                Err(e) => e,
+            };
+            debug!("Write register {} failed, roll back: {}", number, e);
+            // A rejected write changed nothing, after a timeout or a wrong read
+            // back the new value may be in the sensor
+            let written = match e {
+                Error::Exception(_) | Error::Locked { .. } => index,
+                _ => index + 1,
+            };
+            let mut not_restored = vec![];
+            for (number, value) in registers[..written].iter().rev() {
+                let old = match register_value(old, *number) {
+                    Some(old) if old != *value => old,
+                    _ => continue,
+                };
+                if self
+                    .write_register(port.clone(), modbus_address, *number, old)
+                    .await
+                    .is_err()
+                {
+                    not_restored.push(*number);
+                }
+            }
+            if !not_restored.is_empty() {
+                not_restored.sort();
+                return Err(Error::NotRestored(not_restored));
+            }
+            return Err(e);
         }
+        Ok(())
     }
 
-    /// Read Modbus Holding Registers 0x03
+    /// Read the given registers once
     ///
-    /// The NE4 has 100 registers.
-    async fn read_holding_registers(
+    /// Contiguous registers are read with one request. Returns the pairs of register
+    /// number and raw value, the first failing request aborts.
+    pub async fn read_registers_once(
         &self,
         port: Option<String>,
         modbus_address: u8,
-        ui_event_sender: Sender<UiCommand>,
-        // FIXME: Implement state in Ne4 Client
-        state: std::sync::Arc<tokio::sync::Mutex<TokioState>>,
-    ) -> tokio::io::Result<()> {
-        if let Some(tty_path) = port {
-            let slave = Slave(modbus_address);
-            let port = Serial::from_path(tty_path, &self.serial_config.settings)?;
-            let mut ctx = rtu::connect_slave(port, slave).await?;
-            ctx.set_slave(slave);
-
-            tokio::task::spawn(async move {
-                'update: loop {
-                    let state = state.lock().await;
-                    if *state == TokioState::Disconnected {
-                        break;
-                    }
+        function: FunctionCode,
+        numbers: &[u16],
+    ) -> Result<Vec<(u16, u16)>> {
+        let connection = self.connection(port)?;
+        let mut numbers = numbers.to_vec();
+        numbers.sort();
+        numbers.dedup();
 
-                    let mut registers = vec![0u16; 100];
+        let mut values = vec![];
+        for block in contiguous_blocks(&numbers) {
+            let response = match function {
+                FunctionCode::ReadHoldingRegisters => {
+                    connection
+                        .read_holding_registers(modbus_address, block.start, block.count)
+                        .await?
+                }
+                FunctionCode::ReadInputRegisters => {
+                    connection
+                        .read_input_registers(modbus_address, block.start, block.count)
+                        .await?
+                }
+            };
+            values.extend((block.start..).zip(response));
+        }
+        Ok(values)
+    }
 
-                    // Entsperren
-                    let _ = ctx.write_single_register(49, 9876).await;
+    /// Identity, software date and lifetime counters of the sensor
+    pub async fn device_info(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+    ) -> Result<DeviceInfo> {
+        let rregs = self
+            .read_registers_once(
+                port.clone(),
+                modbus_address,
+                FunctionCode::ReadInputRegisters,
+                DEVICE_INFO_RREGS,
+            )
+            .await?;
+        let rwregs = self
+            .read_registers_once(
+                port,
+                modbus_address,
+                FunctionCode::ReadHoldingRegisters,
+                DEVICE_INFO_RWREGS,
+            )
+            .await?;
+        Ok(DeviceInfo::from_registers(&rregs, &rwregs))
+    }
 
-                    for (i, reg) in registers.iter_mut().enumerate() {
-                        match timeout(
-                            Duration::from_millis(100),
-                            ctx.read_holding_registers(i as u16, 1),
-                        )
-                        .await
-                        {
-                            Ok(value) => match value {
-                                Ok(value) => *reg = value[0],
-                                Err(e) => {
-                                    ui_event_sender
-                                        .clone()
-                                        .send(UiCommand::Error(format!(
-                                            "Ein Holding Register {} konnte nicht gelesen werden: {}",
-                                            i,
-                                            e.to_string()
-                                        )))
-                                        .await
-                                        .expect("Failed to send Ui command");
-                                }
-                            },
-                            Err(_) => {
-                                ui_event_sender
-                                    .clone()
-                                    .send(UiCommand::Error(format!(
-                                        "Timeout beim lesen aller Register"
-                                    )))
-                                    .await
-                                    .expect("Failed to send Ui command");
-                            }
-                        };
-                    }
-                    ui_event_sender
-                        .clone()
-                        .send(UiCommand::UpdateSensorRwregValues(Ok(registers)))
-                        .await
-                        .expect("Failed to send Ui command");
-                }
-            });
+    /// Backup of all writable Rwreg's
+    pub async fn backup(&self, port: Option<String>, modbus_address: u8) -> Result<Backup> {
+        let numbers: Vec<u16> = backup_registers().map(|reg| reg.number).collect();
+        let values = self
+            .read_registers_once(
+                port.clone(),
+                modbus_address,
+                FunctionCode::ReadHoldingRegisters,
+                &numbers,
+            )
+            .await?;
+        let software_date = self
+            .read_registers_once(
+                port.clone(),
+                modbus_address,
+                FunctionCode::ReadInputRegisters,
+                &[49],
+            )
+            .await?
+            .first()
+            .map(|(_, value)| *value);
+        Ok(Backup::new(
+            &port.unwrap_or_default(),
+            modbus_address,
+            software_date,
+            &values,
+        ))
+    }
 
-            Ok(())
-        } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "No serial port found",
-            ))
+    /// Differences between the backup and the sensor
+    ///
+    /// The backup must have been made from the connected sensor, i.e. the device
+    /// ids (Rwreg 98) match, unless `other_device` is set.
+    pub async fn restore_diff(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+        backup: &Backup,
+        other_device: bool,
+    ) -> Result<Vec<Change>> {
+        let mut numbers: Vec<u16> = restore_registers().map(|reg| reg.number).collect();
+        numbers.push(98);
+        let values = self
+            .read_registers_once(
+                port,
+                modbus_address,
+                FunctionCode::ReadHoldingRegisters,
+                &numbers,
+            )
+            .await?;
+        if !other_device {
+            backup.check_device(register_value(&values, 98))?;
         }
+        Ok(backup.diff(&values))
     }
 
-    /// Read Modbus Input Registers 0x04
+    /// Restore a backup
     ///
+    /// Only the registers which differ are written, see `restore_diff`. Values out
+    /// of range or commands (11111 in Rwreg 10 and 12) are refused before anything
+    /// is written. Each register is written and read back like all other writes,
+    /// if one fails the registers restored so far get their previous values back,
+    /// see `write_registers`. Afterwards all registers are read again and compared
+    /// with the backup. Returns the written registers.
+    pub async fn restore(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+        backup: &Backup,
+        other_device: bool,
+    ) -> Result<Vec<Change>> {
+        let changes = self
+            .restore_diff(port.clone(), modbus_address, backup, other_device)
+            .await?;
+        if changes.is_empty() {
+            return Ok(changes);
+        }
+        for change in &changes {
+            change.check()?;
+        }
+        let registers: Vec<(u16, u16)> = changes
+            .iter()
+            .map(|change| (change.number, change.backup))
+            .collect();
+        let sensor: Vec<(u16, u16)> = changes
+            .iter()
+            .filter_map(|change| Some((change.number, change.sensor?)))
+            .collect();
+        self.write_registers(port.clone(), modbus_address, &registers, &sensor)
+            .await?;
+
+        // Verify
+        let remaining = self
+            .restore_diff(port, modbus_address, backup, other_device)
+            .await?;
+        if !remaining.is_empty() {
+            return Err(Error::NotRestored(
+                remaining.iter().map(|change| change.number).collect(),
+            ));
+        }
+        Ok(changes)
+    }
+
+    /// Calibration values of the output current, Rwreg 53 and 54
+    pub async fn output_calibration(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+    ) -> Result<OutputCalibration> {
+        let rwregs = self
+            .read_registers_once(
+                port,
+                modbus_address,
+                FunctionCode::ReadHoldingRegisters,
+                &OUTPUT_CALIBRATION_RWREGS,
+            )
+            .await?;
+        OutputCalibration::from_registers(&rwregs)
+            .ok_or(Error::Incomplete("Kalibrierwerte Ausgangsstrom"))
+    }
+
+    /// Write the calibration values of the output current
     ///
-    async fn read_input_registers(
+    /// Both registers are written and read back, see `write_register`.
+    pub async fn write_output_calibration(
         &self,
         port: Option<String>,
         modbus_address: u8,
-        ui_event_sender: Sender<UiCommand>,
-        // FIXME: Implement state in Ne4 Client
-        state: std::sync::Arc<tokio::sync::Mutex<TokioState>>,
-    ) -> tokio::io::Result<()> {
-        if let Some(tty_path) = port {
-            let slave = Slave(modbus_address);
-            let port = Serial::from_path(tty_path, &self.serial_config.settings)?;
-            let mut ctx = rtu::connect_slave(port, slave).await?;
-            ctx.set_slave(slave);
-
-            tokio::task::spawn(async move {
-                'update: loop {
-                    let state = state.lock().await;
-                    if *state == TokioState::Disconnected {
-                        break;
-                    }
+        calibration: OutputCalibration,
+    ) -> Result<OutputCalibration> {
+        let old = self
+            .output_calibration(port.clone(), modbus_address)
+            .await?;
+        self.write_registers(
+            port.clone(),
+            modbus_address,
+            &calibration.registers(),
+            &old.registers(),
+        )
+        .await?;
+        self.output_calibration(port, modbus_address).await
+    }
 
-                    let mut registers = vec![0u16; 50];
-
-                    for (i, reg) in registers.iter_mut().enumerate() {
-                        match timeout(
-                            Duration::from_millis(100),
-                            ctx.read_input_registers(i as u16, 1),
-                        )
-                        .await
-                        {
-                            Ok(value) => match value {
-                                Ok(value) => *reg = value[0],
-                                Err(e) => {
-                                    ui_event_sender
-                                        .clone()
-                                        .send(UiCommand::Error(format!(
-                                            "Ein Input Register {} konnte nicht gelesen werden: {}",
-                                            i,
-                                            e.to_string()
-                                        )))
-                                        .await
-                                        .expect("Failed to send Ui command");
-                                }
-                            },
-                            Err(_) => {
-                                ui_event_sender
-                                    .clone()
-                                    .send(UiCommand::Error(format!(
-                                        "Timeout beim lesen aller Register"
-                                    )))
-                                    .await
-                                    .expect("Failed to send Ui command");
-                            }
-                        };
-                    }
-                    ui_event_sender
-                        .clone()
-                        .send(UiCommand::UpdateSensorValues(Ok(registers)))
-                        .await
-                        .expect("Failed to send Ui command");
-                }
-            });
+    /// Mapping of the gas concentration to the output current, Rwreg 15 to 18
+    pub async fn output_scaling(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+    ) -> Result<OutputScaling> {
+        let rwregs = self
+            .read_registers_once(
+                port,
+                modbus_address,
+                FunctionCode::ReadHoldingRegisters,
+                &OUTPUT_SCALING_RWREGS,
+            )
+            .await?;
+        OutputScaling::from_registers(&rwregs).ok_or(Error::Incomplete("Skalierung Ausgangsstrom"))
+    }
 
-            Ok(())
-        } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "No serial port found",
-            ))
-        }
+    /// Write all four registers of the output scaling
+    ///
+    /// The scaling is validated before the first register is written. The order
+    /// keeps the upper value above the lower one in between, see
+    /// `OutputScaling::write_order`. Each register is read back, on failure the
+    /// previous scaling is written back, see `write_registers`.
+    pub async fn write_output_scaling(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+        scaling: OutputScaling,
+    ) -> Result<OutputScaling> {
+        scaling.validate()?;
+        let old = self.output_scaling(port.clone(), modbus_address).await?;
+        self.write_registers(
+            port.clone(),
+            modbus_address,
+            &scaling.write_order(&old),
+            &old.registers(),
+        )
+        .await?;
+        self.output_scaling(port, modbus_address).await
     }
 
-    /// Read holding and input registers sequenciell
+    /// Temperature curve of the sensor cell, Rwreg 30 to 36
+    pub async fn temperature_curve(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+    ) -> Result<TemperatureCurve> {
+        let rwregs = self
+            .read_registers_once(
+                port,
+                modbus_address,
+                FunctionCode::ReadHoldingRegisters,
+                &CURVE_RWREGS,
+            )
+            .await?;
+        TemperatureCurve::from_registers(&rwregs).ok_or(Error::Incomplete("Temperaturkennlinie"))
+    }
+
+    /// Write all factors of the temperature curve
     ///
+    /// The whole curve is validated before the first register is written, each
+    /// register is read back. On failure the previous curve is written back, see
+    /// `write_registers`.
+    pub async fn write_temperature_curve(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+        curve: TemperatureCurve,
+    ) -> Result<TemperatureCurve> {
+        curve.validate()?;
+        let old = self.temperature_curve(port.clone(), modbus_address).await?;
+        self.write_registers(
+            port.clone(),
+            modbus_address,
+            &curve.registers(),
+            &old.registers(),
+        )
+        .await?;
+        self.temperature_curve(port, modbus_address).await
+    }
+
+    /// Settings of the analog front-end, Rwreg 21 to 29
+    pub async fn front_end(&self, port: Option<String>, modbus_address: u8) -> Result<FrontEnd> {
+        let rwregs = self
+            .read_registers_once(
+                port,
+                modbus_address,
+                FunctionCode::ReadHoldingRegisters,
+                &FRONT_END_RWREGS,
+            )
+            .await?;
+        FrontEnd::from_registers(&rwregs).ok_or(Error::Incomplete("Auswerte IC"))
+    }
+
+    /// Write all settings of the analog front-end
     ///
-    async fn read_registers(
+    /// All fields are validated before the first register is written, each
+    /// register is read back. On failure the previous settings are written back,
+    /// see `write_registers`.
+    pub async fn write_front_end(
         &self,
         port: Option<String>,
         modbus_address: u8,
-        ui_event_sender: Sender<UiCommand>,
-        // FIXME: Implement state in Ne4 Client
-        state: std::sync::Arc<tokio::sync::Mutex<TokioState>>,
-    ) -> tokio::io::Result<()> {
-        self.read_input_registers(
+        front_end: FrontEnd,
+    ) -> Result<FrontEnd> {
+        front_end.validate()?;
+        let old = self.front_end(port.clone(), modbus_address).await?;
+        self.write_registers(
             port.clone(),
             modbus_address,
-            ui_event_sender.clone(),
-            state.clone(),
+            &front_end.write_order(),
+            &old.registers(),
         )
-        .await;
+        .await?;
+        self.front_end(port, modbus_address).await
+    }
 
-        self.read_holding_registers(port, modbus_address, ui_event_sender, state)
-            .await;
+    /// Force a test value, `None` switches the override off
+    ///
+    /// The override is remembered before writing, a sensor which took the value
+    /// but didn't answer is reset by `restore_test_values` as well. Returns the
+    /// read back raw value.
+    pub async fn set_test_value(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+        test_value: TestValue,
+        raw: Option<u16>,
+    ) -> Result<u16> {
+        let tty_path = port.clone().ok_or(Error::NoPort)?;
+        if raw.is_some() {
+            self.overrides
+                .borrow_mut()
+                .insert(&tty_path, modbus_address, test_value);
+        }
+        let value = self
+            .write_register(
+                port,
+                modbus_address,
+                test_value.register_number(),
+                raw.unwrap_or(TEST_VALUE_OFF),
+            )
+            .await?;
+        if raw.is_none() {
+            self.overrides
+                .borrow_mut()
+                .remove(&tty_path, modbus_address, test_value);
+        }
+        Ok(value)
+    }
 
+    /// Switch all test values of the sensor off, set by this client or not
+    pub async fn reset_test_values(&self, port: Option<String>, modbus_address: u8) -> Result<()> {
+        for test_value in &TestValue::ALL {
+            self.set_test_value(port.clone(), modbus_address, *test_value, None)
+                .await?;
+        }
         Ok(())
     }
+
+    /// Test values set by this client which aren't reset yet
+    pub fn has_test_values(&self, port: Option<&str>) -> bool {
+        let overrides = self.overrides.borrow();
+        match port {
+            Some(port) => !overrides.on_port(port).is_empty(),
+            None => !overrides.is_empty(),
+        }
+    }
+
+    /// Reset the test values set by this client, on `port` or on all ports
+    ///
+    /// Every override is tried, the ones which fail stay remembered for the next
+    /// attempt, e.g. after a lost port is back. Returns the reset overrides or
+    /// the first error.
+    pub async fn restore_test_values(&self, port: Option<&str>) -> Result<Vec<Override>> {
+        let overrides = match port {
+            Some(port) => self.overrides.borrow().on_port(port),
+            None => self.overrides.borrow().all().to_vec(),
+        };
+        let mut restored = vec![];
+        let mut error = None;
+        for o in overrides {
+            match self
+                .set_test_value(Some(o.port.clone()), o.modbus_address, o.test_value, None)
+                .await
+            {
+                Ok(_) => restored.push(o),
+                Err(e) => {
+                    debug!("Failed to reset test value {:?}: {}", o, e);
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(restored),
+        }
+    }
+
+    /// Values of one dashboard sensor
+    ///
+    /// Reads working mode, gas concentration and output current with a single
+    /// request, see `sensors::dashboard`.
+    pub async fn read_dashboard_sensor(
+        &self,
+        port: Option<String>,
+        modbus_address: u8,
+    ) -> Result<NE4> {
+        let connection = self.connection(port)?;
+        read_dashboard_sensor(&connection, modbus_address).await
+    }
+}
+
+/// Read the given registers with as few requests as possible
+///
+/// The block plan of the device is used and updated, see `register_blocks`.
+/// Returns the register values indexed by register number and the errors of the
+/// blocks which couldn't be read. Registers of these blocks are `None`.
+async fn read_register_blocks(
+    connection: &Connection,
+    modbus_address: u8,
+    function: FunctionCode,
+    table: &[Register],
+    len: usize,
+    block_plans: &BlockPlans,
+) -> (Vec<Option<u16>>, Vec<String>) {
+    let device = (connection.path().to_string(), modbus_address, function);
+    let numbers: Vec<u16> = table.iter().map(|reg| reg.number).collect();
+    let plan = block_plans.borrow().get(&device).cloned();
+    let mut read = BlockRead::new(&numbers, plan.as_deref());
+    let mut registers = vec![None; len];
+    let mut errors = vec![];
+
+    while let Some(block) = read.next_block() {
+        let response = match function {
+            FunctionCode::ReadHoldingRegisters => {
+                connection
+                    .read_holding_registers(modbus_address, block.start, block.count)
+                    .await
+            }
+            FunctionCode::ReadInputRegisters => {
+                connection
+                    .read_input_registers(modbus_address, block.start, block.count)
+                    .await
+            }
+        };
+        match response {
+            Ok(values) => {
+                for (number, value) in (block.start..).zip(values) {
+                    if let Some(reg) = registers.get_mut(number as usize) {
+                        *reg = Some(value);
+                    }
+                }
+                read.succeeded(block);
+            }
+            Err(e) if e.is_exception() => {
+                debug!("Block {:?} rejected: {}", block, e);
+                if !read.rejected(block) {
+                    errors.push(format!("Register {}: {}", block.start, e));
+                }
+            }
+            Err(e) => {
+                read.failed(block);
+                errors.push(format!("Register {} … {}: {}", block.start, block.end(), e));
+            }
+        }
+    }
+
+    block_plans.borrow_mut().insert(device, read.into_plan());
+    (registers, errors)
+}
+
+/// Read the dashboard registers of one sensor
+async fn read_dashboard_sensor(connection: &Connection, modbus_address: u8) -> Result<NE4> {
+    let values = connection
+        .read_input_registers(modbus_address, DASHBOARD_RREG_START, DASHBOARD_RREG_COUNT)
+        .await?;
+    let mut registers = vec![None; usize::from(DASHBOARD_RREG_START)];
+    registers.extend(values.into_iter().map(Some));
+    let mut ne4 = NE4::new();
+    ne4.update_rregs(&registers);
+    Ok(ne4)
+}
+
+/// Send a command to the UI
+///
+/// Returns false if the UI is gone, e.g. while the application quits. Loops
+/// stop in this case instead of panicking.
+async fn send_ui(ui_event_sender: &Sender<UiCommand>, command: UiCommand) -> bool {
+    match ui_event_sender.clone().send(command).await {
+        Ok(()) => true,
+        Err(e) => {
+            debug!("Failed to send Ui command: {}", e);
+            false
+        }
+    }
+}
+
+/// Reset the test values set by `ne4_client` and report the result to the UI
+///
+/// Nothing is sent if no test values were set.
+async fn restore_test_values(
+    ne4_client: &Ne4Client,
+    port: Option<&str>,
+    ui_event_sender: &Sender<UiCommand>,
+) {
+    if ne4_client.has_test_values(port) {
+        let result = ne4_client.restore_test_values(port).await;
+        send_ui(ui_event_sender, UiCommand::TestValuesRestored(result)).await;
+    }
+}
+
+/// Probe all Modbus addresses of the line
+///
+/// Every probed address is reported with `UiCommand::ScanProgress`, together with
+/// the sensor if one answered. The scan stops early if `cancelled` returns true.
+async fn scan_bus(
+    connection: Result<Connection>,
+    cancelled: impl Fn() -> bool,
+    ui_event_sender: &Sender<UiCommand>,
+) -> Result<()> {
+    let connection = connection?;
+    for modbus_address in SCAN_ADDRESSES {
+        if cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let result = scan_address(&connection, modbus_address).await?;
+        if !send_ui(
+            ui_event_sender,
+            UiCommand::ScanProgress(modbus_address, result),
+        )
+        .await
+        {
+            return Err(Error::Cancelled);
+        }
+    }
+    Ok(())
 }
 
 /// TokioThread
@@ -368,8 +1052,6 @@ pub struct TokioThread {
 
 impl TokioThread {
     pub fn new(ui_event_sender: Sender<UiCommand>) -> Self {
-        let ne4_client = Ne4Client::new();
-
         let (tokio_thread_sender, mut tokio_thread_receiver) = futures::channel::mpsc::channel(0);
         // Clone the ui_event_sender. This is used in a second thread, see below.
         let ui_event_sender2 = ui_event_sender.clone();
@@ -377,118 +1059,345 @@ impl TokioThread {
         std::thread::spawn(move || {
             // Tokio Thread
             let mut rt = tokio::runtime::Runtime::new().expect("create tokio runtime");
-            // Shared State
-            let state = std::sync::Arc::new(tokio::sync::Mutex::new(TokioState::Disconnected));
+            // The modbus session isn't `Send`, all tasks using it run on this thread.
+            let local = tokio::task::LocalSet::new();
+            let ne4_client = Ne4Client::new(LineSettings::default());
+            // Polling task of the connection, at most one runs at a time
+            let mut poller: Option<Poller> = None;
+            let mut poll_interval = DEFAULT_POLL_INTERVAL;
+            // Number of the current bus scan, a running scan stops if it changes
+            let scan_generation = Rc::new(Cell::new(0u32));
 
-            rt.block_on(async {
+            local.block_on(&mut rt, async {
                 while let Some(event) = tokio_thread_receiver.next().await {
                     debug!("Tokio Thread got event: TokioCommand::{:?}", event);
                     match event {
                         TokioCommand::UpdateSensor(port, modbus_address) => {
                             info!("Execute event TokioCommand::UpdateSensor");
-                            ne4_client
-                                .read_registers(
-                                    port,
-                                    modbus_address,
-                                    ui_event_sender.clone(),
-                                    state.clone(),
-                                )
-                                .await
-                                .expect("Could not start read registers loop");
+                            if let Some(poller) = poller.take() {
+                                poller.stop().await;
+                            }
+                            match Poller::start(
+                                &ne4_client,
+                                port,
+                                PollTarget::Sensor(modbus_address),
+                                poll_interval,
+                                ui_event_sender.clone(),
+                            ) {
+                                Ok(started) => poller = Some(started),
+                                Err(e) => {
+                                    send_ui(
+                                        &ui_event_sender,
+                                        UiCommand::Error(format!(
+                                            "Abfrage konnte nicht gestartet werden: {}",
+                                            e
+                                        )),
+                                    )
+                                    .await;
+                                }
+                            }
                         }
-                        // TODO: Remove this after UpdateSensor is refactored to call read_input and read_holding registers in sequence
-                        TokioCommand::UpdateSensorRwregValues(port, modbus_address) => {
-                            info!("Execute event TokioCommand::UpdateSensor");
-                            ne4_client
-                                .read_holding_registers(
-                                    port,
-                                    modbus_address,
-                                    ui_event_sender.clone(),
-                                    state.clone(),
-                                )
-                                .await
-                                .expect("Could not start read registers loop");
+                        TokioCommand::UpdateDashboard(port, modbus_addresses) => {
+                            info!("Execute event TokioCommand::UpdateDashboard");
+                            if let Some(poller) = poller.take() {
+                                poller.stop().await;
+                            }
+                            match Poller::start(
+                                &ne4_client,
+                                port,
+                                PollTarget::Dashboard(modbus_addresses),
+                                poll_interval,
+                                ui_event_sender.clone(),
+                            ) {
+                                Ok(started) => poller = Some(started),
+                                Err(e) => {
+                                    send_ui(
+                                        &ui_event_sender,
+                                        UiCommand::Error(format!(
+                                            "Abfrage konnte nicht gestartet werden: {}",
+                                            e
+                                        )),
+                                    )
+                                    .await;
+                                }
+                            }
+                        }
+                        TokioCommand::SetPollInterval(interval) => {
+                            info!("Execute event TokioCommand::SetPollInterval");
+                            poll_interval = interval;
+                            if let Some(poller) = &poller {
+                                poller.set_interval(interval);
+                            }
                         }
                         TokioCommand::Connect => {
                             info!("Execute event TokioCommand::Connect");
-                            // Adjust shared state
-                            let mut state = state.lock().await;
-                            *state = TokioState::Connected;
-
-                            ui_event_sender
-                                .clone()
-                                .send(UiCommand::DisableConnectUiElements)
-                                .await
-                                .expect("Failed to send Ui command");
+                            send_ui(&ui_event_sender, UiCommand::DisableConnectUiElements).await;
                         }
                         TokioCommand::Disconnect => {
                             info!("Execute event TokioCommand::Disconnect");
-                            // Adjust shared state
-                            let mut state = state.lock().await;
-                            *state = TokioState::Disconnected;
-
-                            ui_event_sender
-                                .clone()
-                                .send(UiCommand::EnableConnectUiElements)
-                                .await
-                                .expect("Failed to send Ui command");
-
-                            let available_ports = get_ports();
-                            ui_event_sender
-                                .clone()
-                                .send(UiCommand::UpdatePorts(available_ports.clone()))
-                                .await
-                                .expect("Failed to send Ui command");
+                            // No more traffic on the bus before the port is closed
+                            if let Some(poller) = poller.take() {
+                                poller.stop().await;
+                            }
+                            // A sensor must never keep a forced value
+                            restore_test_values(&ne4_client, None, &ui_event_sender).await;
+                            ne4_client.disconnect();
+
+                            send_ui(&ui_event_sender, UiCommand::EnableConnectUiElements).await;
+                            // Current ports, without events
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::UpdatePorts(available_ports(), vec![]),
+                            )
+                            .await;
                         }
-                        TokioCommand::Nullpunkt(port, modbus_address) => {
-                            info!("Execute event TokioCommand::Nullpunkt");
-                            // TODO: Implement InfoBar here
-                            // ui_event_sender
-                            //     .clone()
-                            //     .send(UiCommand::ShowInfo("hi".to_string()))
-                            //     .await
-                            //     .expect("Failed to send Ui command");
-                            ui_event_sender
-                                .clone()
-                                .send(UiCommand::Nullpunkt(
-                                    ne4_client.nullpunkt(port, modbus_address).await,
-                                ))
-                                .await
-                                .expect("Failed to send Ui command")
+                        TokioCommand::ReadCalibration(port, modbus_address) => {
+                            info!("Execute event TokioCommand::ReadCalibration");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::CalibrationRead(
+                                    ne4_client.calibration_start(port, modbus_address).await,
+                                ),
+                            )
+                            .await;
                         }
-                        TokioCommand::Messgas(port, modbus_address) => {
-                            info!("Execute event TokioCommand::Messgas");
-                            ui_event_sender
-                                .clone()
-                                .send(UiCommand::Messgas(
-                                    ne4_client.messgas(port, modbus_address).await,
-                                ))
-                                .await
-                                .expect("Failed to send Ui command")
+                        TokioCommand::Calibrate(port, modbus_address, step) => {
+                            info!("Execute event TokioCommand::Calibrate");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::Calibrated(
+                                    step,
+                                    ne4_client.calibrate(port, modbus_address, step).await,
+                                ),
+                            )
+                            .await;
                         }
                         TokioCommand::NewWorkingMode(port, modbus_address, working_mode) => {
                             info!("Execute event TokioCommand::Messgas");
-                            ui_event_sender
-                                .clone()
-                                .send(UiCommand::NewWorkingMode(
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::NewWorkingMode(
                                     ne4_client
                                         .new_working_mode(port, modbus_address, working_mode)
                                         .await,
-                                ))
-                                .await
-                                .expect("Failed to send Ui command")
+                                ),
+                            )
+                            .await;
+                        }
+                        TokioCommand::Backup(port, modbus_address) => {
+                            info!("Execute event TokioCommand::Backup");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::Backup(ne4_client.backup(port, modbus_address).await),
+                            )
+                            .await;
+                        }
+                        TokioCommand::RestoreDiff(port, modbus_address, backup, other_device) => {
+                            info!("Execute event TokioCommand::RestoreDiff");
+                            let changes = ne4_client
+                                .restore_diff(port, modbus_address, &backup, other_device)
+                                .await;
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::RestoreDiff(backup, other_device, changes),
+                            )
+                            .await;
+                        }
+                        TokioCommand::Restore(port, modbus_address, backup, other_device) => {
+                            info!("Execute event TokioCommand::Restore");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::Restore(
+                                    ne4_client
+                                        .restore(port, modbus_address, &backup, other_device)
+                                        .await,
+                                ),
+                            )
+                            .await;
+                        }
+                        TokioCommand::ScanBus(port) => {
+                            info!("Execute event TokioCommand::ScanBus");
+                            // The scan uses its own session, the bus belongs to it alone
+                            if let Some(poller) = poller.take() {
+                                poller.stop().await;
+                            }
+                            let generation = scan_generation.get().wrapping_add(1);
+                            scan_generation.set(generation);
+                            let scan_generation = scan_generation.clone();
+                            let connection = ne4_client.scan_connection(port);
+                            let ui_event_sender = ui_event_sender.clone();
+
+                            tokio::task::spawn_local(async move {
+                                let result = scan_bus(
+                                    connection,
+                                    || scan_generation.get() != generation,
+                                    &ui_event_sender,
+                                )
+                                .await;
+                                send_ui(&ui_event_sender, UiCommand::ScanFinished(result)).await;
+                            });
+                        }
+                        TokioCommand::CancelScan => {
+                            info!("Execute event TokioCommand::CancelScan");
+                            scan_generation.set(scan_generation.get().wrapping_add(1));
+                        }
+                        TokioCommand::SetLineSettings(line_settings) => {
+                            info!("Execute event TokioCommand::SetLineSettings");
+                            ne4_client.set_line_settings(line_settings);
+                        }
+                        TokioCommand::WriteRegister(port, modbus_address, reg_nr, value) => {
+                            info!("Execute event TokioCommand::WriteRegister");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::WriteRegister(
+                                    reg_nr,
+                                    ne4_client
+                                        .write_register(port, modbus_address, reg_nr, value)
+                                        .await,
+                                ),
+                            )
+                            .await;
+                        }
+                        TokioCommand::ReadOutputCalibration(port, modbus_address) => {
+                            info!("Execute event TokioCommand::ReadOutputCalibration");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::OutputCalibrationRead(
+                                    ne4_client.output_calibration(port, modbus_address).await,
+                                ),
+                            )
+                            .await;
+                        }
+                        TokioCommand::WriteOutputCalibration(port, modbus_address, calibration) => {
+                            info!("Execute event TokioCommand::WriteOutputCalibration");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::OutputCalibrationWritten(
+                                    ne4_client
+                                        .write_output_calibration(port, modbus_address, calibration)
+                                        .await,
+                                ),
+                            )
+                            .await;
+                        }
+                        TokioCommand::ReadOutputScaling(port, modbus_address) => {
+                            info!("Execute event TokioCommand::ReadOutputScaling");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::OutputScalingRead(
+                                    ne4_client.output_scaling(port, modbus_address).await,
+                                ),
+                            )
+                            .await;
+                        }
+                        TokioCommand::WriteOutputScaling(port, modbus_address, scaling) => {
+                            info!("Execute event TokioCommand::WriteOutputScaling");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::OutputScalingWritten(
+                                    ne4_client
+                                        .write_output_scaling(port, modbus_address, scaling)
+                                        .await,
+                                ),
+                            )
+                            .await;
+                        }
+                        TokioCommand::ReadTemperatureCurve(port, modbus_address) => {
+                            info!("Execute event TokioCommand::ReadTemperatureCurve");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::TemperatureCurveRead(
+                                    ne4_client.temperature_curve(port, modbus_address).await,
+                                ),
+                            )
+                            .await;
+                        }
+                        TokioCommand::WriteTemperatureCurve(port, modbus_address, curve) => {
+                            info!("Execute event TokioCommand::WriteTemperatureCurve");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::TemperatureCurveWritten(
+                                    ne4_client
+                                        .write_temperature_curve(port, modbus_address, curve)
+                                        .await,
+                                ),
+                            )
+                            .await;
+                        }
+                        TokioCommand::ReadFrontEnd(port, modbus_address) => {
+                            info!("Execute event TokioCommand::ReadFrontEnd");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::FrontEndRead(
+                                    ne4_client.front_end(port, modbus_address).await,
+                                ),
+                            )
+                            .await;
+                        }
+                        TokioCommand::WriteFrontEnd(port, modbus_address, front_end) => {
+                            info!("Execute event TokioCommand::WriteFrontEnd");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::FrontEndWritten(
+                                    ne4_client
+                                        .write_front_end(port, modbus_address, front_end)
+                                        .await,
+                                ),
+                            )
+                            .await;
+                        }
+                        TokioCommand::SetTestValue(port, modbus_address, test_value, raw) => {
+                            info!("Execute event TokioCommand::SetTestValue");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::TestValue(
+                                    test_value,
+                                    ne4_client
+                                        .set_test_value(port, modbus_address, test_value, raw)
+                                        .await,
+                                ),
+                            )
+                            .await;
+                        }
+                        TokioCommand::ResetTestValues(port, modbus_address) => {
+                            info!("Execute event TokioCommand::ResetTestValues");
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::TestValuesReset(
+                                    ne4_client.reset_test_values(port, modbus_address).await,
+                                ),
+                            )
+                            .await;
+                        }
+                        TokioCommand::RestoreTestValues(port) => {
+                            info!("Execute event TokioCommand::RestoreTestValues");
+                            // Only without connection, the session belongs to the poller
+                            if poller.is_none() {
+                                restore_test_values(&ne4_client, port.as_deref(), &ui_event_sender)
+                                    .await;
+                                ne4_client.disconnect();
+                            }
+                        }
+                        TokioCommand::Shutdown => {
+                            info!("Execute event TokioCommand::Shutdown");
+                            if let Some(poller) = poller.take() {
+                                poller.stop().await;
+                            }
+                            restore_test_values(&ne4_client, None, &ui_event_sender).await;
+                            ne4_client.disconnect();
+                            send_ui(&ui_event_sender, UiCommand::Quit).await;
                         }
                         TokioCommand::NewModbusAddress(port, modbus_address, new_modbus) => {
                             info!("Execute event TokioCommand::Messgas");
-                            ui_event_sender
-                                .clone()
-                                .send(UiCommand::NewModbusAddress(
+                            send_ui(
+                                &ui_event_sender,
+                                UiCommand::NewModbusAddress(
                                     ne4_client
                                         .new_modbus_address(port, modbus_address, new_modbus)
                                         .await,
-                                ))
-                                .await
-                                .expect("Failed to send Ui command")
+                                ),
+                            )
+                            .await;
                         }
                     }
                 }
@@ -501,33 +1410,27 @@ impl TokioThread {
             let mut rt = tokio::runtime::Runtime::new().expect("create tokio runtime");
 
             rt.block_on(async {
-                let mut ports: Vec<String> = vec![];
+                let mut watcher = PortWatcher::new();
                 let mut interval = tokio::time::interval(Duration::from_millis(100));
 
                 // Initial send one update ports for program start
-                let available_ports = get_ports();
-                let _ = ui_event_sender2
-                    .clone()
-                    .send(UiCommand::UpdatePorts(available_ports.clone()))
-                    .await;
+                watcher.update(available_ports());
+                let command = UiCommand::UpdatePorts(watcher.ports().to_vec(), vec![]);
+                if !send_ui(&ui_event_sender2, command).await {
+                    return;
+                }
 
                 loop {
-                    let available_ports = get_ports();
-                    if available_ports.len() > ports.len() {
-                        let _ = ui_event_sender2
-                            .clone()
-                            .send(UiCommand::UpdatePorts(available_ports.clone()))
-                            .await;
-                    } else if available_ports.len() < ports.len() {
-                        // let _ = ui_event_sender2.clone().send(UiCommand::Disconnect).await;
-
-                        let _ = ui_event_sender2
-                            .clone()
-                            .send(UiCommand::UpdatePorts(available_ports.clone()))
-                            .await;
-                    };
-                    ports = available_ports;
                     interval.tick().await;
+                    let events = watcher.update(available_ports());
+                    if events.is_empty() {
+                        continue;
+                    }
+                    debug!("Serial ports changed: {:?}", events);
+                    let command = UiCommand::UpdatePorts(watcher.ports().to_vec(), events);
+                    if !send_ui(&ui_event_sender2, command).await {
+                        break;
+                    }
                 }
             });
         });
@@ -538,22 +1441,463 @@ impl TokioThread {
     }
 }
 
-/// List available serial ports
-pub(crate) fn list_ports() -> tokio_serial::Result<Vec<String>> {
-    match tokio_serial::available_ports() {
-        Ok(ports) => Ok(ports.into_iter().map(|x| x.port_name).collect()),
-        Err(e) => Err(e),
-    }
-}
-
 /// Get and filter available serial ports
 ///
-/// This function is called from the gui thread.
+/// Never fails, enumeration errors are reported as no ports.
 pub fn get_ports() -> Vec<String> {
-    let mut ports = list_ports().expect("Scanning for ports should never fail");
-    ports.sort();
-    // Remove unwanted ports under linux
-    ports.retain(|p| p != "/dev/ttyS0");
+    available_ports()
+        .into_iter()
+        .map(|port| port.name)
+        .collect()
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::sensors::ra_gas_ne4::RREGS;
+    use crate::simulator::{model::Ne4Model, Faults, Simulator};
+
+    /// Simulated NE4 with Modbus address 247
+    fn simulator() -> Option<String> {
+        simulate(Ne4Model::new(247))
+    }
+
+    fn simulate(model: Ne4Model) -> Option<String> {
+        let simulator = Simulator::new(model, Faults::default(), 1).unwrap();
+        let path = simulator.path().to_string();
+        simulator.spawn();
+        Some(path)
+    }
+
+    #[tokio::test]
+    async fn write_register_read_back() {
+        let ne4_client = Ne4Client::new(LineSettings::default());
+        let port = simulator();
+        assert_eq!(
+            ne4_client
+                .write_register(port.clone(), 247, 13, 500)
+                .await
+                .unwrap(),
+            500
+        );
+        let e = ne4_client
+            .write_register(port, 247, 13, 10001)
+            .await
+            .unwrap_err();
+        match e {
+            Error::OutOfRange { register: 13, .. } => {}
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn write_modbus_address() {
+        let ne4_client = Ne4Client::new(LineSettings::default());
+        let port = simulator();
+        assert_eq!(
+            ne4_client
+                .write_register(port.clone(), 247, 50, 12)
+                .await
+                .unwrap(),
+            12
+        );
+        let values = ne4_client
+            .read_registers_once(port, 12, FunctionCode::ReadHoldingRegisters, &[50])
+            .await
+            .unwrap();
+        assert_eq!(values, vec![(50, 12)]);
+    }
+
+    #[tokio::test]
+    async fn device_info() {
+        let ne4_client = Ne4Client::new(LineSettings::default());
+        let info = ne4_client.device_info(simulator(), 247).await.unwrap();
+        assert_eq!(info.device_id, Some(12345));
+        assert_eq!(
+            info.software_date_decoded(),
+            Some(chrono::NaiveDate::from_ymd(2020, 4, 9))
+        );
+    }
+
+    #[tokio::test]
+    async fn dashboard_sensor() {
+        let ne4_client = Ne4Client::new(LineSettings::default());
+        let port = simulator();
+        let ne4 = ne4_client
+            .read_dashboard_sensor(port.clone(), 247)
+            .await
+            .unwrap();
+        assert_eq!(ne4.work_mode().code(), 10);
+        assert!(ne4.output_current() >= 4.0);
+        // Keine Antwort von einer anderen Adresse
+        assert!(ne4_client.read_dashboard_sensor(port, 246).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn calibrate() {
+        let ne4_client = Ne4Client::new(LineSettings::default());
+        let port = simulator();
+        let old = ne4_client
+            .calibration_points(port.clone(), 247)
+            .await
+            .unwrap();
+        assert_eq!(old.span_adc, 4800);
+        let record = ne4_client
+            .calibrate(port.clone(), 247, CalibrationStep::Zero)
+            .await
+            .unwrap();
+        assert_eq!(record.before.points, old);
+        assert_eq!(record.after.points.zero_ppm, 0);
+        assert_eq!(record.after.points.span_adc, old.span_adc);
+        let record = ne4_client
+            .calibrate(port, 247, CalibrationStep::Span { concentration: 500 })
+            .await
+            .unwrap();
+        assert_eq!(record.test_gas_ppm, Some(500));
+        assert_eq!(record.after.points.span_ppm, 500);
+        assert_ne!(record.after.points.span_adc, old.span_adc);
+    }
+
+    #[tokio::test]
+    async fn backup_and_restore() {
+        let ne4_client = Ne4Client::new(LineSettings::default());
+        let port = simulator();
+        let backup = ne4_client.backup(port.clone(), 247).await.unwrap();
+        assert_eq!(backup.device_id, Some(12345));
+        assert!(backup
+            .registers
+            .iter()
+            .all(|reg| ![20, 49, 96, 97].contains(&reg.number)));
+
+        ne4_client
+            .write_register(port.clone(), 247, 13, 500)
+            .await
+            .unwrap();
+        let changes = ne4_client
+            .restore_diff(port.clone(), 247, &backup, false)
+            .await
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].sensor, Some(500));
+
+        // Sicherung eines anderen Sensors nur mit ausdrücklicher Freigabe
+        let other = Backup {
+            device_id: Some(4711),
+            ..backup.clone()
+        };
+        match ne4_client.restore(port.clone(), 247, &other, false).await {
+            Err(Error::OtherDevice { .. }) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(
+            ne4_client
+                .restore_diff(port.clone(), 247, &other, true)
+                .await
+                .unwrap(),
+            changes
+        );
+
+        assert_eq!(
+            ne4_client
+                .restore(port.clone(), 247, &backup, false)
+                .await
+                .unwrap(),
+            changes
+        );
+        assert!(ne4_client
+            .restore_diff(port, 247, &backup, false)
+            .await
+            .unwrap()
+            .is_empty());
+    }
 
-    ports
+    #[tokio::test]
+    async fn restore_roll_back() {
+        let ne4_client = Ne4Client::new(LineSettings::default());
+        let mut model = Ne4Model::new(247);
+        model.reject_writes(15);
+        let port = simulate(model);
+        let mut backup = ne4_client.backup(port.clone(), 247).await.unwrap();
+        for register in backup.registers.iter_mut() {
+            if register.number == 15 {
+                register.value = 100;
+            }
+        }
+        for (number, value) in &[(13, 500), (17, 5000)] {
+            ne4_client
+                .write_register(port.clone(), 247, *number, *value)
+                .await
+                .unwrap();
+        }
+
+        // Rwreg 13 ist schon wiederhergestellt wenn 15 fehlschlägt
+        match ne4_client.restore(port.clone(), 247, &backup, false).await {
+            Err(Error::Locked { register: 15 }) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        let values = ne4_client
+            .read_registers_once(port, 247, FunctionCode::ReadHoldingRegisters, &[13, 17])
+            .await
+            .unwrap();
+        assert_eq!(values, vec![(13, 500), (17, 5000)]);
+    }
+
+    #[tokio::test]
+    async fn read_blocks_with_gaps() {
+        let ne4_client = Ne4Client::new(LineSettings::default());
+        let connection = ne4_client.connection(simulator()).unwrap();
+        let (registers, errors) = read_register_blocks(
+            &connection,
+            247,
+            FunctionCode::ReadInputRegisters,
+            RREGS,
+            50,
+            &ne4_client.block_plans,
+        )
+        .await;
+        assert!(errors.is_empty());
+        assert_eq!(registers[49], Some(crate::simulator::model::SOFTWARE_DATE));
+        let plan = ne4_client.block_plans.borrow();
+        let plan = plan
+            .get(&(
+                connection.path().to_string(),
+                247,
+                FunctionCode::ReadInputRegisters,
+            ))
+            .unwrap();
+        // The simulator rejects the unknown Rreg 5 … 39, 47 and 48 like the sensor
+        assert_eq!(
+            plan,
+            &vec![
+                Block { start: 0, count: 5 },
+                Block {
+                    start: 40,
+                    count: 7
+                },
+                Block {
+                    start: 49,
+                    count: 1
+                }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn write_output_calibration() {
+        let ne4_client = Ne4Client::new(LineSettings::default());
+        let port = simulator();
+        let old = ne4_client
+            .output_calibration(port.clone(), 247)
+            .await
+            .unwrap();
+        assert_eq!(
+            old,
+            OutputCalibration {
+                low: 180,
+                high: 900
+            }
+        );
+        let new = OutputCalibration {
+            low: 187,
+            high: 898,
+        };
+        assert_eq!(
+            ne4_client
+                .write_output_calibration(port.clone(), 247, new)
+                .await
+                .unwrap(),
+            new
+        );
+        assert!(ne4_client
+            .write_output_calibration(port, 247, OutputCalibration { low: 5, high: 898 })
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn write_output_calibration_roll_back() {
+        let ne4_client = Ne4Client::new(LineSettings::default());
+        let mut model = Ne4Model::new(247);
+        model.reject_writes(54);
+        let port = simulate(model);
+        let new = OutputCalibration {
+            low: 187,
+            high: 898,
+        };
+
+        // Rwreg 53 ist schon geschrieben wenn 54 fehlschlägt
+        match ne4_client
+            .write_output_calibration(port.clone(), 247, new)
+            .await
+        {
+            Err(Error::Locked { register: 54 }) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(
+            ne4_client.output_calibration(port, 247).await.unwrap(),
+            OutputCalibration {
+                low: 180,
+                high: 900
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn write_output_scaling() {
+        let ne4_client = Ne4Client::new(LineSettings::default());
+        let port = simulator();
+        let old = ne4_client.output_scaling(port.clone(), 247).await.unwrap();
+        assert_eq!(old, OutputScaling::default());
+
+        // Der neue untere Messwert liegt über dem bisherigen oberen
+        let new = OutputScaling {
+            low_value: 2000,
+            low_current: 0,
+            high_value: 5000,
+            high_current: 2000,
+        };
+        assert_eq!(
+            ne4_client
+                .write_output_scaling(port.clone(), 247, new)
+                .await
+                .unwrap(),
+            new
+        );
+
+        let invalid = OutputScaling {
+            high_value: 1000,
+            ..new
+        };
+        assert!(ne4_client
+            .write_output_scaling(port.clone(), 247, invalid)
+            .await
+            .is_err());
+        assert_eq!(ne4_client.output_scaling(port, 247).await.unwrap(), new);
+
+        // Rwreg 15 wird nach 17 und 18 geschrieben, 17 wird zurückgesetzt
+        let mut model = Ne4Model::new(247);
+        model.reject_writes(15);
+        let port = simulate(model);
+        match ne4_client
+            .write_output_scaling(port.clone(), 247, new)
+            .await
+        {
+            Err(Error::Locked { register: 15 }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(ne4_client.output_scaling(port, 247).await.unwrap(), old);
+    }
+
+    #[tokio::test]
+    async fn write_temperature_curve() {
+        let ne4_client = Ne4Client::new(LineSettings::default());
+        let port = simulator();
+        let old = ne4_client
+            .temperature_curve(port.clone(), 247)
+            .await
+            .unwrap();
+        assert_eq!(old, TemperatureCurve::default());
+        let new = TemperatureCurve {
+            factors: [150, 120, 110, 100, 95, 90, 80],
+        };
+        assert_eq!(
+            ne4_client
+                .write_temperature_curve(port.clone(), 247, new)
+                .await
+                .unwrap(),
+            new
+        );
+
+        // Ungültige Kennlinie wird gar nicht erst geschrieben
+        let mut invalid = old;
+        invalid.factors[6] = 201;
+        assert!(ne4_client
+            .write_temperature_curve(port.clone(), 247, invalid)
+            .await
+            .is_err());
+        assert_eq!(ne4_client.temperature_curve(port, 247).await.unwrap(), new);
+
+        // Fehler mitten in der Kennlinie, Rwreg 30 bis 33 werden zurückgesetzt
+        let mut model = Ne4Model::new(247);
+        model.reject_writes(34);
+        let port = simulate(model);
+        match ne4_client
+            .write_temperature_curve(port.clone(), 247, new)
+            .await
+        {
+            Err(Error::Locked { register: 34 }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(ne4_client.temperature_curve(port, 247).await.unwrap(), old);
+    }
+
+    #[tokio::test]
+    async fn write_front_end() {
+        let ne4_client = Ne4Client::new(LineSettings::default());
+        let port = simulator();
+        let old = ne4_client.front_end(port.clone(), 247).await.unwrap();
+        assert_eq!(old.values, [1, 5, 1, 0, 1, 0, 0, 0, 3]);
+        let mut new = old;
+        new.values[1] = 6;
+        new.values[5] = 1;
+        new.values[6] = 4;
+        assert_eq!(
+            ne4_client
+                .write_front_end(port.clone(), 247, new)
+                .await
+                .unwrap(),
+            new
+        );
+
+        // Ungültige Einstellungen werden gar nicht erst geschrieben
+        let mut invalid = old;
+        invalid.values[6] = 14;
+        assert!(ne4_client
+            .write_front_end(port.clone(), 247, invalid)
+            .await
+            .is_err());
+        assert_eq!(ne4_client.front_end(port, 247).await.unwrap(), new);
+
+        let mut model = Ne4Model::new(247);
+        model.reject_writes(29);
+        let port = simulate(model);
+        assert!(ne4_client
+            .write_front_end(port.clone(), 247, new)
+            .await
+            .is_err());
+        assert_eq!(ne4_client.front_end(port, 247).await.unwrap(), old);
+    }
+
+    #[tokio::test]
+    async fn restore_test_values() {
+        let ne4_client = Ne4Client::new(LineSettings::default());
+        let port = simulator();
+        assert_eq!(
+            ne4_client
+                .set_test_value(port.clone(), 247, TestValue::OutputCurrent, Some(1200))
+                .await
+                .unwrap(),
+            1200
+        );
+        assert!(ne4_client.has_test_values(port.as_deref()));
+        let restored = ne4_client.restore_test_values(None).await.unwrap();
+        assert_eq!(restored.len(), 1);
+        assert!(!ne4_client.has_test_values(None));
+        assert_eq!(
+            ne4_client
+                .read_registers_once(port, 247, FunctionCode::ReadHoldingRegisters, &[3])
+                .await
+                .unwrap(),
+            vec![(3, TEST_VALUE_OFF)]
+        );
+
+        // The port is gone, the override stays for the next attempt
+        let lost = Some("/dev/ne4-konfig-lost".to_string());
+        assert!(ne4_client
+            .set_test_value(lost.clone(), 247, TestValue::Concentration, Some(100))
+            .await
+            .is_err());
+        assert!(ne4_client.restore_test_values(None).await.is_err());
+        assert!(ne4_client.has_test_values(lost.as_deref()));
+    }
 }
@@ -0,0 +1,272 @@
+/// Polling task of a connection
+///
+/// One poller runs per connection. It reads the input and holding registers of
+/// the selected sensor, or the dashboard sensors round-robin, sends the values to
+/// the UI, waits the poll interval and starts over. Stopping a poller waits for
+/// the running request, afterwards there is no more traffic on the bus.
+use super::connection::Connection;
+use super::{read_dashboard_sensor, read_register_blocks, send_ui, BlockPlans, Ne4Client};
+use crate::error::Result;
+use crate::gui::gtk3::UiCommand;
+use crate::sensors::ra_gas_ne4::{FunctionCode, NE4, RREGS, RREG_COUNT, RWREGS, RWREG_COUNT};
+use futures::channel::mpsc::Sender;
+use std::{cell::Cell, rc::Rc};
+use tokio::{sync::Notify, task::JoinHandle, time::Duration};
+
+/// Pause between two polling rounds
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What the poller reads
+#[derive(Clone, Debug, PartialEq)]
+pub enum PollTarget {
+    /// Rreg and Rwreg of one sensor
+    Sensor(u8),
+    /// Rreg 1 to 3 of the dashboard sensors, see `sensors::dashboard`
+    Dashboard(Vec<u8>),
+}
+
+/// State shared between the poller handle and its task
+struct Shared {
+    cancelled: Cell<bool>,
+    interval: Cell<Duration>,
+    /// Wakes the task while it waits for the next round
+    wake: Notify,
+}
+
+impl Shared {
+    /// Wait the poll interval, returns early if the poller is stopped
+    async fn pause(&self) {
+        tokio::select! {
+            _ = tokio::time::delay_for(self.interval.get()) => {}
+            _ = self.wake.notified() => {}
+        }
+    }
+}
+
+/// Handle of a running poller
+pub struct Poller {
+    shared: Rc<Shared>,
+    task: JoinHandle<()>,
+}
+
+impl Poller {
+    /// Start polling `target` on the session of `port`
+    ///
+    /// Must be called within a `tokio::task::LocalSet`, the Modbus session isn't
+    /// `Send`.
+    pub fn start(
+        ne4_client: &Ne4Client,
+        port: Option<String>,
+        target: PollTarget,
+        interval: Duration,
+        ui_event_sender: Sender<UiCommand>,
+    ) -> Result<Self> {
+        let connection = ne4_client.connection(port)?;
+        let block_plans = ne4_client.block_plans.clone();
+        let shared = Rc::new(Shared {
+            cancelled: Cell::new(false),
+            interval: Cell::new(interval),
+            wake: Notify::new(),
+        });
+        let task = tokio::task::spawn_local(poll(
+            connection,
+            block_plans,
+            target,
+            shared.clone(),
+            ui_event_sender,
+        ));
+        Ok(Poller { shared, task })
+    }
+
+    /// New pause between two polling rounds, used from the next round on
+    pub fn set_interval(&self, interval: Duration) {
+        self.shared.interval.set(interval);
+    }
+
+    /// Stop polling
+    ///
+    /// Returns after the running request has finished, afterwards the poller
+    /// doesn't touch the bus anymore.
+    pub async fn stop(self) {
+        self.shared.cancelled.set(true);
+        self.shared.wake.notify();
+        if let Err(e) = self.task.await {
+            debug!("Poller task failed: {}", e);
+        }
+    }
+}
+
+/// Polling loop, ends if cancelled or if the UI is gone
+async fn poll(
+    connection: Rc<Connection>,
+    block_plans: BlockPlans,
+    target: PollTarget,
+    shared: Rc<Shared>,
+    ui_event_sender: Sender<UiCommand>,
+) {
+    // Both register banks fill the same sensor
+    let mut ne4 = NE4::new();
+    while !shared.cancelled.get() {
+        let running = match &target {
+            PollTarget::Sensor(modbus_address) => {
+                poll_sensor(
+                    &connection,
+                    *modbus_address,
+                    &mut ne4,
+                    &block_plans,
+                    &shared,
+                    &ui_event_sender,
+                )
+                .await
+            }
+            PollTarget::Dashboard(modbus_addresses) => {
+                poll_dashboard(&connection, modbus_addresses, &shared, &ui_event_sender).await
+            }
+        };
+        if !running || shared.cancelled.get() {
+            break;
+        }
+        shared.pause().await;
+    }
+    debug!("Poller of {} stopped", connection.path());
+}
+
+/// One round of the single sensor view, input registers first
+///
+/// Returns false if the UI is gone.
+async fn poll_sensor(
+    connection: &Connection,
+    modbus_address: u8,
+    ne4: &mut NE4,
+    block_plans: &BlockPlans,
+    shared: &Shared,
+    ui_event_sender: &Sender<UiCommand>,
+) -> bool {
+    let (registers, errors) = read_register_blocks(
+        connection,
+        modbus_address,
+        FunctionCode::ReadInputRegisters,
+        RREGS,
+        RREG_COUNT,
+        block_plans,
+    )
+    .await;
+    ne4.update_rregs(&registers);
+    for e in errors {
+        let message = format!("Input Register konnten nicht gelesen werden: {}", e);
+        if !send_ui(ui_event_sender, UiCommand::Error(message)).await {
+            return false;
+        }
+    }
+    if !send_ui(
+        ui_event_sender,
+        UiCommand::UpdateSensorValues(Ok(ne4.clone())),
+    )
+    .await
+    {
+        return false;
+    }
+    if shared.cancelled.get() {
+        return true;
+    }
+
+    // Entsperren
+    let _ = connection
+        .write_single_register(modbus_address, 49, 9876)
+        .await;
+    let (registers, errors) = read_register_blocks(
+        connection,
+        modbus_address,
+        FunctionCode::ReadHoldingRegisters,
+        RWREGS,
+        RWREG_COUNT,
+        block_plans,
+    )
+    .await;
+    ne4.update_rwregs(&registers);
+    for e in errors {
+        let message = format!("Holding Register konnten nicht gelesen werden: {}", e);
+        if !send_ui(ui_event_sender, UiCommand::Error(message)).await {
+            return false;
+        }
+    }
+    send_ui(
+        ui_event_sender,
+        UiCommand::UpdateSensorRwregValues(Ok(ne4.clone())),
+    )
+    .await
+}
+
+/// One round over all dashboard sensors
+///
+/// Every answer, or error, is sent with `UiCommand::UpdateDashboard`. Returns
+/// false if the UI is gone.
+async fn poll_dashboard(
+    connection: &Connection,
+    modbus_addresses: &[u8],
+    shared: &Shared,
+    ui_event_sender: &Sender<UiCommand>,
+) -> bool {
+    for modbus_address in modbus_addresses {
+        if shared.cancelled.get() {
+            break;
+        }
+        let result = read_dashboard_sensor(connection, *modbus_address).await;
+        if !send_ui(
+            ui_event_sender,
+            UiCommand::UpdateDashboard(*modbus_address, result),
+        )
+        .await
+        {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::simulator::{model::Ne4Model, Faults, Simulator};
+    use crate::tokio_thread::LineSettings;
+    use futures::prelude::*;
+
+    fn simulator() -> Option<String> {
+        let simulator = Simulator::new(Ne4Model::new(247), Faults::default(), 1).unwrap();
+        let path = simulator.path().to_string();
+        simulator.spawn();
+        Some(path)
+    }
+
+    #[tokio::test]
+    async fn stop() {
+        let local = tokio::task::LocalSet::new();
+        local
+            .run_until(async {
+                let ne4_client = Ne4Client::new(LineSettings::default());
+                let (ui_event_sender, mut ui_event_receiver) = futures::channel::mpsc::channel(0);
+                let poller = Poller::start(
+                    &ne4_client,
+                    simulator(),
+                    PollTarget::Sensor(247),
+                    Duration::from_secs(60),
+                    ui_event_sender,
+                )
+                .unwrap();
+                match ui_event_receiver.next().await {
+                    Some(UiCommand::UpdateSensorValues(Ok(ne4))) => {
+                        assert_eq!(ne4.work_mode().code(), 10)
+                    }
+                    command => panic!("unexpected command {:?}", command),
+                }
+                // Stopping wakes the poller from the interval. The task has ended
+                // when `stop` returns, so the channel is closed afterwards.
+                let (_, commands) =
+                    future::join(poller.stop(), ui_event_receiver.collect::<Vec<_>>()).await;
+                assert!(!commands
+                    .iter()
+                    .any(|command| matches!(command, UiCommand::UpdateSensorValues(_))));
+            })
+            .await;
+    }
+}
@@ -0,0 +1,279 @@
+/// Grouping of registers into multi register Modbus requests
+///
+/// Instead of one request per register the poller reads contiguous register
+/// ranges with one 0x03/0x04 request. Not every sensor firmware answers requests
+/// which span registers without meaning (e.g. Rreg 5 … 39). If the sensor rejects
+/// a block it is split into smaller blocks until every part is accepted.
+use std::collections::VecDeque;
+
+/// Maximum number of registers of one read request (Modbus specification)
+pub const MAX_BLOCK_LEN: u16 = 125;
+
+/// Contiguous register range, read with one Modbus request
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Block {
+    pub start: u16,
+    pub count: u16,
+}
+
+impl Block {
+    /// Last register number of the block
+    pub fn end(&self) -> u16 {
+        self.start + self.count - 1
+    }
+
+    /// Register number lies within the block
+    pub fn contains(&self, number: u16) -> bool {
+        self.start <= number && number <= self.end()
+    }
+}
+
+/// Group the register numbers into blocks of contiguous registers
+///
+/// `numbers` must be sorted.
+pub fn contiguous_blocks(numbers: &[u16]) -> Vec<Block> {
+    let mut blocks: Vec<Block> = vec![];
+    for &number in numbers {
+        match blocks.last_mut() {
+            Some(block) if block.end() + 1 == number && block.count < MAX_BLOCK_LEN => {
+                block.count += 1
+            }
+            _ => blocks.push(Block {
+                start: number,
+                count: 1,
+            }),
+        }
+    }
+    blocks
+}
+
+/// Blocks from the first to the last register, including the gaps
+///
+/// This is the optimistic plan, used as long as nothing is known about the sensor.
+pub fn spanning_blocks(numbers: &[u16]) -> Vec<Block> {
+    let mut blocks: Vec<Block> = vec![];
+    for &number in numbers {
+        match blocks.last_mut() {
+            Some(block) if number - block.start < MAX_BLOCK_LEN => {
+                block.count = number - block.start + 1
+            }
+            _ => blocks.push(Block {
+                start: number,
+                count: 1,
+            }),
+        }
+    }
+    blocks
+}
+
+/// Split a block which was rejected by the sensor
+///
+/// A block spanning gaps is split at its largest gap, a block without gaps is
+/// split in halves.
+pub fn split_block(block: Block, numbers: &[u16]) -> Vec<Block> {
+    let inner: Vec<u16> = numbers
+        .iter()
+        .cloned()
+        .filter(|n| block.contains(*n))
+        .collect();
+    let largest_gap = inner
+        .windows(2)
+        .enumerate()
+        .filter(|(_, w)| w[1] - w[0] > 1)
+        .max_by_key(|(_, w)| w[1] - w[0])
+        .map(|(i, _)| i + 1);
+    match largest_gap {
+        Some(i) => {
+            let mut blocks = spanning_blocks(&inner[..i]);
+            blocks.extend(spanning_blocks(&inner[i..]));
+            blocks
+        }
+        None => {
+            let half = block.count / 2;
+            vec![
+                Block {
+                    start: block.start,
+                    count: half,
+                },
+                Block {
+                    start: block.start + half,
+                    count: block.count - half,
+                },
+            ]
+        }
+    }
+}
+
+/// One read cycle over all registers
+///
+/// The cycle hands out the blocks to read. Rejected blocks are split and read
+/// again, the blocks which worked are the plan for the next cycle.
+#[derive(Debug)]
+pub struct BlockRead {
+    numbers: Vec<u16>,
+    queue: VecDeque<Block>,
+    plan: Vec<Block>,
+}
+
+impl BlockRead {
+    /// New read cycle, with the plan of the last cycle if there is one
+    pub fn new(numbers: &[u16], plan: Option<&[Block]>) -> Self {
+        let queue = match plan {
+            Some(plan) => plan.iter().cloned().collect(),
+            None => spanning_blocks(numbers).into_iter().collect(),
+        };
+        BlockRead {
+            numbers: numbers.to_vec(),
+            queue,
+            plan: vec![],
+        }
+    }
+
+    /// Next block to read
+    pub fn next_block(&mut self) -> Option<Block> {
+        self.queue.pop_front()
+    }
+
+    /// The sensor answered the block
+    pub fn succeeded(&mut self, block: Block) {
+        self.plan.push(block);
+    }
+
+    /// The sensor rejected the block (exception response)
+    ///
+    /// Returns `false` if the block can't be split any further, a single register
+    /// is kept in the plan so it is tried again in the next cycle.
+    pub fn rejected(&mut self, block: Block) -> bool {
+        if block.count > 1 {
+            for part in split_block(block, &self.numbers).into_iter().rev() {
+                self.queue.push_front(part);
+            }
+            true
+        } else {
+            self.plan.push(block);
+            false
+        }
+    }
+
+    /// The block failed without an answer of the sensor (e.g. timeout)
+    ///
+    /// The block stays in the plan, splitting wouldn't help here.
+    pub fn failed(&mut self, block: Block) {
+        self.plan.push(block);
+    }
+
+    /// Blocks known to work, plan for the next cycle
+    pub fn into_plan(self) -> Vec<Block> {
+        self.plan
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RREG_NUMBERS: &[u16] = &[0, 1, 2, 3, 4, 40, 41, 42, 43, 44, 45, 46, 49];
+
+    fn block(start: u16, count: u16) -> Block {
+        Block { start, count }
+    }
+
+    #[test]
+    fn contiguous() {
+        assert_eq!(
+            contiguous_blocks(RREG_NUMBERS),
+            vec![block(0, 5), block(40, 7), block(49, 1)]
+        );
+    }
+
+    #[test]
+    fn spanning() {
+        assert_eq!(spanning_blocks(RREG_NUMBERS), vec![block(0, 50)]);
+    }
+
+    #[test]
+    fn spanning_max_len() {
+        assert_eq!(
+            spanning_blocks(&[0, 124, 125, 130]),
+            vec![block(0, 125), block(125, 6)]
+        );
+    }
+
+    #[test]
+    fn split_at_gaps() {
+        assert_eq!(
+            split_block(block(0, 50), RREG_NUMBERS),
+            vec![block(0, 5), block(40, 10)]
+        );
+        assert_eq!(
+            split_block(block(40, 10), RREG_NUMBERS),
+            vec![block(40, 7), block(49, 1)]
+        );
+    }
+
+    #[test]
+    fn split_in_halves() {
+        assert_eq!(
+            split_block(block(40, 7), RREG_NUMBERS),
+            vec![block(40, 3), block(43, 4)]
+        );
+    }
+
+    /// Sensor which rejects every request covering registers 5 … 39
+    #[test]
+    fn read_cycle_with_gaps() {
+        let mut read = BlockRead::new(RREG_NUMBERS, None);
+        let mut requests = 0;
+        while let Some(b) = read.next_block() {
+            requests += 1;
+            if b.start <= 39 && b.end() >= 5 {
+                assert!(read.rejected(b));
+            } else {
+                read.succeeded(b);
+            }
+        }
+        assert_eq!(requests, 3);
+        let plan = read.into_plan();
+        assert_eq!(plan, vec![block(0, 5), block(40, 10)]);
+
+        // The next cycle uses the plan, no probing any more
+        let mut read = BlockRead::new(RREG_NUMBERS, Some(&plan));
+        let mut requests = 0;
+        while let Some(b) = read.next_block() {
+            requests += 1;
+            read.succeeded(b);
+        }
+        assert_eq!(requests, 2);
+        assert_eq!(read.into_plan(), plan);
+    }
+
+    /// Sensor which rejects every request covering a register it doesn't know,
+    /// like the firmware and the simulator do for Rreg 5 … 39, 47 and 48
+    #[test]
+    fn read_cycle_unknown_registers() {
+        let mut read = BlockRead::new(RREG_NUMBERS, None);
+        let mut requests = 0;
+        while let Some(b) = read.next_block() {
+            requests += 1;
+            if (b.start..b.end() + 1).all(|number| RREG_NUMBERS.contains(&number)) {
+                read.succeeded(b);
+            } else {
+                assert!(read.rejected(b));
+            }
+        }
+        assert_eq!(requests, 5);
+        assert_eq!(
+            read.into_plan(),
+            vec![block(0, 5), block(40, 7), block(49, 1)]
+        );
+    }
+
+    #[test]
+    fn single_register_rejected() {
+        let mut read = BlockRead::new(&[7], None);
+        let b = read.next_block().unwrap();
+        assert!(!read.rejected(b));
+        assert_eq!(read.next_block(), None);
+        assert_eq!(read.into_plan(), vec![block(7, 1)]);
+    }
+}